@@ -0,0 +1,55 @@
+#![forbid(unsafe_code)]
+
+// Timings of the service list's hot paths with 10k services, run with
+// cargo bench --features bench
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use mdns_tui_browser::tui_app::bench::ServiceList;
+use std::hint::black_box;
+
+const SERVICES: usize = 10_000;
+
+fn filter(c: &mut Criterion) {
+    let mut list = ServiceList::with_services(SERVICES);
+    c.bench_function("update_filtered_cache", |b| {
+        b.iter(|| black_box(list.update_filtered_cache()))
+    });
+    list.set_filter("host-12");
+    c.bench_function("update_filtered_cache with query", |b| {
+        b.iter(|| black_box(list.update_filtered_cache()))
+    });
+}
+
+fn sort(c: &mut Criterion) {
+    let mut list = ServiceList::with_services(SERVICES);
+    list.update_filtered_cache();
+    c.bench_function("sort_filtered_services by host", |b| {
+        b.iter(|| list.sort_filtered_services())
+    });
+    list.cycle_sort_field();
+    c.bench_function("sort_filtered_services by type", |b| {
+        b.iter(|| list.sort_filtered_services())
+    });
+}
+
+fn update(c: &mut Criterion) {
+    let mut list = ServiceList::with_services(SERVICES);
+    let mut i = 0;
+    c.bench_function("add_or_update_service existing", |b| {
+        b.iter(|| {
+            i = (i + 1) % SERVICES;
+            black_box(list.add_or_update_service(i, i % 2 == 0))
+        })
+    });
+    c.bench_function("ingest_service new", |b| {
+        b.iter_batched_ref(
+            || ServiceList::with_services(SERVICES),
+            |list| black_box(list.ingest_service(SERVICES, true)),
+            BatchSize::LargeInput,
+        )
+    });
+    assert_eq!(list.len(), SERVICES);
+}
+
+criterion_group!(benches, filter, sort, update);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+#![forbid(unsafe_code)]
+
+fn main() {
+    // The messages are written out in src/grpc.rs, checked against the proto
+    // file by its tests, only the service is generated, which spares the build
+    // a protoc
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+        let method = |name: &str, route: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::grpc::{}", input))
+                .output_type(format!("crate::grpc::{}", output))
+                .codec_path("tonic_prost::ProstCodec")
+        };
+        let service = Service::builder()
+            .name("Browser")
+            .package("mdns_tui_browser.v1")
+            .method(
+                method(
+                    "list_services",
+                    "ListServices",
+                    "ListServicesRequest",
+                    "ListServicesResponse",
+                )
+                .build(),
+            )
+            .method(
+                method("watch_events", "WatchEvents", "WatchEventsRequest", "Event")
+                    .server_streaming()
+                    .build(),
+            )
+            .build();
+        Builder::new().compile(&[service]);
+        println!("cargo:rerun-if-changed=build.rs");
+    }
+}
@@ -0,0 +1,210 @@
+#![forbid(unsafe_code)]
+
+#[cfg(feature = "bonjour")]
+use crate::bonjour::BonjourBackend;
+#[cfg(any(test, feature = "mock-discovery"))]
+use crate::mock_discovery::MockDaemon;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Which backend the config asks for.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// The mDNS daemon of mdns-sd, with multicast sockets of its own
+    #[default]
+    MdnsSd,
+    /// The platform's mDNSResponder through its dns-sd tool, needs the bonjour feature
+    Bonjour,
+}
+
+/// What services are discovered with. The mDNS daemon of mdns-sd browses the
+/// network; other backends stand in for it and report their findings as the
+/// same [`ServiceEvent`]s, so everything after the backend works unchanged.
+pub trait DiscoveryBackend: Clone + Send + Sync + 'static {
+    type Events: ServiceEvents;
+
+    /// Starts a browse for the type. A browse of the meta query
+    /// `_services._dns-sd._udp.<domain>` reports the types as found services.
+    fn browse(&self, service_type: &str) -> mdns_sd::Result<Self::Events>;
+
+    /// Ends the browse for the type, its events end.
+    fn stop_browse(&self, service_type: &str) -> mdns_sd::Result<()>;
+
+    /// Starts or stops using the network interface.
+    fn set_interface_enabled(&self, name: &str, enabled: bool) -> mdns_sd::Result<()>;
+
+    /// Asks the instance to answer again, it is reported removed if it doesn't.
+    fn verify(&self, fullname: &str) -> mdns_sd::Result<()>;
+
+    /// Counters for the metrics popup, empty if the backend keeps none.
+    fn metrics(&self) -> impl Future<Output = HashMap<String, i64>> + Send;
+
+    /// Stops all browses.
+    fn shutdown(&self);
+}
+
+/// The events of one browse.
+pub trait ServiceEvents: Send + 'static {
+    /// The next event, None once the browse ended.
+    fn next_event(&self) -> impl Future<Output = Option<ServiceEvent>> + Send;
+}
+
+impl DiscoveryBackend for ServiceDaemon {
+    type Events = mdns_sd::Receiver<ServiceEvent>;
+
+    fn browse(&self, service_type: &str) -> mdns_sd::Result<Self::Events> {
+        ServiceDaemon::browse(self, service_type)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> mdns_sd::Result<()> {
+        ServiceDaemon::stop_browse(self, service_type)
+    }
+
+    fn set_interface_enabled(&self, name: &str, enabled: bool) -> mdns_sd::Result<()> {
+        if enabled {
+            self.enable_interface(name)
+        } else {
+            self.disable_interface(name)
+        }
+    }
+
+    fn verify(&self, fullname: &str) -> mdns_sd::Result<()> {
+        ServiceDaemon::verify(self, fullname.to_string(), mdns_sd::VERIFY_TIMEOUT_DEFAULT)
+    }
+
+    async fn metrics(&self) -> HashMap<String, i64> {
+        match self.get_metrics() {
+            Ok(receiver) => receiver.recv_async().await.unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    fn shutdown(&self) {
+        let _ = ServiceDaemon::shutdown(self);
+    }
+}
+
+impl ServiceEvents for mdns_sd::Receiver<ServiceEvent> {
+    async fn next_event(&self) -> Option<ServiceEvent> {
+        self.recv_async().await.ok()
+    }
+}
+
+#[cfg(any(test, feature = "mock-discovery"))]
+impl DiscoveryBackend for MockDaemon {
+    type Events = flume::Receiver<ServiceEvent>;
+
+    fn browse(&self, service_type: &str) -> mdns_sd::Result<Self::Events> {
+        MockDaemon::browse(self, service_type)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> mdns_sd::Result<()> {
+        MockDaemon::stop_browse(self, service_type);
+        Ok(())
+    }
+
+    // The mock daemon has no interfaces and all its services answer
+    fn set_interface_enabled(&self, _name: &str, _enabled: bool) -> mdns_sd::Result<()> {
+        Ok(())
+    }
+
+    fn verify(&self, _fullname: &str) -> mdns_sd::Result<()> {
+        Ok(())
+    }
+
+    async fn metrics(&self) -> HashMap<String, i64> {
+        HashMap::new()
+    }
+
+    fn shutdown(&self) {
+        MockDaemon::shutdown(self);
+    }
+}
+
+// mdns-sd depends on another version of flume than the other backends
+#[cfg(any(test, feature = "mock-discovery", feature = "bonjour"))]
+impl ServiceEvents for flume::Receiver<ServiceEvent> {
+    async fn next_event(&self) -> Option<ServiceEvent> {
+        self.recv_async().await.ok()
+    }
+}
+
+/// The backend of one of the kinds, as chosen by the config.
+#[derive(Clone)]
+pub enum ConfiguredBackend {
+    MdnsSd(ServiceDaemon),
+    #[cfg(feature = "bonjour")]
+    Bonjour(BonjourBackend),
+}
+
+/// The events of a browse of a [`ConfiguredBackend`].
+pub enum ConfiguredEvents {
+    MdnsSd(mdns_sd::Receiver<ServiceEvent>),
+    #[cfg(feature = "bonjour")]
+    Bonjour(flume::Receiver<ServiceEvent>),
+}
+
+impl DiscoveryBackend for ConfiguredBackend {
+    type Events = ConfiguredEvents;
+
+    fn browse(&self, service_type: &str) -> mdns_sd::Result<Self::Events> {
+        match self {
+            Self::MdnsSd(daemon) => daemon.browse(service_type).map(ConfiguredEvents::MdnsSd),
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.browse(service_type).map(ConfiguredEvents::Bonjour),
+        }
+    }
+
+    fn stop_browse(&self, service_type: &str) -> mdns_sd::Result<()> {
+        match self {
+            Self::MdnsSd(daemon) => DiscoveryBackend::stop_browse(daemon, service_type),
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.stop_browse(service_type),
+        }
+    }
+
+    fn set_interface_enabled(&self, name: &str, enabled: bool) -> mdns_sd::Result<()> {
+        match self {
+            Self::MdnsSd(daemon) => daemon.set_interface_enabled(name, enabled),
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.set_interface_enabled(name, enabled),
+        }
+    }
+
+    fn verify(&self, fullname: &str) -> mdns_sd::Result<()> {
+        match self {
+            Self::MdnsSd(daemon) => DiscoveryBackend::verify(daemon, fullname),
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.verify(fullname),
+        }
+    }
+
+    async fn metrics(&self) -> HashMap<String, i64> {
+        match self {
+            Self::MdnsSd(daemon) => daemon.metrics().await,
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.metrics().await,
+        }
+    }
+
+    fn shutdown(&self) {
+        match self {
+            Self::MdnsSd(daemon) => DiscoveryBackend::shutdown(daemon),
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(bonjour) => bonjour.shutdown(),
+        }
+    }
+}
+
+impl ServiceEvents for ConfiguredEvents {
+    async fn next_event(&self) -> Option<ServiceEvent> {
+        match self {
+            Self::MdnsSd(receiver) => receiver.next_event().await,
+            #[cfg(feature = "bonjour")]
+            Self::Bonjour(receiver) => receiver.next_event().await,
+        }
+    }
+}
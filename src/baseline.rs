@@ -0,0 +1,157 @@
+#![forbid(unsafe_code)]
+
+use crate::data_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// How a service compares to the baseline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BaselineStatus {
+    // Online but not in the baseline
+    New,
+    // In the baseline but not online
+    Missing,
+}
+
+impl BaselineStatus {
+    pub fn label(self) -> &'static str {
+        match self {
+            BaselineStatus::New => "new",
+            BaselineStatus::Missing => "missing",
+        }
+    }
+}
+
+// A service as it was when the baseline was saved
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineService {
+    pub service_type: String,
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BaselineFile {
+    saved_micros: u64,
+    // By fullname
+    services: BTreeMap<String, BaselineService>,
+}
+
+// The services that were online at one point, kept on disk so drift shows up
+// across sessions
+pub struct Baseline {
+    path: Option<PathBuf>,
+    file: BaselineFile,
+}
+
+impl Baseline {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            file: BaselineFile::default(),
+        }
+    }
+
+    pub fn load_default() -> Self {
+        match data_file::path("baseline.json") {
+            Some(path) => Self::load(&path),
+            None => Self::in_memory(),
+        }
+    }
+
+    // A missing or unreadable file starts without a baseline
+    pub fn load(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+            file: data_file::load_json(path),
+        }
+    }
+
+    // Replaces the baseline by the given services and writes it to disk
+    pub fn save(
+        &mut self,
+        services: BTreeMap<String, BaselineService>,
+        now_micros: u64,
+    ) -> Result<(), String> {
+        self.file = BaselineFile {
+            saved_micros: now_micros,
+            services,
+        };
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        data_file::save_json(path, &self.file)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file.services.is_empty()
+    }
+
+    pub fn contains(&self, fullname: &str) -> bool {
+        self.file.services.contains_key(fullname)
+    }
+
+    pub fn services(&self) -> &BTreeMap<String, BaselineService> {
+        &self.file.services
+    }
+
+    pub fn saved_micros(&self) -> u64 {
+        self.file.saved_micros
+    }
+
+    // New for online services outside the baseline, missing for the others in it
+    pub fn status(&self, fullname: &str, online: bool) -> Option<BaselineStatus> {
+        if self.is_empty() {
+            return None;
+        }
+        match (online, self.contains(fullname)) {
+            (true, false) => Some(BaselineStatus::New),
+            (false, true) => Some(BaselineStatus::Missing),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> BaselineService {
+        BaselineService {
+            service_type: "_ipp._tcp.local.".to_string(),
+            host: "printer.local.".to_string(),
+            port: 631,
+        }
+    }
+
+    #[test]
+    fn test_baseline_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("baseline.json");
+
+        let mut baseline = Baseline::load(&path);
+        assert!(baseline.is_empty());
+        assert_eq!(baseline.status("Office._ipp._tcp.local.", true), None);
+        baseline
+            .save(
+                BTreeMap::from([("Office._ipp._tcp.local.".to_string(), service())]),
+                1_000_000,
+            )
+            .unwrap();
+
+        let baseline = Baseline::load(&path);
+        assert_eq!(baseline.file.saved_micros, 1_000_000);
+        assert_eq!(baseline.services()["Office._ipp._tcp.local."], service());
+        assert_eq!(baseline.status("Office._ipp._tcp.local.", true), None);
+        assert_eq!(
+            baseline.status("Office._ipp._tcp.local.", false),
+            Some(BaselineStatus::Missing)
+        );
+        assert_eq!(
+            baseline.status("Guest._ipp._tcp.local.", true),
+            Some(BaselineStatus::New)
+        );
+    }
+}
@@ -0,0 +1,524 @@
+#![forbid(unsafe_code)]
+
+use crate::backend::DiscoveryBackend;
+use crate::config::LimitsConfig;
+use mdns_sd::{ServiceEvent, ServiceInfo};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+// The command line tool of mDNSResponder, part of macOS and of Bonjour for Windows
+const PROGRAM: &str = "dns-sd";
+// How long looking up a service or the addresses of its host may take
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+// The TXT line and the addresses of the other family follow the first answer
+// right away, the lookup ends once they had the time to arrive
+const FOLLOW_UP_WAIT: Duration = Duration::from_millis(250);
+#[cfg(feature = "smol")]
+const NO_TOKIO: &str = "The bonjour backend needs a Tokio runtime";
+
+// Browses through the platform's mDNSResponder with its dns-sd tool instead of
+// multicast sockets of its own, for machines that only let the system's
+// responder onto the network. This wraps the tool rather than binding the
+// DNS-SD API, which would take FFI the crate forbids or a native binding
+// crate linking against dnssd. The tool's output is meant for people, the
+// parsers below follow the format it has had for years
+#[derive(Clone)]
+pub struct BonjourBackend {
+    // Cancels the running browses by the type they browse for, their tools and
+    // the ones of their lookups are killed and waited for
+    browses: Arc<Mutex<HashMap<String, CancellationToken>>>,
+    // Lookups running at once over all browses, each runs two more tools
+    lookups: Arc<Semaphore>,
+}
+
+// A line of `dns-sd -B`
+#[derive(Debug, PartialEq)]
+struct BrowseResult {
+    added: bool,
+    // "_http._tcp.", or "_tcp.local." for the types of the meta query
+    regtype: String,
+    domain: String,
+    instance: String,
+}
+
+impl BonjourBackend {
+    // Fails if the tool cannot be run, or outside a Tokio runtime, which runs
+    // the tools and their lookups
+    pub async fn start(limits: &LimitsConfig) -> Result<Self, String> {
+        #[cfg(feature = "smol")]
+        if !crate::runtime::in_tokio() {
+            return Err(NO_TOKIO.to_string());
+        }
+        let mut child = spawn(&["-V"]).map_err(|e| format!("Cannot run {}: {}", PROGRAM, e))?;
+        // Only whether it runs matters, not the version it prints
+        let _ = tokio::time::timeout(LOOKUP_TIMEOUT, child.wait()).await;
+        reap(child).await;
+        Ok(Self {
+            browses: Arc::default(),
+            lookups: Arc::new(Semaphore::new(LimitsConfig::permits(
+                limits.max_bonjour_lookups,
+            ))),
+        })
+    }
+}
+
+impl DiscoveryBackend for BonjourBackend {
+    type Events = flume::Receiver<ServiceEvent>;
+
+    fn browse(&self, service_type: &str) -> mdns_sd::Result<Self::Events> {
+        #[cfg(feature = "smol")]
+        if !crate::runtime::in_tokio() {
+            return Err(mdns_sd::Error::Msg(NO_TOKIO.to_string()));
+        }
+        let (regtype, domain) = browse_arguments(service_type)
+            .ok_or_else(|| mdns_sd::Error::Msg(format!("Invalid service type {}", service_type)))?;
+        let mut child = spawn(&["-B", &regtype, &domain])
+            .map_err(|e| mdns_sd::Error::Msg(format!("Cannot run {}: {}", PROGRAM, e)))?;
+        let mut lines = output_lines(&mut child)?;
+        let (sender, receiver) = flume::unbounded();
+        let _ = sender.send(ServiceEvent::SearchStarted(service_type.to_string()));
+        let browsed = service_type.to_string();
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let semaphore = Arc::clone(&self.lookups);
+        tokio::spawn(async move {
+            let mut lookups = JoinSet::new();
+            loop {
+                let line = tokio::select! {
+                    _ = token.cancelled() => break,
+                    line = lines.next_line() => match line {
+                        Ok(Some(line)) => line,
+                        _ => break,
+                    },
+                };
+                // Lookups that ended are reaped as the browse goes
+                while lookups.try_join_next().is_some() {}
+                let Some(result) = parse_browse_line(&line) else {
+                    continue;
+                };
+                let found = if is_meta_query(&browsed) {
+                    format!("{}.{}", result.instance, result.regtype)
+                } else {
+                    format!("{}.{}{}", result.instance, result.regtype, result.domain)
+                };
+                let event = if result.added {
+                    ServiceEvent::ServiceFound(browsed.clone(), found)
+                } else {
+                    ServiceEvent::ServiceRemoved(browsed.clone(), found)
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
+                if result.added && !is_meta_query(&browsed) {
+                    let sender = sender.clone();
+                    let browsed = browsed.clone();
+                    let semaphore = Arc::clone(&semaphore);
+                    let token = token.clone();
+                    lookups.spawn(async move {
+                        let permit = tokio::select! {
+                            _ = token.cancelled() => return,
+                            permit = semaphore.acquire_owned() => permit,
+                        };
+                        let Ok(_permit) = permit else {
+                            return;
+                        };
+                        if let Some(resolved) = resolve(&browsed, &result, &token).await {
+                            let _ = sender.send(ServiceEvent::ServiceResolved(Box::new(resolved)));
+                        }
+                    });
+                }
+            }
+            // Cancelled lookups kill their tools before they end
+            while lookups.join_next().await.is_some() {}
+            reap(child).await;
+        });
+        if let Some(previous) = self
+            .browses
+            .lock()
+            .unwrap()
+            .insert(service_type.to_string(), cancel)
+        {
+            previous.cancel();
+        }
+        Ok(receiver)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> mdns_sd::Result<()> {
+        if let Some(cancel) = self.browses.lock().unwrap().remove(service_type) {
+            cancel.cancel();
+        }
+        Ok(())
+    }
+
+    fn set_interface_enabled(&self, _name: &str, _enabled: bool) -> mdns_sd::Result<()> {
+        Err(mdns_sd::Error::Msg(
+            "mDNSResponder picks the interfaces itself".to_string(),
+        ))
+    }
+
+    fn verify(&self, _fullname: &str) -> mdns_sd::Result<()> {
+        Err(mdns_sd::Error::Msg(format!(
+            "{} cannot verify cached services",
+            PROGRAM
+        )))
+    }
+
+    async fn metrics(&self) -> HashMap<String, i64> {
+        let browses = self.browses.lock().unwrap().len();
+        HashMap::from([("browses".to_string(), browses as i64)])
+    }
+
+    fn shutdown(&self) {
+        for (_, cancel) in self.browses.lock().unwrap().drain() {
+            cancel.cancel();
+        }
+    }
+}
+
+// Kills the tool unless it ended already and waits for it, so no zombie is left
+async fn reap(mut child: Child) {
+    let _ = child.start_kill();
+    let _ = child.wait().await;
+}
+
+fn spawn(args: &[&str]) -> std::io::Result<Child> {
+    Command::new(PROGRAM)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+fn output_lines(child: &mut Child) -> mdns_sd::Result<Lines<BufReader<ChildStdout>>> {
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| mdns_sd::Error::Msg(format!("No output of {}", PROGRAM)))?;
+    Ok(BufReader::new(stdout).lines())
+}
+
+fn is_meta_query(service_type: &str) -> bool {
+    service_type.starts_with("_services._dns-sd._udp.")
+}
+
+// The type and domain arguments of dns-sd for a service type:
+// "_http._tcp.local." is browsed as "_http._tcp" in "local.", its subtype
+// "_printer._sub._http._tcp.local." as "_http._tcp,_printer"
+fn browse_arguments(service_type: &str) -> Option<(String, String)> {
+    let (subtype, service_type) = match service_type.split_once("._sub.") {
+        Some((subtype, service_type)) => (Some(subtype), service_type),
+        None => (None, service_type),
+    };
+    let end = ["._tcp.", "._udp."].iter().find_map(|protocol| {
+        service_type
+            .find(protocol)
+            .map(|start| start + protocol.len() - 1)
+    })?;
+    let (regtype, domain) = (&service_type[..end], &service_type[end + 1..]);
+    if domain.is_empty() {
+        return None;
+    }
+    let regtype = match subtype {
+        Some(subtype) => format!("{},{}", regtype, subtype),
+        None => regtype.to_string(),
+    };
+    Some((regtype, domain.to_string()))
+}
+
+// The first count whitespace separated fields and the rest of the line
+fn split_fields(line: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut rest = line.trim_start();
+    let mut fields = Vec::new();
+    for _ in 0..count {
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    Some((fields, rest.trim_end()))
+}
+
+// "12:00:00.001  Add        2   4 local.               _http._tcp.          My Server",
+// the header lines around the results give None
+fn parse_browse_line(line: &str) -> Option<BrowseResult> {
+    let (fields, instance) = split_fields(line, 6)?;
+    let added = match fields[1] {
+        "Add" => true,
+        "Rmv" => false,
+        _ => return None,
+    };
+    if instance.is_empty() {
+        return None;
+    }
+    Some(BrowseResult {
+        added,
+        regtype: fields[5].to_string(),
+        domain: fields[4].to_string(),
+        instance: instance.to_string(),
+    })
+}
+
+// Host and port of "12:00:00.002  My\032Server._http._tcp.local. can be reached
+// at host.local.:80 (interface 4)"
+fn parse_lookup_line(line: &str) -> Option<(String, u16)> {
+    let (_, target) = line.split_once(" can be reached at ")?;
+    let (host, port) = target.split(' ').next()?.rsplit_once(':')?;
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+// The entries of " path=/ note=Second\ floor", spaces in them are escaped with
+// a backslash and control characters written as \xHH
+fn parse_txt_line(line: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut entry = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        entry.push(c);
+                    }
+                }
+                Some(c) => entry.push(c),
+                None => {}
+            },
+            ' ' => {
+                if !entry.is_empty() {
+                    entries.push(std::mem::take(&mut entry));
+                }
+            }
+            c => entry.push(c),
+        }
+    }
+    if !entry.is_empty() {
+        entries.push(entry);
+    }
+    entries
+}
+
+// The address of "12:00:00.003  Add        2  4 host.local.    192.168.1.10    120",
+// None for removals and header lines
+fn parse_address_line(line: &str) -> Option<IpAddr> {
+    let (fields, rest) = split_fields(line, 5)?;
+    if fields[1] != "Add" {
+        return None;
+    }
+    let address = rest.split_whitespace().next()?;
+    // Scoped IPv6 addresses carry their zone id after a '%'
+    address.split('%').next()?.parse().ok()
+}
+
+// Looks up the host, port, TXT and addresses of a browse result, until cancelled
+async fn resolve(
+    browsed: &str,
+    result: &BrowseResult,
+    cancel: &CancellationToken,
+) -> Option<mdns_sd::ResolvedService> {
+    let regtype = result.regtype.trim_end_matches('.');
+    let (host, port, txt) =
+        lookup_service(&result.instance, regtype, &result.domain, cancel).await?;
+    let addrs: Vec<String> = lookup_addresses(&host, cancel)
+        .await
+        .iter()
+        .map(IpAddr::to_string)
+        .collect();
+    let properties: Vec<(&str, &str)> = txt
+        .iter()
+        .map(|entry| entry.split_once('=').unwrap_or((entry, "")))
+        .collect();
+    let info = ServiceInfo::new(
+        browsed,
+        &result.instance,
+        &host,
+        addrs.join(",").as_str(),
+        port,
+        properties.as_slice(),
+    )
+    .ok()?;
+    Some(info.as_resolved_service())
+}
+
+// Host, port and TXT entries of an instance from `dns-sd -L`
+async fn lookup_service(
+    instance: &str,
+    regtype: &str,
+    domain: &str,
+    cancel: &CancellationToken,
+) -> Option<(String, u16, Vec<String>)> {
+    let mut child = spawn(&["-L", instance, regtype, domain]).ok()?;
+    let found = match output_lines(&mut child) {
+        Ok(lines) => cancel
+            .run_until_cancelled(read_lookup(lines))
+            .await
+            .flatten(),
+        Err(_) => None,
+    };
+    reap(child).await;
+    found
+}
+
+async fn read_lookup(
+    mut lines: Lines<BufReader<ChildStdout>>,
+) -> Option<(String, u16, Vec<String>)> {
+    let mut deadline = Instant::now() + LOOKUP_TIMEOUT;
+    let mut target = None;
+    loop {
+        let line = match tokio::time::timeout_at(deadline, lines.next_line()).await {
+            Ok(Ok(Some(line))) => line,
+            // Services without TXT entries have no TXT line
+            _ => return target.map(|(host, port)| (host, port, Vec::new())),
+        };
+        match target {
+            None => {
+                target = parse_lookup_line(&line);
+                if target.is_some() {
+                    deadline = deadline.min(Instant::now() + FOLLOW_UP_WAIT);
+                }
+            }
+            Some((host, port)) => {
+                let txt = if line.starts_with(' ') {
+                    parse_txt_line(&line)
+                } else {
+                    Vec::new()
+                };
+                return Some((host, port, txt));
+            }
+        }
+    }
+}
+
+// The IPv4 and IPv6 addresses of a host from `dns-sd -G v4v6`
+async fn lookup_addresses(host: &str, cancel: &CancellationToken) -> Vec<IpAddr> {
+    let Ok(mut child) = spawn(&["-G", "v4v6", host]) else {
+        return Vec::new();
+    };
+    let addresses = match output_lines(&mut child) {
+        Ok(lines) => cancel
+            .run_until_cancelled(read_addresses(lines))
+            .await
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    reap(child).await;
+    addresses
+}
+
+async fn read_addresses(mut lines: Lines<BufReader<ChildStdout>>) -> Vec<IpAddr> {
+    let mut addresses = Vec::new();
+    let mut deadline = Instant::now() + LOOKUP_TIMEOUT;
+    while let Ok(Ok(Some(line))) = tokio::time::timeout_at(deadline, lines.next_line()).await {
+        if let Some(address) = parse_address_line(&line)
+            && !addresses.contains(&address)
+        {
+            addresses.push(address);
+            deadline = deadline.min(Instant::now() + FOLLOW_UP_WAIT);
+        }
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browse_arguments() {
+        assert_eq!(
+            browse_arguments("_http._tcp.local."),
+            Some(("_http._tcp".to_string(), "local.".to_string()))
+        );
+        assert_eq!(
+            browse_arguments("_printer._sub._http._tcp.local."),
+            Some(("_http._tcp,_printer".to_string(), "local.".to_string()))
+        );
+        assert_eq!(
+            browse_arguments("_services._dns-sd._udp.example.com."),
+            Some((
+                "_services._dns-sd._udp".to_string(),
+                "example.com.".to_string()
+            ))
+        );
+        assert_eq!(browse_arguments("_http._tcp."), None);
+        assert_eq!(browse_arguments("printer.local."), None);
+    }
+
+    #[test]
+    fn test_parse_browse_line() {
+        assert_eq!(
+            parse_browse_line(
+                "12:00:00.001  Add        2   4 local.               _ipp._tcp.           HP LaserJet 400"
+            ),
+            Some(BrowseResult {
+                added: true,
+                regtype: "_ipp._tcp.".to_string(),
+                domain: "local.".to_string(),
+                instance: "HP LaserJet 400".to_string(),
+            })
+        );
+        let removed = parse_browse_line(
+            "12:00:01.000  Rmv        0   4 .                    _tcp.local.          _ipp",
+        )
+        .unwrap();
+        assert!(!removed.added);
+        assert_eq!(
+            format!("{}.{}", removed.instance, removed.regtype),
+            "_ipp._tcp.local."
+        );
+        assert_eq!(parse_browse_line("Browsing for _ipp._tcp"), None);
+        assert_eq!(
+            parse_browse_line(
+                "Timestamp     A/R    Flags  if Domain               Service Type         Instance Name"
+            ),
+            None
+        );
+        assert_eq!(parse_browse_line("12:00:00.000  ...STARTING..."), None);
+    }
+
+    #[test]
+    fn test_parse_lookup() {
+        assert_eq!(
+            parse_lookup_line(
+                "12:00:00.002  HP\\032LaserJet\\032400._ipp._tcp.local. can be reached at hp.local.:631 (interface 4)"
+            ),
+            Some(("hp.local.".to_string(), 631))
+        );
+        assert_eq!(
+            parse_lookup_line("Lookup HP LaserJet 400._ipp._tcp.local"),
+            None
+        );
+        assert_eq!(
+            parse_txt_line(" rp=ipp/print note=Second\\ floor\\\\A empty= bell\\x07"),
+            ["rp=ipp/print", "note=Second floor\\A", "empty=", "bell\x07"]
+        );
+        assert_eq!(
+            parse_address_line(
+                "12:00:00.003  Add        3  4 hp.local.                    192.168.1.20                                 120"
+            ),
+            Some("192.168.1.20".parse().unwrap())
+        );
+        assert_eq!(
+            parse_address_line(
+                "12:00:00.003  Add        2  4 hp.local.                    FE80:0000:0000:0000:0000:0000:0000:0001%en0  120"
+            ),
+            Some("fe80::1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_address_line(
+                "12:00:09.000  Rmv        0  4 hp.local.                    192.168.1.20                                 0"
+            ),
+            None
+        );
+    }
+}
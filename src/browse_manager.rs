@@ -0,0 +1,304 @@
+#![forbid(unsafe_code)]
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// Browses started right away before pacing kicks in
+const BROWSE_BURST: u32 = 4;
+// One more browse may start per interval once the burst is used up
+pub const BROWSE_SPACING: Duration = Duration::from_millis(250);
+// Delay before restarting a failed browse, doubled with every further failure
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+// Failures in a row after which a type is given up
+const MAX_BROWSE_ATTEMPTS: u32 = 5;
+// A browse running this long counts as healthy again, its failures are forgotten
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+// What happens to a browse that failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum Retry {
+    After(Duration),
+    GiveUp,
+}
+
+// A type whose browse keeps failing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowseFailure {
+    pub attempts: u32,
+    pub error: String,
+}
+
+// Starts the browses of discovered service types at a polite pace, every new
+// browse sends its queries, so a network announcing hundreds of types would
+// otherwise get a query storm (RFC 6762 section 7)
+#[derive(Debug)]
+pub struct BrowseManager {
+    // Types waiting for their browse to start, oldest first
+    queue: VecDeque<String>,
+    // Types whose browse was started, each type is browsed once, with the
+    // generation and start of the browse to tell a restarted one from the old
+    started: HashMap<String, (u64, Instant)>,
+    next_generation: u64,
+    // Failed types waiting for their restart, by when it is due
+    retries: Vec<(Instant, String)>,
+    failures: BTreeMap<String, BrowseFailure>,
+    tokens: u32,
+    last_refill: Instant,
+    // Browses running at once, 0 for no limit
+    max_started: usize,
+}
+
+impl BrowseManager {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            started: HashMap::new(),
+            next_generation: 0,
+            retries: Vec::new(),
+            failures: BTreeMap::new(),
+            tokens: BROWSE_BURST,
+            last_refill: now,
+            max_started: 0,
+        }
+    }
+
+    // Queued types wait while this many browses run, 0 starts them all
+    pub fn set_max_started(&mut self, max_started: usize) {
+        self.max_started = max_started;
+    }
+
+    // False if the type is already browsed or waiting
+    pub fn enqueue(&mut self, service_type: &str) -> bool {
+        if self.started.contains_key(service_type)
+            || self.queue.iter().any(|t| t == service_type)
+            || self.retries.iter().any(|(_, t)| t == service_type)
+        {
+            return false;
+        }
+        self.queue.push_back(service_type.to_string());
+        true
+    }
+
+    // Lets a failed or removed type be browsed again once rediscovered
+    pub fn forget(&mut self, service_type: &str) {
+        self.started.remove(service_type);
+        self.queue.retain(|t| t != service_type);
+        self.retries.retain(|(_, t)| t != service_type);
+        self.failures.remove(service_type);
+    }
+
+    // Queues all started browses again, e.g. to restart them for a rescan
+    pub fn requeue_started(&mut self) -> Vec<String> {
+        let mut restarted: Vec<String> = self.started.drain().map(|(t, _)| t).collect();
+        restarted.sort();
+        for service_type in &restarted {
+            if !self.queue.contains(service_type) {
+                self.queue.push_back(service_type.clone());
+            }
+        }
+        restarted
+    }
+
+    // The types whose browse may start now, they count as started from here on
+    pub fn take_ready(&mut self, now: Instant) -> Vec<String> {
+        let (due, waiting) = std::mem::take(&mut self.retries)
+            .into_iter()
+            .partition(|(at, _)| *at <= now);
+        self.retries = waiting;
+        for (_, service_type) in due {
+            if !self.queue.contains(&service_type) {
+                self.queue.push_back(service_type);
+            }
+        }
+        let refills = now.duration_since(self.last_refill).as_millis() / BROWSE_SPACING.as_millis();
+        if refills > 0 {
+            self.tokens = (self.tokens + refills as u32).min(BROWSE_BURST);
+            self.last_refill += BROWSE_SPACING * refills as u32;
+        }
+        let room = match self.max_started {
+            0 => usize::MAX,
+            max => max.saturating_sub(self.started.len()),
+        };
+        let count = (self.tokens as usize).min(self.queue.len()).min(room);
+        self.tokens -= count as u32;
+        let ready: Vec<String> = self.queue.drain(..count).collect();
+        for service_type in &ready {
+            self.started
+                .insert(service_type.clone(), (self.next_generation, now));
+            self.next_generation += 1;
+        }
+        ready
+    }
+
+    // Generation of the running browse of the type
+    pub fn generation(&self, service_type: &str) -> Option<u64> {
+        self.started
+            .get(service_type)
+            .map(|(generation, _)| *generation)
+    }
+
+    // The browse of the type could not start or stopped, it is restarted after
+    // a delay growing with every failure in a row, or given up after too many
+    pub fn fail(&mut self, service_type: &str, error: &str, now: Instant) -> Retry {
+        if let Some((_, started_at)) = self.started.remove(service_type)
+            && now.duration_since(started_at) >= HEALTHY_AFTER
+        {
+            self.failures.remove(service_type);
+        }
+        let failure = self
+            .failures
+            .entry(service_type.to_string())
+            .or_insert(BrowseFailure {
+                attempts: 0,
+                error: String::new(),
+            });
+        failure.attempts += 1;
+        failure.error = error.to_string();
+        if failure.attempts >= MAX_BROWSE_ATTEMPTS {
+            self.failures.remove(service_type);
+            return Retry::GiveUp;
+        }
+        let delay = RETRY_DELAY
+            .saturating_mul(1 << (failure.attempts - 1))
+            .min(MAX_RETRY_DELAY);
+        self.retries.push((now + delay, service_type.to_string()));
+        Retry::After(delay)
+    }
+
+    // A browse ended without being stopped, None if it was already replaced by
+    // a newer one, e.g. by a rescan
+    pub fn ended(&mut self, service_type: &str, generation: u64, now: Instant) -> Option<Retry> {
+        if self.generation(service_type) != Some(generation) {
+            return None;
+        }
+        Some(self.fail(service_type, "browse ended", now))
+    }
+
+    // Types whose browse is waiting for its restart
+    pub fn failures(&self) -> &BTreeMap<String, BrowseFailure> {
+        &self.failures
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn started(&self) -> usize {
+        self.started.len()
+    }
+
+    // Queued types held back by the limit of running browses rather than the pace
+    pub fn starved(&self) -> usize {
+        if self.max_started == 0 || self.started.len() < self.max_started {
+            0
+        } else {
+            self.queue.len()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_paced() {
+        let start = Instant::now();
+        let mut manager = BrowseManager::new(start);
+        for i in 0..10 {
+            assert!(manager.enqueue(&format!("_type{}._tcp.local.", i)));
+        }
+        assert!(!manager.enqueue("_type0._tcp.local."));
+
+        assert_eq!(manager.take_ready(start).len(), 4);
+        assert!(manager.take_ready(start).is_empty());
+        assert_eq!(manager.take_ready(start + BROWSE_SPACING).len(), 1);
+        assert_eq!(manager.take_ready(start + BROWSE_SPACING * 3).len(), 2);
+        // The bucket never holds more than a burst
+        assert_eq!(manager.take_ready(start + BROWSE_SPACING * 100).len(), 3);
+        assert_eq!(manager.queued(), 0);
+        assert_eq!(manager.started(), 10);
+
+        // Started types are deduplicated too
+        assert!(!manager.enqueue("_type9._tcp.local."));
+    }
+
+    #[test]
+    fn test_failed_browses_restart_with_backoff() {
+        let start = Instant::now();
+        let mut manager = BrowseManager::new(start);
+        manager.enqueue("_http._tcp.local.");
+        assert_eq!(manager.take_ready(start).len(), 1);
+
+        let generation = manager.generation("_http._tcp.local.").unwrap();
+        assert_eq!(
+            manager.ended("_http._tcp.local.", generation, start),
+            Some(Retry::After(RETRY_DELAY))
+        );
+        assert_eq!(manager.failures()["_http._tcp.local."].attempts, 1);
+        // Not before the delay, and not started twice meanwhile
+        assert!(!manager.enqueue("_http._tcp.local."));
+        assert!(manager.take_ready(start).is_empty());
+        let restart = start + RETRY_DELAY;
+        assert_eq!(manager.take_ready(restart), ["_http._tcp.local."]);
+
+        // Ends of replaced browses are ignored
+        assert_eq!(
+            manager.ended("_http._tcp.local.", generation, restart),
+            None
+        );
+        assert_eq!(
+            manager.fail("_http._tcp.local.", "no socket", restart),
+            Retry::After(RETRY_DELAY * 2)
+        );
+        assert_eq!(manager.failures()["_http._tcp.local."].error, "no socket");
+        for _ in 2..MAX_BROWSE_ATTEMPTS - 1 {
+            manager.fail("_http._tcp.local.", "no socket", restart);
+        }
+        assert_eq!(
+            manager.fail("_http._tcp.local.", "no socket", restart),
+            Retry::GiveUp
+        );
+        assert!(manager.failures().is_empty());
+    }
+
+    #[test]
+    fn test_max_started() {
+        let start = Instant::now();
+        let mut manager = BrowseManager::new(start);
+        manager.set_max_started(2);
+        for i in 0..3 {
+            manager.enqueue(&format!("_type{}._tcp.local.", i));
+        }
+        assert_eq!(manager.take_ready(start).len(), 2);
+        assert!(manager.take_ready(start + BROWSE_SPACING * 4).is_empty());
+        assert_eq!(manager.queued(), 1);
+        assert_eq!(manager.starved(), 1);
+
+        // A given up type makes room for the next one
+        manager.forget("_type0._tcp.local.");
+        assert_eq!(
+            manager.take_ready(start + BROWSE_SPACING * 4),
+            ["_type2._tcp.local."]
+        );
+    }
+
+    #[test]
+    fn test_forget_and_requeue() {
+        let start = Instant::now();
+        let mut manager = BrowseManager::new(start);
+        manager.enqueue("_http._tcp.local.");
+        manager.enqueue("_ipp._tcp.local.");
+        assert_eq!(manager.take_ready(start).len(), 2);
+
+        manager.forget("_ipp._tcp.local.");
+        assert_eq!(manager.requeue_started(), ["_http._tcp.local."]);
+        assert_eq!(manager.queued(), 1);
+        assert!(manager.enqueue("_ipp._tcp.local."));
+        assert_eq!(
+            manager.take_ready(start + BROWSE_SPACING * 2),
+            ["_http._tcp.local.", "_ipp._tcp.local."]
+        );
+    }
+}
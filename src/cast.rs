@@ -0,0 +1,123 @@
+#![forbid(unsafe_code)]
+
+use crate::http_probe::{self, HttpRequest};
+use serde_json::Value;
+use std::time::Duration;
+
+const EUREKA_INFO_PATH: &str =
+    "/setup/eureka_info?params=name,build_info,device_info,uptime&options=detail";
+// Newer firmware only answers over TLS, older one only over plain HTTP
+const EUREKA_TLS_PORT: u16 = 8443;
+const EUREKA_PORT: u16 = 8008;
+
+// What the local setup API of a cast device tells about it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CastDeviceInfo {
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub build: Option<String>,
+    pub uptime_secs: Option<u64>,
+}
+
+pub async fn query_device_info(
+    address: &str,
+    host: &str,
+    timeout: Duration,
+) -> Result<CastDeviceInfo, String> {
+    let mut errors = Vec::new();
+    for (port, tls) in [(EUREKA_TLS_PORT, true), (EUREKA_PORT, false)] {
+        let request = HttpRequest {
+            address,
+            port,
+            host,
+            path: EUREKA_INFO_PATH,
+            tls,
+        };
+        match http_probe::get(&request, timeout).await {
+            Ok((200, body)) => return parse_eureka_info(&body),
+            Ok((status, _)) => errors.push(format!("port {}: HTTP status {}", port, status)),
+            Err(e) => errors.push(format!("port {}: {}", port, e)),
+        }
+    }
+    Err(errors.join(", "))
+}
+
+fn parse_eureka_info(body: &[u8]) -> Result<CastDeviceInfo, String> {
+    let info: Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let text = |value: &Value| value.as_str().map(str::to_string);
+    Ok(CastDeviceInfo {
+        name: text(&info["name"]),
+        manufacturer: text(&info["device_info"]["manufacturer"]),
+        model: text(&info["device_info"]["model_name"]),
+        build: text(&info["build_info"]["cast_build_revision"]),
+        // Older firmware reports it at the top level, newer one in the device info
+        uptime_secs: info["uptime"]
+            .as_f64()
+            .or(info["device_info"]["uptime"].as_f64())
+            .map(|uptime| uptime as u64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const EUREKA_INFO: &str = r#"{
+        "name": "Living Room TV",
+        "uptime": 3725.4,
+        "build_info": {"cast_build_revision": "1.56.281627"},
+        "device_info": {"manufacturer": "Google Inc.", "model_name": "Chromecast Ultra"}
+    }"#;
+
+    #[test]
+    fn test_parse_eureka_info() {
+        assert_eq!(
+            parse_eureka_info(EUREKA_INFO.as_bytes()),
+            Ok(CastDeviceInfo {
+                name: Some("Living Room TV".to_string()),
+                manufacturer: Some("Google Inc.".to_string()),
+                model: Some("Chromecast Ultra".to_string()),
+                build: Some("1.56.281627".to_string()),
+                uptime_secs: Some(3725),
+            })
+        );
+        assert_eq!(parse_eureka_info(b"{}"), Ok(CastDeviceInfo::default()));
+        assert!(parse_eureka_info(b"<html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_eureka_info() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let len = socket.read(&mut request).await.unwrap();
+            assert!(request[..len].starts_with(b"GET /setup/eureka_info?"));
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                EUREKA_INFO.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(EUREKA_INFO.as_bytes()).await.unwrap();
+        });
+
+        let request = HttpRequest {
+            address: "127.0.0.1",
+            port,
+            host: "cast.local",
+            path: EUREKA_INFO_PATH,
+            tls: false,
+        };
+        let (status, body) = http_probe::get(&request, Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(
+            parse_eureka_info(&body).unwrap().model.as_deref(),
+            Some("Chromecast Ultra")
+        );
+    }
+}
@@ -0,0 +1,650 @@
+#![forbid(unsafe_code)]
+
+use crate::backend::BackendKind;
+use crate::device_category::IconStyle;
+use crate::discovery_events::EventKind;
+use crate::event_log::EventLogTarget;
+use crate::home_assistant::HomeAssistantComponent;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub actions: Vec<ActionConfig>,
+    // Rank routable addresses before link-local ones when picking the address
+    // to show, copy and connect to
+    pub prefer_routable_addresses: bool,
+    // TCP ports probed by the port scan of a host
+    pub port_scan_ports: Vec<u16>,
+    // Whether device categories are shown as text, emoji or Nerd Font glyphs
+    pub category_icons: IconStyle,
+    // Friendly names by service type, in addition to and overriding the built-in ones
+    pub service_type_names: HashMap<String, String>,
+    // Flag HomeKit accessories that are open for pairing in the list
+    pub highlight_unpaired_homekit: bool,
+    // Command lines offered for copying, replace the built-in ones when given
+    pub copy_commands: Vec<CopyCommandConfig>,
+    // Domains whose service types are enumerated, "local." is browsed via mDNS
+    pub browse_domains: Vec<String>,
+    // DNS server asked for DNS-SD of the other domains, the system's ones if unset
+    pub unicast_dns_server: Option<String>,
+    // Online services neither announced nor resolved for this long are marked stale, 0 never
+    pub stale_after_secs: u64,
+    // Offline services are removed after this long, 0 keeps them until removed by hand
+    pub offline_grace_secs: u64,
+    // What discovers the services, the mdns-sd daemon or the platform's mDNSResponder
+    pub backend: BackendKind,
+    // Hop limit of the app's own multicast queries, the daemon always sends with 255
+    pub multicast_ttl: u8,
+    // Whether multicast sent by this host is looped back, so local responders are seen
+    pub multicast_loop: bool,
+    // Interfaces mDNS is used on, all if empty, the first one's IPv4 address sends
+    // the app's own queries
+    pub multicast_interfaces: Vec<String>,
+    // How many browses and probes may run at once
+    pub limits: LimitsConfig,
+    // Publish discovery events to an MQTT broker when set
+    pub mqtt: Option<MqttConfig>,
+    // Announce services of the selected types to Home Assistant via MQTT discovery
+    // when set, using the broker of the mqtt section
+    pub home_assistant: Option<HomeAssistantConfig>,
+    // Desktop notifications are shown for events matching any of these
+    pub notifications: Vec<NotificationRule>,
+    // Log discovery events to syslog or the journal when set
+    pub event_log: Option<EventLogTarget>,
+    // Expected services checked against the discovered ones when set
+    pub inventory: Option<PathBuf>,
+    // Remember the services found on disk, the ones absent on the next start are
+    // listed dimmed until seen again
+    pub history: bool,
+    // Discovery events are appended to this session file when set, by --record only
+    #[serde(skip)]
+    pub record: Option<PathBuf>,
+    // Start the TUI with the default view instead of the one of the last session,
+    // by --fresh only
+    #[serde(skip)]
+    pub fresh_view: bool,
+    // List a service seen by several --connect agents once, attributed to the
+    // one that reported it last, by --merge-agents only
+    #[serde(skip)]
+    pub merge_agents: bool,
+}
+
+// Built-in copy command templates as service type, name and command
+const DEFAULT_COPY_COMMANDS: &[(&str, &str, &str)] = &[
+    ("_http._tcp", "curl", "curl http://{host}:{port}/"),
+    ("_https._tcp", "curl", "curl -k https://{host}:{port}/"),
+    ("_ssh._tcp", "ssh", "ssh {host} -p {port}"),
+    ("_sftp-ssh._tcp", "sftp", "sftp -P {port} {host}"),
+    ("_rfb._tcp", "vncviewer", "vncviewer {host}:{port}"),
+    ("_smb._tcp", "smbclient", "smbclient -L {host} -p {port} -N"),
+    (
+        "_ipp._tcp",
+        "ipptool",
+        "ipptool -tv ipp://{host}:{port}/ipp/print get-printer-attributes.test",
+    ),
+];
+
+const DEFAULT_PORT_SCAN_PORTS: [u16; 28] = [
+    21, 22, 23, 25, 53, 80, 110, 139, 143, 443, 445, 515, 548, 554, 631, 1883, 3389, 5000, 5001,
+    5900, 8000, 8008, 8009, 8080, 8443, 8883, 9100, 62078,
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            actions: Vec::new(),
+            prefer_routable_addresses: true,
+            port_scan_ports: DEFAULT_PORT_SCAN_PORTS.to_vec(),
+            category_icons: IconStyle::Label,
+            service_type_names: HashMap::new(),
+            highlight_unpaired_homekit: true,
+            copy_commands: DEFAULT_COPY_COMMANDS
+                .iter()
+                .map(|(service_type, name, command)| CopyCommandConfig {
+                    service_type: service_type.to_string(),
+                    name: name.to_string(),
+                    command: command.to_string(),
+                })
+                .collect(),
+            browse_domains: vec!["local.".to_string()],
+            unicast_dns_server: None,
+            stale_after_secs: 600,
+            offline_grace_secs: 0,
+            backend: BackendKind::MdnsSd,
+            multicast_ttl: 255,
+            multicast_loop: true,
+            multicast_interfaces: Vec::new(),
+            limits: LimitsConfig::default(),
+            mqtt: None,
+            home_assistant: None,
+            notifications: Vec::new(),
+            event_log: None,
+            inventory: None,
+            history: false,
+            record: None,
+            fresh_view: false,
+            merge_agents: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    // Service types browsed at once, further ones wait for a browse to be
+    // given up, 0 browses all
+    pub max_browses: usize,
+    // Reachability probes of the listed services running at once, 0 runs all
+    pub max_tcp_probes: usize,
+    // Ports of a port scan probed at once, 0 probes all
+    pub max_port_probes: usize,
+    // Reverse DNS lookups of new addresses running at once, 0 runs all
+    pub max_reverse_lookups: usize,
+    // Service lookups of the bonjour backend running at once, each runs two
+    // dns-sd processes, 0 runs all
+    pub max_bonjour_lookups: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            // Browses don't end on their own, types beyond a limit would wait
+            max_browses: 0,
+            max_tcp_probes: 16,
+            max_port_probes: 32,
+            max_reverse_lookups: 8,
+            max_bonjour_lookups: 8,
+        }
+    }
+}
+
+impl LimitsConfig {
+    // Permits of a probe limit, as many as a semaphore takes for 0
+    pub fn permits(limit: usize) -> usize {
+        match limit {
+            0 => tokio::sync::Semaphore::MAX_PERMITS,
+            limit => limit,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // Topic per event, {event}, {type} and {name} are substituted
+    pub topic: String,
+    // Retained messages let late subscribers see the last event of a topic
+    pub retain: bool,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "mdns-tui-browser".to_string(),
+            username: None,
+            password: None,
+            topic: "mdns-tui-browser/{event}/{type}/{name}".to_string(),
+            retain: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HomeAssistantConfig {
+    // Topic prefix Home Assistant listens on for discovery configs
+    pub discovery_prefix: String,
+    // Entity created per service
+    pub component: HomeAssistantComponent,
+    // Service types without domain whose instances become entities
+    pub service_types: Vec<String>,
+    // Prefix of the state and attribute topics of the entities
+    pub state_topic_prefix: String,
+}
+
+impl Default for HomeAssistantConfig {
+    fn default() -> Self {
+        Self {
+            discovery_prefix: "homeassistant".to_string(),
+            component: HomeAssistantComponent::BinarySensor,
+            service_types: Vec::new(),
+            state_topic_prefix: "mdns-tui-browser/home-assistant".to_string(),
+        }
+    }
+}
+
+impl HomeAssistantConfig {
+    pub fn applies_to(&self, service_type: &str) -> bool {
+        self.service_types
+            .iter()
+            .any(|configured| is_service_type(service_type, configured))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationRule {
+    pub events: Vec<EventKind>,
+    // Service type without domain, all types if unset
+    pub service_type: Option<String>,
+    // Only services on the watch list
+    pub watched_only: bool,
+}
+
+impl Default for NotificationRule {
+    fn default() -> Self {
+        Self {
+            events: vec![EventKind::Added, EventKind::Removed],
+            service_type: None,
+            watched_only: false,
+        }
+    }
+}
+
+impl NotificationRule {
+    pub fn applies_to(&self, service_type: &str) -> bool {
+        self.service_type
+            .as_deref()
+            .is_none_or(|configured| is_service_type(service_type, configured))
+    }
+}
+
+// A command template offered in the actions popup for services of one type
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ActionConfig {
+    // Service type without domain, e.g. "_ipp._tcp"
+    pub service_type: String,
+    pub name: String,
+    // Split into arguments like a shell would, placeholders are substituted per argument
+    pub command: String,
+    // Interactive commands take over the terminal, others run in the background
+    #[serde(default)]
+    pub terminal: bool,
+}
+
+impl ActionConfig {
+    pub fn applies_to(&self, service_type: &str) -> bool {
+        is_service_type(service_type, &self.service_type)
+    }
+}
+
+// A command line template put on the clipboard for services of one type
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CopyCommandConfig {
+    // Service type without domain, e.g. "_ssh._tcp"
+    pub service_type: String,
+    pub name: String,
+    // Same placeholders as action commands
+    pub command: String,
+}
+
+impl CopyCommandConfig {
+    pub fn applies_to(&self, service_type: &str) -> bool {
+        is_service_type(service_type, &self.service_type)
+    }
+}
+
+pub fn is_service_type(service_type: &str, configured: &str) -> bool {
+    let configured = configured.trim_end_matches('.');
+    service_type
+        .strip_prefix(configured)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+impl Config {
+    // An explicitly given file has to exist, the default one is optional
+    pub fn load(path: Option<&Path>) -> Result<Self, crate::Error> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_config_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(source) => return Err(crate::Error::ReadConfig { path, source }),
+        };
+        Self::parse(&content).map_err(|source| crate::Error::Config { path, source })
+    }
+
+    fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    pub fn service_type_name(&self, service_type: &str) -> Option<&str> {
+        crate::service_names::friendly_name(&self.service_type_names, service_type)
+    }
+
+    pub fn copy_commands_for<'a>(
+        &'a self,
+        service_type: &'a str,
+    ) -> impl Iterator<Item = &'a CopyCommandConfig> + 'a {
+        self.copy_commands
+            .iter()
+            .filter(move |command| command.applies_to(service_type))
+    }
+
+    // Configured domains as fully qualified names without duplicates, "local." if none
+    pub fn browse_domains(&self) -> Vec<String> {
+        let mut domains: Vec<String> = Vec::new();
+        for domain in &self.browse_domains {
+            let domain = domain.trim_matches('.');
+            if domain.is_empty() {
+                continue;
+            }
+            let domain = format!("{}.", domain.to_lowercase());
+            if !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        }
+        if domains.is_empty() {
+            domains.push("local.".to_string());
+        }
+        domains
+    }
+
+    pub fn actions_for<'a>(
+        &'a self,
+        service_type: &'a str,
+    ) -> impl Iterator<Item = &'a ActionConfig> + 'a {
+        self.actions
+            .iter()
+            .filter(move |action| action.applies_to(service_type))
+    }
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("mdns-tui-browser").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty_config() {
+        let config = Config::parse("").unwrap();
+        assert!(config.actions.is_empty());
+        assert!(config.prefer_routable_addresses);
+        assert!(config.highlight_unpaired_homekit);
+    }
+
+    #[test]
+    fn test_parse_port_scan_ports() {
+        assert_eq!(Config::parse("").unwrap().port_scan_ports.len(), 28);
+        let config = Config::parse("port_scan_ports = [22, 80]").unwrap();
+        assert_eq!(config.port_scan_ports, vec![22, 80]);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(Config::parse("").unwrap().limits, LimitsConfig::default());
+        let config = Config::parse("[limits]\nmax_browses = 64\nmax_tcp_probes = 2").unwrap();
+        assert_eq!(config.limits.max_browses, 64);
+        assert_eq!(config.limits.max_tcp_probes, 2);
+        assert_eq!(config.limits.max_port_probes, 32);
+        assert_eq!(LimitsConfig::permits(2), 2);
+        assert_eq!(
+            LimitsConfig::permits(0),
+            tokio::sync::Semaphore::MAX_PERMITS
+        );
+    }
+
+    #[test]
+    fn test_parse_category_icons() {
+        assert_eq!(Config::parse("").unwrap().category_icons, IconStyle::Label);
+        let config = Config::parse("category_icons = \"nerd-font\"").unwrap();
+        assert_eq!(config.category_icons, IconStyle::NerdFont);
+        assert!(Config::parse("category_icons = \"ascii\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_service_type_names() {
+        let config = Config::parse(
+            r#"
+            [service_type_names]
+            "_foo._tcp" = "Foo devices"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.service_type_name("_foo._tcp.local."),
+            Some("Foo devices")
+        );
+        assert_eq!(
+            config.service_type_name("_ipp._tcp.local."),
+            Some("Printers (IPP)")
+        );
+    }
+
+    #[test]
+    fn test_parse_prefer_routable_addresses() {
+        let config = Config::parse("prefer_routable_addresses = false").unwrap();
+        assert!(!config.prefer_routable_addresses);
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let config = Config::parse(
+            r#"
+            [[actions]]
+            service_type = "_ipp._tcp"
+            name = "Open printer"
+            command = "xdg-open ipp://{host}:{port}"
+
+            [[actions]]
+            service_type = "_vnc._tcp"
+            name = "VNC viewer"
+            command = "vncviewer {address}:{port}"
+            terminal = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.actions.len(), 2);
+        assert!(!config.actions[0].terminal);
+        assert!(config.actions[1].terminal);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_fields() {
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_actions_for_service_type() {
+        let config = Config::parse(
+            r#"
+            [[actions]]
+            service_type = "_ipp._tcp"
+            name = "Open printer"
+            command = "xdg-open ipp://{host}:{port}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.actions_for("_ipp._tcp.local.").count(), 1);
+        assert_eq!(config.actions_for("_ipp._tcp").count(), 1);
+        assert_eq!(config.actions_for("_ipps._tcp.local.").count(), 0);
+        assert_eq!(config.actions_for("_http._tcp.local.").count(), 0);
+    }
+
+    #[test]
+    fn test_copy_commands() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.copy_commands_for("_ssh._tcp.local.").count(), 1);
+        assert_eq!(config.copy_commands_for("_foo._tcp.local.").count(), 0);
+
+        let config = Config::parse(
+            r#"
+            [[copy_commands]]
+            service_type = "_foo._tcp"
+            name = "nc"
+            command = "nc {address} {port}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.copy_commands_for("_foo._tcp.local.").count(), 1);
+        assert_eq!(config.copy_commands_for("_ssh._tcp.local.").count(), 0);
+    }
+
+    #[test]
+    fn test_browse_domains() {
+        assert_eq!(Config::parse("").unwrap().browse_domains(), vec!["local."]);
+        let config =
+            Config::parse(r#"browse_domains = ["local", ".Corp.Example.com", "local.", ""]"#)
+                .unwrap();
+        assert_eq!(config.browse_domains(), vec!["local.", "corp.example.com."]);
+        let config = Config::parse("browse_domains = []").unwrap();
+        assert_eq!(config.browse_domains(), vec!["local."]);
+    }
+
+    #[test]
+    fn test_parse_unicast_dns_server() {
+        assert_eq!(Config::parse("").unwrap().unicast_dns_server, None);
+        let config = Config::parse(r#"unicast_dns_server = "192.168.1.1""#).unwrap();
+        assert_eq!(config.unicast_dns_server.as_deref(), Some("192.168.1.1"));
+    }
+
+    #[test]
+    fn test_parse_stale_after_secs() {
+        assert_eq!(Config::parse("").unwrap().stale_after_secs, 600);
+        let config = Config::parse("stale_after_secs = 0").unwrap();
+        assert_eq!(config.stale_after_secs, 0);
+    }
+
+    #[test]
+    fn test_parse_offline_grace_secs() {
+        assert_eq!(Config::parse("").unwrap().offline_grace_secs, 0);
+        let config = Config::parse("offline_grace_secs = 3600").unwrap();
+        assert_eq!(config.offline_grace_secs, 3600);
+    }
+
+    #[test]
+    fn test_parse_backend() {
+        assert_eq!(Config::parse("").unwrap().backend, BackendKind::MdnsSd);
+        let config = Config::parse(r#"backend = "bonjour""#).unwrap();
+        assert_eq!(config.backend, BackendKind::Bonjour);
+        assert!(Config::parse(r#"backend = "avahi""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_multicast_options() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.multicast_ttl, 255);
+        assert!(config.multicast_loop);
+        assert!(config.multicast_interfaces.is_empty());
+
+        let config = Config::parse(
+            "multicast_ttl = 1\nmulticast_loop = false\nmulticast_interfaces = [\"eth0.20\"]",
+        )
+        .unwrap();
+        assert_eq!(config.multicast_ttl, 1);
+        assert!(!config.multicast_loop);
+        assert_eq!(config.multicast_interfaces, ["eth0.20"]);
+        assert!(Config::parse("multicast_ttl = 256").is_err());
+    }
+
+    #[test]
+    fn test_parse_mqtt() {
+        assert_eq!(Config::parse("").unwrap().mqtt, None);
+        let config = Config::parse(
+            r#"
+[mqtt]
+host = "broker.lan"
+topic = "lan/{event}"
+"#,
+        )
+        .unwrap();
+        let mqtt = config.mqtt.unwrap();
+        assert_eq!(mqtt.host, "broker.lan");
+        assert_eq!(mqtt.port, 1883);
+        assert_eq!(mqtt.topic, "lan/{event}");
+        assert!(Config::parse("[mqtt]\nbroker = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_home_assistant() {
+        assert_eq!(Config::parse("").unwrap().home_assistant, None);
+        let config = Config::parse(
+            r#"
+[home_assistant]
+component = "device_tracker"
+service_types = ["_companion-link._tcp"]
+"#,
+        )
+        .unwrap();
+        let home_assistant = config.home_assistant.unwrap();
+        assert_eq!(home_assistant.discovery_prefix, "homeassistant");
+        assert_eq!(
+            home_assistant.component,
+            HomeAssistantComponent::DeviceTracker
+        );
+        assert!(home_assistant.applies_to("_companion-link._tcp.local."));
+        assert!(!home_assistant.applies_to("_ipp._tcp.local."));
+        assert!(
+            Config::parse(
+                "[home_assistant]
+component = \"sensor\""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_notifications() {
+        let config = Config::parse(
+            r#"
+[[notifications]]
+watched_only = true
+
+[[notifications]]
+events = ["added"]
+service_type = "_ipp._tcp"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.notifications.len(), 2);
+        assert_eq!(
+            config.notifications[0].events,
+            [EventKind::Added, EventKind::Removed]
+        );
+        assert!(config.notifications[1].applies_to("_ipp._tcp.local."));
+        assert!(!config.notifications[1].applies_to("_ipps._tcp.local."));
+        assert!(Config::parse("[[notifications]]\nevents = [\"gone\"]").is_err());
+    }
+
+    #[test]
+    fn test_parse_event_log() {
+        assert_eq!(Config::parse("").unwrap().event_log, None);
+        let config = Config::parse("event_log = \"journald\"").unwrap();
+        assert_eq!(config.event_log, Some(EventLogTarget::Journald));
+        assert!(Config::parse("event_log = \"stdout\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_inventory_path() {
+        assert_eq!(Config::parse("").unwrap().inventory, None);
+        let config = Config::parse("inventory = \"/etc/lab.toml\"").unwrap();
+        assert_eq!(config.inventory, Some(PathBuf::from("/etc/lab.toml")));
+    }
+
+    #[test]
+    fn test_load_missing_explicit_config_fails() {
+        assert!(matches!(
+            Config::load(Some(Path::new("/nonexistent/config.toml"))),
+            Err(crate::Error::ReadConfig { .. })
+        ));
+    }
+}
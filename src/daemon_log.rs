@@ -0,0 +1,124 @@
+#![forbid(unsafe_code)]
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+const MAX_LINES: usize = 200;
+
+// Keeps the most recent log lines of the mdns-sd daemon in memory, writing them to
+// the terminal would garble the TUI
+struct DaemonLogger {
+    lines: Mutex<VecDeque<String>>,
+}
+
+static LOGGER: DaemonLogger = DaemonLogger {
+    lines: Mutex::new(VecDeque::new()),
+};
+
+impl Log for DaemonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("mdns_sd")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {} {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            record.level(),
+            record.args()
+        );
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() == MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Installs the logger, silent until a level is picked
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Off);
+    }
+}
+
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
+// Off, Warn, Info, Debug, Trace and around again
+pub fn cycle_level() -> LevelFilter {
+    let level = next_level(log::max_level());
+    log::set_max_level(level);
+    level
+}
+
+fn next_level(level: LevelFilter) -> LevelFilter {
+    match level {
+        LevelFilter::Off => LevelFilter::Warn,
+        LevelFilter::Error | LevelFilter::Warn => LevelFilter::Info,
+        LevelFilter::Info => LevelFilter::Debug,
+        LevelFilter::Debug => LevelFilter::Trace,
+        LevelFilter::Trace => LevelFilter::Off,
+    }
+}
+
+// The last `count` lines, oldest first
+pub fn recent_lines(count: usize) -> Vec<String> {
+    let Ok(lines) = LOGGER.lines.lock() else {
+        return Vec::new();
+    };
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(count))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_level() {
+        let mut level = LevelFilter::Off;
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            level = next_level(level);
+            seen.push(level);
+        }
+        assert_eq!(
+            seen,
+            [
+                LevelFilter::Warn,
+                LevelFilter::Info,
+                LevelFilter::Debug,
+                LevelFilter::Trace,
+                LevelFilter::Off
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_daemon_records_are_kept() {
+        let record = |target| {
+            Record::builder()
+                .target(target)
+                .level(log::Level::Warn)
+                .args(format_args!("socket error"))
+                .build()
+        };
+        LOGGER.log(&record("hickory_proto::udp"));
+        LOGGER.log(&record("mdns_sd::service_daemon"));
+        let lines = recent_lines(MAX_LINES);
+        assert!(lines.iter().any(|line| line.ends_with("WARN socket error")));
+        assert_eq!(lines.len(), 1);
+    }
+}
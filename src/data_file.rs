@@ -0,0 +1,77 @@
+#![forbid(unsafe_code)]
+
+// The JSON files kept in the platform data directory across sessions: the
+// baseline, learned MACs, history, pins and view
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+
+// The file of the given name in the mdns-tui-browser data directory
+pub fn path(name: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("mdns-tui-browser").join(name))
+}
+
+// A missing or unreadable file loads as the default
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Writes a temporary file next to the path and renames it over the path, so
+// a crash or a full disk while saving leaves the previous contents intact
+pub fn save_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    // Unique per process, as two instances may save at the same time
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    let temp = PathBuf::from(temp);
+    std::fs::write(&temp, json)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp);
+            e.to_string()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("data.json");
+        assert_eq!(load_json::<BTreeMap<String, u16>>(&path), BTreeMap::new());
+
+        let data = BTreeMap::from([("printer".to_string(), 631)]);
+        save_json(&path, &data).unwrap();
+        assert_eq!(load_json::<BTreeMap<String, u16>>(&path), data);
+        // Only the file itself is left
+        let files: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["data.json"]);
+
+        std::fs::write(&path, "{").unwrap();
+        assert_eq!(load_json::<BTreeMap<String, u16>>(&path), BTreeMap::new());
+    }
+
+    #[test]
+    fn test_failed_save_keeps_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // A directory can't be replaced by a file
+        let path = dir.path().join("data.json");
+        std::fs::create_dir(&path).unwrap();
+        assert!(save_json(&path, &[1, 2]).is_err());
+        assert!(path.is_dir());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+#![forbid(unsafe_code)]
+
+use crate::config::NotificationRule;
+use crate::discovery_events::{DiscoveryEvent, EventKind};
+
+impl NotificationRule {
+    pub fn matches(&self, event: &DiscoveryEvent, watched: bool) -> bool {
+        (watched || !self.watched_only)
+            && self.events.contains(&event.event)
+            && self.applies_to(&event.service_type)
+    }
+}
+
+// Summary and body of the notification for an event
+pub fn notification_text(event: &DiscoveryEvent) -> (String, String) {
+    let summary = match event.event {
+        EventKind::Added => format!("{} appeared", event.instance()),
+        EventKind::Updated => format!("{} changed", event.instance()),
+        EventKind::Removed => format!("{} went offline", event.instance()),
+    };
+    let body = format!(
+        "{}\n{}:{}",
+        event.service_type.trim_end_matches('.'),
+        event.host.trim_end_matches('.'),
+        event.port
+    );
+    (summary, body)
+}
+
+// Shows the notification via the desktop's notification service, blocks until
+// it was handed over
+pub fn show(event: &DiscoveryEvent) -> Result<(), String> {
+    let (summary, body) = notification_text(event);
+    notify_rust::Notification::new()
+        .appname("mdns-tui-browser")
+        .summary(&summary)
+        .body(&body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFICE: &str = "Office._ipp._tcp.local.";
+
+    #[test]
+    fn test_rule_matches() {
+        let rule = NotificationRule::default();
+        assert!(rule.matches(
+            &DiscoveryEvent::for_test(EventKind::Added, OFFICE, &[]),
+            false
+        ));
+        assert!(!rule.matches(
+            &DiscoveryEvent::for_test(EventKind::Updated, OFFICE, &[]),
+            false
+        ));
+
+        let rule = NotificationRule {
+            service_type: Some("_http._tcp".to_string()),
+            ..NotificationRule::default()
+        };
+        assert!(!rule.matches(
+            &DiscoveryEvent::for_test(EventKind::Removed, OFFICE, &[]),
+            true
+        ));
+
+        let rule = NotificationRule {
+            watched_only: true,
+            ..NotificationRule::default()
+        };
+        assert!(!rule.matches(
+            &DiscoveryEvent::for_test(EventKind::Removed, OFFICE, &[]),
+            false
+        ));
+        assert!(rule.matches(
+            &DiscoveryEvent::for_test(EventKind::Removed, OFFICE, &[]),
+            true
+        ));
+    }
+
+    #[test]
+    fn test_notification_text() {
+        assert_eq!(
+            notification_text(&DiscoveryEvent::for_test(EventKind::Removed, OFFICE, &[])),
+            (
+                "Office went offline".to_string(),
+                "_ipp._tcp.local\nprinter.local:631".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,267 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceCategory {
+    Printer,
+    Tv,
+    Speaker,
+    Camera,
+    Nas,
+    Iot,
+}
+
+impl DeviceCategory {
+    pub const ALL: [DeviceCategory; 6] = [
+        DeviceCategory::Printer,
+        DeviceCategory::Tv,
+        DeviceCategory::Speaker,
+        DeviceCategory::Camera,
+        DeviceCategory::Nas,
+        DeviceCategory::Iot,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DeviceCategory::Printer => "printer",
+            DeviceCategory::Tv => "tv",
+            DeviceCategory::Speaker => "speaker",
+            DeviceCategory::Camera => "camera",
+            DeviceCategory::Nas => "nas",
+            DeviceCategory::Iot => "iot",
+        }
+    }
+
+    pub fn icon(self, style: IconStyle) -> &'static str {
+        match (style, self) {
+            (IconStyle::Label, _) => self.label(),
+            (IconStyle::Emoji, DeviceCategory::Printer) => "🖨️",
+            (IconStyle::Emoji, DeviceCategory::Tv) => "📺",
+            (IconStyle::Emoji, DeviceCategory::Speaker) => "🔊",
+            (IconStyle::Emoji, DeviceCategory::Camera) => "📷",
+            (IconStyle::Emoji, DeviceCategory::Nas) => "🗄️",
+            (IconStyle::Emoji, DeviceCategory::Iot) => "💡",
+            // Nerd Font Material Design glyphs
+            (IconStyle::NerdFont, DeviceCategory::Printer) => "\u{f042a}",
+            (IconStyle::NerdFont, DeviceCategory::Tv) => "\u{f0502}",
+            (IconStyle::NerdFont, DeviceCategory::Speaker) => "\u{f04c3}",
+            (IconStyle::NerdFont, DeviceCategory::Camera) => "\u{f0100}",
+            (IconStyle::NerdFont, DeviceCategory::Nas) => "\u{f048b}",
+            (IconStyle::NerdFont, DeviceCategory::Iot) => "\u{f0335}",
+        }
+    }
+}
+
+// How categories are shown in the services list
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IconStyle {
+    #[default]
+    Label,
+    Emoji,
+    NerdFont,
+}
+
+// What a rule looks at, service types are given without domain
+enum Matcher {
+    ServiceType(&'static str),
+    // Service type plus a TXT key whose value contains one of the needles, case insensitively
+    TxtContains(&'static str, &'static str, &'static [&'static str]),
+    // Service type plus a TXT key holding one of the numbers
+    TxtNumber(&'static str, &'static str, &'static [u32]),
+}
+
+// Checked in order, the first matching rule wins, so specific TXT based rules
+// come before the plain service type ones
+const RULES: &[(Matcher, DeviceCategory)] = &[
+    // HomeKit accessory categories
+    (
+        Matcher::TxtNumber("_hap._tcp", "ci", &[17, 18]),
+        DeviceCategory::Camera,
+    ),
+    (
+        Matcher::TxtNumber("_hap._tcp", "ci", &[31, 35, 36]),
+        DeviceCategory::Tv,
+    ),
+    (
+        Matcher::TxtNumber("_hap._tcp", "ci", &[34]),
+        DeviceCategory::Speaker,
+    ),
+    (
+        Matcher::TxtNumber("_hap._udp", "ci", &[17, 18]),
+        DeviceCategory::Camera,
+    ),
+    // Apple devices announce their model identifier
+    (
+        Matcher::TxtContains("_airplay._tcp", "model", &["AudioAccessory", "HomePod"]),
+        DeviceCategory::Speaker,
+    ),
+    (
+        Matcher::TxtContains("_raop._tcp", "am", &["AudioAccessory", "HomePod"]),
+        DeviceCategory::Speaker,
+    ),
+    (
+        Matcher::TxtContains(
+            "_googlecast._tcp",
+            "md",
+            &["Home", "Nest Audio", "Nest Mini"],
+        ),
+        DeviceCategory::Speaker,
+    ),
+    (
+        Matcher::TxtContains("_device-info._tcp", "model", &["Xserve", "TimeCapsule"]),
+        DeviceCategory::Nas,
+    ),
+    (Matcher::ServiceType("_ipp._tcp"), DeviceCategory::Printer),
+    (Matcher::ServiceType("_ipps._tcp"), DeviceCategory::Printer),
+    (
+        Matcher::ServiceType("_printer._tcp"),
+        DeviceCategory::Printer,
+    ),
+    (
+        Matcher::ServiceType("_pdl-datastream._tcp"),
+        DeviceCategory::Printer,
+    ),
+    (Matcher::ServiceType("_uscan._tcp"), DeviceCategory::Printer),
+    (
+        Matcher::ServiceType("_scanner._tcp"),
+        DeviceCategory::Printer,
+    ),
+    (Matcher::ServiceType("_googlecast._tcp"), DeviceCategory::Tv),
+    (Matcher::ServiceType("_airplay._tcp"), DeviceCategory::Tv),
+    (
+        Matcher::ServiceType("_androidtvremote2._tcp"),
+        DeviceCategory::Tv,
+    ),
+    (Matcher::ServiceType("_roku._tcp"), DeviceCategory::Tv),
+    (Matcher::ServiceType("_raop._tcp"), DeviceCategory::Speaker),
+    (
+        Matcher::ServiceType("_spotify-connect._tcp"),
+        DeviceCategory::Speaker,
+    ),
+    (Matcher::ServiceType("_sonos._tcp"), DeviceCategory::Speaker),
+    (Matcher::ServiceType("_daap._tcp"), DeviceCategory::Speaker),
+    (Matcher::ServiceType("_rtsp._tcp"), DeviceCategory::Camera),
+    (
+        Matcher::ServiceType("_axis-video._tcp"),
+        DeviceCategory::Camera,
+    ),
+    (Matcher::ServiceType("_onvif._tcp"), DeviceCategory::Camera),
+    (Matcher::ServiceType("_smb._tcp"), DeviceCategory::Nas),
+    (
+        Matcher::ServiceType("_afpovertcp._tcp"),
+        DeviceCategory::Nas,
+    ),
+    (Matcher::ServiceType("_nfs._tcp"), DeviceCategory::Nas),
+    (Matcher::ServiceType("_adisk._tcp"), DeviceCategory::Nas),
+    (Matcher::ServiceType("_hap._tcp"), DeviceCategory::Iot),
+    (Matcher::ServiceType("_hap._udp"), DeviceCategory::Iot),
+    (Matcher::ServiceType("_matter._tcp"), DeviceCategory::Iot),
+    (Matcher::ServiceType("_matterc._udp"), DeviceCategory::Iot),
+    (
+        Matcher::ServiceType("_esphomelib._tcp"),
+        DeviceCategory::Iot,
+    ),
+    (Matcher::ServiceType("_shelly._tcp"), DeviceCategory::Iot),
+    (Matcher::ServiceType("_hue._tcp"), DeviceCategory::Iot),
+    (Matcher::ServiceType("_mqtt._tcp"), DeviceCategory::Iot),
+];
+
+// Guesses the kind of device behind a service from its type and TXT records
+pub fn classify(service_type: &str, txt: &[String]) -> Option<DeviceCategory> {
+    RULES
+        .iter()
+        .find(|(matcher, _)| matches_rule(matcher, service_type, txt))
+        .map(|(_, category)| *category)
+}
+
+fn matches_rule(matcher: &Matcher, service_type: &str, txt: &[String]) -> bool {
+    match matcher {
+        Matcher::ServiceType(rule_type) => is_service_type(service_type, rule_type),
+        Matcher::TxtContains(rule_type, key, needles) => {
+            is_service_type(service_type, rule_type)
+                && txt_value(txt, key).is_some_and(|value| {
+                    let value = value.to_lowercase();
+                    needles
+                        .iter()
+                        .any(|needle| value.contains(&needle.to_lowercase()))
+                })
+        }
+        Matcher::TxtNumber(rule_type, key, numbers) => {
+            is_service_type(service_type, rule_type)
+                && txt_value(txt, key)
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .is_some_and(|number| numbers.contains(&number))
+        }
+    }
+}
+
+fn is_service_type(service_type: &str, rule_type: &str) -> bool {
+    service_type
+        .strip_prefix(rule_type)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+fn txt_value<'a>(txt: &'a [String], key: &str) -> Option<&'a str> {
+    txt.iter()
+        .filter_map(|entry| entry.split_once('='))
+        .find(|(entry_key, _)| entry_key.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txt(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn test_classify_by_service_type() {
+        assert_eq!(
+            classify("_ipp._tcp.local.", &[]),
+            Some(DeviceCategory::Printer)
+        );
+        assert_eq!(classify("_smb._tcp.local.", &[]), Some(DeviceCategory::Nas));
+        assert_eq!(classify("_ippx._tcp.local.", &[]), None);
+        assert_eq!(classify("_http._tcp.local.", &[]), None);
+    }
+
+    #[test]
+    fn test_classify_by_txt_records() {
+        assert_eq!(
+            classify("_hap._tcp.local.", &txt(&["ci=17"])),
+            Some(DeviceCategory::Camera)
+        );
+        assert_eq!(
+            classify("_hap._tcp.local.", &txt(&["ci=5"])),
+            Some(DeviceCategory::Iot)
+        );
+        assert_eq!(
+            classify("_airplay._tcp.local.", &txt(&["model=AudioAccessory5,1"])),
+            Some(DeviceCategory::Speaker)
+        );
+        assert_eq!(
+            classify("_airplay._tcp.local.", &txt(&["model=AppleTV6,2"])),
+            Some(DeviceCategory::Tv)
+        );
+        assert_eq!(
+            classify("_googlecast._tcp.local.", &txt(&["md=Google Nest Mini"])),
+            Some(DeviceCategory::Speaker)
+        );
+    }
+
+    #[test]
+    fn test_icon_styles() {
+        assert_eq!(DeviceCategory::Tv.icon(IconStyle::Label), "tv");
+        assert_eq!(DeviceCategory::Tv.icon(IconStyle::Emoji), "📺");
+        assert!(
+            DeviceCategory::ALL
+                .iter()
+                .all(|category| !category.icon(IconStyle::NerdFont).is_empty())
+        );
+    }
+}
@@ -0,0 +1,115 @@
+#![forbid(unsafe_code)]
+
+use crate::schema::SchemaVersion;
+use serde::{Deserialize, Serialize};
+
+// Events buffered per subscriber before slow ones start missing some, a few
+// times the updates applied at once so a single batch never overflows it
+pub const EVENT_CAPACITY: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    // Discovered, or back online
+    Added,
+    // Host, port, addresses or TXT changed
+    Updated,
+    // Went offline
+    Removed,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Added => "added",
+            EventKind::Updated => "updated",
+            EventKind::Removed => "removed",
+        }
+    }
+}
+
+// A change of a service as published to the event outputs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiscoveryEvent {
+    // Missing in events of agents from before the schema was versioned
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    pub event: EventKind,
+    pub fullname: String,
+    pub service_type: String,
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<String>,
+    pub txt: Vec<String>,
+    pub timestamp_micros: u64,
+}
+
+impl DiscoveryEvent {
+    // The instance name without the service type
+    pub fn instance(&self) -> &str {
+        self.fullname
+            .strip_suffix(&self.service_type)
+            .map(|name| name.trim_end_matches('.'))
+            .unwrap_or(&self.fullname)
+    }
+}
+
+// An event of a printer for the tests of the outputs, of the type its fullname
+// ends in; tests needing other values change the fields
+#[cfg(test)]
+impl DiscoveryEvent {
+    pub(crate) fn for_test(event: EventKind, fullname: &str, addrs: &[&str]) -> Self {
+        let service_type = fullname
+            .find("._")
+            .map_or("_ipp._tcp.local.", |start| &fullname[start + 1..]);
+        DiscoveryEvent {
+            schema_version: SchemaVersion,
+            event,
+            fullname: fullname.to_string(),
+            service_type: service_type.to_string(),
+            host: "printer.local.".to_string(),
+            port: 631,
+            addrs: addrs.iter().map(|addr| addr.to_string()).collect(),
+            txt: vec!["txtvers=1".to_string(), "note=Room \"4\"".to_string()],
+            timestamp_micros: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_event() {
+        let event = DiscoveryEvent {
+            schema_version: SchemaVersion,
+            event: EventKind::Added,
+            fullname: "Office Printer._ipp._tcp.local.".to_string(),
+            service_type: "_ipp._tcp.local.".to_string(),
+            host: "printer.local.".to_string(),
+            port: 631,
+            addrs: vec!["192.168.1.20".to_string()],
+            txt: vec!["rp=ipp/print".to_string()],
+            timestamp_micros: 1_000_000,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "added");
+        assert_eq!(json["port"], 631);
+        assert_eq!(event.instance(), "Office Printer");
+        assert_eq!(json["schema_version"], crate::schema::VERSION);
+        crate::schema::assert_matches_definition(&json, "event");
+    }
+
+    #[test]
+    fn test_event_for_test() {
+        let event = DiscoveryEvent::for_test(
+            EventKind::Removed,
+            "Phone._companion-link._tcp.local.",
+            &["192.168.1.30"],
+        );
+        assert_eq!(event.service_type, "_companion-link._tcp.local.");
+        assert_eq!(event.instance(), "Phone");
+        assert_eq!(event.addrs, ["192.168.1.30"]);
+    }
+}
@@ -0,0 +1,234 @@
+#![forbid(unsafe_code)]
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Why discovery could not start, the TUI had to stop or a command failed. The
+/// message says what failed, [`Error::report`] adds its causes and, where the
+/// cause is a known one, what to try about it.
+#[derive(Debug)]
+pub enum Error {
+    /// The mDNS daemon could not be started or its sockets configured.
+    Daemon(mdns_sd::Error),
+    /// mDNS could not be turned off on an interface outside the configured ones.
+    Interface {
+        name: String,
+        source: mdns_sd::Error,
+    },
+    /// Browsing for a service type could not be started.
+    Browse {
+        service_type: String,
+        source: mdns_sd::Error,
+    },
+    /// The backend of the config is not available in this build.
+    Backend(String),
+    /// The terminal could not be set up, drawn on or read from.
+    Terminal(std::io::Error),
+    /// Browsing a wide-area domain or the bonjour backend needs a Tokio
+    /// runtime, which the caller is not running on.
+    NoTokio { needed_by: String },
+    /// The config file could not be read.
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The config file is not valid.
+    Config {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// The session file to replay could not be read or parsed.
+    Session { path: PathBuf, message: String },
+    /// The trace file could not be opened.
+    TraceLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The async runtime could not be started.
+    Runtime(std::io::Error),
+    /// The API could not listen on the address.
+    Listen {
+        address: SocketAddr,
+        source: std::io::Error,
+    },
+    /// Something was asked for that needs a feature this build is without.
+    Unsupported(&'static str),
+    /// The report could not be rendered.
+    Report(String),
+    /// The output could not be written.
+    Output(std::io::Error),
+}
+
+impl Error {
+    /// What to try about the error, if its cause is a known one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Daemon(source) | Self::Browse { source, .. } => socket_hint(&source.to_string()),
+            Self::Interface { .. } => {
+                Some("check the names in multicast_interfaces or --interface against ip link")
+            }
+            // ENXIO without a controlling terminal, ENOTTY with a redirected one
+            Self::Terminal(source) if matches!(source.raw_os_error(), Some(6 | 25)) => {
+                Some("run it in an interactive terminal, or print the services with --output")
+            }
+            Self::NoTokio { .. } => Some(
+                "start the browser on a Tokio runtime, or browse local. with the mdns-sd backend only",
+            ),
+            Self::Listen { source, .. } if source.kind() == std::io::ErrorKind::AddrInUse => {
+                Some("another program listens there, pick another address with --listen")
+            }
+            _ => None,
+        }
+    }
+
+    /// The message followed by the messages of its causes and the hint, as
+    /// shown to users.
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            report.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        if let Some(hint) = self.hint() {
+            report.push_str(&format!(", {}", hint));
+        }
+        report
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daemon(_) => write!(f, "Cannot start the mDNS daemon"),
+            Self::Interface { name, .. } => {
+                write!(f, "Cannot turn off mDNS on interface {}", name)
+            }
+            Self::Browse { service_type, .. } => write!(f, "Cannot browse {}", service_type),
+            Self::Backend(message) => write!(f, "{}", message),
+            Self::Terminal(_) => write!(f, "Terminal error"),
+            Self::NoTokio { needed_by } => write!(f, "{} needs a Tokio runtime", needed_by),
+            Self::ReadConfig { path, .. } => {
+                write!(f, "Failed to read config {}", path.display())
+            }
+            Self::Config { path, .. } => write!(f, "Invalid config {}", path.display()),
+            Self::Session { path, message } => {
+                write!(f, "Cannot load session {}: {}", path.display(), message)
+            }
+            Self::TraceLog { path, .. } => {
+                write!(f, "Cannot open the trace file {}", path.display())
+            }
+            Self::Runtime(_) => write!(f, "Cannot start the async runtime"),
+            Self::Listen { address, .. } => write!(f, "Cannot listen on {}", address),
+            Self::Unsupported(message) => write!(f, "{}", message),
+            Self::Report(message) => write!(f, "Cannot render the report: {}", message),
+            Self::Output(_) => write!(f, "Cannot write the output"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Daemon(source) | Self::Interface { source, .. } | Self::Browse { source, .. } => {
+                Some(source)
+            }
+            Self::Terminal(source)
+            | Self::ReadConfig { source, .. }
+            | Self::TraceLog { source, .. }
+            | Self::Runtime(source)
+            | Self::Listen { source, .. }
+            | Self::Output(source) => Some(source),
+            Self::Config { source, .. } => Some(source),
+            Self::Backend(_)
+            | Self::NoTokio { .. }
+            | Self::Session { .. }
+            | Self::Unsupported(_)
+            | Self::Report(_) => None,
+        }
+    }
+}
+
+// The daemon only reports the OS error as text, so the usual causes of failing
+// multicast sockets are told apart by it
+pub(crate) fn socket_hint(message: &str) -> Option<&'static str> {
+    let message = message.to_lowercase();
+    if message.contains("permission denied") || message.contains("operation not permitted") {
+        Some(
+            "multicast on UDP port 5353 was refused, allow it in the firewall or sandbox or pick another network with --interface",
+        )
+    } else if message.contains("address already in use") || message.contains("address in use") {
+        Some(
+            "another program holds UDP port 5353 exclusively, stop it or use the system's responder with --backend bonjour",
+        )
+    } else if message.contains("network is unreachable") || message.contains("no route to host") {
+        Some("no network with multicast is up, connect to one first")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_error_with_hint() {
+        let error = Error::Daemon(mdns_sd::Error::Msg(
+            "bind failed: Permission denied (os error 13)".to_string(),
+        ));
+        assert_eq!(error.to_string(), "Cannot start the mDNS daemon");
+        assert_eq!(
+            error.report(),
+            "Cannot start the mDNS daemon: bind failed: Permission denied (os error 13), \
+             multicast on UDP port 5353 was refused, allow it in the firewall or sandbox or pick another network with --interface"
+        );
+    }
+
+    #[test]
+    fn test_unknown_causes_have_no_hint() {
+        let error = Error::Browse {
+            service_type: "_http._tcp.local.".to_string(),
+            source: mdns_sd::Error::Again,
+        };
+        assert_eq!(error.hint(), None);
+        assert_eq!(error.to_string(), "Cannot browse _http._tcp.local.");
+        assert_eq!(error.report(), "Cannot browse _http._tcp.local.: try again");
+        assert!(
+            Error::Terminal(std::io::Error::other("gone"))
+                .hint()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_terminal_missing() {
+        let error = Error::Terminal(std::io::Error::from_raw_os_error(25));
+        assert!(error.hint().unwrap().contains("--output"));
+    }
+
+    #[test]
+    fn test_report_follows_the_causes() {
+        let error = Error::Listen {
+            address: "127.0.0.1:8080".parse().unwrap(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "Address already in use (os error 98)",
+            ),
+        };
+        assert_eq!(error.to_string(), "Cannot listen on 127.0.0.1:8080");
+        assert_eq!(
+            error.report(),
+            "Cannot listen on 127.0.0.1:8080: Address already in use (os error 98), \
+             another program listens there, pick another address with --listen"
+        );
+    }
+
+    #[test]
+    fn test_socket_hints() {
+        assert!(socket_hint("Address already in use (os error 98)").is_some());
+        assert!(socket_hint("Network is unreachable (os error 101)").is_some());
+        assert_eq!(socket_hint("browse ended"), None);
+    }
+}
@@ -0,0 +1,229 @@
+#![forbid(unsafe_code)]
+
+use crate::discovery_events::{DiscoveryEvent, EventKind};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+const IDENTIFIER: &str = "mdns-tui-browser";
+// Structured data ID, 32473 is the enterprise number reserved for examples (RFC 5612)
+const SD_ID: &str = "mdns@32473";
+#[cfg(target_os = "linux")]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+// Where discovery events are logged to
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventLogTarget {
+    // The local syslog daemon, RFC 5424 with the service as structured data
+    Syslog,
+    // The systemd journal with the service in its own fields
+    Journald,
+}
+
+enum Sink {
+    Syslog(syslog::Logger<syslog::LoggerBackend, syslog::Formatter5424>),
+    #[cfg(target_os = "linux")]
+    Journald(std::os::unix::net::UnixDatagram),
+}
+
+impl Sink {
+    fn open(target: EventLogTarget) -> Result<Self, String> {
+        match target {
+            EventLogTarget::Syslog => syslog::unix(syslog::Formatter5424 {
+                process: IDENTIFIER.to_string(),
+                ..Default::default()
+            })
+            .map(Sink::Syslog)
+            .map_err(|e| e.to_string()),
+            #[cfg(target_os = "linux")]
+            EventLogTarget::Journald => {
+                let socket =
+                    std::os::unix::net::UnixDatagram::unbound().map_err(|e| e.to_string())?;
+                socket
+                    .connect(JOURNAL_SOCKET)
+                    .map_err(|e| format!("{}: {}", JOURNAL_SOCKET, e))?;
+                Ok(Sink::Journald(socket))
+            }
+            #[cfg(not(target_os = "linux"))]
+            EventLogTarget::Journald => Err("journald is only available on Linux".to_string()),
+        }
+    }
+
+    fn log(&mut self, event: &DiscoveryEvent) -> Result<(), String> {
+        match self {
+            Sink::Syslog(logger) => {
+                let message = (0, structured_data(event), event_message(event));
+                match event.event {
+                    EventKind::Updated => logger.info(message),
+                    EventKind::Added | EventKind::Removed => logger.notice(message),
+                }
+                .map_err(|e| e.to_string())
+            }
+            #[cfg(target_os = "linux")]
+            Sink::Journald(socket) => socket
+                .send(&journal_entry(&journal_fields(event)))
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// Logs every discovery event until the event stream closes, failures are reported
+// on `errors` once until logging works again
+pub async fn log_events(
+    target: EventLogTarget,
+    mut events: broadcast::Receiver<DiscoveryEvent>,
+    errors: flume::Sender<String>,
+) {
+    let mut sink = match Sink::open(target) {
+        Ok(sink) => sink,
+        Err(e) => {
+            let _ = errors.send(format!("Event log unavailable: {}", e));
+            return;
+        }
+    };
+    let mut failing = false;
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match sink.log(&event) {
+            Ok(()) => failing = false,
+            Err(e) => {
+                if !failing {
+                    let _ = errors.send(format!("Event log failed: {}", e));
+                }
+                failing = true;
+            }
+        }
+    }
+}
+
+fn event_message(event: &DiscoveryEvent) -> String {
+    format!(
+        "mDNS service {}: {} at {}:{}",
+        event.event.as_str(),
+        event.fullname,
+        event.host,
+        event.port
+    )
+}
+
+fn structured_data(event: &DiscoveryEvent) -> HashMap<String, HashMap<String, String>> {
+    let params = [
+        ("event", event.event.as_str().to_string()),
+        ("fullname", event.fullname.clone()),
+        ("type", event.service_type.clone()),
+        ("host", event.host.clone()),
+        ("port", event.port.to_string()),
+        ("addresses", event.addrs.join(" ")),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), escape_param_value(&value)))
+    .collect();
+    HashMap::from([(SD_ID.to_string(), params)])
+}
+
+// '"', '\' and ']' have to be escaped in parameter values (RFC 5424 section 6.3.3)
+fn escape_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn journal_fields(event: &DiscoveryEvent) -> Vec<(&'static str, String)> {
+    let priority = match event.event {
+        EventKind::Updated => "6",
+        EventKind::Added | EventKind::Removed => "5",
+    };
+    let mut fields = vec![
+        ("MESSAGE", event_message(event)),
+        ("PRIORITY", priority.to_string()),
+        ("SYSLOG_IDENTIFIER", IDENTIFIER.to_string()),
+        ("MDNS_EVENT", event.event.as_str().to_string()),
+        ("MDNS_FULLNAME", event.fullname.clone()),
+        ("MDNS_SERVICE_TYPE", event.service_type.clone()),
+        ("MDNS_HOST", event.host.clone()),
+        ("MDNS_PORT", event.port.to_string()),
+    ];
+    // Fields may repeat in the journal
+    fields.extend(
+        event
+            .addrs
+            .iter()
+            .map(|addr| ("MDNS_ADDRESS", addr.clone())),
+    );
+    fields.extend(event.txt.iter().map(|txt| ("MDNS_TXT", txt.clone())));
+    fields
+}
+
+// The journal's native protocol, values with a newline are sent length-prefixed
+fn journal_entry(fields: &[(&str, String)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    for (name, value) in fields {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESSES: [&str; 2] = ["192.168.1.20", "fe80::1%eth0"];
+
+    #[test]
+    fn test_structured_data() {
+        let data = structured_data(&DiscoveryEvent::for_test(
+            EventKind::Added,
+            "Office \"A\"._ipp._tcp.local.",
+            &ADDRESSES,
+        ));
+        let params = &data[SD_ID];
+        assert_eq!(params["fullname"], "Office \\\"A\\\"._ipp._tcp.local.");
+        assert_eq!(params["addresses"], "192.168.1.20 fe80::1%eth0");
+        assert_eq!(escape_param_value("a]b\\c"), "a\\]b\\\\c");
+    }
+
+    #[test]
+    fn test_journal_entry() {
+        let fields = journal_fields(&DiscoveryEvent::for_test(
+            EventKind::Added,
+            "Office._ipp._tcp.local.",
+            &ADDRESSES,
+        ));
+        assert!(fields.contains(&("MDNS_PORT", "631".to_string())));
+        assert_eq!(
+            fields
+                .iter()
+                .filter(|(name, _)| *name == "MDNS_ADDRESS")
+                .count(),
+            2
+        );
+
+        let entry = journal_entry(&[
+            ("MESSAGE", "hello".to_string()),
+            ("MDNS_TXT", "a\nb".to_string()),
+        ]);
+        assert_eq!(
+            entry,
+            b"MESSAGE=hello\nMDNS_TXT\n\x03\0\0\0\0\0\0\0a\nb\n".to_vec()
+        );
+    }
+}
@@ -0,0 +1,212 @@
+#![forbid(unsafe_code)]
+
+use crate::report::{self, ReportFormat, ReportGrouping};
+use serde_json::Value;
+
+// Columns of the tabular formats as header and field of the exported service
+const COLUMNS: &[(&str, &str)] = &[
+    ("Name", "fullname"),
+    ("Type", "service_type"),
+    ("Host", "host"),
+    ("Port", "port"),
+    ("Addresses", "addrs"),
+    ("TXT", "txt"),
+    ("Online", "online"),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    Yaml,
+    Markdown,
+    // A readable inventory rather than a table
+    Report(ReportFormat, ReportGrouping),
+}
+
+// Extensions of all formats, replaced when switching the format
+const EXTENSIONS: &[&str] = &["json", "csv", "yaml", "md", "html"];
+
+impl ExportFormat {
+    pub fn next(self) -> Self {
+        match self {
+            ExportFormat::Json => ExportFormat::Csv,
+            ExportFormat::Csv => ExportFormat::Yaml,
+            ExportFormat::Yaml => ExportFormat::Markdown,
+            ExportFormat::Markdown => {
+                ExportFormat::Report(ReportFormat::Markdown, ReportGrouping::Type)
+            }
+            ExportFormat::Report(format, ReportGrouping::Type) => {
+                ExportFormat::Report(format, ReportGrouping::Host)
+            }
+            ExportFormat::Report(ReportFormat::Markdown, ReportGrouping::Host) => {
+                ExportFormat::Report(ReportFormat::Html, ReportGrouping::Type)
+            }
+            ExportFormat::Report(ReportFormat::Html, ReportGrouping::Host) => ExportFormat::Json,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            ExportFormat::Json => "JSON".to_string(),
+            ExportFormat::Csv => "CSV".to_string(),
+            ExportFormat::Yaml => "YAML".to_string(),
+            ExportFormat::Markdown => "Markdown".to_string(),
+            ExportFormat::Report(format, grouping) => format!(
+                "{} report by {}",
+                match format {
+                    ReportFormat::Markdown => "Markdown",
+                    ReportFormat::Html => "HTML",
+                },
+                grouping.label()
+            ),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Yaml => "yaml",
+            ExportFormat::Markdown | ExportFormat::Report(ReportFormat::Markdown, _) => "md",
+            ExportFormat::Report(ReportFormat::Html, _) => "html",
+        }
+    }
+}
+
+// Replaces the extension of a path by the one of the format, keeps other paths
+pub fn with_extension(path: &str, format: ExportFormat) -> String {
+    EXTENSIONS
+        .iter()
+        .find_map(|known| path.strip_suffix(&format!(".{}", known)))
+        .map(|stem| format!("{}.{}", stem, format.extension()))
+        .unwrap_or_else(|| path.to_string())
+}
+
+// The services in the format, JSON and YAML keep every field, CSV and Markdown
+// have a column per field of COLUMNS, reports a section per type or host
+pub fn render<T: serde::Serialize>(format: ExportFormat, services: &[T]) -> Result<String, String> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(services).map_err(|e| e.to_string()),
+        ExportFormat::Yaml => serde_yaml_ng::to_string(services).map_err(|e| e.to_string()),
+        ExportFormat::Csv => render_csv(&rows(services)?),
+        ExportFormat::Markdown => Ok(render_markdown(&rows(services)?)),
+        ExportFormat::Report(format, grouping) => {
+            report::render(format, grouping, services, chrono::Local::now())
+        }
+    }
+}
+
+fn rows<T: serde::Serialize>(services: &[T]) -> Result<Vec<Vec<String>>, String> {
+    services
+        .iter()
+        .map(|service| {
+            let value = serde_json::to_value(service).map_err(|e| e.to_string())?;
+            Ok(COLUMNS
+                .iter()
+                .map(|(_, field)| cell(&value[*field]))
+                .collect())
+        })
+        .collect()
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(" "),
+        other => other.to_string(),
+    }
+}
+
+fn render_csv(rows: &[Vec<String>]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(COLUMNS.iter().map(|(header, _)| header))
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        writer.write_record(row).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+fn render_markdown(rows: &[Vec<String>]) -> String {
+    let headers: Vec<&str> = COLUMNS.iter().map(|(header, _)| *header).collect();
+    let mut lines = vec![
+        format!("| {} |", headers.join(" | ")),
+        format!("|{}", "---|".repeat(headers.len())),
+    ];
+    lines.extend(rows.iter().map(|row| {
+        let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+        format!("| {} |", cells.join(" | "))
+    }));
+    lines.join("\n") + "\n"
+}
+
+// Pipes would end the cell and line breaks the row
+pub fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn services() -> Vec<Value> {
+        vec![json!({
+            "fullname": "Printer | 2._ipp._tcp.local.",
+            "service_type": "_ipp._tcp.local.",
+            "host": "printer.local.",
+            "port": 631,
+            "addrs": ["192.168.1.20", "fe80::1"],
+            "txt": ["rp=ipp/print", "note=a, b"],
+            "online": true,
+            "refresh_count": 3,
+        })]
+    }
+
+    #[test]
+    fn test_render_csv() {
+        assert_eq!(
+            render(ExportFormat::Csv, &services()).unwrap(),
+            "Name,Type,Host,Port,Addresses,TXT,Online\n\
+             Printer | 2._ipp._tcp.local.,_ipp._tcp.local.,printer.local.,631,192.168.1.20 fe80::1,\"rp=ipp/print note=a, b\",true\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = render(ExportFormat::Markdown, &services()).unwrap();
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert_eq!(
+            lines[0],
+            "| Name | Type | Host | Port | Addresses | TXT | Online |"
+        );
+        assert_eq!(lines[1], "|---|---|---|---|---|---|---|");
+        assert!(lines[2].starts_with("| Printer \\| 2._ipp._tcp.local. |"));
+    }
+
+    #[test]
+    fn test_render_keeps_all_fields() {
+        let yaml = render(ExportFormat::Yaml, &services()).unwrap();
+        assert!(yaml.contains("refresh_count: 3"));
+        let json = render(ExportFormat::Json, &services()).unwrap();
+        assert!(json.contains("\"refresh_count\": 3"));
+    }
+
+    #[test]
+    fn test_with_extension() {
+        assert_eq!(with_extension("out.json", ExportFormat::Markdown), "out.md");
+        assert_eq!(with_extension("out.md", ExportFormat::Csv), "out.csv");
+        assert_eq!(with_extension("out", ExportFormat::Csv), "out");
+        let html = ExportFormat::Report(ReportFormat::Html, ReportGrouping::Host);
+        assert_eq!(with_extension("out.md", html), "out.html");
+        assert_eq!(html.next(), ExportFormat::Json);
+        assert_eq!(html.label(), "HTML report by host");
+    }
+}
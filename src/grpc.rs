@@ -0,0 +1,465 @@
+#![forbid(unsafe_code)]
+
+use crate::discovery_events::{self, DiscoveryEvent};
+use crate::server::Catalog;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status};
+
+// The service generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/mdns_tui_browser.v1.Browser.rs"));
+
+// The messages of proto/mdns_tui_browser.proto, keep both in sync; the tests
+// check them against the file
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListServicesRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListServicesResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub services: Vec<Service>,
+    #[prost(string, repeated, tag = "2")]
+    pub service_types: Vec<String>,
+}
+
+// Deserialized from the services as the HTTP API lists them
+#[derive(Clone, PartialEq, prost::Message, Deserialize)]
+pub struct Service {
+    #[prost(string, tag = "1")]
+    pub fullname: String,
+    #[prost(string, tag = "2")]
+    pub service_type: String,
+    #[prost(string, optional, tag = "3")]
+    pub subtype: Option<String>,
+    #[prost(string, tag = "4")]
+    pub host: String,
+    #[prost(uint32, tag = "5")]
+    pub port: u32,
+    #[prost(string, repeated, tag = "6")]
+    pub addrs: Vec<String>,
+    #[prost(string, repeated, tag = "7")]
+    pub txt: Vec<String>,
+    #[prost(bool, tag = "8")]
+    pub online: bool,
+    #[prost(string, optional, tag = "9")]
+    pub agent: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WatchEventsRequest {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum EventKind {
+    Unspecified = 0,
+    Added = 1,
+    Updated = 2,
+    Removed = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Event {
+    #[prost(enumeration = "EventKind", tag = "1")]
+    pub kind: i32,
+    #[prost(string, tag = "2")]
+    pub fullname: String,
+    #[prost(string, tag = "3")]
+    pub service_type: String,
+    #[prost(string, tag = "4")]
+    pub host: String,
+    #[prost(uint32, tag = "5")]
+    pub port: u32,
+    #[prost(string, repeated, tag = "6")]
+    pub addrs: Vec<String>,
+    #[prost(string, repeated, tag = "7")]
+    pub txt: Vec<String>,
+    #[prost(uint64, tag = "8")]
+    pub timestamp_micros: u64,
+}
+
+impl From<DiscoveryEvent> for Event {
+    fn from(event: DiscoveryEvent) -> Self {
+        let kind = match event.event {
+            discovery_events::EventKind::Added => EventKind::Added,
+            discovery_events::EventKind::Updated => EventKind::Updated,
+            discovery_events::EventKind::Removed => EventKind::Removed,
+        };
+        Self {
+            kind: kind.into(),
+            fullname: event.fullname,
+            service_type: event.service_type,
+            host: event.host,
+            port: event.port.into(),
+            addrs: event.addrs,
+            txt: event.txt,
+            timestamp_micros: event.timestamp_micros,
+        }
+    }
+}
+
+// Events buffered per watcher before it misses some
+const WATCH_BUFFER: usize = 64;
+
+struct BrowserService<C> {
+    catalog: C,
+    events: broadcast::Sender<DiscoveryEvent>,
+}
+
+#[tonic::async_trait]
+impl<C: Catalog> browser_server::Browser for BrowserService<C> {
+    async fn list_services(
+        &self,
+        _request: Request<ListServicesRequest>,
+    ) -> Result<Response<ListServicesResponse>, Status> {
+        let services = serde_json::from_value(self.catalog.services().await)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ListServicesResponse {
+            services,
+            service_types: self.catalog.service_types().await,
+        }))
+    }
+
+    type WatchEventsStream = ReceiverStream<Result<Event, Status>>;
+
+    async fn watch_events(
+        &self,
+        _request: Request<WatchEventsRequest>,
+    ) -> Result<Response<Self::WatchEventsStream>, Status> {
+        let (sender, receiver) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(forward_events(self.events.subscribe(), sender));
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+// Passes the events on to a watcher until it hangs up; if it fell behind, the
+// stream ends with DATA_LOSS so the watcher lists the services again instead
+// of going on with a gap
+async fn forward_events(
+    mut events: broadcast::Receiver<DiscoveryEvent>,
+    sender: mpsc::Sender<Result<Event, Status>>,
+) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                // The watcher hung up
+                if sender.send(Ok(event.into())).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                let status = Status::data_loss(format!("missed {} events", count));
+                let _ = sender.send(Err(status)).await;
+                break;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+// Serves the Browser service of proto/mdns_tui_browser.proto until the
+// listener fails
+pub async fn serve<C: Catalog>(
+    listener: TcpListener,
+    catalog: C,
+    events: broadcast::Sender<DiscoveryEvent>,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(browser_server::BrowserServer::new(BrowserService {
+            catalog,
+            events,
+        }))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use serde_json::json;
+    use std::collections::{BTreeSet, HashMap};
+
+    #[derive(Clone)]
+    struct TestCatalog;
+
+    impl Catalog for TestCatalog {
+        async fn services(&self) -> serde_json::Value {
+            json!([{
+                "fullname": "Office._ipp._tcp.local.",
+                "service_type": "_ipp._tcp.local.",
+                "subtype": null,
+                "host": "printer.local.",
+                "port": 631,
+                "addrs": ["192.168.1.20"],
+                "txt": [],
+                "online": true,
+                "first_seen_micros": 0,
+            }])
+        }
+
+        async fn service_types(&self) -> Vec<String> {
+            vec!["_ipp._tcp.local.".to_string()]
+        }
+    }
+
+    // A field of a message, or a value of an enum without label and kind
+    struct ProtoField {
+        label: &'static str,
+        kind: &'static str,
+        name: &'static str,
+        number: u64,
+    }
+
+    type ProtoDefinitions = HashMap<&'static str, Vec<ProtoField>>;
+
+    // The messages and the enums of the proto file, by name
+    fn proto_definitions() -> (ProtoDefinitions, ProtoDefinitions) {
+        let (mut messages, mut enums) = (HashMap::new(), HashMap::new());
+        let mut current = None;
+        for line in include_str!("../proto/mdns_tui_browser.proto").lines() {
+            let line = line.split("//").next().unwrap_or_default();
+            let words: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == '=' || c == ';')
+                .filter(|word| !word.is_empty())
+                .collect();
+            match words.as_slice() {
+                [keyword @ ("message" | "enum"), name, rest @ ..] => {
+                    let definitions = if *keyword == "message" {
+                        &mut messages
+                    } else {
+                        &mut enums
+                    };
+                    definitions.insert(*name, Vec::new());
+                    // Empty ones are closed on the same line
+                    current = (rest != ["{}"]).then_some((*keyword, *name));
+                }
+                ["}"] => current = None,
+                [prefix @ .., name, number] if current.is_some() => {
+                    let (label, kind) = match prefix {
+                        [] => ("", ""),
+                        [kind] => ("", *kind),
+                        [label, kind] => (*label, *kind),
+                        _ => panic!("Unexpected line {}", line),
+                    };
+                    let (keyword, definition) = current.unwrap();
+                    let definitions = if keyword == "message" {
+                        &mut messages
+                    } else {
+                        &mut enums
+                    };
+                    definitions.get_mut(definition).unwrap().push(ProtoField {
+                        label,
+                        kind,
+                        name,
+                        number: number.parse().unwrap(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        (messages, enums)
+    }
+
+    // The wire type a field of the proto file is encoded with
+    fn wire_type(field: &ProtoField, enums: &ProtoDefinitions) -> u64 {
+        let wire_type = match field.kind {
+            "bool" | "int32" | "int64" | "uint32" | "uint64" | "sint32" | "sint64" => 0,
+            "fixed64" | "sfixed64" | "double" => 1,
+            "fixed32" | "sfixed32" | "float" => 5,
+            kind if enums.contains_key(kind) => 0,
+            // Strings, bytes and messages
+            _ => 2,
+        };
+        // Repeated numbers are packed
+        if field.label == "repeated" {
+            2
+        } else {
+            wire_type
+        }
+    }
+
+    // The field numbers and wire types of an encoded message
+    fn wire_fields(mut bytes: &[u8]) -> BTreeSet<(u64, u64)> {
+        let mut fields = BTreeSet::new();
+        while !bytes.is_empty() {
+            let key = prost::encoding::decode_varint(&mut bytes).unwrap();
+            match key & 7 {
+                0 => {
+                    prost::encoding::decode_varint(&mut bytes).unwrap();
+                }
+                1 => bytes = &bytes[8..],
+                2 => {
+                    let length = prost::encoding::decode_varint(&mut bytes).unwrap();
+                    bytes = &bytes[length as usize..];
+                }
+                5 => bytes = &bytes[4..],
+                wire_type => panic!("Unexpected wire type {}", wire_type),
+            }
+            fields.insert((key >> 3, key & 7));
+        }
+        fields
+    }
+
+    // The hand-written messages have the fields of the proto file, with the same
+    // names, numbers, types and optional ones
+    #[test]
+    fn test_messages_match_the_proto_file() {
+        let (messages, enums) = proto_definitions();
+        let text = || "text".to_string();
+        let service = Service {
+            fullname: text(),
+            service_type: text(),
+            subtype: Some(text()),
+            host: text(),
+            port: 1,
+            addrs: vec![text()],
+            txt: vec![text()],
+            online: true,
+            agent: Some(text()),
+        };
+        let event = Event {
+            kind: EventKind::Added.into(),
+            fullname: text(),
+            service_type: text(),
+            host: text(),
+            port: 1,
+            addrs: vec![text()],
+            txt: vec![text()],
+            timestamp_micros: 1,
+        };
+        let response = ListServicesResponse {
+            services: vec![service.clone()],
+            service_types: vec![text()],
+        };
+        // Each message with every field set, and with only the optional ones
+        // set to their defaults
+        let samples = [
+            (
+                "ListServicesRequest",
+                format!("{:?}", ListServicesRequest {}),
+                ListServicesRequest {}.encode_to_vec(),
+                Vec::new(),
+            ),
+            (
+                "ListServicesResponse",
+                format!("{:?}", response),
+                response.encode_to_vec(),
+                ListServicesResponse::default().encode_to_vec(),
+            ),
+            (
+                "Service",
+                format!("{:?}", service),
+                service.encode_to_vec(),
+                Service {
+                    subtype: Some(String::new()),
+                    agent: Some(String::new()),
+                    ..Service::default()
+                }
+                .encode_to_vec(),
+            ),
+            (
+                "WatchEventsRequest",
+                format!("{:?}", WatchEventsRequest {}),
+                WatchEventsRequest {}.encode_to_vec(),
+                Vec::new(),
+            ),
+            (
+                "Event",
+                format!("{:?}", event),
+                event.encode_to_vec(),
+                Event::default().encode_to_vec(),
+            ),
+        ];
+        assert_eq!(samples.len(), messages.len());
+        for (name, debug, full, defaults) in samples {
+            let fields = &messages[name];
+            assert!(debug.starts_with(name), "{}", debug);
+            for field in fields {
+                assert!(debug.contains(&format!(" {}: ", field.name)), "{}", debug);
+            }
+            let expected: BTreeSet<(u64, u64)> = fields
+                .iter()
+                .map(|field| (field.number, wire_type(field, &enums)))
+                .collect();
+            assert_eq!(wire_fields(&full), expected, "{}", name);
+            let optional: BTreeSet<u64> = fields
+                .iter()
+                .filter(|field| field.label == "optional")
+                .map(|field| field.number)
+                .collect();
+            let present: BTreeSet<u64> = wire_fields(&defaults)
+                .into_iter()
+                .map(|(number, _)| number)
+                .collect();
+            assert_eq!(present, optional, "{}", name);
+        }
+
+        let values = &enums["EventKind"];
+        for value in values {
+            let kind = EventKind::try_from(value.number as i32).unwrap();
+            assert_eq!(format!("EVENT_KIND_{:?}", kind).to_uppercase(), value.name);
+        }
+        assert!(EventKind::try_from(values.len() as i32).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_api() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (events, _) = broadcast::channel(16);
+        tokio::spawn(serve(listener, TestCatalog, events.clone()));
+
+        let mut client = browser_client::BrowserClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        let list = client
+            .list_services(ListServicesRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(list.service_types, ["_ipp._tcp.local."]);
+        assert_eq!(list.services[0].port, 631);
+        assert_eq!(list.services[0].agent, None);
+
+        let mut stream = client
+            .watch_events(WatchEventsRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        events
+            .send(DiscoveryEvent {
+                timestamp_micros: 42,
+                ..DiscoveryEvent::for_test(
+                    discovery_events::EventKind::Removed,
+                    "Office._ipp._tcp.local.",
+                    &[],
+                )
+            })
+            .unwrap();
+        let event = stream.message().await.unwrap().unwrap();
+        assert_eq!(event.kind(), EventKind::Removed);
+        assert_eq!(event.timestamp_micros, 42);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_watcher_gets_data_loss() {
+        let (events, receiver) = broadcast::channel(2);
+        for _ in 0..3 {
+            events
+                .send(DiscoveryEvent::for_test(
+                    discovery_events::EventKind::Added,
+                    "Office._ipp._tcp.local.",
+                    &[],
+                ))
+                .unwrap();
+        }
+        let (sender, mut watcher) = mpsc::channel(WATCH_BUFFER);
+        forward_events(receiver, sender).await;
+        let status = watcher.recv().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+        assert!(watcher.recv().await.is_none());
+    }
+}
@@ -0,0 +1,247 @@
+#![forbid(unsafe_code)]
+
+use crate::config::{HomeAssistantConfig, MqttConfig};
+use crate::discovery_events::{DiscoveryEvent, EventKind};
+use crate::mqtt;
+use rumqttc::QoS;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+
+// Prefixes the unique ids, so they don't clash with other integrations
+const UNIQUE_ID_PREFIX: &str = "mdns_tui_browser";
+
+// The Home Assistant entity type services are announced as
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HomeAssistantComponent {
+    // On while the service is online, with device class presence
+    BinarySensor,
+    // Home while the service is online, not home otherwise
+    DeviceTracker,
+}
+
+impl HomeAssistantComponent {
+    fn as_str(self) -> &'static str {
+        match self {
+            HomeAssistantComponent::BinarySensor => "binary_sensor",
+            HomeAssistantComponent::DeviceTracker => "device_tracker",
+        }
+    }
+
+    fn state(self, online: bool) -> &'static str {
+        match (self, online) {
+            (HomeAssistantComponent::BinarySensor, true) => "ON",
+            (HomeAssistantComponent::BinarySensor, false) => "OFF",
+            (HomeAssistantComponent::DeviceTracker, true) => "home",
+            (HomeAssistantComponent::DeviceTracker, false) => "not_home",
+        }
+    }
+}
+
+// Announces every service of the selected types to Home Assistant the first
+// time it is seen, then keeps its state and attributes up to date, all retained
+// so Home Assistant picks them up after a restart
+pub async fn bridge_events(
+    mqtt_config: MqttConfig,
+    config: HomeAssistantConfig,
+    mut events: broadcast::Receiver<DiscoveryEvent>,
+    errors: flume::Sender<String>,
+) {
+    // The broker drops the older of two connections with the same client id
+    let client_id = format!("{}-home-assistant", mqtt_config.client_id);
+    let (client, connection) = mqtt::connect(&mqtt_config, &client_id, errors);
+    let mut announced: HashSet<String> = HashSet::new();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if !config.applies_to(&event.service_type) {
+            continue;
+        }
+        let object_id = object_id(&event.fullname);
+        if !announced.contains(&object_id) {
+            let published = client.try_publish(
+                config_topic(&config, &object_id),
+                QoS::AtLeastOnce,
+                true,
+                config_payload(&config, &event, &object_id).to_string(),
+            );
+            // Try again with the next event when the request queue was full
+            if published.is_ok() {
+                announced.insert(object_id.clone());
+            }
+        }
+        let online = event.event != EventKind::Removed;
+        let _ = client.try_publish(
+            state_topic(&config, &object_id),
+            QoS::AtLeastOnce,
+            true,
+            config.component.state(online),
+        );
+        let _ = client.try_publish(
+            attributes_topic(&config, &object_id),
+            QoS::AtLeastOnce,
+            true,
+            attributes_payload(&event).to_string(),
+        );
+    }
+    connection.abort();
+}
+
+// Home Assistant only accepts letters, digits, '_' and '-' in object ids. Names
+// that only differ in other characters or case would collide, so a hash of the
+// fullname tells them apart
+fn object_id(fullname: &str) -> String {
+    let mut id = String::new();
+    for c in fullname.trim_end_matches('.').chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c.to_ascii_lowercase());
+        } else if !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    format!(
+        "{}_{:08x}",
+        id.trim_matches('_'),
+        fnv1a(fullname.as_bytes())
+    )
+}
+
+// 32-bit FNV-1a, stable across builds unlike the hasher of the standard library
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn config_topic(config: &HomeAssistantConfig, object_id: &str) -> String {
+    format!(
+        "{}/{}/{}/{}/config",
+        config.discovery_prefix,
+        config.component.as_str(),
+        UNIQUE_ID_PREFIX,
+        object_id
+    )
+}
+
+fn state_topic(config: &HomeAssistantConfig, object_id: &str) -> String {
+    format!("{}/{}/state", config.state_topic_prefix, object_id)
+}
+
+fn attributes_topic(config: &HomeAssistantConfig, object_id: &str) -> String {
+    format!("{}/{}/attributes", config.state_topic_prefix, object_id)
+}
+
+fn config_payload(
+    config: &HomeAssistantConfig,
+    event: &DiscoveryEvent,
+    object_id: &str,
+) -> serde_json::Value {
+    let unique_id = format!("{}_{}", UNIQUE_ID_PREFIX, object_id);
+    let mut payload = json!({
+        "name": event.instance(),
+        "unique_id": unique_id,
+        "state_topic": state_topic(config, object_id),
+        "json_attributes_topic": attributes_topic(config, object_id),
+        "device": {
+            "identifiers": [unique_id],
+            "name": event.instance(),
+            "model": event.service_type.trim_end_matches('.'),
+        },
+    });
+    match config.component {
+        HomeAssistantComponent::BinarySensor => {
+            payload["device_class"] = json!("presence");
+            payload["payload_on"] = json!(config.component.state(true));
+            payload["payload_off"] = json!(config.component.state(false));
+        }
+        HomeAssistantComponent::DeviceTracker => {
+            payload["source_type"] = json!("router");
+            payload["payload_home"] = json!(config.component.state(true));
+            payload["payload_not_home"] = json!(config.component.state(false));
+        }
+    }
+    payload
+}
+
+fn attributes_payload(event: &DiscoveryEvent) -> serde_json::Value {
+    json!({
+        "fullname": event.fullname,
+        "service_type": event.service_type,
+        "host": event.host,
+        "port": event.port,
+        "addresses": event.addrs,
+        "txt": event.txt,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHONE: &str = "Alice's iPhone._companion-link._tcp.local.";
+
+    #[test]
+    fn test_object_id() {
+        assert_eq!(
+            object_id("Alice's iPhone._companion-link._tcp.local."),
+            "alice_s_iphone_companion_link_tcp_local_a6b7462b"
+        );
+        // Only the case differs
+        assert_ne!(
+            object_id("Printer._ipp._tcp.local."),
+            object_id("printer._ipp._tcp.local.")
+        );
+    }
+
+    #[test]
+    fn test_binary_sensor_config() {
+        let config = HomeAssistantConfig::default();
+        let object_id = object_id(PHONE);
+        assert_eq!(
+            config_topic(&config, &object_id),
+            "homeassistant/binary_sensor/mdns_tui_browser/alice_s_iphone_companion_link_tcp_local_a6b7462b/config"
+        );
+        let payload = config_payload(
+            &config,
+            &DiscoveryEvent::for_test(EventKind::Added, PHONE, &["192.168.1.30"]),
+            &object_id,
+        );
+        assert_eq!(payload["name"], "Alice's iPhone");
+        assert_eq!(payload["device_class"], "presence");
+        assert_eq!(
+            payload["state_topic"],
+            "mdns-tui-browser/home-assistant/alice_s_iphone_companion_link_tcp_local_a6b7462b/state"
+        );
+        assert_eq!(payload["device"]["model"], "_companion-link._tcp.local");
+    }
+
+    #[test]
+    fn test_device_tracker_config() {
+        let config = HomeAssistantConfig {
+            component: HomeAssistantComponent::DeviceTracker,
+            ..HomeAssistantConfig::default()
+        };
+        let payload = config_payload(
+            &config,
+            &DiscoveryEvent::for_test(EventKind::Added, PHONE, &["192.168.1.30"]),
+            "phone",
+        );
+        assert_eq!(payload["source_type"], "router");
+        assert_eq!(payload["payload_not_home"], "not_home");
+        assert!(payload.get("device_class").is_none());
+        assert_eq!(
+            attributes_payload(&DiscoveryEvent::for_test(
+                EventKind::Added,
+                PHONE,
+                &["192.168.1.30"]
+            ))["port"],
+            631
+        );
+    }
+}
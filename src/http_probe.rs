@@ -0,0 +1,516 @@
+#![forbid(unsafe_code)]
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Enough to get past the <head> of any sane page
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+const MAX_TITLE_CHARS: usize = 120;
+// Replies to API requests are read in full, up to this size
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub title: Option<String>,
+}
+
+pub struct HttpRequest<'a> {
+    // Where to connect to, may be a scoped IPv6 address
+    pub address: &'a str,
+    pub port: u16,
+    // Sent as Host header and TLS server name
+    pub host: &'a str,
+    pub path: &'a str,
+    pub tls: bool,
+}
+
+// Sends a GET request and extracts the status code and page title of the response
+pub async fn probe_http(
+    request: &HttpRequest<'_>,
+    timeout: Duration,
+) -> Result<HttpResponse, String> {
+    match tokio::time::timeout(timeout, fetch(request)).await {
+        Ok(result) => result.and_then(|response| parse_response(&response)),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+async fn fetch(request: &HttpRequest<'_>) -> Result<Vec<u8>, String> {
+    let head = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: mdns-tui-browser/{}\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+        request.path,
+        request.host,
+        env!("CARGO_PKG_VERSION")
+    );
+    send(request, head.as_bytes(), MAX_RESPONSE_BYTES).await
+}
+
+// Sends a GET request for JSON, returns the status code and the complete body of the response
+pub async fn get(request: &HttpRequest<'_>, timeout: Duration) -> Result<(u16, Vec<u8>), String> {
+    let head = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: mdns-tui-browser/{}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+        request.path,
+        request.host,
+        env!("CARGO_PKG_VERSION")
+    );
+    match tokio::time::timeout(timeout, send(request, head.as_bytes(), MAX_BODY_BYTES)).await {
+        Ok(result) => result.and_then(|response| parse_response_body(&response)),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+// Sends a POST request, returns the status code and the complete body of the response
+pub async fn post(
+    request: &HttpRequest<'_>,
+    content_type: &str,
+    body: &[u8],
+    timeout: Duration,
+) -> Result<(u16, Vec<u8>), String> {
+    let mut message = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: mdns-tui-browser/{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        request.path,
+        request.host,
+        env!("CARGO_PKG_VERSION"),
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    message.extend_from_slice(body);
+    match tokio::time::timeout(timeout, send(request, &message, MAX_BODY_BYTES)).await {
+        Ok(result) => result.and_then(|response| parse_response_body(&response)),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+async fn send(request: &HttpRequest<'_>, message: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let stream = TcpStream::connect((request.address, request.port))
+        .await
+        .map_err(|e| e.to_string())?;
+    if request.tls {
+        let stream = connect_tls(stream, request.host).await?;
+        exchange(stream, message, limit).await
+    } else {
+        exchange(stream, message, limit).await
+    }
+}
+
+// Devices mostly use self-signed certificates, so any certificate is accepted,
+// the point is to look at the service, not to trust it
+pub async fn connect_tls(
+    stream: TcpStream,
+    host: &str,
+) -> Result<tokio_rustls::client::TlsStream<TcpStream>, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host.to_string())
+        .or_else(|_| ServerName::try_from("localhost".to_string()))
+        .map_err(|e| e.to_string())?;
+    tokio_rustls::TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Parts of a certificate worth looking at when hunting for broken devices
+#[derive(Clone, Debug, PartialEq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub subject_alt_names: Vec<String>,
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl CertificateInfo {
+    pub fn is_self_signed(&self) -> bool {
+        self.subject == self.issuer
+    }
+
+    pub fn is_expired_at(&self, timestamp: i64) -> bool {
+        timestamp > self.not_after
+    }
+}
+
+// Connects with TLS and returns the certificate chain the server presented,
+// leaf certificate first
+pub async fn inspect_certificates(
+    address: &str,
+    port: u16,
+    host: &str,
+    timeout: Duration,
+) -> Result<Vec<CertificateInfo>, String> {
+    let handshake = async {
+        let stream = TcpStream::connect((address, port))
+            .await
+            .map_err(|e| e.to_string())?;
+        let stream = connect_tls(stream, host).await?;
+        let (_, connection) = stream.get_ref();
+        connection
+            .peer_certificates()
+            .unwrap_or_default()
+            .iter()
+            .map(|der| describe_certificate(der))
+            .collect()
+    };
+    match tokio::time::timeout(timeout, handshake).await {
+        Ok(result) => result,
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+fn describe_certificate(der: &[u8]) -> Result<CertificateInfo, String> {
+    use x509_parser::extensions::GeneralName;
+
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).map_err(|e| e.to_string())?;
+    let subject_alt_names = match certificate.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .map(|name| match name {
+                GeneralName::DNSName(name) => name.to_string(),
+                GeneralName::IPAddress(bytes) => format_ip_bytes(bytes),
+                other => other.to_string(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(CertificateInfo {
+        subject: certificate.subject().to_string(),
+        issuer: certificate.issuer().to_string(),
+        subject_alt_names,
+        not_before: certificate.validity().not_before.timestamp(),
+        not_after: certificate.validity().not_after.timestamp(),
+    })
+}
+
+fn format_ip_bytes(bytes: &[u8]) -> String {
+    use std::net::IpAddr;
+
+    match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).unwrap_or_default()).to_string(),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).unwrap_or_default()).to_string(),
+        _ => format!("{:02x?}", bytes),
+    }
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    message: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, String> {
+    stream.write_all(message).await.map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while response.len() < limit {
+        match stream.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(len) => response.extend_from_slice(&chunk[..len]),
+            // Plenty of devices drop TLS connections without a close_notify
+            Err(_) if !response.is_empty() => break,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(response)
+}
+
+fn parse_response(response: &[u8]) -> Result<HttpResponse, String> {
+    let text = String::from_utf8_lossy(response);
+    let status_line = text.lines().next().unwrap_or_default();
+    let status = status_line
+        .strip_prefix("HTTP/")
+        .and_then(|rest| rest.split_whitespace().nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| "not an HTTP response".to_string())?;
+    Ok(HttpResponse {
+        status,
+        title: extract_title(&text),
+    })
+}
+
+fn parse_response_body(response: &[u8]) -> Result<(u16, Vec<u8>), String> {
+    let status = parse_response(response)?.status;
+    let head_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| "incomplete HTTP response".to_string())?;
+    let head = String::from_utf8_lossy(&response[..head_end]).to_ascii_lowercase();
+    let body = &response[head_end + 4..];
+    let chunked = head
+        .lines()
+        .any(|line| line.starts_with("transfer-encoding:") && line.contains("chunked"));
+    if chunked {
+        Ok((status, decode_chunked(body)?))
+    } else {
+        Ok((status, body.to_vec()))
+    }
+}
+
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| "truncated chunked body".to_string())?;
+        let size_line = String::from_utf8_lossy(&body[..line_end]);
+        // Chunk extensions follow the size after a semicolon
+        let size_text = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_text, 16)
+            .map_err(|_| format!("invalid chunk size {:?}", size_text))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(decoded);
+        }
+        let chunk = body
+            .get(..size)
+            .ok_or_else(|| "truncated chunked body".to_string())?;
+        decoded.extend_from_slice(chunk);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+fn extract_title(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets intact
+    let lowercase = html.to_ascii_lowercase();
+    let tag_start = lowercase.find("<title")?;
+    let content_start = tag_start + lowercase[tag_start..].find('>')? + 1;
+    let content_end = content_start + lowercase[content_start..].find("</title")?;
+
+    let title = decode_html_entities(&html[content_start..content_end])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.chars().take(MAX_TITLE_CHARS).collect())
+    }
+}
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html><head><TITLE>\n  My  NAS &amp; Co\n</TITLE></head></html>";
+        assert_eq!(
+            parse_response(response),
+            Ok(HttpResponse {
+                status: 200,
+                title: Some("My NAS & Co".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_response_without_title() {
+        let response = b"HTTP/1.0 404 Not Found\r\n\r\n";
+        assert_eq!(
+            parse_response(response),
+            Ok(HttpResponse {
+                status: 404,
+                title: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_response() {
+        assert!(parse_response(b"SSH-2.0-OpenSSH_9.6\r\n").is_err());
+        assert!(parse_response(b"").is_err());
+    }
+
+    #[test]
+    fn test_parse_response_body() {
+        assert_eq!(
+            parse_response_body(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello"),
+            Ok((200, b"hello".to_vec()))
+        );
+        assert_eq!(
+            parse_response_body(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n1\r\n!\r\n0\r\n\r\n"
+            ),
+            Ok((200, b"hello!".to_vec()))
+        );
+        assert!(
+            parse_response_body(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n9\r\nhi")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_extract_title() {
+        assert_eq!(
+            extract_title("<title lang=\"en\">Printer</title>"),
+            Some("Printer".to_string())
+        );
+        assert_eq!(extract_title("<title>   </title>"), None);
+        assert_eq!(extract_title("<title>unterminated"), None);
+        assert_eq!(
+            extract_title(&format!("<title>{}</title>", "x".repeat(200))).map(|title| title.len()),
+            Some(MAX_TITLE_CHARS)
+        );
+    }
+
+    #[test]
+    fn test_decode_html_entities() {
+        assert_eq!(decode_html_entities("a &lt;b&gt; &amp;amp;"), "a <b> &amp;");
+    }
+
+    fn self_signed_certificate() -> rcgen::CertifiedKey<rcgen::KeyPair> {
+        rcgen::generate_simple_self_signed(vec!["nas.local".to_string(), "192.168.1.2".to_string()])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_describe_certificate() {
+        let certified = self_signed_certificate();
+        let info = describe_certificate(certified.cert.der()).unwrap();
+        assert_eq!(info.subject_alt_names, vec!["nas.local", "192.168.1.2"]);
+        assert!(info.is_self_signed());
+        assert!(info.not_before < info.not_after);
+        assert!(!info.is_expired_at(info.not_after));
+        assert!(info.is_expired_at(info.not_after + 1));
+    }
+
+    #[test]
+    fn test_describe_invalid_certificate() {
+        assert!(describe_certificate(b"not a certificate").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inspect_certificates() {
+        use rustls::pki_types::PrivateKeyDer;
+
+        let certified = self_signed_certificate();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certified.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(certified.signing_key.serialize_der().into()),
+        )
+        .unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let _ = acceptor.accept(socket).await;
+        });
+
+        let chain = inspect_certificates("127.0.0.1", port, "nas.local", Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].subject_alt_names, vec!["nas.local", "192.168.1.2"]);
+    }
+
+    #[tokio::test]
+    async fn test_probe_http() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let len = socket.read(&mut request).await.unwrap();
+            assert!(request[..len].starts_with(b"GET /status HTTP/1.1\r\nHost: nas.local\r\n"));
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\n\r\n<title>NAS</title>")
+                .await
+                .unwrap();
+        });
+
+        let request = HttpRequest {
+            address: "127.0.0.1",
+            port,
+            host: "nas.local",
+            path: "/status",
+            tls: false,
+        };
+        assert_eq!(
+            probe_http(&request, Duration::from_secs(2)).await,
+            Ok(HttpResponse {
+                status: 200,
+                title: Some("NAS".to_string()),
+            })
+        );
+    }
+}
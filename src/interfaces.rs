@@ -0,0 +1,148 @@
+#![forbid(unsafe_code)]
+
+use if_addrs::IfAddr;
+use std::net::IpAddr;
+
+// An address of a local network interface with the prefix length of its subnet
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalInterface {
+    pub name: String,
+    pub index: Option<u32>,
+    pub ip: IpAddr,
+    pub prefix_len: u8,
+}
+
+// Addresses of all local interfaces except loopback ones, empty if they can't be listed
+pub fn local_interfaces() -> Vec<LocalInterface> {
+    let Ok(interfaces) = if_addrs::get_if_addrs() else {
+        return Vec::new();
+    };
+    interfaces
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .map(|interface| {
+            let (ip, prefix_len) = match &interface.addr {
+                IfAddr::V4(v4) => (IpAddr::V4(v4.ip), v4.prefixlen),
+                IfAddr::V6(v6) => (IpAddr::V6(v6.ip), v6.prefixlen),
+            };
+            LocalInterface {
+                name: interface.name,
+                index: interface.index,
+                ip,
+                prefix_len,
+            }
+        })
+        .collect()
+}
+
+// Names of the interfaces a service's addresses are reachable on, scoped IPv6
+// addresses name theirs after the '%', all others are matched by subnet
+pub fn interfaces_for_addresses(addrs: &[String], interfaces: &[LocalInterface]) -> Vec<String> {
+    let mut names: Vec<String> = addrs
+        .iter()
+        .flat_map(|addr| interfaces_for_address(addr, interfaces))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn interfaces_for_address(addr: &str, interfaces: &[LocalInterface]) -> Vec<String> {
+    let (ip, zone) = match addr.split_once('%') {
+        Some((ip, zone)) => (ip, Some(zone)),
+        None => (addr, None),
+    };
+    if let Some(zone) = zone {
+        // Windows uses the interface index as zone id
+        let name = match zone.parse::<u32>() {
+            Ok(index) => interfaces
+                .iter()
+                .find(|interface| interface.index == Some(index))
+                .map_or(zone, |interface| interface.name.as_str()),
+            Err(_) => zone,
+        };
+        return vec![name.to_string()];
+    }
+    let Ok(ip) = ip.parse::<IpAddr>() else {
+        return Vec::new();
+    };
+    interfaces
+        .iter()
+        .filter(|interface| in_subnet(ip, interface.ip, interface.prefix_len))
+        .map(|interface| interface.name.clone())
+        .collect()
+}
+
+fn in_subnet(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            prefix_matches(&ip.octets(), &network.octets(), prefix_len)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            prefix_matches(&ip.octets(), &network.octets(), prefix_len)
+        }
+        _ => false,
+    }
+}
+
+fn prefix_matches(ip: &[u8], network: &[u8], prefix_len: u8) -> bool {
+    let prefix_len = usize::from(prefix_len).min(ip.len() * 8);
+    let full_bytes = prefix_len / 8;
+    if ip[..full_bytes] != network[..full_bytes] {
+        return false;
+    }
+    let remaining_bits = prefix_len % 8;
+    if remaining_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - remaining_bits);
+    ip[full_bytes] & mask == network[full_bytes] & mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface(name: &str, index: u32, ip: &str, prefix_len: u8) -> LocalInterface {
+        LocalInterface {
+            name: name.to_string(),
+            index: Some(index),
+            ip: ip.parse().unwrap(),
+            prefix_len,
+        }
+    }
+
+    fn addrs(addrs: &[&str]) -> Vec<String> {
+        addrs.iter().map(|addr| addr.to_string()).collect()
+    }
+
+    #[test]
+    fn test_interfaces_for_addresses() {
+        let interfaces = [
+            interface("eth0", 2, "192.168.1.10", 24),
+            interface("eth0", 2, "2001:db8:1::10", 64),
+            interface("vlan20", 5, "10.20.0.1", 20),
+        ];
+        assert_eq!(
+            interfaces_for_addresses(&addrs(&["192.168.1.55"]), &interfaces),
+            vec!["eth0"]
+        );
+        assert_eq!(
+            interfaces_for_addresses(&addrs(&["10.20.15.3", "2001:db8:1::99"]), &interfaces),
+            vec!["eth0", "vlan20"]
+        );
+        assert_eq!(
+            interfaces_for_addresses(&addrs(&["fe80::1%wlan0", "fe80::2%5"]), &interfaces),
+            vec!["vlan20", "wlan0"]
+        );
+        assert!(interfaces_for_addresses(&addrs(&["10.20.16.1", "bogus"]), &interfaces).is_empty());
+    }
+
+    #[test]
+    fn test_prefix_matches() {
+        assert!(prefix_matches(&[10, 0, 0, 1], &[10, 0, 0, 254], 24));
+        assert!(!prefix_matches(&[10, 0, 1, 1], &[10, 0, 0, 254], 24));
+        assert!(prefix_matches(&[10, 0, 0, 1], &[192, 0, 0, 1], 0));
+        assert!(prefix_matches(&[10, 0, 0, 1], &[10, 0, 0, 1], 40));
+    }
+}
@@ -0,0 +1,277 @@
+#![forbid(unsafe_code)]
+
+use crate::config::is_service_type;
+use serde::Deserialize;
+use std::path::Path;
+
+// A service that should be on the network, every given field has to match
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedService {
+    // Instance name, compared ignoring case
+    pub name: Option<String>,
+    // Service type without domain, e.g. "_ipp._tcp"
+    pub service_type: Option<String>,
+    // Hostname, with or without ".local"
+    pub host: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Inventory {
+    #[serde(default)]
+    pub services: Vec<ExpectedService>,
+}
+
+// What the checklist is built from, borrowed from the services list
+pub struct ObservedService<'a> {
+    pub fullname: &'a str,
+    pub service_type: &'a str,
+    pub host: &'a str,
+    pub online: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Present,
+    // Only seen offline during the session
+    Offline,
+    Missing,
+    // Online but not in the inventory
+    Unexpected,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChecklistEntry {
+    pub status: CheckStatus,
+    pub label: String,
+    // The matching services of expected ones, the host of unexpected ones
+    pub detail: String,
+}
+
+impl Inventory {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let inventory: Inventory = toml::from_str(text).map_err(|e| e.to_string())?;
+        if let Some(index) = inventory
+            .services
+            .iter()
+            .position(|expected| expected.label().is_empty())
+        {
+            return Err(format!(
+                "service {} needs a name, service_type or host",
+                index + 1
+            ));
+        }
+        Ok(inventory)
+    }
+
+    // Expected services in file order, then the unexpected ones of the types the
+    // inventory covers, all types if an entry doesn't name one
+    pub fn check(&self, observed: &[ObservedService]) -> Vec<ChecklistEntry> {
+        let mut checklist: Vec<ChecklistEntry> = self
+            .services
+            .iter()
+            .map(|expected| {
+                let matching: Vec<&ObservedService> = observed
+                    .iter()
+                    .filter(|service| expected.matches(service))
+                    .collect();
+                let online: Vec<&str> = matching
+                    .iter()
+                    .filter(|service| service.online)
+                    .map(|service| service.fullname)
+                    .collect();
+                let (status, detail) = if !online.is_empty() {
+                    (CheckStatus::Present, online.join(", "))
+                } else if !matching.is_empty() {
+                    (CheckStatus::Offline, "offline".to_string())
+                } else {
+                    (CheckStatus::Missing, String::new())
+                };
+                ChecklistEntry {
+                    status,
+                    label: expected.label(),
+                    detail,
+                }
+            })
+            .collect();
+        let all_types = self.services.iter().any(|e| e.service_type.is_none());
+        checklist.extend(
+            observed
+                .iter()
+                .filter(|service| service.online)
+                .filter(|service| {
+                    all_types
+                        || self.services.iter().any(|expected| {
+                            expected
+                                .service_type
+                                .as_deref()
+                                .is_some_and(|t| is_service_type(service.service_type, t))
+                        })
+                })
+                .filter(|service| !self.services.iter().any(|e| e.matches(service)))
+                .map(|service| ChecklistEntry {
+                    status: CheckStatus::Unexpected,
+                    label: service.fullname.to_string(),
+                    detail: service.host.to_string(),
+                }),
+        );
+        checklist
+    }
+}
+
+impl ExpectedService {
+    fn matches(&self, service: &ObservedService) -> bool {
+        let instance = service
+            .fullname
+            .strip_suffix(service.service_type)
+            .map(|name| name.trim_end_matches('.'))
+            .unwrap_or(service.fullname);
+        self.name
+            .as_deref()
+            .is_none_or(|name| name.eq_ignore_ascii_case(instance))
+            && self
+                .service_type
+                .as_deref()
+                .is_none_or(|t| is_service_type(service.service_type, t))
+            && self
+                .host
+                .as_deref()
+                .is_none_or(|host| normalize_host(host) == normalize_host(service.host))
+    }
+
+    // e.g. "Office Printer (_ipp._tcp) on printer"
+    fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(name) = &self.name {
+            parts.push(name.clone());
+        }
+        if let Some(service_type) = &self.service_type {
+            parts.push(if self.name.is_some() {
+                format!("({})", service_type)
+            } else {
+                service_type.clone()
+            });
+        }
+        if let Some(host) = &self.host {
+            parts.push(if parts.is_empty() {
+                host.clone()
+            } else {
+                format!("on {}", host)
+            });
+        }
+        parts.join(" ")
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host.strip_suffix(".local").unwrap_or(&host).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observed<'a>(
+        fullname: &'a str,
+        service_type: &'a str,
+        host: &'a str,
+        online: bool,
+    ) -> ObservedService<'a> {
+        ObservedService {
+            fullname,
+            service_type,
+            host,
+            online,
+        }
+    }
+
+    #[test]
+    fn test_parse_inventory() {
+        let inventory = Inventory::parse(
+            r#"
+[[services]]
+name = "Office"
+service_type = "_ipp._tcp"
+
+[[services]]
+host = "nas.local"
+"#,
+        )
+        .unwrap();
+        assert_eq!(inventory.services.len(), 2);
+        assert_eq!(inventory.services[0].label(), "Office (_ipp._tcp)");
+        assert!(Inventory::parse("[[services]]").is_err());
+        assert!(Inventory::parse("[[services]]\nport = 80").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let inventory = Inventory::parse(
+            r#"
+[[services]]
+name = "office"
+service_type = "_ipp._tcp"
+
+[[services]]
+service_type = "_ipp._tcp"
+host = "basement"
+
+[[services]]
+name = "Lobby"
+service_type = "_ipp._tcp"
+"#,
+        )
+        .unwrap();
+        let services = [
+            observed(
+                "Office._ipp._tcp.local.",
+                "_ipp._tcp.local.",
+                "printer.local.",
+                true,
+            ),
+            observed(
+                "Basement._ipp._tcp.local.",
+                "_ipp._tcp.local.",
+                "basement.local.",
+                false,
+            ),
+            observed(
+                "Guest._ipp._tcp.local.",
+                "_ipp._tcp.local.",
+                "guest.local.",
+                true,
+            ),
+            observed(
+                "Web._http._tcp.local.",
+                "_http._tcp.local.",
+                "web.local.",
+                true,
+            ),
+        ];
+        let statuses: Vec<(CheckStatus, String)> = inventory
+            .check(&services)
+            .into_iter()
+            .map(|entry| (entry.status, entry.label))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (CheckStatus::Present, "office (_ipp._tcp)".to_string()),
+                (CheckStatus::Offline, "_ipp._tcp on basement".to_string()),
+                (CheckStatus::Missing, "Lobby (_ipp._tcp)".to_string()),
+                (
+                    CheckStatus::Unexpected,
+                    "Guest._ipp._tcp.local.".to_string()
+                ),
+            ]
+        );
+    }
+}
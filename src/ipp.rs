@@ -0,0 +1,380 @@
+#![forbid(unsafe_code)]
+
+use crate::http_probe::{self, HttpRequest};
+use std::time::Duration;
+
+const GET_PRINTER_ATTRIBUTES: u16 = 0x000b;
+
+// Delimiter and value tags (RFC 8010 section 3.5)
+const OPERATION_ATTRIBUTES_TAG: u8 = 0x01;
+const END_OF_ATTRIBUTES_TAG: u8 = 0x03;
+const INTEGER_TAG: u8 = 0x21;
+const ENUM_TAG: u8 = 0x23;
+const TEXT_WITH_LANGUAGE_TAG: u8 = 0x35;
+const NAME_WITH_LANGUAGE_TAG: u8 = 0x36;
+const URI_TAG: u8 = 0x45;
+const KEYWORD_TAG: u8 = 0x44;
+const CHARSET_TAG: u8 = 0x47;
+const NATURAL_LANGUAGE_TAG: u8 = 0x48;
+
+const REQUESTED_ATTRIBUTES: &[&str] = &[
+    "printer-make-and-model",
+    "printer-state",
+    "printer-state-reasons",
+    "document-format-supported",
+    "marker-names",
+    "marker-levels",
+    "marker-colors",
+];
+
+// The parts of a printer's attributes useful for diagnosing it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrinterStatus {
+    pub make_and_model: Option<String>,
+    pub state: Option<String>,
+    pub state_reasons: Vec<String>,
+    pub document_formats: Vec<String>,
+    pub markers: Vec<Marker>,
+}
+
+// A toner, ink or other consumable
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    // Percent, negative values mean unknown or "some remaining"
+    pub level: i32,
+    pub color: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Integer(i32),
+    Text(String),
+    Other,
+}
+
+// Where to send the request, `path` is the resource path from the rp TXT key
+pub struct PrinterRequest<'a> {
+    pub address: &'a str,
+    pub port: u16,
+    pub host: &'a str,
+    pub path: &'a str,
+    pub tls: bool,
+}
+
+// Sends a Get-Printer-Attributes request over IPP or IPPS
+pub async fn query_printer(
+    request: &PrinterRequest<'_>,
+    timeout: Duration,
+) -> Result<PrinterStatus, String> {
+    let path = format!("/{}", request.path.trim_start_matches('/'));
+    let scheme = if request.tls { "ipps" } else { "ipp" };
+    let printer_uri = format!("{}://{}:{}{}", scheme, request.host, request.port, path);
+    let http_request = HttpRequest {
+        address: request.address,
+        port: request.port,
+        host: request.host,
+        path: &path,
+        tls: request.tls,
+    };
+    let (status, body) = http_probe::post(
+        &http_request,
+        "application/ipp",
+        &encode_request(&printer_uri),
+        timeout,
+    )
+    .await?;
+    if status != 200 {
+        return Err(format!("HTTP status {}", status));
+    }
+    parse_response(&body)
+}
+
+fn encode_request(printer_uri: &str) -> Vec<u8> {
+    let mut request = vec![2, 0];
+    request.extend_from_slice(&GET_PRINTER_ATTRIBUTES.to_be_bytes());
+    request.extend_from_slice(&1u32.to_be_bytes());
+    request.push(OPERATION_ATTRIBUTES_TAG);
+    encode_attribute(&mut request, CHARSET_TAG, "attributes-charset", "utf-8");
+    encode_attribute(
+        &mut request,
+        NATURAL_LANGUAGE_TAG,
+        "attributes-natural-language",
+        "en",
+    );
+    encode_attribute(&mut request, URI_TAG, "printer-uri", printer_uri);
+    for (i, attribute) in REQUESTED_ATTRIBUTES.iter().enumerate() {
+        // Additional values of the same attribute come without a name
+        let name = if i == 0 { "requested-attributes" } else { "" };
+        encode_attribute(&mut request, KEYWORD_TAG, name, attribute);
+    }
+    request.push(END_OF_ATTRIBUTES_TAG);
+    request
+}
+
+fn encode_attribute(request: &mut Vec<u8>, tag: u8, name: &str, value: &str) {
+    request.push(tag);
+    request.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    request.extend_from_slice(name.as_bytes());
+    request.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    request.extend_from_slice(value.as_bytes());
+}
+
+// Reads big endian length prefixed fields off the front of a slice
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("truncated IPP response".to_string());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn prefixed(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+fn parse_response(response: &[u8]) -> Result<PrinterStatus, String> {
+    let mut reader = Reader { data: response };
+    reader.take(2)?;
+    let status_code = reader.u16()?;
+    reader.take(4)?;
+    // 0x0000-0x00ff are successful, possibly with ignored or substituted attributes
+    if status_code > 0x00ff {
+        return Err(format!("IPP status 0x{:04x}", status_code));
+    }
+
+    let mut attributes: Vec<(String, Vec<Value>)> = Vec::new();
+    loop {
+        let tag = reader.u8()?;
+        if tag == END_OF_ATTRIBUTES_TAG {
+            break;
+        }
+        // Start of another attribute group
+        if tag < 0x10 {
+            continue;
+        }
+        let name = String::from_utf8_lossy(reader.prefixed()?).into_owned();
+        let value = decode_value(tag, reader.prefixed()?);
+        match attributes.last_mut() {
+            Some((_, values)) if name.is_empty() => values.push(value),
+            _ => attributes.push((name, vec![value])),
+        }
+    }
+    Ok(printer_status(&attributes))
+}
+
+fn decode_value(tag: u8, value: &[u8]) -> Value {
+    match tag {
+        INTEGER_TAG | ENUM_TAG if value.len() == 4 => {
+            Value::Integer(i32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+        }
+        TEXT_WITH_LANGUAGE_TAG | NAME_WITH_LANGUAGE_TAG => {
+            let mut reader = Reader { data: value };
+            match reader.prefixed().and_then(|_| reader.prefixed()) {
+                Ok(text) => Value::Text(String::from_utf8_lossy(text).into_owned()),
+                Err(_) => Value::Other,
+            }
+        }
+        // All character string types
+        0x41..=0x49 => Value::Text(String::from_utf8_lossy(value).into_owned()),
+        _ => Value::Other,
+    }
+}
+
+fn printer_status(attributes: &[(String, Vec<Value>)]) -> PrinterStatus {
+    let values = |name: &str| {
+        attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or_default()
+    };
+    let texts = |name: &str| -> Vec<String> {
+        values(name)
+            .iter()
+            .filter_map(|value| match value {
+                Value::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    let integers = |name: &str| -> Vec<i32> {
+        values(name)
+            .iter()
+            .filter_map(|value| match value {
+                Value::Integer(integer) => Some(*integer),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let state = integers("printer-state").first().map(|state| match state {
+        3 => "idle".to_string(),
+        4 => "processing".to_string(),
+        5 => "stopped".to_string(),
+        other => format!("unknown ({})", other),
+    });
+    let colors = texts("marker-colors");
+    let markers = texts("marker-names")
+        .into_iter()
+        .zip(integers("marker-levels"))
+        .enumerate()
+        .map(|(i, (name, level))| Marker {
+            name,
+            level,
+            color: colors.get(i).cloned(),
+        })
+        .collect();
+    PrinterStatus {
+        make_and_model: texts("printer-make-and-model").into_iter().next(),
+        state,
+        // "none" is the only value of a printer without problems
+        state_reasons: texts("printer-state-reasons")
+            .into_iter()
+            .filter(|reason| reason != "none")
+            .collect(),
+        document_formats: texts("document-format-supported"),
+        markers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn encode_integer(response: &mut Vec<u8>, tag: u8, name: &str, value: i32) {
+        response.push(tag);
+        response.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        response.extend_from_slice(name.as_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn printer_response() -> Vec<u8> {
+        let mut response = vec![2, 0, 0, 0, 0, 0, 0, 1];
+        response.push(OPERATION_ATTRIBUTES_TAG);
+        encode_attribute(&mut response, CHARSET_TAG, "attributes-charset", "utf-8");
+        // Printer attributes group
+        response.push(0x04);
+        encode_attribute(
+            &mut response,
+            0x41,
+            "printer-make-and-model",
+            "Office Laser 9000",
+        );
+        encode_integer(&mut response, ENUM_TAG, "printer-state", 3);
+        encode_attribute(&mut response, KEYWORD_TAG, "printer-state-reasons", "none");
+        encode_attribute(
+            &mut response,
+            0x49,
+            "document-format-supported",
+            "application/pdf",
+        );
+        encode_attribute(&mut response, 0x49, "", "image/urf");
+        encode_attribute(&mut response, 0x42, "marker-names", "Black Toner");
+        encode_attribute(&mut response, 0x42, "", "Cyan Toner");
+        encode_integer(&mut response, INTEGER_TAG, "marker-levels", 80);
+        encode_integer(&mut response, INTEGER_TAG, "", -3);
+        encode_attribute(&mut response, 0x42, "marker-colors", "#000000");
+        encode_attribute(&mut response, 0x42, "", "#00FFFF");
+        response.push(END_OF_ATTRIBUTES_TAG);
+        response
+    }
+
+    fn expected_status() -> PrinterStatus {
+        PrinterStatus {
+            make_and_model: Some("Office Laser 9000".to_string()),
+            state: Some("idle".to_string()),
+            state_reasons: vec![],
+            document_formats: vec!["application/pdf".to_string(), "image/urf".to_string()],
+            markers: vec![
+                Marker {
+                    name: "Black Toner".to_string(),
+                    level: 80,
+                    color: Some("#000000".to_string()),
+                },
+                Marker {
+                    name: "Cyan Toner".to_string(),
+                    level: -3,
+                    color: Some("#00FFFF".to_string()),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let request = encode_request("ipp://printer.local:631/ipp/print");
+        assert_eq!(&request[..8], &[2, 0, 0, 0x0b, 0, 0, 0, 1]);
+        assert_eq!(request[8], OPERATION_ATTRIBUTES_TAG);
+        assert_eq!(request.last(), Some(&END_OF_ATTRIBUTES_TAG));
+        let text = String::from_utf8_lossy(&request);
+        assert!(text.contains("ipp://printer.local:631/ipp/print"));
+        assert!(text.contains("requested-attributes"));
+        assert!(text.contains("marker-levels"));
+    }
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response(&printer_response()), Ok(expected_status()));
+    }
+
+    #[test]
+    fn test_parse_error_response() {
+        let response = [2, 0, 0x04, 0x06, 0, 0, 0, 1, END_OF_ATTRIBUTES_TAG];
+        assert_eq!(
+            parse_response(&response),
+            Err("IPP status 0x0406".to_string())
+        );
+        assert!(parse_response(&[2, 0, 0]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_printer() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let len = socket.read(&mut request).await.unwrap();
+            assert!(request[..len].starts_with(b"POST /ipp/print HTTP/1.1\r\n"));
+            let body = printer_response();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&body).await.unwrap();
+        });
+
+        let request = PrinterRequest {
+            address: "127.0.0.1",
+            port,
+            host: "printer.local",
+            path: "ipp/print",
+            tls: false,
+        };
+        assert_eq!(
+            query_printer(&request, Duration::from_secs(2)).await,
+            Ok(expected_status())
+        );
+    }
+}
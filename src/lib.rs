@@ -0,0 +1,71 @@
+#![forbid(unsafe_code)]
+
+//! The mDNS aggregation behind the mdns-tui-browser TUI, for embedding in other
+//! programs.
+//!
+//! [`Browser::start`] browses for all service types on the local network and
+//! keeps the services found, including the ones gone offline for a while:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let browser = mdns_tui_browser::Browser::start(Default::default()).await?;
+//! let (services, mut events) = browser.watch().await;
+//! for service in services {
+//!     println!("{} at {}:{}", service.fullname, service.host, service.port);
+//! }
+//! while let Ok(event) = events.recv().await {
+//!     println!("{} {}", event.event.as_str(), event.fullname);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod backend;
+mod baseline;
+#[cfg(feature = "bonjour")]
+pub mod bonjour;
+mod browse_manager;
+mod cast;
+pub mod config;
+pub mod daemon_log;
+mod data_file;
+mod desktop_notify;
+mod device_category;
+pub mod discovery_events;
+pub mod error;
+mod event_log;
+mod export;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod home_assistant;
+mod http_probe;
+mod interfaces;
+mod inventory;
+mod ipp;
+mod mdns_query;
+#[cfg(any(test, feature = "mock-discovery"))]
+mod mock_discovery;
+mod mqtt;
+pub mod parsable_output;
+mod pins;
+pub mod remote;
+pub mod report;
+mod reverse_dns;
+mod runtime;
+pub mod schema;
+mod server;
+mod service_history;
+mod service_names;
+pub mod session;
+pub mod trace_log;
+pub mod tui_app;
+mod txt_decode;
+mod txt_schema;
+mod unicast_dns_sd;
+mod wake_on_lan;
+
+pub use backend::{BackendKind, ConfiguredBackend, DiscoveryBackend, ServiceEvents};
+pub use config::Config;
+pub use discovery_events::{DiscoveryEvent, EventKind};
+pub use error::Error;
+pub use tui_app::{Browser, ServiceEntry};
@@ -1,8 +1,10 @@
 #![forbid(unsafe_code)]
 
-mod tui_app;
-
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use mdns_tui_browser::{
+    Error, backend, config, daemon_log, parsable_output, remote, report, schema, session,
+    trace_log, tui_app,
+};
 
 #[derive(Parser)]
 #[command(
@@ -10,11 +12,190 @@ use clap::Parser;
     about = "A terminal-based mDNS service browser",
     after_help = "TUI Controls:\n  ?\t- Show/hide help popup with all key bindings\n  q\t- Quit the application\n\nFor complete key binding reference, press '?' in the application.",
 )]
-struct Cli {}
+struct Cli {
+    /// Path to the config file, defaults to config.toml in the platform config directory
+    #[arg(short, long, value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+
+    /// What discovers the services, overrides backend; bonjour runs the dns-sd tool of
+    /// mDNSResponder and needs a build with that feature
+    #[arg(long, value_enum, value_name = "BACKEND")]
+    backend: Option<backend::BackendKind>,
+
+    /// Hop limit of the queries sent by the app itself, overrides multicast_ttl
+    #[arg(long, value_name = "TTL")]
+    multicast_ttl: Option<u8>,
+
+    /// Don't loop back multicast sent by this host, hides local responders
+    #[arg(long)]
+    no_multicast_loop: bool,
+
+    /// Use mDNS only on this interface, can be repeated, overrides multicast_interfaces
+    #[arg(long = "interface", value_name = "NAME")]
+    interfaces: Vec<String>,
+
+    /// Check the discovered services against this inventory file, overrides inventory
+    #[arg(long, value_name = "PATH")]
+    inventory: Option<std::path::PathBuf>,
+
+    /// Append every discovery event with its timestamp to this JSON lines file
+    #[arg(long, value_name = "PATH")]
+    record: Option<std::path::PathBuf>,
+
+    /// Show the services of a recorded session instead of browsing the network
+    #[arg(long, value_name = "PATH", conflicts_with = "output")]
+    replay: Option<std::path::PathBuf>,
+
+    /// Show the services of a serve instance at HOST:PORT instead of browsing the network,
+    /// repeat to merge the services of several
+    #[arg(long, value_name = "HOST:PORT", conflicts_with_all = ["output", "replay"], value_parser = remote::RemoteAgent::parse)]
+    connect: Vec<remote::RemoteAgent>,
+
+    /// List a service seen by several --connect agents once instead of once per agent
+    #[arg(long, requires = "connect")]
+    merge_agents: bool,
+
+    /// Show the services of a session file announced by a mock daemon instead of the network
+    #[cfg(feature = "mock-discovery")]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["output", "replay", "connect"])]
+    mock: Option<std::path::PathBuf>,
+
+    /// How many times faster than recorded to replay, e.g. 10x
+    #[arg(long, value_name = "SPEED", requires = "replay", default_value = "1x", value_parser = session::parse_speed)]
+    speed: f64,
+
+    /// Print the JSON Schema of the exported and streamed services and events, then exit
+    #[arg(long)]
+    schema: bool,
+
+    /// Print discovered services as lines in this format instead of showing the TUI
+    #[arg(long, value_name = "FORMAT")]
+    output: Option<parsable_output::OutputFormat>,
+
+    /// Start with the default sorting and filters instead of the ones of the last session
+    #[arg(long)]
+    fresh: bool,
+
+    /// Trace discovery, state changes and rendering up to this level to the trace file
+    #[arg(long, value_enum, value_name = "LEVEL", default_value = "off")]
+    log_level: trace_log::LogLevel,
+
+    /// Trace file of --log-level, defaults to trace.log in the platform cache directory
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Keep discovering and serve the services over an HTTP API, see the README
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+
+        /// Also serve the gRPC API of proto/mdns_tui_browser.proto on this address,
+        /// needs a build with the grpc feature
+        #[arg(long, value_name = "ADDR")]
+        grpc: Option<std::net::SocketAddr>,
+
+        /// Let pages of this origin read the HTTP API from the browser,
+        /// e.g. https://dashboard.example
+        #[arg(long, value_name = "ORIGIN")]
+        cors_origin: Option<String>,
+    },
+    /// Browse for a while, then print a report of the services found
+    Report {
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: report::ReportFormat,
+
+        /// Section the report by service type or host
+        #[arg(long, value_enum, default_value = "type")]
+        group_by: report::ReportGrouping,
+
+        /// Seconds to browse before the report is printed
+        #[arg(long, value_name = "SECS", default_value_t = 10)]
+        duration: u64,
+    },
+}
+
+// Errors are printed with their message rather than debug formatted, so the
+// hints of the ones discovery and the TUI fail with are readable
+fn main() -> std::process::ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e.report());
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    Cli::parse();
+fn run(cli: Cli) -> Result<(), Error> {
+    if cli.schema {
+        print!("{}", schema::DOCUMENT);
+        return Ok(());
+    }
+    let mut config = config::Config::load(cli.config.as_deref())?;
+    if let Some(backend) = cli.backend {
+        config.backend = backend;
+    }
+    if let Some(ttl) = cli.multicast_ttl {
+        config.multicast_ttl = ttl;
+    }
+    if cli.no_multicast_loop {
+        config.multicast_loop = false;
+    }
+    if !cli.interfaces.is_empty() {
+        config.multicast_interfaces = cli.interfaces;
+    }
+    if cli.inventory.is_some() {
+        config.inventory = cli.inventory;
+    }
+    config.record = cli.record;
+    config.fresh_view = cli.fresh;
+    config.merge_agents = cli.merge_agents;
+    let source = match cli.replay {
+        Some(path) => tui_app::DiscoverySource::Replay(session::Replay::load(&path, cli.speed)?),
+        None if !cli.connect.is_empty() => tui_app::DiscoverySource::Remote(cli.connect),
+        None => tui_app::DiscoverySource::Network,
+    };
+    #[cfg(feature = "mock-discovery")]
+    let source = match cli.mock {
+        Some(path) => tui_app::DiscoverySource::Mock(session::Replay::load(&path, 1.0)?.events),
+        None => source,
+    };
+    daemon_log::init();
+    if let Some(path) = cli.log_file.or_else(trace_log::default_path) {
+        trace_log::init(cli.log_level, &path).map_err(|source| Error::TraceLog { path, source })?;
+    }
 
-    let rt = tokio::runtime::Runtime::new()?;
-    rt.block_on(tui_app::run_tui())
+    let rt = tokio::runtime::Runtime::new().map_err(Error::Runtime)?;
+    match (cli.command, cli.output) {
+        (
+            Some(Command::Report {
+                format,
+                group_by,
+                duration,
+            }),
+            _,
+        ) => rt.block_on(tui_app::run_report(
+            config,
+            format,
+            group_by,
+            std::time::Duration::from_secs(duration),
+        )),
+        (
+            Some(Command::Serve {
+                listen,
+                grpc,
+                cors_origin,
+            }),
+            _,
+        ) => rt.block_on(tui_app::run_serve(config, listen, grpc, cors_origin)),
+        (None, Some(format)) => rt.block_on(tui_app::run_headless(config, format)),
+        (None, None) => rt.block_on(tui_app::run_tui(config, source)),
+    }
 }
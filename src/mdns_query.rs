@@ -0,0 +1,456 @@
+#![forbid(unsafe_code)]
+
+use crate::interfaces::LocalInterface;
+use hickory_resolver::proto::op::{Message, MessageType, OpCode, Query};
+use hickory_resolver::proto::rr::{Name, RData, Record, RecordType};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const MDNS_V4_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_V6_GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+const MDNS_PORT: u16 = 5353;
+pub const MDNS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(MDNS_V4_GROUP), MDNS_PORT);
+
+// How the app's own queries are sent, set once at startup
+#[derive(Clone, Debug, PartialEq)]
+pub struct SocketOptions {
+    pub multicast_ttl: u8,
+    pub multicast_loop: bool,
+    // Address of the interface queries leave on, the routing table decides if None
+    pub multicast_if: Option<Ipv4Addr>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            multicast_ttl: 255,
+            multicast_loop: true,
+            multicast_if: None,
+        }
+    }
+}
+
+static SOCKET_OPTIONS: OnceLock<SocketOptions> = OnceLock::new();
+
+pub fn set_socket_options(options: SocketOptions) {
+    let _ = SOCKET_OPTIONS.set(options);
+}
+
+// A record received over mDNS and who sent it
+#[derive(Clone, Debug, PartialEq)]
+pub struct MdnsAnswer {
+    pub source: SocketAddr,
+    pub record: Record,
+}
+
+// What the SRV, TXT and address records of a service instance say right now
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InstanceRecords {
+    pub host: String,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+    pub txt: Vec<Vec<u8>>,
+    pub addrs: Vec<IpAddr>,
+}
+
+// Sends a legacy unicast query (RFC 6762 section 6.7) and collects the answers and
+// additional records of all responders until the timeout, responders answer directly
+// to the ephemeral source port, so this does not interfere with the daemon's socket
+pub async fn query(
+    questions: &[(Name, RecordType)],
+    timeout: Duration,
+) -> Result<Vec<MdnsAnswer>, String> {
+    let query = build_query(questions)?;
+    let socket = query_socket().await?;
+    socket
+        .send_to(&query, MDNS_ADDR)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut answers = Vec::new();
+    let receive = async {
+        let mut buffer = [0u8; 9000];
+        while let Ok((len, source)) = socket.recv_from(&mut buffer).await {
+            answers.extend(parse_response(&buffer[..len], source));
+        }
+    };
+    let _ = tokio::time::timeout(timeout, receive).await;
+    Ok(answers)
+}
+
+// An ephemeral port socket for legacy unicast queries with the configured options
+pub async fn query_socket() -> Result<UdpSocket, String> {
+    let options = SOCKET_OPTIONS.get().cloned().unwrap_or_default();
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|e| e.to_string())?;
+    socket
+        .set_multicast_ttl_v4(u32::from(options.multicast_ttl))
+        .map_err(|e| e.to_string())?;
+    socket
+        .set_multicast_loop_v4(options.multicast_loop)
+        .map_err(|e| e.to_string())?;
+    if let Some(ip) = options.multicast_if {
+        socket2::SockRef::from(&socket)
+            .set_multicast_if_v4(&ip)
+            .map_err(|e| format!("{}: {}", ip, e))?;
+    }
+    Ok(socket)
+}
+
+// Answers and additional records of a response, nothing for queries or garbage
+pub fn parse_response(bytes: &[u8], source: SocketAddr) -> Vec<MdnsAnswer> {
+    let Ok(message) = Message::from_vec(bytes) else {
+        return Vec::new();
+    };
+    if message.message_type() != MessageType::Response {
+        return Vec::new();
+    }
+    message
+        .answers()
+        .iter()
+        .chain(message.additionals())
+        .map(|record| MdnsAnswer {
+            source,
+            record: record.clone(),
+        })
+        .collect()
+}
+
+// Sockets sharing port 5353 with the daemon, which receive every multicast response
+// on the local networks, the groups are joined on each of the given interfaces
+pub fn multicast_listeners(interfaces: &[LocalInterface]) -> Result<Vec<UdpSocket>, String> {
+    let mut listeners = Vec::new();
+    let mut errors = Vec::new();
+    let v4: Vec<Ipv4Addr> = interfaces
+        .iter()
+        .filter_map(|interface| match interface.ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let mut v6: Vec<u32> = interfaces
+        .iter()
+        .filter(|interface| interface.ip.is_ipv6())
+        .filter_map(|interface| interface.index)
+        .collect();
+    v6.sort_unstable();
+    v6.dedup();
+
+    if !v4.is_empty() {
+        let address = SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT));
+        match shared_socket(Domain::IPV4, address) {
+            Ok(socket) => {
+                for ip in &v4 {
+                    if let Err(e) = socket.join_multicast_v4(&MDNS_V4_GROUP, ip) {
+                        errors.push(format!("{}: {}", ip, e));
+                    }
+                }
+                listeners.push(socket);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if !v6.is_empty() {
+        let address = SocketAddr::from((Ipv6Addr::UNSPECIFIED, MDNS_PORT));
+        match shared_socket(Domain::IPV6, address) {
+            Ok(socket) => {
+                for index in &v6 {
+                    if let Err(e) = socket.join_multicast_v6(&MDNS_V6_GROUP, *index) {
+                        errors.push(format!("interface {}: {}", index, e));
+                    }
+                }
+                listeners.push(socket);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if listeners.is_empty() && !errors.is_empty() {
+        return Err(errors.join(", "));
+    }
+    listeners
+        .into_iter()
+        .map(|socket| UdpSocket::from_std(socket.into()).map_err(|e| e.to_string()))
+        .collect()
+}
+
+// The daemon binds port 5353 with address and port reuse, so must everyone else
+fn shared_socket(domain: Domain, address: SocketAddr) -> Result<Socket, String> {
+    let setup = || {
+        let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+        if domain == Domain::IPV6 {
+            socket.set_only_v6(true)?;
+        }
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&address.into())?;
+        Ok::<_, std::io::Error>(socket)
+    };
+    setup().map_err(|e| format!("{}: {}", address, e))
+}
+
+pub fn build_query(questions: &[(Name, RecordType)]) -> Result<Vec<u8>, String> {
+    let mut message = Message::new();
+    message
+        .set_id(next_query_id())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false);
+    for (name, record_type) in questions {
+        message.add_query(Query::query(name.clone(), *record_type));
+    }
+    message.to_vec().map_err(|e| e.to_string())
+}
+
+// Asks for SRV and TXT of the instance, and for the addresses of its host unless
+// the responder already sent them along, None if nobody answered
+pub async fn resolve_instance(
+    instance: &str,
+    service_type: &str,
+    timeout: Duration,
+) -> Result<Option<InstanceRecords>, String> {
+    let name = instance_name(instance, service_type)?;
+    let answers = query(
+        &[
+            (name.clone(), RecordType::SRV),
+            (name.clone(), RecordType::TXT),
+        ],
+        timeout,
+    )
+    .await?;
+    let Some(mut records) = instance_records(&name, &answers) else {
+        return Ok(None);
+    };
+    if records.addrs.is_empty() {
+        records.addrs = resolve_host(&records.host, timeout).await?;
+    }
+    Ok(Some(records))
+}
+
+pub async fn resolve_host(host: &str, timeout: Duration) -> Result<Vec<IpAddr>, String> {
+    let name = Name::from_utf8(host).map_err(|e| e.to_string())?;
+    let answers = query(
+        &[
+            (name.clone(), RecordType::A),
+            (name.clone(), RecordType::AAAA),
+        ],
+        timeout,
+    )
+    .await?;
+    Ok(host_addresses(&name, &answers))
+}
+
+// "_ipp._tcp.local PTR" or "octopi" (ANY), names without a domain get local.
+// appended, labels are taken verbatim so instance names may contain spaces
+pub fn parse_question(input: &str) -> Result<(Name, RecordType), String> {
+    let input = input.trim();
+    let (name, record_type) = match input.rsplit_once(char::is_whitespace) {
+        Some((name, record_type)) => match record_type.to_uppercase().parse::<RecordType>() {
+            Ok(record_type) => (name.trim_end(), record_type),
+            Err(_) => (input, RecordType::ANY),
+        },
+        None => (input, RecordType::ANY),
+    };
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        return Err("Empty name".to_string());
+    }
+    let mut labels: Vec<&str> = name.split('.').collect();
+    if labels.len() == 1 {
+        labels.push("local");
+    }
+    if labels.iter().any(|label| label.is_empty()) {
+        return Err(format!("Invalid name: {}", name));
+    }
+    let mut name = Name::from_labels(labels.iter().map(|label| label.as_bytes()))
+        .map_err(|e| e.to_string())?;
+    name.set_fqdn(true);
+    Ok((name, record_type))
+}
+
+// "Office Printer" and "_ipp._tcp.local." -> a name whose first label keeps the dots and spaces
+pub fn instance_name(instance: &str, service_type: &str) -> Result<Name, String> {
+    let mut labels = vec![instance.as_bytes()];
+    labels.extend(
+        service_type
+            .split('.')
+            .filter(|label| !label.is_empty())
+            .map(str::as_bytes),
+    );
+    let mut name = Name::from_labels(labels).map_err(|e| e.to_string())?;
+    name.set_fqdn(true);
+    Ok(name)
+}
+
+fn instance_records(name: &Name, answers: &[MdnsAnswer]) -> Option<InstanceRecords> {
+    let srv = answers
+        .iter()
+        .find_map(|answer| match answer.record.data() {
+            RData::SRV(srv) if answer.record.name() == name => Some(srv.clone()),
+            _ => None,
+        })?;
+    let mut txt: Vec<Vec<u8>> = Vec::new();
+    for answer in answers {
+        if let RData::TXT(data) = answer.record.data()
+            && answer.record.name() == name
+        {
+            for entry in data.iter() {
+                if !entry.is_empty() && !txt.iter().any(|known| **known == **entry) {
+                    txt.push(entry.to_vec());
+                }
+            }
+        }
+    }
+    Some(InstanceRecords {
+        host: srv.target().to_string(),
+        port: srv.port(),
+        priority: srv.priority(),
+        weight: srv.weight(),
+        txt,
+        addrs: host_addresses(srv.target(), answers),
+    })
+}
+
+fn host_addresses(host: &Name, answers: &[MdnsAnswer]) -> Vec<IpAddr> {
+    let mut addrs: Vec<IpAddr> = answers
+        .iter()
+        .filter(|answer| answer.record.name() == host)
+        .filter_map(|answer| match answer.record.data() {
+            RData::A(a) => Some(IpAddr::V4(a.0)),
+            RData::AAAA(aaaa) => Some(IpAddr::V6(aaaa.0)),
+            _ => None,
+        })
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    addrs
+}
+
+// Query ids only need to differ between concurrent queries
+fn next_query_id() -> u16 {
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    static NEXT_ID: AtomicU16 = AtomicU16::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::rr::rdata::{A, SRV, TXT};
+
+    fn answer(record: Record) -> MdnsAnswer {
+        MdnsAnswer {
+            source: SocketAddr::from(([192, 168, 1, 20], 5353)),
+            record,
+        }
+    }
+
+    #[test]
+    fn test_build_query() {
+        let name = Name::from_utf8("octopi.local.").unwrap();
+        let query = Message::from_vec(
+            &build_query(&[(name.clone(), RecordType::A), (name, RecordType::AAAA)]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(query.queries().len(), 2);
+        assert_eq!(query.queries()[1].query_type(), RecordType::AAAA);
+        assert!(!query.recursion_desired());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let source = SocketAddr::from(([192, 168, 1, 20], 5353));
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        response.add_answer(Record::from_rdata(
+            Name::from_utf8("printer.local.").unwrap(),
+            120,
+            RData::A(A::new(192, 168, 1, 20)),
+        ));
+        let answers = parse_response(&response.to_vec().unwrap(), source);
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].record.ttl(), 120);
+
+        let query =
+            build_query(&[(Name::from_utf8("printer.local.").unwrap(), RecordType::A)]).unwrap();
+        assert!(parse_response(&query, source).is_empty());
+        assert!(parse_response(b"garbage", source).is_empty());
+    }
+
+    #[test]
+    fn test_parse_question() {
+        let (name, record_type) = parse_question("_ipp._tcp.local ptr").unwrap();
+        assert_eq!(name.to_string(), "_ipp._tcp.local.");
+        assert_eq!(record_type, RecordType::PTR);
+
+        let (name, record_type) = parse_question("octopi").unwrap();
+        assert_eq!(name.to_string(), "octopi.local.");
+        assert_eq!(record_type, RecordType::ANY);
+
+        let (name, record_type) = parse_question("Office Printer._ipp._tcp.local. TXT").unwrap();
+        assert_eq!(name.iter().next().unwrap(), b"Office Printer");
+        assert_eq!(record_type, RecordType::TXT);
+
+        assert!(parse_question("  ").is_err());
+        assert!(parse_question("a..local AAAA").is_err());
+    }
+
+    #[test]
+    fn test_instance_name() {
+        let name = instance_name("Office Printer v2.1", "_ipp._tcp.local.").unwrap();
+        assert_eq!(name.num_labels(), 4);
+        assert_eq!(
+            name.iter().next().unwrap(),
+            "Office Printer v2.1".as_bytes()
+        );
+        assert!(name.is_fqdn());
+    }
+
+    #[test]
+    fn test_instance_records() {
+        let name = instance_name("printer", "_ipp._tcp.local.").unwrap();
+        let host = Name::from_utf8("printer.local.").unwrap();
+        let other = Name::from_utf8("other.local.").unwrap();
+        let answers = vec![
+            answer(Record::from_rdata(
+                name.clone(),
+                120,
+                RData::SRV(SRV::new(10, 5, 631, host.clone())),
+            )),
+            answer(Record::from_rdata(
+                name.clone(),
+                4500,
+                RData::TXT(TXT::new(vec!["rp=ipp/print".to_string(), String::new()])),
+            )),
+            answer(Record::from_rdata(
+                host.clone(),
+                120,
+                RData::A(A::new(192, 168, 1, 20)),
+            )),
+            answer(Record::from_rdata(
+                other,
+                120,
+                RData::A(A::new(192, 168, 1, 99)),
+            )),
+        ];
+        assert_eq!(
+            instance_records(&name, &answers),
+            Some(InstanceRecords {
+                host: "printer.local.".to_string(),
+                port: 631,
+                priority: 10,
+                weight: 5,
+                txt: vec![b"rp=ipp/print".to_vec()],
+                addrs: vec![IpAddr::from([192, 168, 1, 20])],
+            })
+        );
+        assert_eq!(instance_records(&name, &answers[1..]), None);
+    }
+}
@@ -0,0 +1,226 @@
+#![forbid(unsafe_code)]
+
+use crate::session::{RecordedEvent, RecordedService, SessionEvent};
+use mdns_sd::{ServiceEvent, ServiceInfo};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// Stands in for the mDNS daemon's browsing without touching the network: the
+// services announced to it are reported to the browses like the daemon reports
+// responses, so everything after the daemon runs as it does live
+#[derive(Clone, Default)]
+pub struct MockDaemon {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    // Announced services by fullname
+    services: BTreeMap<String, RecordedService>,
+    // Open browses by the type they browse for
+    browses: Vec<(String, flume::Sender<ServiceEvent>)>,
+}
+
+impl MockDaemon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Like ServiceDaemon::browse, the meta query reports every announced type
+    pub fn browse(&self, service_type: &str) -> mdns_sd::Result<flume::Receiver<ServiceEvent>> {
+        let (sender, receiver) = flume::unbounded();
+        let mut inner = self.inner.lock().unwrap();
+        let _ = sender.send(ServiceEvent::SearchStarted(service_type.to_string()));
+        let services: Vec<RecordedService> = inner.services.values().cloned().collect();
+        for service in &services {
+            for event in events_for(service_type, service) {
+                let _ = sender.send(event);
+            }
+        }
+        inner.browses.push((service_type.to_string(), sender));
+        Ok(receiver)
+    }
+
+    // Ends the browses of the type, their receivers get no more events
+    pub fn stop_browse(&self, service_type: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .browses
+            .retain(|(browsed, _)| browsed != service_type);
+    }
+
+    // Ends all browses
+    pub fn shutdown(&self) {
+        self.inner.lock().unwrap().browses.clear();
+    }
+
+    // Announces a new service or an update of a known one
+    pub fn announce(&self, service: RecordedService) {
+        let mut inner = self.inner.lock().unwrap();
+        let new_type = !inner
+            .services
+            .values()
+            .any(|known| known.service_type == service.service_type);
+        inner.browses.retain(|(browsed, sender)| {
+            events_for(browsed, &service)
+                .into_iter()
+                // The meta query reports each type once
+                .filter(|event| new_type || !matches!(event, ServiceEvent::ServiceFound(meta, _) if is_meta_query(meta)))
+                .all(|event| sender.send(event).is_ok())
+        });
+        inner.services.insert(service.fullname.clone(), service);
+    }
+
+    // Says goodbye for an announced service
+    pub fn remove(&self, fullname: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(service) = inner.services.remove(fullname) else {
+            return;
+        };
+        inner.browses.retain(|(browsed, sender)| {
+            !browses_for(browsed, &service)
+                || sender
+                    .send(ServiceEvent::ServiceRemoved(
+                        browsed.clone(),
+                        service.fullname.clone(),
+                    ))
+                    .is_ok()
+        });
+    }
+
+    // Announces and removes the services of a session file at the recorded
+    // pace, the types follow from the services
+    pub async fn play(&self, events: Vec<RecordedEvent>) {
+        let started = tokio::time::Instant::now();
+        let first = events.first().map_or(0, |e| e.timestamp_micros);
+        for event in events {
+            let offset = event.timestamp_micros.saturating_sub(first);
+            tokio::time::sleep_until(started + std::time::Duration::from_micros(offset)).await;
+            match event.event {
+                SessionEvent::Resolved(service) => self.announce(service),
+                SessionEvent::Removed { fullname } => self.remove(&fullname),
+                SessionEvent::TypeFound { .. } | SessionEvent::TypeRemoved { .. } => {}
+            }
+        }
+    }
+}
+
+fn is_meta_query(service_type: &str) -> bool {
+    service_type.starts_with("_services._dns-sd._udp.")
+}
+
+fn browses_for(browsed: &str, service: &RecordedService) -> bool {
+    browsed == service.service_type || service.subtype.as_deref() == Some(browsed)
+}
+
+// What a browse of the type hears of the service
+fn events_for(browsed: &str, service: &RecordedService) -> Vec<ServiceEvent> {
+    if is_meta_query(browsed) {
+        return vec![ServiceEvent::ServiceFound(
+            browsed.to_string(),
+            service.service_type.clone(),
+        )];
+    }
+    if !browses_for(browsed, service) {
+        return Vec::new();
+    }
+    let mut events = vec![ServiceEvent::ServiceFound(
+        browsed.to_string(),
+        service.fullname.clone(),
+    )];
+    if let Some(resolved) = resolved_service(service) {
+        events.push(ServiceEvent::ServiceResolved(Box::new(resolved)));
+    }
+    events
+}
+
+fn resolved_service(service: &RecordedService) -> Option<mdns_sd::ResolvedService> {
+    let instance = service
+        .fullname
+        .strip_suffix(&service.service_type)?
+        .trim_end_matches('.');
+    let ty_domain = service.subtype.as_deref().unwrap_or(&service.service_type);
+    let txt: Vec<(&str, &str)> = service
+        .txt
+        .iter()
+        .map(|entry| entry.split_once('=').unwrap_or((entry, "")))
+        .collect();
+    let info = ServiceInfo::new(
+        ty_domain,
+        instance,
+        &service.host,
+        service.addrs.join(",").as_str(),
+        service.port,
+        txt.as_slice(),
+    )
+    .ok()?;
+    Some(info.as_resolved_service())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, port: u16) -> RecordedService {
+        RecordedService {
+            fullname: format!("{}._http._tcp.local.", name),
+            service_type: "_http._tcp.local.".to_string(),
+            subtype: None,
+            host: format!("{}.local.", name),
+            port,
+            addrs: vec!["192.168.1.10".to_string()],
+            txt: vec!["path=/".to_string()],
+            srv_priority: None,
+            srv_weight: None,
+        }
+    }
+
+    #[test]
+    fn test_browse_reports_announced_services() {
+        let daemon = MockDaemon::new();
+        daemon.announce(service("alpha", 80));
+        let types = daemon.browse("_services._dns-sd._udp.local.").unwrap();
+        let services = daemon.browse("_http._tcp.local.").unwrap();
+        daemon.announce(service("beta", 81));
+        daemon.remove("alpha._http._tcp.local.");
+
+        let found_types: Vec<String> = types
+            .drain()
+            .filter_map(|event| match event {
+                ServiceEvent::ServiceFound(_, found) => Some(found),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(found_types, ["_http._tcp.local."]);
+
+        let events: Vec<String> = services
+            .drain()
+            .map(|event| match event {
+                ServiceEvent::SearchStarted(_) => "started".to_string(),
+                ServiceEvent::ServiceFound(_, name) => format!("found {}", name),
+                ServiceEvent::ServiceResolved(resolved) => {
+                    format!("resolved {}:{}", resolved.host, resolved.port)
+                }
+                ServiceEvent::ServiceRemoved(_, name) => format!("removed {}", name),
+                _ => "other".to_string(),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                "started",
+                "found alpha._http._tcp.local.",
+                "resolved alpha.local.:80",
+                "found beta._http._tcp.local.",
+                "resolved beta.local.:81",
+                "removed alpha._http._tcp.local.",
+            ]
+        );
+
+        daemon.stop_browse("_http._tcp.local.");
+        daemon.announce(service("gamma", 82));
+        assert!(services.is_disconnected());
+        assert!(services.is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+#![forbid(unsafe_code)]
+
+use crate::config::MqttConfig;
+use crate::discovery_events::DiscoveryEvent;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+// The event loop reconnects on the next poll, this keeps it from spinning
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Publishes queued while the broker is unreachable, newer ones are dropped
+const REQUEST_CAPACITY: usize = 64;
+
+// Publishes every discovery event as JSON until the event stream closes,
+// connection problems are reported on `errors` and retried
+pub async fn publish_events(
+    config: MqttConfig,
+    mut events: broadcast::Receiver<DiscoveryEvent>,
+    errors: flume::Sender<String>,
+) {
+    let (client, connection) = connect(&config, &config.client_id, errors);
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let Ok(payload) = serde_json::to_vec(&event) else {
+            continue;
+        };
+        let _ = client.try_publish(
+            event_topic(&config.topic, &event),
+            QoS::AtLeastOnce,
+            config.retain,
+            payload,
+        );
+    }
+    connection.abort();
+}
+
+// Starts a client and the task driving its connection, connection problems are
+// reported on `errors` and retried until the task is aborted
+pub fn connect(
+    config: &MqttConfig,
+    client_id: &str,
+    errors: flume::Sender<String>,
+) -> (AsyncClient, tokio::task::JoinHandle<()>) {
+    let mut options = MqttOptions::new(client_id, &config.host, config.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+    let (client, mut event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+
+    let connection = tokio::spawn(async move {
+        let mut connected = true;
+        loop {
+            match event_loop.poll().await {
+                Ok(_) => connected = true,
+                Err(e) => {
+                    // Only the first failure of an outage is worth reporting
+                    if connected {
+                        let _ = errors.send(format!("MQTT broker unreachable: {}", e));
+                    }
+                    connected = false;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+    (client, connection)
+}
+
+// Fills in {event}, {type} and {name}, characters with a meaning in topics are
+// replaced so a service can't address other topics
+pub fn event_topic(template: &str, event: &DiscoveryEvent) -> String {
+    template
+        .replace("{event}", event.event.as_str())
+        .replace("{type}", &topic_level(&event.service_type))
+        .replace("{name}", &topic_level(event.instance()))
+}
+
+fn topic_level(value: &str) -> String {
+    value
+        .trim_end_matches('.')
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' | '\0' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery_events::EventKind;
+    use crate::schema::SchemaVersion;
+
+    #[test]
+    fn test_event_topic() {
+        let event = DiscoveryEvent {
+            schema_version: SchemaVersion,
+            event: EventKind::Removed,
+            fullname: "Living Room/TV #2._airplay._tcp.local.".to_string(),
+            service_type: "_airplay._tcp.local.".to_string(),
+            host: "tv.local.".to_string(),
+            port: 7000,
+            addrs: Vec::new(),
+            txt: Vec::new(),
+            timestamp_micros: 0,
+        };
+        assert_eq!(
+            event_topic("mdns/{event}/{type}/{name}", &event),
+            "mdns/removed/_airplay._tcp.local/Living Room_TV _2"
+        );
+        assert_eq!(event_topic("lan/services", &event), "lan/services");
+    }
+}
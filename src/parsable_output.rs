@@ -0,0 +1,400 @@
+#![forbid(unsafe_code)]
+
+use crate::discovery_events::{DiscoveryEvent, EventKind};
+use crate::interfaces::{self, LocalInterface};
+use chrono::{DateTime, Local, TimeZone};
+use std::net::IpAddr;
+
+// Flag dns-sd sets on browse results of services that appeared
+const DNS_SERVICE_FLAGS_ADD: u32 = 0x2;
+
+// Line formats of the headless mode, for scripts written against other tools
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    // Like `avahi-browse -a -r -p`
+    Avahi,
+    // Like the browse listing of `dns-sd -B`
+    DnsSd,
+    // Like the resolve results of `dns-sd -L`
+    DnsSdLookup,
+}
+
+// Lines printed once before the first event
+pub fn header(format: OutputFormat, now: DateTime<Local>) -> Vec<String> {
+    let date = format!("DATE: ---{}---", now.format("%a %d %b %Y"));
+    match format {
+        OutputFormat::Avahi => Vec::new(),
+        OutputFormat::DnsSd => vec![
+            "Browsing for all service types".to_string(),
+            date,
+            format!(
+                "Timestamp     A/R    Flags  if {:<20} {:<20} {}",
+                "Domain", "Service Type", "Instance Name"
+            ),
+        ],
+        OutputFormat::DnsSdLookup => vec!["Lookup of all service instances".to_string(), date],
+    }
+}
+
+// The lines printed for an event, possibly none
+pub fn format_event(
+    format: OutputFormat,
+    event: &DiscoveryEvent,
+    local_interfaces: &[LocalInterface],
+) -> Vec<String> {
+    match format {
+        OutputFormat::Avahi => avahi_lines(event, local_interfaces),
+        OutputFormat::DnsSd => dns_sd_browse_lines(event, local_interfaces),
+        OutputFormat::DnsSdLookup => dns_sd_lookup_lines(event, local_interfaces),
+    }
+}
+
+// The service type without and the domain without the trailing dot, as avahi
+// prints them, e.g. ("_ipp._tcp", "local")
+fn split_service_type(service_type: &str) -> (&str, &str) {
+    let service_type = service_type.trim_end_matches('.');
+    ["._tcp.", "._udp."]
+        .iter()
+        .find_map(|protocol| {
+            service_type.find(protocol).map(|start| {
+                let end = start + protocol.len() - 1;
+                (&service_type[..end], &service_type[end + 1..])
+            })
+        })
+        .unwrap_or((service_type, "local"))
+}
+
+// An address without its zone id, with the interface it is reachable on
+fn endpoint(addr: &str, local_interfaces: &[LocalInterface]) -> (String, &'static str, String) {
+    let interface = interfaces::interfaces_for_addresses(&[addr.to_string()], local_interfaces)
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| "n/a".to_string());
+    let ip = addr.split('%').next().unwrap_or(addr);
+    let protocol = match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => "IPv6",
+        _ => "IPv4",
+    };
+    (interface, protocol, ip.to_string())
+}
+
+// `+` when a service appears, `-` when it goes away, once per interface and
+// protocol, `=` with host, address, port and TXT once per resolved address
+fn avahi_lines(event: &DiscoveryEvent, local_interfaces: &[LocalInterface]) -> Vec<String> {
+    let (service_type, domain) = split_service_type(&event.service_type);
+    let name = avahi_escape_label(event.instance());
+    let endpoints: Vec<(String, &str, String)> = event
+        .addrs
+        .iter()
+        .map(|addr| endpoint(addr, local_interfaces))
+        .collect();
+    let mut browse_lines: Vec<String> = Vec::new();
+    let prefix = match event.event {
+        EventKind::Added => Some('+'),
+        EventKind::Removed => Some('-'),
+        EventKind::Updated => None,
+    };
+    if let Some(prefix) = prefix {
+        let mut seen: Vec<(&str, &str)> = Vec::new();
+        for (interface, protocol, _) in &endpoints {
+            if !seen.contains(&(interface.as_str(), protocol)) {
+                seen.push((interface.as_str(), protocol));
+            }
+        }
+        if seen.is_empty() {
+            seen.push(("n/a", "IPv4"));
+        }
+        browse_lines.extend(seen.into_iter().map(|(interface, protocol)| {
+            format!(
+                "{};{};{};{};{};{}",
+                prefix, interface, protocol, name, service_type, domain
+            )
+        }));
+    }
+    if event.event == EventKind::Removed {
+        return browse_lines;
+    }
+    let txt = event
+        .txt
+        .iter()
+        .map(|entry| format!("\"{}\"", avahi_escape_txt(entry)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    browse_lines.extend(endpoints.iter().map(|(interface, protocol, ip)| {
+        format!(
+            "=;{};{};{};{};{};{};{};{};{}",
+            interface,
+            protocol,
+            name,
+            service_type,
+            domain,
+            event.host.trim_end_matches('.'),
+            ip,
+            event.port,
+            txt
+        )
+    }));
+    browse_lines
+}
+
+// Like avahi_escape_label: dots and backslashes get a backslash, everything but
+// letters, digits, '-' and '_' is written as a three digit decimal byte value
+fn avahi_escape_label(label: &str) -> String {
+    let mut escaped = String::new();
+    for byte in label.bytes() {
+        match byte {
+            b'.' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            }
+            b'-' | b'_' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03}", byte)),
+        }
+    }
+    escaped
+}
+
+// Quotes and backslashes would end or garble the quoted TXT string
+fn avahi_escape_txt(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// "HH:MM:SS.mmm" of the local time, the prefix of every dns-sd result
+fn dns_sd_timestamp(timestamp_micros: u64) -> String {
+    Local
+        .timestamp_micros(timestamp_micros as i64)
+        .single()
+        .map(|time| time.format("%H:%M:%S%.3f").to_string())
+        .unwrap_or_default()
+}
+
+// The distinct indexes of the interfaces a service was seen on, dns-sd reports
+// a result per interface, 0 if the interface is unknown
+fn interface_indexes(addrs: &[String], local_interfaces: &[LocalInterface]) -> Vec<u32> {
+    let mut indexes: Vec<u32> = interfaces::interfaces_for_addresses(addrs, local_interfaces)
+        .iter()
+        .map(|name| {
+            local_interfaces
+                .iter()
+                .find(|interface| &interface.name == name)
+                .and_then(|interface| interface.index)
+                .unwrap_or(0)
+        })
+        .collect();
+    indexes.dedup();
+    if indexes.is_empty() {
+        indexes.push(0);
+    }
+    indexes
+}
+
+fn dns_sd_browse_lines(event: &DiscoveryEvent, local_interfaces: &[LocalInterface]) -> Vec<String> {
+    let (operation, flags) = match event.event {
+        EventKind::Added => ("Add", DNS_SERVICE_FLAGS_ADD),
+        EventKind::Removed => ("Rmv", 0),
+        EventKind::Updated => return Vec::new(),
+    };
+    let (service_type, domain) = split_service_type(&event.service_type);
+    let timestamp = dns_sd_timestamp(event.timestamp_micros);
+    interface_indexes(&event.addrs, local_interfaces)
+        .into_iter()
+        .map(|index| {
+            format!(
+                "{}  {} {:>8X} {:>3} {:<20} {:<20} {}",
+                timestamp,
+                operation,
+                flags,
+                index,
+                format!("{}.", domain),
+                format!("{}.", service_type),
+                event.instance()
+            )
+        })
+        .collect()
+}
+
+// "<name> can be reached at <host>:<port> (interface <index>)", followed by the
+// TXT entries on a line of their own
+fn dns_sd_lookup_lines(event: &DiscoveryEvent, local_interfaces: &[LocalInterface]) -> Vec<String> {
+    if event.event == EventKind::Removed {
+        return Vec::new();
+    }
+    let fullname = format!(
+        "{}.{}",
+        dns_sd_escape_label(event.instance()),
+        event.service_type
+    );
+    let timestamp = dns_sd_timestamp(event.timestamp_micros);
+    let txt: String = event
+        .txt
+        .iter()
+        .map(|entry| format!(" {}", dns_sd_escape_txt(entry)))
+        .collect();
+    let mut lines = Vec::new();
+    for index in interface_indexes(&event.addrs, local_interfaces) {
+        lines.push(format!(
+            "{}  {} can be reached at {}:{} (interface {})",
+            timestamp, fullname, event.host, event.port, index
+        ));
+        if !txt.is_empty() {
+            lines.push(txt.clone());
+        }
+    }
+    lines
+}
+
+// Like mDNSResponder's label escaping: dots and backslashes get a backslash,
+// control characters and spaces are written as a three digit decimal value
+fn dns_sd_escape_label(label: &str) -> String {
+    let mut escaped = String::new();
+    for c in label.chars() {
+        match c {
+            '.' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c <= ' ' || c == '\x7f' => escaped.push_str(&format!("\\{:03}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Spaces separate the entries, so they and backslashes are escaped
+fn dns_sd_escape_txt(entry: &str) -> String {
+    let mut escaped = String::new();
+    for c in entry.chars() {
+        match c {
+            '\\' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c < ' ' => escaped.push_str(&format!("\\x{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LASERJET: &str = "HP LaserJet 400._ipp._tcp.local.";
+    const ADDRESSES: [&str; 2] = ["192.168.1.5", "fe80::1%eth0"];
+
+    fn interfaces() -> Vec<LocalInterface> {
+        vec![LocalInterface {
+            name: "eth0".to_string(),
+            index: Some(2),
+            ip: "192.168.1.10".parse().unwrap(),
+            prefix_len: 24,
+        }]
+    }
+
+    #[test]
+    fn test_avahi_added() {
+        assert_eq!(
+            format_event(
+                OutputFormat::Avahi,
+                &DiscoveryEvent::for_test(EventKind::Added, LASERJET, &ADDRESSES),
+                &interfaces()
+            ),
+            [
+                "+;eth0;IPv4;HP\\032LaserJet\\032400;_ipp._tcp;local",
+                "+;eth0;IPv6;HP\\032LaserJet\\032400;_ipp._tcp;local",
+                "=;eth0;IPv4;HP\\032LaserJet\\032400;_ipp._tcp;local;printer.local;192.168.1.5;631;\"txtvers=1\" \"note=Room \\\"4\\\"\"",
+                "=;eth0;IPv6;HP\\032LaserJet\\032400;_ipp._tcp;local;printer.local;fe80::1;631;\"txtvers=1\" \"note=Room \\\"4\\\"\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_avahi_removed_and_updated() {
+        let mut removed = DiscoveryEvent::for_test(EventKind::Removed, LASERJET, &ADDRESSES);
+        removed.addrs.truncate(1);
+        assert_eq!(
+            format_event(OutputFormat::Avahi, &removed, &interfaces()),
+            ["-;eth0;IPv4;HP\\032LaserJet\\032400;_ipp._tcp;local"]
+        );
+        let mut updated = DiscoveryEvent::for_test(EventKind::Updated, LASERJET, &ADDRESSES);
+        updated.addrs = vec!["10.0.0.5".to_string()];
+        updated.txt.clear();
+        assert_eq!(
+            format_event(OutputFormat::Avahi, &updated, &interfaces()),
+            ["=;n/a;IPv4;HP\\032LaserJet\\032400;_ipp._tcp;local;printer.local;10.0.0.5;631;"]
+        );
+    }
+
+    #[test]
+    fn test_split_service_type() {
+        assert_eq!(
+            split_service_type("_http._tcp.example.com."),
+            ("_http._tcp", "example.com")
+        );
+        assert_eq!(avahi_escape_label("a.b\\c"), "a\\.b\\\\c");
+    }
+
+    #[test]
+    fn test_dns_sd_browse() {
+        let timestamp = dns_sd_timestamp(0);
+        assert_eq!(
+            format_event(
+                OutputFormat::DnsSd,
+                &DiscoveryEvent::for_test(EventKind::Added, LASERJET, &ADDRESSES),
+                &interfaces()
+            ),
+            [format!(
+                "{}  Add        2   2 local.               _ipp._tcp.           HP LaserJet 400",
+                timestamp
+            )]
+        );
+        let mut removed = DiscoveryEvent::for_test(EventKind::Removed, LASERJET, &ADDRESSES);
+        removed.addrs = vec!["10.0.0.5".to_string()];
+        assert_eq!(
+            format_event(OutputFormat::DnsSd, &removed, &interfaces()),
+            [format!(
+                "{}  Rmv        0   0 local.               _ipp._tcp.           HP LaserJet 400",
+                timestamp
+            )]
+        );
+        assert!(
+            format_event(
+                OutputFormat::DnsSd,
+                &DiscoveryEvent::for_test(EventKind::Updated, LASERJET, &ADDRESSES),
+                &interfaces()
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            header(OutputFormat::DnsSd, Local::now())[2],
+            "Timestamp     A/R    Flags  if Domain               Service Type         Instance Name"
+        );
+    }
+
+    #[test]
+    fn test_dns_sd_lookup() {
+        assert_eq!(
+            format_event(
+                OutputFormat::DnsSdLookup,
+                &DiscoveryEvent::for_test(EventKind::Updated, LASERJET, &ADDRESSES),
+                &interfaces()
+            ),
+            [
+                format!(
+                    "{}  HP\\032LaserJet\\032400._ipp._tcp.local. can be reached at printer.local.:631 (interface 2)",
+                    dns_sd_timestamp(0)
+                ),
+                " txtvers=1 note=Room\\ \"4\"".to_string(),
+            ]
+        );
+        assert!(
+            format_event(
+                OutputFormat::DnsSdLookup,
+                &DiscoveryEvent::for_test(EventKind::Removed, LASERJET, &ADDRESSES),
+                &interfaces()
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,158 @@
+#![forbid(unsafe_code)]
+
+use crate::data_file;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pin {
+    pub pinned_micros: u64,
+    // What the user noted about the service
+    #[serde(default)]
+    pub note: String,
+    // The service as it was last listed, shown while it isn't
+    #[serde(default)]
+    pub last_seen: Option<Sighting>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sighting {
+    pub host: String,
+    pub port: u16,
+    pub online: bool,
+    pub last_seen_micros: u64,
+}
+
+// Services pinned to the top of the list by fullname, kept on disk so the
+// important ones stay in sight across sessions
+pub struct PinStore {
+    path: Option<PathBuf>,
+    pins: BTreeMap<String, Pin>,
+}
+
+impl PinStore {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            pins: BTreeMap::new(),
+        }
+    }
+
+    pub fn load_default() -> Self {
+        match data_file::path("pins.json") {
+            Some(path) => Self::load(&path),
+            None => Self::in_memory(),
+        }
+    }
+
+    // A missing or unreadable file starts without pins
+    pub fn load(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+            pins: data_file::load_json(path),
+        }
+    }
+
+    pub fn contains(&self, fullname: &str) -> bool {
+        self.pins.contains_key(fullname)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    pub fn get(&self, fullname: &str) -> Option<&Pin> {
+        self.pins.get(fullname)
+    }
+
+    // By fullname
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Pin)> {
+        self.pins.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+
+    // Pins the service or unpins it if it was, returns whether it is pinned now
+    pub fn toggle(&mut self, fullname: &str, now_micros: u64) -> bool {
+        if self.remove(fullname) {
+            return false;
+        }
+        self.pins.insert(
+            fullname.to_string(),
+            Pin {
+                pinned_micros: now_micros,
+                note: String::new(),
+                last_seen: None,
+            },
+        );
+        true
+    }
+
+    pub fn remove(&mut self, fullname: &str) -> bool {
+        self.pins.remove(fullname).is_some()
+    }
+
+    pub fn set_note(&mut self, fullname: &str, note: &str) {
+        if let Some(pin) = self.pins.get_mut(fullname) {
+            pin.note = note.trim().to_string();
+        }
+    }
+
+    pub fn note_sighting(&mut self, fullname: &str, sighting: Sighting) {
+        if let Some(pin) = self.pins.get_mut(fullname) {
+            pin.last_seen = Some(sighting);
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        data_file::save_json(path, &self.pins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pins_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("pins.json");
+
+        let mut pins = PinStore::load(&path);
+        assert!(pins.is_empty());
+        assert!(pins.toggle("NAS._smb._tcp.local.", 1));
+        assert!(pins.toggle("Printer._ipp._tcp.local.", 2));
+        assert!(!pins.toggle("Printer._ipp._tcp.local.", 3));
+        pins.set_note("NAS._smb._tcp.local.", " backups ");
+        let sighting = Sighting {
+            host: "nas.local.".to_string(),
+            port: 445,
+            online: true,
+            last_seen_micros: 4,
+        };
+        pins.note_sighting("NAS._smb._tcp.local.", sighting.clone());
+        pins.save().unwrap();
+
+        let pins = PinStore::load(&path);
+        assert_eq!(pins.len(), 1);
+        let pin = pins.get("NAS._smb._tcp.local.").unwrap();
+        assert_eq!(pin.note, "backups");
+        assert_eq!(pin.last_seen, Some(sighting));
+        assert!(!pins.contains("Printer._ipp._tcp.local."));
+    }
+
+    #[test]
+    fn test_pins_without_note_or_sighting_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pins.json");
+        std::fs::write(&path, r#"{"NAS._smb._tcp.local.": {"pinned_micros": 1}}"#).unwrap();
+        let pins = PinStore::load(&path);
+        assert_eq!(pins.get("NAS._smb._tcp.local.").unwrap().note, "");
+    }
+}
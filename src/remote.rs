@@ -0,0 +1,202 @@
+#![forbid(unsafe_code)]
+
+use crate::discovery_events::{DiscoveryEvent, EventKind};
+use crate::http_probe::{self, HttpRequest};
+use crate::session::{RecordedService, SessionEvent};
+use serde::Deserialize;
+use std::time::Duration;
+
+// Port of serve unless another one is given
+const DEFAULT_PORT: u16 = 8080;
+// The server answers a poll after at most POLL_TIMEOUT without events
+const POLL_TIMEOUT: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// A serve instance, whose discoveries are shown instead of the local ones
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteAgent {
+    host: String,
+    port: u16,
+}
+
+// A service as /services lists it
+#[derive(Debug, Deserialize)]
+struct RemoteService {
+    fullname: String,
+    service_type: String,
+    subtype: Option<String>,
+    host: String,
+    port: u16,
+    addrs: Vec<String>,
+    txt: Vec<String>,
+    online: bool,
+    srv_priority: Option<u16>,
+    srv_weight: Option<u16>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventBatch {
+    next: u64,
+    // Events dropped from the backlog before they were polled, from agents
+    // before it was reported too
+    #[serde(default)]
+    missed: u64,
+    events: Vec<DiscoveryEvent>,
+}
+
+impl RemoteAgent {
+    // "host", "host:port" or "[v6 address]:port"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid address {:?}, expected host:port", text);
+        let (host, port) = match text.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') || host.starts_with('[') => {
+                (host, port.parse().map_err(|_| invalid())?)
+            }
+            _ => (text, DEFAULT_PORT),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    // The current state of the agent as events, the types first, and where
+    // the events after it start
+    pub async fn snapshot(&self) -> Result<(u64, Vec<SessionEvent>), String> {
+        // Asked for first, so no event between it and the lists is missed
+        let start: EventBatch = self.get("/events?timeout=0", REQUEST_TIMEOUT).await?;
+        let service_types: Vec<String> = self.get("/types", REQUEST_TIMEOUT).await?;
+        let services: Vec<RemoteService> = self.get("/services", REQUEST_TIMEOUT).await?;
+        let mut events: Vec<SessionEvent> = service_types
+            .into_iter()
+            .map(|service_type| SessionEvent::TypeFound { service_type })
+            .collect();
+        for service in services {
+            let online = service.online;
+            let fullname = service.fullname.clone();
+            events.push(SessionEvent::Resolved(RecordedService {
+                fullname: service.fullname,
+                service_type: service.service_type,
+                subtype: service.subtype,
+                host: service.host,
+                port: service.port,
+                addrs: service.addrs,
+                txt: service.txt,
+                srv_priority: service.srv_priority,
+                srv_weight: service.srv_weight,
+            }));
+            if !online {
+                events.push(SessionEvent::Removed { fullname });
+            }
+        }
+        Ok((start.next, events))
+    }
+
+    // Waits for the events from since on, returns them with where the next start
+    pub async fn poll(&self, since: u64) -> Result<(u64, Vec<SessionEvent>), String> {
+        let batch: EventBatch = self
+            .get(
+                &format!("/events?since={}&timeout={}", since, POLL_TIMEOUT.as_secs()),
+                POLL_TIMEOUT + REQUEST_TIMEOUT,
+            )
+            .await?;
+        // The services are out of step then, a new snapshot brings them back
+        if batch.missed > 0 {
+            return Err(format!("missed {} events", batch.missed));
+        }
+        Ok((
+            batch.next,
+            batch.events.into_iter().map(session_event).collect(),
+        ))
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        timeout: Duration,
+    ) -> Result<T, String> {
+        // serve only answers requests naming its address, not a host name
+        let address = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| format!("{}: {}", self.host, e))?
+            .next()
+            .ok_or_else(|| format!("{}: no address", self.host))?;
+        let host = std::net::SocketAddr::new(address.ip(), address.port()).to_string();
+        let request = HttpRequest {
+            address: &self.host,
+            port: self.port,
+            host: &host,
+            path,
+            tls: false,
+        };
+        let (status, body) = http_probe::get(&request, timeout).await?;
+        if status != 200 {
+            return Err(format!("{} answered {}", path, status));
+        }
+        serde_json::from_slice(&body).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+// Additions and updates carry the whole service, removals only need the name
+fn session_event(event: DiscoveryEvent) -> SessionEvent {
+    match event.event {
+        EventKind::Added | EventKind::Updated => SessionEvent::Resolved(RecordedService {
+            fullname: event.fullname,
+            service_type: event.service_type,
+            subtype: None,
+            host: event.host,
+            port: event.port,
+            addrs: event.addrs,
+            txt: event.txt,
+            srv_priority: None,
+            srv_weight: None,
+        }),
+        EventKind::Removed => SessionEvent::Removed {
+            fullname: event.fullname,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_agent() {
+        let agent = RemoteAgent::parse("nas.lan:9000").unwrap();
+        assert_eq!((agent.host.as_str(), agent.port), ("nas.lan", 9000));
+        assert_eq!(RemoteAgent::parse("nas.lan").unwrap().port, DEFAULT_PORT);
+        let agent = RemoteAgent::parse("[fd00::1]:8081").unwrap();
+        assert_eq!((agent.host.as_str(), agent.port), ("fd00::1", 8081));
+        assert_eq!(agent.label(), "[fd00::1]:8081");
+        assert_eq!(RemoteAgent::parse("fd00::1").unwrap().host, "fd00::1");
+        assert!(RemoteAgent::parse("nas.lan:http").is_err());
+        assert!(RemoteAgent::parse(":8080").is_err());
+    }
+
+    #[test]
+    fn test_session_event() {
+        let event: DiscoveryEvent = serde_json::from_str(
+            r#"{"event":"removed","fullname":"Office._ipp._tcp.local.","service_type":"_ipp._tcp.local.","host":"printer.local.","port":631,"addrs":[],"txt":[],"timestamp_micros":0}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            session_event(event),
+            SessionEvent::Removed {
+                fullname: "Office._ipp._tcp.local.".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,285 @@
+#![forbid(unsafe_code)]
+
+use crate::export::markdown_cell;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+// The sections of a report, one per service type or host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportGrouping {
+    Type,
+    Host,
+}
+
+impl ReportGrouping {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportGrouping::Type => "type",
+            ReportGrouping::Host => "host",
+        }
+    }
+}
+
+// The fields of an exported service a report shows
+#[derive(Debug, Deserialize)]
+struct ReportService {
+    fullname: String,
+    service_type: String,
+    host: String,
+    port: u16,
+    addrs: Vec<String>,
+    txt: Vec<String>,
+    online: bool,
+}
+
+impl ReportService {
+    fn instance(&self) -> &str {
+        self.fullname
+            .strip_suffix(&self.service_type)
+            .map(|name| name.trim_end_matches('.'))
+            .unwrap_or(&self.fullname)
+    }
+
+    fn status(&self) -> &'static str {
+        if self.online { "online" } else { "offline" }
+    }
+
+    // Heading within a section, names are only unique per type
+    fn heading(&self, grouping: ReportGrouping) -> String {
+        match grouping {
+            ReportGrouping::Type => self.instance().to_string(),
+            ReportGrouping::Host => format!("{} ({})", self.instance(), self.service_type),
+        }
+    }
+
+    fn txt_entries(&self) -> Vec<(&str, &str)> {
+        self.txt
+            .iter()
+            .map(|entry| entry.split_once('=').unwrap_or((entry, "")))
+            .collect()
+    }
+}
+
+// A readable inventory of the services grouped into sections, with a table of
+// TXT records per service
+pub fn render<T: serde::Serialize>(
+    format: ReportFormat,
+    grouping: ReportGrouping,
+    services: &[T],
+    generated: DateTime<Local>,
+) -> Result<String, String> {
+    let services = services
+        .iter()
+        .map(|service| {
+            serde_json::to_value(service)
+                .and_then(serde_json::from_value::<ReportService>)
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut sections: BTreeMap<&str, Vec<&ReportService>> = BTreeMap::new();
+    for service in &services {
+        let key = match grouping {
+            ReportGrouping::Type => service.service_type.as_str(),
+            ReportGrouping::Host => service.host.as_str(),
+        };
+        sections.entry(key).or_default().push(service);
+    }
+    for section in sections.values_mut() {
+        section.sort_by_key(|service| service.heading(grouping));
+    }
+    let summary = format!(
+        "{} services, {} online, generated {}",
+        services.len(),
+        services.iter().filter(|service| service.online).count(),
+        generated.format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(match format {
+        ReportFormat::Markdown => render_markdown(grouping, &sections, &summary),
+        ReportFormat::Html => render_html(grouping, &sections, &summary),
+    })
+}
+
+fn render_markdown(
+    grouping: ReportGrouping,
+    sections: &BTreeMap<&str, Vec<&ReportService>>,
+    summary: &str,
+) -> String {
+    let mut lines = vec![
+        "# mDNS services".to_string(),
+        String::new(),
+        summary.to_string(),
+    ];
+    for (section, services) in sections {
+        lines.push(String::new());
+        lines.push(format!("## {} ({})", section, services.len()));
+        for service in services {
+            lines.push(String::new());
+            lines.push(format!("### {}", service.heading(grouping)));
+            lines.push(String::new());
+            match grouping {
+                ReportGrouping::Type => lines.push(format!("- Host: {}", service.host)),
+                ReportGrouping::Host => lines.push(format!("- Type: {}", service.service_type)),
+            }
+            lines.push(format!("- Port: {}", service.port));
+            lines.push(format!("- Addresses: {}", service.addrs.join(", ")));
+            lines.push(format!("- Status: {}", service.status()));
+            let txt = service.txt_entries();
+            if !txt.is_empty() {
+                lines.push(String::new());
+                lines.push("| Key | Value |".to_string());
+                lines.push("|---|---|".to_string());
+                lines.extend(txt.iter().map(|(key, value)| {
+                    format!("| {} | {} |", markdown_cell(key), markdown_cell(value))
+                }));
+            }
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn render_html(
+    grouping: ReportGrouping,
+    sections: &BTreeMap<&str, Vec<&ReportService>>,
+    summary: &str,
+) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>mDNS services</title>\n\
+         <style>\nbody { font-family: sans-serif; }\ntable { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }\n.offline { color: #888; }\n</style>\n\
+         </head>\n<body>\n<h1>mDNS services</h1>\n",
+    );
+    html.push_str(&format!("<p>{}</p>\n", escape_html(summary)));
+    for (section, services) in sections {
+        html.push_str(&format!(
+            "<h2>{} ({})</h2>\n",
+            escape_html(section),
+            services.len()
+        ));
+        for service in services {
+            html.push_str(&format!(
+                "<h3 class=\"{}\">{}</h3>\n<table>\n",
+                service.status(),
+                escape_html(&service.heading(grouping))
+            ));
+            let first = match grouping {
+                ReportGrouping::Type => ("Host", service.host.clone()),
+                ReportGrouping::Host => ("Type", service.service_type.clone()),
+            };
+            let rows = [
+                first,
+                ("Port", service.port.to_string()),
+                ("Addresses", service.addrs.join(", ")),
+                ("Status", service.status().to_string()),
+            ];
+            for (name, value) in rows {
+                html.push_str(&format!(
+                    "<tr><th>{}</th><td>{}</td></tr>\n",
+                    name,
+                    escape_html(&value)
+                ));
+            }
+            html.push_str("</table>\n");
+            let txt = service.txt_entries();
+            if !txt.is_empty() {
+                html.push_str("<table>\n<tr><th>Key</th><th>Value</th></tr>\n");
+                for (key, value) in txt {
+                    html.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td></tr>\n",
+                        escape_html(key),
+                        escape_html(value)
+                    ));
+                }
+                html.push_str("</table>\n");
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::{Value, json};
+
+    fn services() -> Vec<Value> {
+        vec![
+            json!({
+                "fullname": "Office._ipp._tcp.local.",
+                "service_type": "_ipp._tcp.local.",
+                "host": "printer.local.",
+                "port": 631,
+                "addrs": ["192.168.1.20"],
+                "txt": ["rp=ipp/print", "note=<2nd floor>"],
+                "online": true,
+            }),
+            json!({
+                "fullname": "Web UI._http._tcp.local.",
+                "service_type": "_http._tcp.local.",
+                "host": "printer.local.",
+                "port": 80,
+                "addrs": ["192.168.1.20"],
+                "txt": [],
+                "online": false,
+            }),
+        ]
+    }
+
+    fn generated() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap()
+    }
+
+    #[test]
+    fn test_markdown_by_type() {
+        let report = render(
+            ReportFormat::Markdown,
+            ReportGrouping::Type,
+            &services(),
+            generated(),
+        )
+        .unwrap();
+        assert!(report.starts_with(
+            "# mDNS services\n\n2 services, 1 online, generated 2026-01-02 03:04:05\n\n## _http._tcp.local. (1)"
+        ));
+        assert!(report.contains("### Office\n\n- Host: printer.local.\n- Port: 631\n"));
+        assert!(report.contains("| rp | ipp/print |\n| note | <2nd floor> |\n"));
+    }
+
+    #[test]
+    fn test_html_by_host() {
+        let report = render(
+            ReportFormat::Html,
+            ReportGrouping::Host,
+            &services(),
+            generated(),
+        )
+        .unwrap();
+        assert!(report.contains("<h2>printer.local. (2)</h2>"));
+        assert!(report.contains("<h3 class=\"offline\">Web UI (_http._tcp.local.)</h3>"));
+        assert!(report.contains("<tr><td>note</td><td>&lt;2nd floor&gt;</td></tr>"));
+        assert!(report.ends_with("</html>\n"));
+    }
+}
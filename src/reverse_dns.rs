@@ -0,0 +1,158 @@
+#![forbid(unsafe_code)]
+
+use crate::mdns_query::{MDNS_ADDR, build_query, query_socket};
+use hickory_resolver::TokioResolver;
+use hickory_resolver::proto::op::Message;
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use std::net::IpAddr;
+use std::time::Duration;
+
+const MDNS_TIMEOUT: Duration = Duration::from_secs(1);
+const UNICAST_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Resolves addresses back to names via PTR queries, over mDNS and the system's
+// unicast DNS servers
+pub struct ReverseResolver {
+    unicast: Option<TokioResolver>,
+}
+
+impl ReverseResolver {
+    pub fn new() -> Self {
+        Self {
+            // Without a usable system config only mDNS is asked
+            unicast: TokioResolver::builder_tokio()
+                .ok()
+                .map(|builder| builder.build()),
+        }
+    }
+
+    // Lowercase fully qualified names from both sources, without duplicates
+    pub async fn lookup(&self, ip: IpAddr) -> Vec<String> {
+        let unicast = async {
+            let Some(resolver) = &self.unicast else {
+                return Vec::new();
+            };
+            match tokio::time::timeout(UNICAST_TIMEOUT, resolver.reverse_lookup(ip)).await {
+                Ok(Ok(lookup)) => lookup.iter().map(|ptr| ptr.0.to_string()).collect(),
+                _ => Vec::new(),
+            }
+        };
+        let (mut names, unicast_names) = tokio::join!(mdns_reverse_lookup(ip), unicast);
+        names.extend(unicast_names);
+
+        let mut names: Vec<String> = names
+            .into_iter()
+            .map(|name| normalize_name(&name))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+pub fn normalize_name(name: &str) -> String {
+    let name = name.to_lowercase();
+    if name.ends_with('.') {
+        name
+    } else {
+        format!("{}.", name)
+    }
+}
+
+// A legacy unicast query (RFC 6762 section 6.7), responders answer directly to
+// the ephemeral source port, so this does not interfere with the daemon's socket
+async fn mdns_reverse_lookup(ip: IpAddr) -> Vec<String> {
+    let name = Name::from(ip);
+    let Ok(query) = build_ptr_query(&name) else {
+        return Vec::new();
+    };
+    let Ok(socket) = query_socket().await else {
+        return Vec::new();
+    };
+    if socket.send_to(&query, MDNS_ADDR).await.is_err() {
+        return Vec::new();
+    }
+
+    let receive = async {
+        let mut buffer = [0u8; 9000];
+        loop {
+            let Ok((len, _)) = socket.recv_from(&mut buffer).await else {
+                return Vec::new();
+            };
+            let names = ptr_names_from_response(&buffer[..len], &name);
+            if !names.is_empty() {
+                return names;
+            }
+        }
+    };
+    tokio::time::timeout(MDNS_TIMEOUT, receive)
+        .await
+        .unwrap_or_default()
+}
+
+fn build_ptr_query(name: &Name) -> Result<Vec<u8>, String> {
+    build_query(&[(name.clone(), RecordType::PTR)])
+}
+
+fn ptr_names_from_response(response: &[u8], name: &Name) -> Vec<String> {
+    let Ok(message) = Message::from_vec(response) else {
+        return Vec::new();
+    };
+    message
+        .answers()
+        .iter()
+        .filter(|record| record.name() == name)
+        .filter_map(|record| match record.data() {
+            RData::PTR(ptr) => Some(ptr.0.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::MessageType;
+    use hickory_resolver::proto::rr::Record;
+    use hickory_resolver::proto::rr::rdata::PTR;
+
+    #[test]
+    fn test_build_ptr_query() {
+        let name = Name::from(IpAddr::from([192, 168, 1, 2]));
+        assert_eq!(name.to_string(), "2.1.168.192.in-addr.arpa.");
+
+        let query = Message::from_vec(&build_ptr_query(&name).unwrap()).unwrap();
+        assert_eq!(query.queries().len(), 1);
+        assert_eq!(query.queries()[0].name(), &name);
+        assert_eq!(query.queries()[0].query_type(), RecordType::PTR);
+        assert!(!query.recursion_desired());
+    }
+
+    #[test]
+    fn test_ptr_names_from_response() {
+        let name = Name::from(IpAddr::from([192, 168, 1, 2]));
+        let other = Name::from(IpAddr::from([192, 168, 1, 3]));
+        let mut response = Message::new();
+        response.set_message_type(MessageType::Response);
+        response.add_answer(Record::from_rdata(
+            name.clone(),
+            120,
+            RData::PTR(PTR(Name::from_ascii("nas.local.").unwrap())),
+        ));
+        response.add_answer(Record::from_rdata(
+            other,
+            120,
+            RData::PTR(PTR(Name::from_ascii("printer.local.").unwrap())),
+        ));
+        let bytes = response.to_vec().unwrap();
+
+        assert_eq!(ptr_names_from_response(&bytes, &name), vec!["nas.local."]);
+        assert!(ptr_names_from_response(b"garbage", &name).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("NAS.local"), "nas.local.");
+        assert_eq!(normalize_name("nas.local."), "nas.local.");
+    }
+}
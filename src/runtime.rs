@@ -0,0 +1,107 @@
+#![forbid(unsafe_code)]
+
+// The few things the discovery engine needs from an executor: spawning,
+// sleeping and timeouts. They go to the Tokio runtime the caller runs on, any
+// flavor; with the smol feature the engine also runs without one, on smol's
+// global executor. The rest is channels and locks, which need no runtime
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "smol")]
+pub fn in_tokio() -> bool {
+    tokio::runtime::Handle::try_current().is_ok()
+}
+
+// Runs the task in the background, on the Tokio runtime of the caller if any
+pub fn spawn(task: impl Future<Output = ()> + Send + 'static) {
+    #[cfg(feature = "smol")]
+    if !in_tokio() {
+        smol::spawn(task).detach();
+        return;
+    }
+    tokio::spawn(task);
+}
+
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "smol")]
+    if !in_tokio() {
+        smol::Timer::after(duration).await;
+        return;
+    }
+    tokio::time::sleep(duration).await;
+}
+
+// The output of the future, None if it took longer than the duration
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    #[cfg(feature = "smol")]
+    if !in_tokio() {
+        return smol::future::or(async { Some(future.await) }, async {
+            smol::Timer::after(duration).await;
+            None
+        })
+        .await;
+    }
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+// Ticks once per period, the first time right away; ticks missed while busy
+// are skipped rather than caught up on
+pub struct Interval {
+    period: Duration,
+    next: Instant,
+}
+
+impl Interval {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            next: Instant::now(),
+        }
+    }
+
+    pub async fn tick(&mut self) {
+        let now = Instant::now();
+        if self.next > now {
+            sleep(self.next - now).await;
+        }
+        self.next = (self.next + self.period).max(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_interval_ticks_once_per_period() {
+        let period = Duration::from_millis(20);
+        let mut interval = Interval::new(period);
+        let started = Instant::now();
+        interval.tick().await;
+        assert!(started.elapsed() < period);
+        interval.tick().await;
+        interval.tick().await;
+        assert!(started.elapsed() >= period * 2);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_timeout() {
+        assert_eq!(timeout(Duration::from_secs(1), async { 7 }).await, Some(7));
+        assert_eq!(
+            timeout(Duration::from_millis(10), sleep(Duration::from_secs(5))).await,
+            None
+        );
+    }
+
+    #[cfg(feature = "smol")]
+    #[test]
+    fn test_runs_without_tokio() {
+        let (sender, receiver) = flume::bounded(1);
+        spawn(async move {
+            sleep(Duration::from_millis(5)).await;
+            let _ = sender.send_async(1).await;
+        });
+        assert_eq!(smol::block_on(receiver.recv_async()), Ok(1));
+    }
+}
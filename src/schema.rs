@@ -0,0 +1,80 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Version of the JSON of services and events, raised on incompatible changes
+// like removed or renamed fields, not on added ones
+pub const VERSION: u32 = 1;
+
+// The JSON Schema of the services and events, printed by --schema
+pub const DOCUMENT: &str = include_str!("schema.json");
+
+// The schema_version field every service and event carries, always VERSION
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SchemaVersion;
+
+impl Serialize for SchemaVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(VERSION)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = u32::deserialize(deserializer)?;
+        if version == VERSION {
+            Ok(SchemaVersion)
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "unsupported schema version {}, expected {}",
+                version, VERSION
+            )))
+        }
+    }
+}
+
+// Checks that the object has the required and no other properties of the
+// definition, so the structs and the document don't drift apart
+#[cfg(test)]
+pub fn assert_matches_definition(value: &serde_json::Value, definition: &str) {
+    let document: serde_json::Value = serde_json::from_str(DOCUMENT).unwrap();
+    let definition = &document["$defs"][definition];
+    let object = value.as_object().unwrap();
+    for key in object.keys() {
+        assert!(
+            definition["properties"].get(key).is_some(),
+            "{} is not in the schema",
+            key
+        );
+    }
+    for key in definition["required"].as_array().unwrap() {
+        assert!(
+            object.contains_key(key.as_str().unwrap()),
+            "{} is missing",
+            key
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_version() {
+        assert_eq!(serde_json::to_string(&SchemaVersion).unwrap(), "1");
+        assert_eq!(
+            serde_json::from_str::<SchemaVersion>("1").unwrap(),
+            SchemaVersion
+        );
+        assert!(serde_json::from_str::<SchemaVersion>("2").is_err());
+
+        let document: serde_json::Value = serde_json::from_str(DOCUMENT).unwrap();
+        for definition in ["service", "event"] {
+            assert_eq!(
+                document["$defs"][definition]["properties"]["schema_version"]["const"],
+                VERSION
+            );
+        }
+    }
+}
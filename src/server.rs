@@ -0,0 +1,482 @@
+#![forbid(unsafe_code)]
+
+use crate::discovery_events::DiscoveryEvent;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Notify, broadcast};
+
+// Events kept for clients polling /events, older ones are dropped
+const EVENT_BACKLOG: usize = 1024;
+// How long /events waits for an event unless asked otherwise, and at most
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(120);
+// Requests are a request line and a few headers, anything larger is refused
+const MAX_REQUEST_SIZE: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Pause after a failed accept, e.g. while out of file descriptors
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+// A page showing the live services table, served at /
+const WEB_UI: &str = include_str!("web_ui.html");
+
+// Status, content type and body of an answer
+type Response = (u16, &'static str, Vec<u8>);
+
+// What the API serves, the discovered services and types as they are now
+pub trait Catalog: Clone + Send + Sync + 'static {
+    fn services(&self) -> impl Future<Output = serde_json::Value> + Send;
+    fn service_types(&self) -> impl Future<Output = Vec<String>> + Send;
+}
+
+// The latest events numbered in order, so pollers can ask for the ones after
+// the last they saw
+#[derive(Default)]
+struct EventBacklog {
+    // Number of the first event in events
+    first_seq: u64,
+    events: VecDeque<DiscoveryEvent>,
+}
+
+impl EventBacklog {
+    fn next_seq(&self) -> u64 {
+        self.first_seq + self.events.len() as u64
+    }
+
+    fn push(&mut self, event: DiscoveryEvent) {
+        if self.events.len() == EVENT_BACKLOG {
+            self.events.pop_front();
+            self.first_seq += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    // Events the collector lagged behind on: kept ones are dropped too, so
+    // every poller still before the gap sees the loss
+    fn skip(&mut self, count: u64) {
+        self.first_seq = self.next_seq() + count;
+        self.events.clear();
+    }
+
+    // Events from since on with how many of them were dropped already, the
+    // kept ones follow from the oldest
+    fn since(&self, since: u64) -> (u64, Vec<DiscoveryEvent>) {
+        // Numbers beyond the end were handed out before serve restarted, the
+        // poller missed what happened in between
+        if since > self.next_seq() {
+            return (
+                self.next_seq().max(1),
+                self.events.iter().cloned().collect(),
+            );
+        }
+        let missed = self.first_seq.saturating_sub(since);
+        let skip = since.saturating_sub(self.first_seq) as usize;
+        (missed, self.events.iter().skip(skip).cloned().collect())
+    }
+}
+
+struct Api<C> {
+    catalog: C,
+    listen: SocketAddr,
+    cors_origin: Option<String>,
+    backlog: Mutex<EventBacklog>,
+    new_events: Notify,
+}
+
+// Serves the API, failed accepts are logged and retried:
+//   GET /          a page with the live services table
+//   GET /services  all services as JSON
+//   GET /types     the discovered service types
+//   GET /events    events after ?since=N, waits up to ?timeout=SECS for one
+// Pages of cors_origin may read the API from the browser, other origins may not
+pub async fn serve<C: Catalog>(
+    listener: TcpListener,
+    catalog: C,
+    mut events: broadcast::Receiver<DiscoveryEvent>,
+    cors_origin: Option<String>,
+) {
+    let listen = match listener.local_addr() {
+        Ok(address) => address,
+        Err(e) => {
+            tracing::warn!(error = %e, "the listen address is unknown");
+            return;
+        }
+    };
+    let api = Arc::new(Api {
+        catalog,
+        listen,
+        cors_origin,
+        backlog: Mutex::new(EventBacklog::default()),
+        new_events: Notify::new(),
+    });
+    let collector = Arc::clone(&api);
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    collector.backlog.lock().unwrap().push(event);
+                    collector.new_events.notify_waiters();
+                }
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    collector.backlog.lock().unwrap().skip(count);
+                    collector.new_events.notify_waiters();
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!(error = %e, "accepting a connection failed");
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let api = Arc::clone(&api);
+        tokio::spawn(async move {
+            // Clients going away mid-request are not worth reporting
+            let _ = handle_connection(stream, &api).await;
+        });
+    }
+}
+
+async fn handle_connection<C: Catalog>(mut stream: TcpStream, api: &Api<C>) -> std::io::Result<()> {
+    let request = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(request) => request?,
+        Err(_) => return Ok(()),
+    };
+    let host = header(&request, "host");
+    let response = match parse_request_line(&request) {
+        // Pages of any site could reach the API through a name of theirs
+        // resolving to this address, their requests carry that name
+        Some(_) if !host.is_some_and(|host| host_allowed(host, api.listen)) => {
+            json_response(403, json!({"error": "host not allowed"}))
+        }
+        Some(("GET", target)) => route(api, target).await,
+        Some(_) => json_response(405, json!({"error": "method not allowed"})),
+        None => json_response(400, json!({"error": "bad request"})),
+    };
+    stream
+        .write_all(&encode_response(response, api.cors_origin.as_deref()))
+        .await?;
+    stream.shutdown().await
+}
+
+async fn route<C: Catalog>(api: &Api<C>, target: &str) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match path {
+        "/" => (200, "text/html; charset=utf-8", WEB_UI.as_bytes().to_vec()),
+        "/services" => json_response(200, api.catalog.services().await),
+        "/types" => json_response(200, json!(api.catalog.service_types().await)),
+        "/events" => {
+            let since = query_param(query, "since").and_then(|value| value.parse().ok());
+            let timeout = query_param(query, "timeout")
+                .and_then(|value| value.parse().ok())
+                .map_or(DEFAULT_POLL_TIMEOUT, Duration::from_secs)
+                .min(MAX_POLL_TIMEOUT);
+            let (next, missed, events) = poll_events(api, since, timeout).await;
+            json_response(
+                200,
+                json!({"next": next, "missed": missed, "events": events}),
+            )
+        }
+        _ => json_response(404, json!({"error": "not found"})),
+    }
+}
+
+// Events from since on, waiting up to the timeout if there are none yet, and how
+// many of them were dropped before they could be polled; without since only
+// events from now on count
+async fn poll_events<C>(
+    api: &Api<C>,
+    since: Option<u64>,
+    timeout: Duration,
+) -> (u64, u64, Vec<DiscoveryEvent>) {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let since = since.unwrap_or_else(|| api.backlog.lock().unwrap().next_seq());
+    loop {
+        // Registered before looking, so an event pushed in between isn't missed
+        let notified = api.new_events.notified();
+        {
+            let backlog = api.backlog.lock().unwrap();
+            let (missed, events) = backlog.since(since);
+            if !events.is_empty() || missed > 0 {
+                return (backlog.next_seq(), missed, events);
+            }
+        }
+        if tokio::time::timeout_at(deadline, notified).await.is_err() {
+            return (since, 0, Vec::new());
+        }
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let len = stream.read(&mut buffer).await?;
+        if len == 0 || request.len() + len > MAX_REQUEST_SIZE {
+            break;
+        }
+        request.extend_from_slice(&buffer[..len]);
+    }
+    Ok(request)
+}
+
+// Method and target of "GET /services HTTP/1.1"
+fn parse_request_line(request: &[u8]) -> Option<(&str, &str)> {
+    let line = std::str::from_utf8(request).ok()?.lines().next()?;
+    let mut parts = line.split(' ');
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts
+        .next()?
+        .starts_with("HTTP/")
+        .then_some((method, target))
+}
+
+// Value of the first header of the given name
+fn header<'a>(request: &'a [u8], name: &str) -> Option<&'a str> {
+    std::str::from_utf8(request)
+        .ok()?
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+// Whether a Host header names the listen address: its IP address, any IP
+// address when listening on all of them, or localhost on loopback. Names are
+// refused, the port defaults to 80 like in URLs
+fn host_allowed(host: &str, listen: SocketAddr) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') || name.ends_with(']') => {
+            match port.parse::<u16>() {
+                Ok(port) => (name, port),
+                Err(_) => return false,
+            }
+        }
+        _ => (host, 80),
+    };
+    if port != listen.port() {
+        return false;
+    }
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    match name.parse::<IpAddr>() {
+        Ok(ip) => ip == listen.ip() || listen.ip().is_unspecified(),
+        Err(_) => {
+            name.eq_ignore_ascii_case("localhost")
+                && (listen.ip().is_loopback() || listen.ip().is_unspecified())
+        }
+    }
+}
+
+fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn json_response(status: u16, body: serde_json::Value) -> Response {
+    (status, "application/json", body.to_string().into_bytes())
+}
+
+fn encode_response((status, content_type, body): Response, cors_origin: Option<&str>) -> Vec<u8> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "",
+    };
+    let cors = cors_origin
+        .map(|origin| format!("Access-Control-Allow-Origin: {}\r\n", origin))
+        .unwrap_or_default();
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        cors
+    )
+    .into_bytes();
+    response.extend(body);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery_events::EventKind;
+
+    #[derive(Clone)]
+    struct TestCatalog;
+
+    impl Catalog for TestCatalog {
+        async fn services(&self) -> serde_json::Value {
+            json!([{"fullname": "Office._ipp._tcp.local."}])
+        }
+
+        async fn service_types(&self) -> Vec<String> {
+            vec!["_ipp._tcp.local.".to_string()]
+        }
+    }
+
+    async fn get_text(address: SocketAddr, target: &str) -> (String, String) {
+        get_from_host(address, &address.to_string(), target).await
+    }
+
+    async fn get_from_host(address: SocketAddr, host: &str, target: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, host).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_string(), body.to_string())
+    }
+
+    async fn get(address: SocketAddr, target: &str) -> (String, serde_json::Value) {
+        let (head, body) = get_text(address, target).await;
+        let status = head.lines().next().unwrap().to_string();
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    #[test]
+    fn test_backlog() {
+        let mut backlog = EventBacklog::default();
+        for i in 0..EVENT_BACKLOG + 2 {
+            backlog.push(DiscoveryEvent::for_test(
+                EventKind::Added,
+                &i.to_string(),
+                &[],
+            ));
+        }
+        assert_eq!(backlog.next_seq(), EVENT_BACKLOG as u64 + 2);
+        let (missed, events) = backlog.since(0);
+        assert_eq!(missed, 2);
+        assert_eq!(events[0].fullname, "2");
+        let (missed, events) = backlog.since(backlog.next_seq() - 1);
+        assert_eq!((missed, events.len()), (0, 1));
+
+        // A poller from before a restart is told it missed events
+        let mut restarted = EventBacklog::default();
+        assert_eq!(restarted.since(5).0, 1);
+        restarted.push(DiscoveryEvent::for_test(EventKind::Added, "0", &[]));
+        assert_eq!(restarted.since(5).0, 1);
+        assert_eq!(restarted.since(1).0, 0);
+
+        // Events the collector lagged behind on count as missed
+        let next = backlog.next_seq();
+        backlog.skip(3);
+        assert_eq!(backlog.next_seq(), next + 3);
+        assert_eq!(backlog.since(next - 1), (4, Vec::new()));
+        backlog.push(DiscoveryEvent::for_test(EventKind::Added, "after", &[]));
+        let (missed, events) = backlog.since(next + 3);
+        assert_eq!((missed, events.len()), (0, 1));
+    }
+
+    #[test]
+    fn test_host_allowed() {
+        let loopback: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        assert!(host_allowed("127.0.0.1:8080", loopback));
+        assert!(host_allowed("localhost:8080", loopback));
+        assert!(!host_allowed("127.0.0.1:8081", loopback));
+        assert!(!host_allowed("127.0.0.1", loopback));
+        assert!(!host_allowed("rebound.example:8080", loopback));
+        assert!(!host_allowed("192.168.1.2:8080", loopback));
+
+        let any: SocketAddr = "[::]:80".parse().unwrap();
+        assert!(host_allowed("192.168.1.2", any));
+        assert!(host_allowed("[fe80::1]:80", any));
+        assert!(host_allowed("[fe80::1]", any));
+        assert!(!host_allowed("nas.lan", any));
+        assert!(!host_allowed("192.168.1.2:x", any));
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = broadcast::channel(16);
+        tokio::spawn(serve(listener, TestCatalog, receiver, None));
+
+        let (head, body) = get_text(address, "/services").await;
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        assert!(!head.contains("Access-Control-Allow-Origin"));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body[0]["fullname"], "Office._ipp._tcp.local.");
+        assert_eq!(get(address, "/types").await.1, json!(["_ipp._tcp.local."]));
+        assert_eq!(get(address, "/nope").await.0, "HTTP/1.1 404 Not Found");
+        let (head, page) = get_text(address, "/").await;
+        assert!(head.contains("Content-Type: text/html"));
+        assert!(page.contains("fetch(\"services\")"));
+
+        let (_, body) = get(address, "/events?timeout=0").await;
+        assert_eq!(body, json!({"next": 0, "missed": 0, "events": []}));
+        let poll = tokio::spawn(get(address, "/events?since=0&timeout=5"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sender
+            .send(DiscoveryEvent::for_test(
+                EventKind::Added,
+                "Office._ipp._tcp.local.",
+                &[],
+            ))
+            .unwrap();
+        let (_, body) = poll.await.unwrap();
+        assert_eq!(body["next"], 1);
+        assert_eq!(body["events"][0]["event"], "added");
+
+        let (head, _) = get_from_host(address, "rebound.example:80", "/services").await;
+        assert!(head.starts_with("HTTP/1.1 403 Forbidden"));
+    }
+
+    #[tokio::test]
+    async fn test_cors_origin() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (_sender, receiver) = broadcast::channel(16);
+        let origin = Some("https://dashboard.example".to_string());
+        tokio::spawn(serve(listener, TestCatalog, receiver, origin));
+
+        let (head, _) = get_text(address, "/types").await;
+        assert!(head.contains("Access-Control-Allow-Origin: https://dashboard.example\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_lagged_events_are_missed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = broadcast::channel(2);
+        // Overflows the channel before the collector runs
+        for i in 0..5 {
+            sender
+                .send(DiscoveryEvent::for_test(
+                    EventKind::Added,
+                    &i.to_string(),
+                    &[],
+                ))
+                .unwrap();
+        }
+        tokio::spawn(serve(listener, TestCatalog, receiver, None));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let (_, body) = get(address, "/events?since=0&timeout=5").await;
+        assert_eq!(body["missed"], 3);
+        assert_eq!(body["next"], 5);
+    }
+}
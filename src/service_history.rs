@@ -0,0 +1,72 @@
+#![forbid(unsafe_code)]
+
+use crate::data_file;
+use crate::session::RecordedService;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Services kept at most, the ones seen last win
+pub const MAX_HISTORY_SERVICES: usize = 10_000;
+
+// A service as it was last seen, with when it was first and last seen
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    #[serde(flatten)]
+    pub service: RecordedService,
+    pub first_seen_micros: u64,
+    pub last_seen_micros: u64,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    data_file::path("history.json")
+}
+
+// A missing or unreadable file starts without history
+pub fn load(path: &Path) -> Vec<HistoryEntry> {
+    data_file::load_json(path)
+}
+
+// Replaces the history by the given services, the most recently seen first
+pub fn save(path: &Path, mut entries: Vec<HistoryEntry>) -> Result<(), String> {
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_seen_micros));
+    entries.truncate(MAX_HISTORY_SERVICES);
+    data_file::save_json(path, &entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fullname: &str, last_seen_micros: u64) -> HistoryEntry {
+        HistoryEntry {
+            service: RecordedService {
+                fullname: fullname.to_string(),
+                service_type: "_http._tcp.local.".to_string(),
+                subtype: None,
+                host: "sensor.local.".to_string(),
+                port: 80,
+                addrs: vec!["192.168.1.20".to_string()],
+                txt: Vec::new(),
+                srv_priority: None,
+                srv_weight: None,
+            },
+            first_seen_micros: 1,
+            last_seen_micros,
+        }
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("history.json");
+        assert!(load(&path).is_empty());
+
+        let older = entry("Sensor._http._tcp.local.", 10);
+        let newer = entry("Camera._http._tcp.local.", 20);
+        save(&path, vec![older.clone(), newer.clone()]).unwrap();
+        assert_eq!(load(&path), vec![newer, older]);
+
+        std::fs::write(&path, "[").unwrap();
+        assert!(load(&path).is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+#![forbid(unsafe_code)]
+
+use std::collections::HashMap;
+
+// Service types without domain, with a friendly name and a short description
+const KNOWN_SERVICE_TYPES: &[(&str, &str, &str)] = &[
+    ("_http._tcp", "Web servers", "Hypertext Transfer Protocol"),
+    ("_https._tcp", "Secure web servers", "HTTP over TLS"),
+    ("_ssh._tcp", "SSH", "Secure shell remote login"),
+    ("_sftp-ssh._tcp", "SFTP", "File transfer over SSH"),
+    ("_ftp._tcp", "FTP servers", "File Transfer Protocol"),
+    ("_webdav._tcp", "WebDAV", "File access over HTTP"),
+    ("_ipp._tcp", "Printers (IPP)", "Internet Printing Protocol"),
+    (
+        "_ipps._tcp",
+        "Printers (IPPS)",
+        "Internet Printing Protocol over TLS",
+    ),
+    (
+        "_printer._tcp",
+        "Printers (LPD)",
+        "Line Printer Daemon protocol",
+    ),
+    (
+        "_pdl-datastream._tcp",
+        "Printers (raw)",
+        "Raw printing, usually on port 9100",
+    ),
+    (
+        "_uscan._tcp",
+        "Scanners (eSCL)",
+        "Driverless scanning, also known as AirScan",
+    ),
+    ("_scanner._tcp", "Scanners", "Network scanners"),
+    ("_googlecast._tcp", "Chromecast", "Google Cast receivers"),
+    (
+        "_airplay._tcp",
+        "AirPlay",
+        "Apple video and screen streaming receivers",
+    ),
+    (
+        "_raop._tcp",
+        "AirPlay audio",
+        "Remote Audio Output Protocol receivers",
+    ),
+    (
+        "_spotify-connect._tcp",
+        "Spotify Connect",
+        "Spotify playback targets",
+    ),
+    ("_sonos._tcp", "Sonos", "Sonos speakers"),
+    (
+        "_daap._tcp",
+        "iTunes libraries",
+        "Digital Audio Access Protocol shares",
+    ),
+    (
+        "_androidtvremote2._tcp",
+        "Android TV",
+        "Android TV remote control",
+    ),
+    ("_amzn-wplay._tcp", "Fire TV", "Amazon Fire TV devices"),
+    ("_hap._tcp", "HomeKit", "HomeKit Accessory Protocol over IP"),
+    (
+        "_hap._udp",
+        "HomeKit (Thread)",
+        "HomeKit Accessory Protocol over Thread",
+    ),
+    ("_matter._tcp", "Matter", "Commissioned Matter devices"),
+    (
+        "_matterc._udp",
+        "Matter commissioning",
+        "Matter devices ready to be paired",
+    ),
+    (
+        "_home-assistant._tcp",
+        "Home Assistant",
+        "Home Assistant instances",
+    ),
+    ("_esphomelib._tcp", "ESPHome", "ESPHome devices"),
+    (
+        "_mqtt._tcp",
+        "MQTT brokers",
+        "MQTT publish/subscribe messaging",
+    ),
+    (
+        "_smb._tcp",
+        "Windows file sharing",
+        "Server Message Block shares",
+    ),
+    (
+        "_afpovertcp._tcp",
+        "Apple file sharing",
+        "Apple Filing Protocol shares",
+    ),
+    ("_nfs._tcp", "NFS", "Network File System exports"),
+    ("_adisk._tcp", "Time Machine", "Time Machine backup disks"),
+    (
+        "_rfb._tcp",
+        "Screen sharing (VNC)",
+        "Remote Framebuffer protocol",
+    ),
+    (
+        "_rdp._tcp",
+        "Remote desktop (RDP)",
+        "Remote Desktop Protocol",
+    ),
+    (
+        "_workstation._tcp",
+        "Workstations",
+        "Hosts announcing themselves",
+    ),
+    (
+        "_device-info._tcp",
+        "Device info",
+        "Apple device model information",
+    ),
+    (
+        "_companion-link._tcp",
+        "Apple companion link",
+        "Communication between Apple devices",
+    ),
+    (
+        "_sleep-proxy._udp",
+        "Sleep proxy",
+        "Bonjour sleep proxy servers",
+    ),
+    ("_rtsp._tcp", "RTSP streams", "Real Time Streaming Protocol"),
+    ("_axis-video._tcp", "Axis cameras", "Axis network cameras"),
+];
+
+// Friendly name of a service type, configured names take precedence over built-in ones
+pub fn friendly_name<'a>(
+    custom_names: &'a HashMap<String, String>,
+    service_type: &str,
+) -> Option<&'a str> {
+    let service_type = without_domain(service_type);
+    custom_names
+        .iter()
+        .find(|(configured, _)| without_domain(configured) == service_type)
+        .map(|(_, name)| name.as_str())
+        .or_else(|| {
+            KNOWN_SERVICE_TYPES
+                .iter()
+                .find(|(known, _, _)| *known == service_type)
+                .map(|(_, name, _)| *name)
+        })
+}
+
+pub fn description(service_type: &str) -> Option<&'static str> {
+    let service_type = without_domain(service_type);
+    KNOWN_SERVICE_TYPES
+        .iter()
+        .find(|(known, _, _)| *known == service_type)
+        .map(|(_, _, description)| *description)
+}
+
+// "_ipp._tcp.local." -> "_ipp._tcp"
+fn without_domain(service_type: &str) -> &str {
+    match service_type.match_indices('.').nth(1) {
+        Some((end, _)) => &service_type[..end],
+        None => service_type,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_friendly_name_builtin() {
+        let custom = HashMap::new();
+        assert_eq!(
+            friendly_name(&custom, "_ipp._tcp.local."),
+            Some("Printers (IPP)")
+        );
+        assert_eq!(
+            friendly_name(&custom, "_googlecast._tcp"),
+            Some("Chromecast")
+        );
+        assert_eq!(friendly_name(&custom, "_unknown._tcp.local."), None);
+    }
+
+    #[test]
+    fn test_friendly_name_custom_overrides() {
+        let custom = HashMap::from([
+            ("_ipp._tcp".to_string(), "Office printers".to_string()),
+            ("_foo._udp.local.".to_string(), "Foo".to_string()),
+        ]);
+        assert_eq!(
+            friendly_name(&custom, "_ipp._tcp.local."),
+            Some("Office printers")
+        );
+        assert_eq!(friendly_name(&custom, "_foo._udp.local."), Some("Foo"));
+    }
+
+    #[test]
+    fn test_description() {
+        assert_eq!(
+            description("_ipp._tcp.local."),
+            Some("Internet Printing Protocol")
+        );
+        assert_eq!(description("_unknown._tcp.local."), None);
+    }
+
+    #[test]
+    fn test_without_domain() {
+        assert_eq!(without_domain("_ipp._tcp.local."), "_ipp._tcp");
+        assert_eq!(without_domain("_ipp._tcp"), "_ipp._tcp");
+    }
+}
@@ -0,0 +1,223 @@
+#![forbid(unsafe_code)]
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+// A service as a discovery backend resolved it, before the app merges it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedService {
+    pub fullname: String,
+    pub service_type: String,
+    pub subtype: Option<String>,
+    pub host: String,
+    pub port: u16,
+    pub addrs: Vec<String>,
+    pub txt: Vec<String>,
+    // Only unicast DNS-SD reports the SRV priority and weight
+    pub srv_priority: Option<u16>,
+    pub srv_weight: Option<u16>,
+}
+
+// What the discovery backends report, as they report it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    TypeFound { service_type: String },
+    TypeRemoved { service_type: String },
+    Resolved(RecordedService),
+    Removed { fullname: String },
+}
+
+// One line of a session file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub timestamp_micros: u64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+// Recorded events fed into the app instead of browsing
+pub struct Replay {
+    pub events: Vec<RecordedEvent>,
+    // How many times faster than recorded
+    pub speed: f64,
+}
+
+impl Replay {
+    pub fn load(path: &Path, speed: f64) -> Result<Self, crate::Error> {
+        let error = |message: String| crate::Error::Session {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let events = parse(&text).map_err(error)?;
+        Ok(Self { events, speed })
+    }
+
+    // Offset of an event from the start of the replay, scaled by the speed
+    pub fn offset(&self, event: &RecordedEvent) -> std::time::Duration {
+        let first = self.events.first().map_or(0, |e| e.timestamp_micros);
+        let micros = event.timestamp_micros.saturating_sub(first);
+        std::time::Duration::from_secs_f64(micros as f64 / 1_000_000.0 / self.speed)
+    }
+}
+
+// The events of a session file, sorted by time as concurrent tasks may have
+// recorded them slightly out of order
+pub fn parse(text: &str) -> Result<Vec<RecordedEvent>, String> {
+    let mut events = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", index + 1, e))
+        })
+        .collect::<Result<Vec<RecordedEvent>, String>>()?;
+    events.sort_by_key(|event| event.timestamp_micros);
+    Ok(events)
+}
+
+// A replay speed like "10x", "0.5x" or "2"
+pub fn parse_speed(text: &str) -> Result<f64, String> {
+    let number = text.strip_suffix(['x', 'X']).unwrap_or(text);
+    match number.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => Err(format!("invalid speed {:?}, expected e.g. 10x", text)),
+    }
+}
+
+// Appends the events to a session file as JSON lines, a thread does the
+// writing so the discovery tasks never wait for the disk
+#[derive(Clone)]
+pub struct Recorder {
+    sender: flume::Sender<RecordedEvent>,
+}
+
+impl Recorder {
+    // Write errors end the recording and are sent to errors
+    pub fn create(path: &Path, errors: flume::Sender<String>) -> Result<Self, String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let (sender, receiver) = flume::unbounded::<RecordedEvent>();
+        let path = path.to_path_buf();
+        std::thread::spawn(move || {
+            let mut writer = std::io::BufWriter::new(file);
+            for event in receiver.iter() {
+                let result = serde_json::to_string(&event)
+                    .map_err(std::io::Error::other)
+                    .and_then(|line| writeln!(writer, "{}", line))
+                    // Flush once caught up, so the file is complete after a crash
+                    .and_then(|()| {
+                        if receiver.is_empty() {
+                            writer.flush()
+                        } else {
+                            Ok(())
+                        }
+                    });
+                if let Err(e) = result {
+                    let _ = errors.send(format!("Recording to {} failed: {}", path.display(), e));
+                    break;
+                }
+            }
+        });
+        Ok(Self { sender })
+    }
+
+    pub fn record(&self, event: SessionEvent, timestamp_micros: u64) {
+        let _ = self.sender.send(RecordedEvent {
+            timestamp_micros,
+            event,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session() {
+        let events = parse(
+            r#"{"timestamp_micros":3000000,"event":"removed","fullname":"Office._ipp._tcp.local."}
+
+{"timestamp_micros":1000000,"event":"type_found","service_type":"_ipp._tcp.local."}
+"#,
+        )
+        .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].event,
+            SessionEvent::TypeFound {
+                service_type: "_ipp._tcp.local.".to_string()
+            }
+        );
+        let replay = Replay { events, speed: 4.0 };
+        assert_eq!(
+            replay.offset(&replay.events[1]),
+            std::time::Duration::from_millis(500)
+        );
+        assert_eq!(parse("{}\n").unwrap_err().split(':').next(), Some("line 1"));
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(parse_speed("10x"), Ok(10.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0x").is_err());
+        assert!(parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn test_record_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        let (errors, _error_receiver) = flume::unbounded();
+        let recorder = Recorder::create(&path, errors).unwrap();
+        recorder.record(
+            SessionEvent::TypeFound {
+                service_type: "_ipp._tcp.local.".to_string(),
+            },
+            1_000_000,
+        );
+        recorder.record(
+            SessionEvent::Resolved(RecordedService {
+                fullname: "Office._ipp._tcp.local.".to_string(),
+                service_type: "_ipp._tcp.local.".to_string(),
+                subtype: None,
+                host: "printer.local.".to_string(),
+                port: 631,
+                addrs: vec!["192.168.1.20".to_string()],
+                txt: vec!["rp=ipp/print".to_string()],
+                srv_priority: None,
+                srv_weight: None,
+            }),
+            1_500_000,
+        );
+        // The writer thread ends once every sender is gone
+        drop(recorder);
+
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect();
+            if lines.len() == 2 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(
+            lines[0],
+            r#"{"timestamp_micros":1000000,"event":"type_found","service_type":"_ipp._tcp.local."}"#
+        );
+        let event: RecordedEvent = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(event.timestamp_micros, 1_500_000);
+        assert!(matches!(event.event, SessionEvent::Resolved(service) if service.port == 631));
+    }
+}
@@ -0,0 +1,81 @@
+#![forbid(unsafe_code)]
+
+use std::path::{Path, PathBuf};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+// How much of the discovery, state changes and rendering goes to the trace
+// file; off installs no subscriber, leaving the spans and events nearly free
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogLevel {
+    #[default]
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> tracing::level_filters::LevelFilter {
+        use tracing::level_filters::LevelFilter;
+        match self {
+            Self::Off => LevelFilter::OFF,
+            Self::Error => LevelFilter::ERROR,
+            Self::Warn => LevelFilter::WARN,
+            Self::Info => LevelFilter::INFO,
+            Self::Debug => LevelFilter::DEBUG,
+            Self::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+// trace.log in the platform cache directory
+pub fn default_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("mdns-tui-browser").join("trace.log"))
+}
+
+// Appends the spans and events up to the level to the file, with the time each
+// span took once it closes. The terminal belongs to the TUI, so nothing is
+// ever written there
+pub fn init(level: LogLevel, path: &Path) -> std::io::Result<()> {
+    if level == LogLevel::Off {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let _ = tracing_subscriber::fmt()
+        .with_writer(std::sync::Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(level.filter())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_thread_ids(true)
+        .try_init();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_off_creates_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traces").join("trace.log");
+        init(LogLevel::Off, &path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_levels_map_to_filters() {
+        use tracing::level_filters::LevelFilter;
+        assert_eq!(LogLevel::default().filter(), LevelFilter::OFF);
+        assert_eq!(LogLevel::Debug.filter(), LevelFilter::DEBUG);
+        assert!(LogLevel::Trace.filter() > LogLevel::Info.filter());
+    }
+}
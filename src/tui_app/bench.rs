@@ -0,0 +1,106 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// The service list with synthetic services, to time its hot paths from
+// benches/ without making the state public
+pub struct ServiceList {
+    state: AppState,
+}
+
+impl ServiceList {
+    // Services spread over a few hosts and types, like a busy network
+    pub fn with_services(count: usize) -> Self {
+        let mut state = AppState::new();
+        for i in 0..count {
+            state.add_or_update_service(synthetic_service(i, i % 7 != 0));
+        }
+        state.invalidate_cache_and_validate();
+        // Shown once, as the TUI does right away
+        state.get_filtered_services();
+        Self { state }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.services.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.services.is_empty()
+    }
+
+    // Rebuilds the filtered list from all services
+    pub fn update_filtered_cache(&mut self) -> usize {
+        self.state.mark_cache_dirty();
+        self.state.update_filtered_cache();
+        self.state.cached_filtered_services.len()
+    }
+
+    // Sorts the filtered list anew, e.g. after the sort field changed
+    pub fn sort_filtered_services(&mut self) {
+        self.state.sort_filtered_services();
+    }
+
+    // Sorting by the next field, for more than the default host order
+    pub fn cycle_sort_field(&mut self) {
+        self.state.cycle_sort_field(true);
+    }
+
+    pub fn set_filter(&mut self, query: &str) {
+        self.state.filter_query = InputLine::default();
+        query
+            .chars()
+            .for_each(|ch| self.state.filter_query.insert(ch));
+        self.state.invalidate_cache_and_validate();
+    }
+
+    // Adds service i or updates it if it exists, as a discovery would
+    pub fn add_or_update_service(&mut self, i: usize, online: bool) -> bool {
+        self.state
+            .add_or_update_service(synthetic_service(i, online))
+    }
+
+    // Like add_or_update_service, also updating the list as shown
+    pub fn ingest_service(&mut self, i: usize, online: bool) -> usize {
+        self.state
+            .ingest_resolved_service(synthetic_service(i, online));
+        self.state.get_filtered_services().len()
+    }
+}
+
+fn synthetic_service(i: usize, online: bool) -> ServiceEntry {
+    const TYPES: [&str; 6] = [
+        "_http._tcp.local.",
+        "_ipp._tcp.local.",
+        "_smb._tcp.local.",
+        "_airplay._tcp.local.",
+        "_googlecast._tcp.local.",
+        "_ssh._tcp.local.",
+    ];
+    let service_type = TYPES[i % TYPES.len()];
+    let now = current_timestamp_micros();
+    ServiceEntry {
+        schema_version: SchemaVersion,
+        fullname: format!("Device {}.{}", i, service_type),
+        host: format!("host-{}.local.", i / 3).into(),
+        service_type: service_type.into(),
+        subtype: None,
+        addrs: vec![
+            format!("10.{}.{}.{}", i / 65536 % 256, i / 256 % 256, i % 256),
+            format!("fe80::{:x}", i),
+        ],
+        port: 1024 + (i % 50000) as u16,
+        txt: vec![format!("id={}", i), "model=bench".to_string()],
+        online,
+        first_seen_micros: now,
+        last_updated_micros: now,
+        last_seen_micros: now,
+        offline_since_micros: (!online).then_some(now),
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+    }
+}
@@ -0,0 +1,127 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use tokio::sync::broadcast;
+
+/// Browses for all service types and keeps what it finds, the same way the TUI
+/// does. The network is browsed by the backend of the config unless another
+/// [`DiscoveryBackend`] is given.
+///
+/// Services going offline stay listed with `online` unset, until
+/// `offline_grace_secs` of the config have passed if set. Browsing stops when
+/// the browser is dropped, [`Browser::shutdown`] also waits for its tasks to end.
+pub struct Browser<B: DiscoveryBackend = ConfiguredBackend> {
+    state: Arc<RwLock<AppState>>,
+    backend: B,
+    events: broadcast::Sender<DiscoveryEvent>,
+    tasks: BackgroundTasks,
+}
+
+impl Browser {
+    /// Starts the backend of the config and browsing, on the Tokio runtime of the
+    /// caller or, with the `smol` feature, without one. Browsing wide-area domains
+    /// and the bonjour backend still need Tokio, without it [`Error::NoTokio`] is
+    /// returned.
+    pub async fn start(config: Config) -> Result<Self, Error> {
+        #[cfg(feature = "smol")]
+        if !runtime::in_tokio() {
+            if config.backend == BackendKind::Bonjour {
+                return Err(Error::NoTokio {
+                    needed_by: "The bonjour backend".to_string(),
+                });
+            }
+            if let Some(domain) = config
+                .browse_domains()
+                .into_iter()
+                .find(|domain| domain != "local.")
+            {
+                return Err(Error::NoTokio {
+                    needed_by: format!("Browsing {}", domain),
+                });
+            }
+        }
+        let mut app_state = AppState::new();
+        app_state.config = config;
+        app_state.local_interfaces = interfaces::local_interfaces();
+        let backend = start_backend(&mut app_state).await?;
+        Ok(Self::start_on(app_state, backend).await)
+    }
+}
+
+impl<B: DiscoveryBackend> Browser<B> {
+    /// Starts browsing on the backend, on the Tokio runtime of the caller or, with
+    /// the `smol` feature, without one.
+    pub async fn start_with(config: Config, backend: B) -> Self {
+        let mut app_state = AppState::new();
+        app_state.config = config;
+        app_state.local_interfaces = interfaces::local_interfaces();
+        Self::start_on(app_state, backend).await
+    }
+
+    async fn start_on(app_state: AppState, backend: B) -> Self {
+        let events = app_state.discovery_events.clone();
+        let state = Arc::new(RwLock::new(app_state));
+        // Nothing is drawn, so the notifications go nowhere
+        let (notification_sender, _) = notification_channel();
+        let tasks = BackgroundTasks::default();
+        start_browsing(&tasks, backend.clone(), &state, &notification_sender).await;
+
+        let state_for_expiry = Arc::clone(&state);
+        tasks.spawn(async move {
+            let mut interval = runtime::Interval::new(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                state_for_expiry
+                    .write()
+                    .await
+                    .expire_offline_services(current_timestamp_micros());
+            }
+        });
+
+        Self {
+            state,
+            backend,
+            events,
+            tasks,
+        }
+    }
+
+    /// The services found so far.
+    pub async fn services(&self) -> Vec<ServiceEntry> {
+        self.state.read().await.services.to_vec()
+    }
+
+    /// The service types found so far.
+    pub async fn service_types(&self) -> Vec<String> {
+        self.state.read().await.service_types.clone()
+    }
+
+    /// The changes of the services from now on.
+    ///
+    /// Subscribers reading slower than the services change miss events and get
+    /// [`broadcast::error::RecvError::Lagged`].
+    pub fn events(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.events.subscribe()
+    }
+
+    /// The services found so far and their changes from then on, without
+    /// missing any in between.
+    pub async fn watch(&self) -> (Vec<ServiceEntry>, broadcast::Receiver<DiscoveryEvent>) {
+        // Events are sent while the state is locked for writing
+        let state = self.state.read().await;
+        (state.services.to_vec(), state.discovery_events.subscribe())
+    }
+
+    /// Stops browsing, applies the changes still queued and shuts the backend
+    /// down once the background tasks ended.
+    pub async fn shutdown(self) {
+        shut_down(&self.backend, &self.tasks).await;
+    }
+}
+
+impl<B: DiscoveryBackend> Drop for Browser<B> {
+    fn drop(&mut self) {
+        self.tasks.cancel();
+        self.backend.shutdown();
+    }
+}
@@ -0,0 +1,1118 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use tracing::Instrument;
+
+// Connect time if something accepts TCP connections on the address, scoped IPv6
+// addresses like fe80::1%eth0 go through the resolver which understands the zone id
+pub(super) async fn probe_tcp(address: &str, port: u16) -> Option<Duration> {
+    let start = std::time::Instant::now();
+    let connect = tokio::net::TcpStream::connect((address, port));
+    match tokio::time::timeout(TCP_PROBE_TIMEOUT, connect).await {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        _ => None,
+    }
+}
+
+// Services to probe and what to probe them for
+pub(super) struct ProbeTarget {
+    pub(super) agent: Option<String>,
+    pub(super) fullname: String,
+    pub(super) address: String,
+    pub(super) port: u16,
+    pub(super) host: String,
+    // TLS and path for web services
+    pub(super) http: Option<(bool, String)>,
+}
+
+// Reachability by TCP connect, latency by ICMP echo where the system permits
+// unprivileged ping sockets, falling back to the TCP connect time
+pub(super) async fn probe_service(target: &ProbeTarget) -> ProbeResult {
+    let connect_time = probe_tcp(&target.address, target.port).await;
+    let icmp_rtt = match target.address.parse::<std::net::Ipv4Addr>() {
+        Ok(ip) => tokio::task::spawn_blocking(move || icmp_echo_rtt(ip))
+            .await
+            .ok()
+            .flatten(),
+        Err(_) => None,
+    };
+    let http = match &target.http {
+        Some((tls, path)) if connect_time.is_some() => {
+            let request = http_probe::HttpRequest {
+                address: &target.address,
+                port: target.port,
+                host: &target.host,
+                path,
+                tls: *tls,
+            };
+            Some(http_probe::probe_http(&request, HTTP_PROBE_TIMEOUT).await)
+        }
+        _ => None,
+    };
+    ProbeResult {
+        reachable: Some(connect_time.is_some()),
+        latency_micros: icmp_rtt
+            .or(connect_time)
+            .map(|latency| latency.as_micros() as u64),
+        http,
+    }
+}
+
+fn icmp_echo_rtt(address: std::net::Ipv4Addr) -> Option<Duration> {
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use std::io::Read;
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    static SEQUENCE: AtomicU16 = AtomicU16::new(0);
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+    // Datagram ICMP sockets don't need privileges, but may be disabled
+    let mut socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).ok()?;
+    socket.set_read_timeout(Some(TCP_PROBE_TIMEOUT)).ok()?;
+
+    let start = std::time::Instant::now();
+    let target = SockAddr::from(std::net::SocketAddr::from((address, 0)));
+    socket.send_to(&icmp_echo_request(sequence), &target).ok()?;
+
+    let mut buffer = [0u8; 1500];
+    while start.elapsed() < TCP_PROBE_TIMEOUT {
+        let len = socket.read(&mut buffer).ok()?;
+        if is_icmp_echo_reply(&buffer[..len], sequence) {
+            return Some(start.elapsed());
+        }
+    }
+    None
+}
+
+// The identifier is left zero, the kernel sets it for datagram ICMP sockets
+pub(super) fn icmp_echo_request(sequence: u16) -> [u8; 16] {
+    let mut packet = [0u8; 16];
+    packet[0] = 8; // Echo request
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    packet[8..].copy_from_slice(b"mdns-tui");
+    let checksum = internet_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+// Some systems, e.g. macOS, deliver the reply including its IPv4 header
+pub(super) fn is_icmp_echo_reply(packet: &[u8], sequence: u16) -> bool {
+    let icmp = match packet.first() {
+        Some(first) if first >> 4 == 4 => packet.get(usize::from(first & 0x0f) * 4..),
+        _ => Some(packet),
+    };
+    matches!(icmp, Some(icmp) if icmp.len() >= 8 && icmp[0] == 0 && icmp[6..8] == sequence.to_be_bytes())
+}
+
+pub(super) fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| u32::from(u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+pub(super) async fn handle_background_request<B: DiscoveryBackend>(
+    request: BackgroundRequest,
+    mdns: B,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+    tasks: BackgroundTasks,
+) {
+    match request {
+        BackgroundRequest::SetInterfaceEnabled { name, enabled } => {
+            if let Err(e) = mdns.set_interface_enabled(&name, enabled) {
+                let mut state = state.write().await;
+                // Show the state the daemon is actually in
+                if enabled {
+                    state.disabled_interfaces.insert(name.clone());
+                } else {
+                    state.disabled_interfaces.remove(&name);
+                }
+                state.status_message = Some(format!("Cannot change {}: {}", name, e));
+            }
+        }
+        BackgroundRequest::Rescan => {
+            let (browses_local, service_types) = {
+                let mut state = state.write().await;
+                state.update_metric("rescans");
+                state.unicast_rescan.notify_waiters();
+                // Restarted by the browse manager at its usual pace
+                (
+                    state.config.browse_domains().iter().any(|d| d == "local."),
+                    state.browse_manager.requeue_started(),
+                )
+            };
+            let mut errors = Vec::new();
+            // Stopping first drops the old receivers, so their tasks end
+            if browses_local {
+                let meta_query = meta_query_name("local.");
+                let _ = mdns.stop_browse(&meta_query);
+                match mdns.browse(&meta_query) {
+                    Ok(receiver) => spawn_service_type_discovery(
+                        &tasks,
+                        receiver,
+                        Arc::clone(&state),
+                        notification_sender.clone(),
+                    ),
+                    Err(source) => errors.push(
+                        Error::Browse {
+                            service_type: meta_query,
+                            source,
+                        }
+                        .report(),
+                    ),
+                }
+            }
+            for service_type in &service_types {
+                let _ = mdns.stop_browse(service_type);
+            }
+            state.write().await.status_message = Some(if errors.is_empty() {
+                format!("Rescanning {} service types", service_types.len())
+            } else {
+                format!("Rescan failed: {}", errors.join("; "))
+            });
+        }
+        BackgroundRequest::ReResolve {
+            fullname,
+            service_type,
+        } => {
+            let result = with_spinner(
+                re_resolve_service(&fullname, &service_type, &state),
+                &notification_sender,
+            )
+            .await;
+            let mut state = state.write().await;
+            state.re_resolving.remove(&fullname);
+            state.status_message = Some(match result {
+                Ok(Some(entry)) => {
+                    state.ingest_resolved_service(entry);
+                    format!("Re-resolved {}", fullname)
+                }
+                Ok(None) => format!("No answer for {}", fullname),
+                Err(e) => format!("Cannot re-resolve {}: {}", fullname, e),
+            });
+        }
+        BackgroundRequest::ResolveHostname { hostname } => {
+            let result = with_spinner(
+                mdns_query::resolve_host(&hostname, RE_RESOLVE_TIMEOUT),
+                &notification_sender,
+            )
+            .await;
+            state
+                .write()
+                .await
+                .queries
+                .set_host_lookup_result(&hostname, result);
+        }
+        BackgroundRequest::QueryRecords { query_id, question } => {
+            let query = async {
+                let question = mdns_query::parse_question(&question)?;
+                mdns_query::query(&[question], RE_RESOLVE_TIMEOUT).await
+            };
+            let result = with_spinner(query, &notification_sender).await;
+            state
+                .write()
+                .await
+                .queries
+                .set_record_query_result(query_id, result);
+        }
+        BackgroundRequest::VerifyCache => {
+            // The daemon has no way to drop its cache, but instances that don't answer a
+            // verification get flushed from it and reported as removed
+            let fullnames: Vec<String> = {
+                let mut state = state.write().await;
+                state.update_metric("cache_verifications");
+                state
+                    .services
+                    .iter()
+                    .filter(|s| s.online && service_domain(&s.service_type) == "local.")
+                    .map(|s| s.fullname.clone())
+                    .collect()
+            };
+            let mut errors = 0;
+            for fullname in &fullnames {
+                if mdns.verify(fullname).is_err() {
+                    errors += 1;
+                }
+            }
+            state.write().await.status_message = Some(if errors == 0 {
+                format!(
+                    "Verifying {} cached services, unanswered ones get flushed",
+                    fullnames.len()
+                )
+            } else {
+                format!(
+                    "Verifying {} of {} cached services failed",
+                    errors,
+                    fullnames.len()
+                )
+            });
+        }
+        BackgroundRequest::BrowseServiceType { service_type } => {
+            browse_service_type(service_type, &state, &notification_sender).await;
+        }
+        BackgroundRequest::InspectCertificates {
+            fullname,
+            address,
+            port,
+            host,
+        } => {
+            let result =
+                http_probe::inspect_certificates(&address, port, &host, HTTP_PROBE_TIMEOUT).await;
+            state
+                .write()
+                .await
+                .probes
+                .set_certificate_inspection(fullname, result);
+        }
+        BackgroundRequest::QueryPrinter {
+            fullname,
+            address,
+            port,
+            host,
+            path,
+            tls,
+        } => {
+            let request = ipp::PrinterRequest {
+                address: &address,
+                port,
+                host: &host,
+                path: &path,
+                tls,
+            };
+            let result = ipp::query_printer(&request, HTTP_PROBE_TIMEOUT).await;
+            state
+                .write()
+                .await
+                .probes
+                .set_printer_query(fullname, result);
+        }
+        BackgroundRequest::QueryCastDevice {
+            fullname,
+            address,
+            host,
+        } => {
+            let result = cast::query_device_info(&address, &host, HTTP_PROBE_TIMEOUT).await;
+            state.write().await.probes.set_cast_query(fullname, result);
+        }
+        BackgroundRequest::ScanPorts {
+            scan_id,
+            address,
+            ports,
+        } => {
+            let permits = LimitsConfig::permits(state.read().await.config.limits.max_port_probes);
+            let semaphore = Arc::new(tokio::sync::Semaphore::new(permits));
+            let mut interval = tokio::time::interval(PORT_PROBE_SPACING);
+            for port in ports {
+                interval.tick().await;
+                let Ok(permit) = Arc::clone(&semaphore).acquire_owned().await else {
+                    return;
+                };
+                let address = address.clone();
+                let state = Arc::clone(&state);
+                let notification_sender = notification_sender.clone();
+                tokio::spawn(async move {
+                    let connect = tokio::net::TcpStream::connect((address.as_str(), port));
+                    let open = matches!(
+                        tokio::time::timeout(PORT_PROBE_TIMEOUT, connect).await,
+                        Ok(Ok(_))
+                    );
+                    drop(permit);
+                    state
+                        .write()
+                        .await
+                        .probes
+                        .update_port_scan(scan_id, port, open);
+                    let _ = notification_sender.try_send(Notification::ServiceChanged);
+                });
+            }
+            return;
+        }
+    }
+    let _ = notification_sender.try_send(Notification::ServiceChanged);
+}
+
+// Queries the records of one instance directly, over mDNS for local. and over
+// unicast DNS for wide-area domains, None if nobody answered
+async fn re_resolve_service(
+    fullname: &str,
+    service_type: &str,
+    state: &Arc<RwLock<AppState>>,
+) -> Result<Option<ServiceEntry>, String> {
+    let Some(instance) = fullname.strip_suffix(&format!(".{}", service_type)) else {
+        return Err(format!("{} is not of type {}", fullname, service_type));
+    };
+    let records = if service_domain(service_type) == "local." {
+        mdns_query::resolve_instance(instance, service_type, RE_RESOLVE_TIMEOUT).await?
+    } else {
+        let server = state.read().await.config.unicast_dns_server.clone();
+        let browser = server
+            .as_deref()
+            .map(unicast_dns_sd::parse_server_address)
+            .transpose()
+            .and_then(UnicastBrowser::new)?;
+        let name = mdns_query::instance_name(instance, service_type)?;
+        let service = browser.resolve(&name, service_type).await?;
+        Some(mdns_query::InstanceRecords {
+            host: service.host,
+            port: service.port,
+            priority: service.priority,
+            weight: service.weight,
+            txt: service.txt,
+            addrs: service.addrs,
+        })
+    };
+    Ok(records.map(|records| {
+        ServiceEntry::from(UnicastService {
+            fullname: fullname.to_string(),
+            service_type: service_type.to_string(),
+            host: records.host,
+            port: records.port,
+            priority: records.priority,
+            weight: records.weight,
+            addrs: records.addrs,
+            txt: records.txt,
+        })
+    }))
+}
+
+// Notes the TTL of every record in the multicast responses on the network, also the
+// ones answering the daemon's queries, and redraws every second for the countdowns
+pub(super) fn spawn_record_monitor(
+    tasks: &BackgroundTasks,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    let listener_tasks = tasks.clone();
+    tasks.spawn(async move {
+        let interfaces: Vec<LocalInterface> = {
+            let state = state.read().await;
+            state
+                .local_interfaces
+                .iter()
+                .filter(|interface| !state.disabled_interfaces.contains(&interface.name))
+                .cloned()
+                .collect()
+        };
+        let listeners = match mdns_query::multicast_listeners(&interfaces) {
+            Ok(listeners) => listeners,
+            Err(e) => {
+                state.write().await.status_message =
+                    Some(format!("Record TTLs unavailable: {}", e));
+                return;
+            }
+        };
+        for socket in listeners {
+            let state = Arc::clone(&state);
+            listener_tasks.spawn(async move {
+                let mut buffer = [0u8; 9000];
+                while let Ok((len, source)) = socket.recv_from(&mut buffer).await {
+                    let answers = mdns_query::parse_response(&buffer[..len], source);
+                    if !answers.is_empty() {
+                        state
+                            .write()
+                            .await
+                            .observe_records(&answers, std::time::Instant::now());
+                    }
+                }
+            });
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let mut state = state.write().await;
+            state.prune_observed_records(std::time::Instant::now());
+            if !state.observed_records.is_empty() {
+                let _ = notification_sender.try_send(Notification::ServiceChanged);
+            }
+        }
+    });
+}
+
+// Keeps spinners turning while nothing else redraws
+async fn with_spinner<T>(
+    future: impl std::future::Future<Output = T>,
+    notification_sender: &flume::Sender<Notification>,
+) -> T {
+    tokio::pin!(future);
+    let mut spinner = tokio::time::interval(SPINNER_INTERVAL);
+    loop {
+        tokio::select! {
+            result = &mut future => return result,
+            _ = spinner.tick() => {
+                let _ = notification_sender.try_send(Notification::ServiceChanged);
+            }
+        }
+    }
+}
+
+// Polls the domain for its service types and their instances, services that
+// are no longer listed go offline
+fn spawn_unicast_discovery(
+    tasks: &BackgroundTasks,
+    browser: Arc<UnicastBrowser>,
+    domain: String,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    tasks.spawn(async move {
+        let mut interval = runtime::Interval::new(UNICAST_BROWSE_INTERVAL);
+        let mut known_services: HashSet<String> = HashSet::new();
+        let rescan = Arc::clone(&state.read().await.unicast_rescan);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = rescan.notified() => {}
+            }
+
+            let mut errors = Vec::new();
+            let mut found_services = HashSet::new();
+            for browse_domain in browser.browse_domains(&domain).await {
+                let service_types = match browser.service_types(&browse_domain).await {
+                    Ok(service_types) => service_types,
+                    Err(e) => {
+                        errors.push(format!("{}: {}", browse_domain, e));
+                        continue;
+                    }
+                };
+                for service_type in service_types {
+                    {
+                        let mut state = state.write().await;
+                        state.record_session_event(SessionEvent::TypeFound {
+                            service_type: service_type.clone(),
+                        });
+                        if state.add_service_type(&service_type) {
+                            state.update_metric("service_types_discovered");
+                            let _ = notification_sender.try_send(Notification::ServiceChanged);
+                        }
+                    }
+                    let instances = match browser.instances(&service_type).await {
+                        Ok(instances) => instances,
+                        Err(e) => {
+                            errors.push(format!("{}: {}", service_type, e));
+                            continue;
+                        }
+                    };
+                    let parent_type = parent_service_type(&service_type);
+                    for instance in instances {
+                        match browser.resolve(&instance, parent_type).await {
+                            Ok(service) => {
+                                found_services.insert(service.fullname.clone());
+                                let mut entry = ServiceEntry::from(service);
+                                if is_subtype(&service_type) {
+                                    entry.subtype = Some(service_type.clone());
+                                }
+                                let mut state = state.write().await;
+                                state.record_session_event(SessionEvent::Resolved(
+                                    recorded_service(&entry),
+                                ));
+                                state.ingest_resolved_service(entry);
+                                let _ = notification_sender.try_send(Notification::ServiceChanged);
+                            }
+                            Err(e) => errors.push(format!("{}: {}", instance, e)),
+                        }
+                    }
+                }
+            }
+
+            let mut state = state.write().await;
+            for fullname in known_services.difference(&found_services) {
+                state.record_session_event(SessionEvent::Removed {
+                    fullname: fullname.clone(),
+                });
+                if state.mark_service_offline(fullname) {
+                    let _ = notification_sender.try_send(Notification::ServiceChanged);
+                }
+            }
+            if !errors.is_empty() {
+                state.update_metric_by("browse_failures", errors.len() as u64);
+                state.status_message = Some(format!(
+                    "Unicast DNS-SD of {} failed: {}",
+                    domain,
+                    errors.join(", ")
+                ));
+                let _ = notification_sender.try_send(Notification::ServiceChanged);
+            }
+            known_services = found_services;
+        }
+    });
+}
+
+// Lists the service type and queues its browse, the meta query reports known types
+// again, e.g. after a rescan, the browse manager starts each one once
+async fn browse_service_type(
+    service_type: String,
+    state: &Arc<RwLock<AppState>>,
+    notification_sender: &flume::Sender<Notification>,
+) {
+    let mut state = state.write().await;
+    if state.add_service_type(&service_type) {
+        state.update_metric("service_types_discovered");
+        let _ = notification_sender.try_send(Notification::ServiceChanged);
+    }
+    state.browse_manager.enqueue(&service_type);
+}
+
+// Starts the configured outputs of discovery events, their errors show up in the
+// status bar
+pub(super) async fn spawn_event_outputs(
+    tasks: &BackgroundTasks,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    let (error_sender, error_receiver) = flume::unbounded::<String>();
+    {
+        let mut state = state.write().await;
+        if let Some(path) = state.config.record.clone() {
+            match session::Recorder::create(&path, error_sender.clone()) {
+                Ok(recorder) => state.session_recorder = Some(recorder),
+                Err(e) => state.status_message = Some(format!("Cannot record session: {}", e)),
+            }
+        }
+    }
+    {
+        let state_for_outputs = Arc::clone(&state);
+        let state = state.read().await;
+        if let Some(config) = state.config.mqtt.clone() {
+            tasks.spawn(mqtt::publish_events(
+                config,
+                state.discovery_events.subscribe(),
+                error_sender.clone(),
+            ));
+        }
+        if let Some(config) = state.config.home_assistant.clone() {
+            tasks.spawn(home_assistant::bridge_events(
+                state.config.mqtt.clone().unwrap_or_default(),
+                config,
+                state.discovery_events.subscribe(),
+                error_sender.clone(),
+            ));
+        }
+        if let Some(target) = state.config.event_log {
+            tasks.spawn(event_log::log_events(
+                target,
+                state.discovery_events.subscribe(),
+                error_sender.clone(),
+            ));
+        }
+        if !state.config.notifications.is_empty() {
+            spawn_desktop_notifications(
+                tasks,
+                state.discovery_events.subscribe(),
+                Arc::clone(&state_for_outputs),
+                error_sender,
+            );
+        }
+    }
+    tasks.spawn(async move {
+        while let Ok(error) = error_receiver.recv_async().await {
+            state.write().await.report_error(error);
+            let _ = notification_sender.try_send(Notification::ServiceChanged);
+        }
+    });
+}
+
+// Shows a desktop notification for every event matching one of the configured rules
+fn spawn_desktop_notifications(
+    tasks: &BackgroundTasks,
+    mut events: tokio::sync::broadcast::Receiver<DiscoveryEvent>,
+    state: Arc<RwLock<AppState>>,
+    error_sender: flume::Sender<String>,
+) {
+    tasks.spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+            let notify = {
+                let state = state.read().await;
+                let watched = state.watched_services.contains(&event.fullname);
+                state
+                    .config
+                    .notifications
+                    .iter()
+                    .any(|rule| rule.matches(&event, watched))
+            };
+            if notify {
+                let error_sender = error_sender.clone();
+                // Talking to the notification service blocks
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = desktop_notify::show(&event) {
+                        let _ = error_sender.send(format!("Desktop notification failed: {}", e));
+                    }
+                });
+            }
+        }
+    });
+}
+
+// Where the TUI gets its services from
+pub enum DiscoverySource {
+    Network,
+    Replay(Replay),
+    // The merged discoveries of serve instances elsewhere
+    Remote(Vec<RemoteAgent>),
+    // Scripted services announced by a mock daemon
+    #[cfg(feature = "mock-discovery")]
+    Mock(Vec<session::RecordedEvent>),
+}
+
+// Starts the browse manager and the browses of the configured domains
+pub(super) async fn start_browsing<B: DiscoveryBackend>(
+    tasks: &BackgroundTasks,
+    browser: B,
+    state: &Arc<RwLock<AppState>>,
+    notification_sender: &flume::Sender<Notification>,
+) {
+    let updates = spawn_update_aggregator(tasks, Arc::clone(state), notification_sender.clone());
+    spawn_browse_manager(
+        tasks,
+        browser.clone(),
+        Arc::clone(state),
+        updates,
+        notification_sender.clone(),
+    );
+    browse_domains(tasks, &browser, state, notification_sender).await;
+}
+
+// A change a browse saw, applied to the state by the aggregation task
+pub(super) enum DiscoveryUpdate {
+    Resolved(Box<ServiceEntry>),
+    Removed(String),
+    // The backend ended the browse of the given generation without being asked to
+    BrowseEnded {
+        service_type: String,
+        generation: u64,
+    },
+}
+
+impl DiscoveryUpdate {
+    // The service updated, None for updates of browses which are never merged
+    fn fullname(&self) -> Option<&str> {
+        match self {
+            Self::Resolved(entry) => Some(&entry.fullname),
+            Self::Removed(fullname) => Some(fullname),
+            Self::BrowseEnded { .. } => None,
+        }
+    }
+}
+
+// The updates taken from the queue at once, only the latest of each service is kept
+#[derive(Default)]
+pub(super) struct UpdateBatch {
+    pub(super) updates: Vec<DiscoveryUpdate>,
+    // Position in updates by fullname
+    positions: HashMap<String, usize>,
+    // Updates replaced by a later one of the same service
+    pub(super) merged: u64,
+}
+
+impl UpdateBatch {
+    pub(super) fn push(&mut self, update: DiscoveryUpdate) {
+        let Some(fullname) = update.fullname() else {
+            self.updates.push(update);
+            return;
+        };
+        match self.positions.get(fullname) {
+            Some(&idx) => {
+                self.updates[idx] = update;
+                self.merged += 1;
+            }
+            None => {
+                self.positions
+                    .insert(fullname.to_string(), self.updates.len());
+                self.updates.push(update);
+            }
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.updates.len()
+    }
+}
+
+// Applies the updates of all browses in one task. Whatever queued up meanwhile
+// is taken along and merged, so a burst costs one write lock and one redraw
+// instead of one per event. On shutdown the queued updates are still applied
+fn spawn_update_aggregator(
+    tasks: &BackgroundTasks,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) -> flume::Sender<DiscoveryUpdate> {
+    let (sender, receiver) = flume::bounded(DISCOVERY_QUEUE_CAPACITY);
+    let cancelled = tasks.cancelled();
+    tasks.spawn_draining(async move {
+        tokio::pin!(cancelled);
+        loop {
+            let update = tokio::select! {
+                update = receiver.recv_async() => match update {
+                    Ok(update) => update,
+                    Err(_) => break,
+                },
+                _ = &mut cancelled => {
+                    let mut batch = UpdateBatch::default();
+                    receiver.drain().for_each(|update| batch.push(update));
+                    state.write().await.apply_discovery_updates(batch);
+                    break;
+                }
+            };
+            let mut batch = UpdateBatch::default();
+            batch.push(update);
+            while batch.len() < MAX_UPDATE_BATCH
+                && let Ok(update) = receiver.try_recv()
+            {
+                batch.push(update);
+            }
+            if state.write().await.apply_discovery_updates(batch) {
+                let _ = notification_sender.try_send(Notification::ServiceChanged);
+            }
+        }
+    });
+    sender
+}
+
+// Starts the queued browses as the browse manager allows and hands the services
+// they resolve to the aggregation task
+fn spawn_browse_manager<B: DiscoveryBackend>(
+    tasks: &BackgroundTasks,
+    mdns: B,
+    state: Arc<RwLock<AppState>>,
+    updates: flume::Sender<DiscoveryUpdate>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    let browse_tasks = tasks.clone();
+    tasks.spawn(async move {
+        {
+            let mut state = state.write().await;
+            let max_browses = state.config.limits.max_browses;
+            state.browse_manager.set_max_started(max_browses);
+        }
+        let mut interval = runtime::Interval::new(browse_manager::BROWSE_SPACING);
+        let mut reported_starved = 0;
+        loop {
+            interval.tick().await;
+
+            let ready: Vec<(String, u64)> = {
+                let mut state = state.write().await;
+                let manager = &mut state.browse_manager;
+                let ready: Vec<(String, u64)> = manager
+                    .take_ready(std::time::Instant::now())
+                    .into_iter()
+                    .filter_map(|t| manager.generation(&t).map(|generation| (t, generation)))
+                    .collect();
+                // Browses don't end on their own, so the user is told why types wait
+                let starved = manager.starved();
+                if starved > reported_starved {
+                    state.status_message = Some(format!(
+                        "{} service types wait for one of the {} browses to end, raise max_browses in [limits]",
+                        starved, state.config.limits.max_browses
+                    ));
+                    let _ = notification_sender.try_send(Notification::ServiceChanged);
+                }
+                reported_starved = starved;
+                ready
+            };
+            for (service_type, generation) in ready {
+                let result =
+                    start_service_browse(&browse_tasks, &mdns, &service_type, generation, &updates);
+                let mut state = state.write().await;
+                match result {
+                    Ok(()) => state.update_metric("browses_started"),
+                    Err(e) => {
+                        let retry =
+                            state
+                                .browse_manager
+                                .fail(&service_type, &e, std::time::Instant::now());
+                        state.note_browse_failure(&service_type, &e, retry);
+                        let _ = notification_sender.try_send(Notification::ServiceChanged);
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Sends the type's query right away and hands the resolved services to the
+// aggregation task, waiting while its queue is full. A browse ending on its own
+// is reported too, so the browse manager restarts it
+fn start_service_browse<B: DiscoveryBackend>(
+    tasks: &BackgroundTasks,
+    mdns: &B,
+    service_type: &str,
+    generation: u64,
+    updates: &flume::Sender<DiscoveryUpdate>,
+) -> Result<(), String> {
+    let service_receiver = mdns.browse(service_type).map_err(|e| e.to_string())?;
+
+    let span = tracing::info_span!("browse", service_type, generation);
+    let service_type = service_type.to_string();
+    let updates = updates.clone();
+    tasks.spawn(
+        async move {
+            tracing::info!("browse started");
+            while let Some(service_event) = service_receiver.next_event().await {
+                let update = match service_event {
+                    ServiceEvent::ServiceRemoved(_service_type, fullname) => {
+                        tracing::trace!(%fullname, "removed");
+                        DiscoveryUpdate::Removed(fullname)
+                    }
+                    ServiceEvent::ServiceResolved(resolved_service) => {
+                        tracing::trace!(fullname = resolved_service.fullname, "resolved");
+                        DiscoveryUpdate::Resolved(Box::new(ServiceEntry::from(*resolved_service)))
+                    }
+                    _ => continue,
+                };
+                if updates.send_async(update).await.is_err() {
+                    return;
+                }
+            }
+            // Also the end of a stopped browse, the browse manager knows it was replaced
+            tracing::info!("browse ended");
+            let _ = updates
+                .send_async(DiscoveryUpdate::BrowseEnded {
+                    service_type,
+                    generation,
+                })
+                .await;
+        }
+        .instrument(span),
+    );
+    Ok(())
+}
+
+// Browses every service type announced through the meta query receiver
+fn spawn_service_type_discovery(
+    tasks: &BackgroundTasks,
+    receiver: impl ServiceEvents,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    tasks.spawn(async move {
+        while let Some(event) = receiver.next_event().await {
+            match event {
+                ServiceEvent::ServiceRemoved(_service_type, fullname) => {
+                    let mut state = state.write().await;
+                    state.record_session_event(SessionEvent::TypeRemoved {
+                        service_type: fullname.clone(),
+                    });
+                    if state.remove_service_type(&fullname) {
+                        let _ = notification_sender.try_send(Notification::ServiceChanged);
+                    }
+                }
+                ServiceEvent::ServiceFound(_service_type, fullname) => {
+                    state
+                        .read()
+                        .await
+                        .record_session_event(SessionEvent::TypeFound {
+                            service_type: fullname.clone(),
+                        });
+                    // Invalid types are covered by browse resulting in an error
+                    browse_service_type(fullname.to_string(), &state, &notification_sender).await;
+                }
+                _ => (),
+            }
+        }
+    });
+}
+
+// Feeds the recorded events into the state at their recorded pace
+pub(super) fn spawn_replay(
+    tasks: &BackgroundTasks,
+    replay: Replay,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    tasks.spawn(async move {
+        let started = tokio::time::Instant::now();
+        let offsets: Vec<Duration> = replay.events.iter().map(|e| replay.offset(e)).collect();
+        let count = replay.events.len();
+        for (event, offset) in replay.events.into_iter().zip(offsets) {
+            tokio::time::sleep_until(started + offset).await;
+            state.write().await.apply_session_event(event.event);
+            let _ = notification_sender.try_send(Notification::ServiceChanged);
+        }
+        state.write().await.status_message = Some(format!("Replay finished, {} events", count));
+        let _ = notification_sender.try_send(Notification::ServiceChanged);
+    });
+}
+
+// Mirrors the services of a remote agent, taking a snapshot on connecting and
+// then following its events; reconnects with a new snapshot after errors
+pub(super) fn spawn_remote(
+    tasks: &BackgroundTasks,
+    agent: RemoteAgent,
+    state: Arc<RwLock<AppState>>,
+    notification_sender: flume::Sender<Notification>,
+) {
+    tasks.spawn(async move {
+        let label = agent.label();
+        loop {
+            let error = match agent.snapshot().await {
+                Ok((mut next, events)) => {
+                    {
+                        let mut state = state.write().await;
+                        // Removals missed while disconnected
+                        let listed: HashSet<&str> = events
+                            .iter()
+                            .filter_map(|event| match event {
+                                SessionEvent::Resolved(service) => Some(service.fullname.as_str()),
+                                _ => None,
+                            })
+                            .collect();
+                        let gone: Vec<String> = state
+                            .services
+                            .iter()
+                            .filter(|s| {
+                                s.online
+                                    && s.agent.as_deref() == Some(label.as_str())
+                                    && !listed.contains(s.fullname.as_str())
+                            })
+                            .map(|s| s.fullname.clone())
+                            .collect();
+                        for fullname in gone {
+                            state.apply_remote_event(&label, SessionEvent::Removed { fullname });
+                        }
+                        for event in events {
+                            state.apply_remote_event(&label, event);
+                        }
+                        state.status_message = Some(format!("Connected to {}", label));
+                    }
+                    let _ = notification_sender.try_send(Notification::ServiceChanged);
+                    loop {
+                        match agent.poll(next).await {
+                            Ok((polled_next, events)) => {
+                                next = polled_next;
+                                if events.is_empty() {
+                                    continue;
+                                }
+                                let mut state = state.write().await;
+                                for event in events {
+                                    state.apply_remote_event(&label, event);
+                                }
+                                drop(state);
+                                let _ = notification_sender.try_send(Notification::ServiceChanged);
+                            }
+                            Err(e) => break e,
+                        }
+                    }
+                }
+                Err(e) => e,
+            };
+            state.write().await.status_message = Some(format!(
+                "Connection to {} failed: {}, retrying",
+                label, error
+            ));
+            let _ = notification_sender.try_send(Notification::ServiceChanged);
+            tokio::time::sleep(REMOTE_RETRY_INTERVAL).await;
+        }
+    });
+}
+
+// Creates the backend of the config, the mDNS daemon with the socket options
+// and interfaces of the config unless another one is asked for
+pub(super) async fn start_backend(app_state: &mut AppState) -> Result<ConfiguredBackend, Error> {
+    mdns_query::set_socket_options(mdns_query::SocketOptions {
+        multicast_ttl: app_state.config.multicast_ttl,
+        multicast_loop: app_state.config.multicast_loop,
+        multicast_if: app_state.multicast_if(),
+    });
+    match app_state.config.backend {
+        BackendKind::MdnsSd => {
+            let mdns = ServiceDaemon::new().map_err(Error::Daemon)?;
+            mdns.set_multicast_loop_v4(app_state.config.multicast_loop)
+                .map_err(Error::Daemon)?;
+            mdns.set_multicast_loop_v6(app_state.config.multicast_loop)
+                .map_err(Error::Daemon)?;
+            for name in app_state.restrict_to_multicast_interfaces() {
+                mdns.disable_interface(name.as_str())
+                    .map_err(|source| Error::Interface { name, source })?;
+            }
+            Ok(ConfiguredBackend::MdnsSd(mdns))
+        }
+        #[cfg(feature = "bonjour")]
+        BackendKind::Bonjour => Ok(ConfiguredBackend::Bonjour(
+            BonjourBackend::start(&app_state.config.limits)
+                .await
+                .map_err(Error::Backend)?,
+        )),
+        #[cfg(not(feature = "bonjour"))]
+        BackendKind::Bonjour => Err(Error::Backend(
+            "The bonjour backend needs a build with --features bonjour".to_string(),
+        )),
+    }
+}
+
+// Browses for all service types of every configured domain, other domains
+// than local. are asked over unicast DNS
+pub(super) async fn browse_domains<B: DiscoveryBackend>(
+    tasks: &BackgroundTasks,
+    mdns: &B,
+    state: &Arc<RwLock<AppState>>,
+    sender: &flume::Sender<Notification>,
+) {
+    let (browse_domains, unicast_dns_server) = {
+        let state = state.read().await;
+        (
+            state.config.browse_domains(),
+            state.config.unicast_dns_server.clone(),
+        )
+    };
+    let mut unicast_browser = None;
+    for domain in browse_domains {
+        if domain != "local." {
+            // The unicast resolver runs on Tokio only
+            #[cfg(feature = "smol")]
+            if !runtime::in_tokio() {
+                let error = Error::NoTokio {
+                    needed_by: format!("Browsing {}", domain),
+                };
+                state.write().await.report_error(error.report());
+                continue;
+            }
+            if unicast_browser.is_none() {
+                let browser = unicast_dns_server
+                    .as_deref()
+                    .map(unicast_dns_sd::parse_server_address)
+                    .transpose()
+                    .and_then(UnicastBrowser::new);
+                match browser {
+                    Ok(browser) => unicast_browser = Some(Arc::new(browser)),
+                    Err(e) => {
+                        state.write().await.status_message =
+                            Some(format!("Cannot browse {}: {}", domain, e));
+                        continue;
+                    }
+                }
+            }
+            if let Some(browser) = &unicast_browser {
+                spawn_unicast_discovery(
+                    tasks,
+                    Arc::clone(browser),
+                    domain,
+                    Arc::clone(state),
+                    sender.clone(),
+                );
+            }
+            continue;
+        }
+        match mdns.browse(&meta_query_name(&domain)) {
+            Ok(receiver) => {
+                spawn_service_type_discovery(tasks, receiver, Arc::clone(state), sender.clone())
+            }
+            Err(source) => {
+                let error = Error::Browse {
+                    service_type: meta_query_name(&domain),
+                    source,
+                };
+                state.write().await.report_error(error.report());
+            }
+        }
+    }
+}
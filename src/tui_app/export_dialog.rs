@@ -0,0 +1,181 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// The prompts exporting the selected service or the listed ones to a file
+#[derive(Debug, Default)]
+pub(super) struct ExportState {
+    // Target file of the selected service's JSON export while asking for it
+    service_path_input: Option<InputLine>,
+    // Export of the shown or all services while the dialog is open
+    dialog: Option<ExportDialog>,
+}
+
+// What the export dialog writes where
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct ExportDialog {
+    pub(super) path: InputLine,
+    pub(super) format: ExportFormat,
+    // Only the services shown for the selected type and filter, all otherwise
+    pub(super) filtered_only: bool,
+    // Set when the file exists and Enter has to be pressed again to overwrite it
+    pub(super) confirm_overwrite: bool,
+}
+
+impl ExportState {
+    pub(super) fn service_path_input(&self) -> Option<&InputLine> {
+        self.service_path_input.as_ref()
+    }
+
+    pub(super) fn dialog(&self) -> Option<&ExportDialog> {
+        self.dialog.as_ref()
+    }
+}
+
+impl AppState {
+    pub(super) fn start_service_export(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let name = self.services[service_idx]
+            .fullname
+            .split('.')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        self.exports.service_path_input = Some(InputLine::from(format!(
+            "mdns-service-{}-{}.json",
+            name,
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )));
+    }
+
+    pub(super) fn handle_export_path_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(path) = self.exports.service_path_input.take() {
+                    self.export_selected_service(path.trim());
+                }
+            }
+            KeyCode::Esc => self.exports.service_path_input = None,
+            _ => {
+                if let Some(path) = &mut self.exports.service_path_input {
+                    edit_input_line(path, key);
+                }
+            }
+        }
+        true
+    }
+
+    // Writes the selected service as JSON to the path, "-" or nothing prints it on exit
+    fn export_selected_service(&mut self, path: &str) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let json = match serde_json::to_string_pretty(&self.services[service_idx]) {
+            Ok(json) => json,
+            Err(e) => {
+                self.status_message = Some(format!("Export failed: {}", e));
+                return;
+            }
+        };
+        if path.is_empty() || path == "-" {
+            self.stdout_on_exit.push(json);
+            self.status_message = Some("The service will be printed on exit".to_string());
+            return;
+        }
+        self.status_message = Some(match std::fs::write(path, json) {
+            Ok(()) => format!("Exported the service to {}", path),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    pub(super) fn open_export_dialog(&mut self) {
+        let format = ExportFormat::default();
+        self.exports.dialog = Some(ExportDialog {
+            path: InputLine::from(format!(
+                "mdns-services-{}.{}",
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                format.extension()
+            )),
+            format,
+            filtered_only: true,
+            confirm_overwrite: false,
+        });
+    }
+
+    pub(super) fn handle_export_dialog_key(&mut self, key: KeyEvent) -> bool {
+        let Some(dialog) = &mut self.exports.dialog else {
+            return true;
+        };
+        match key.code {
+            KeyCode::Enter => self.export_services(),
+            KeyCode::Esc => self.exports.dialog = None,
+            KeyCode::Tab => {
+                dialog.format = dialog.format.next();
+                dialog.path =
+                    InputLine::from(export::with_extension(&dialog.path.text, dialog.format));
+                dialog.confirm_overwrite = false;
+            }
+            KeyCode::BackTab => dialog.filtered_only = !dialog.filtered_only,
+            _ => {
+                if edit_input_line(&mut dialog.path, key) {
+                    dialog.confirm_overwrite = false;
+                }
+            }
+        }
+        true
+    }
+
+    // The services shown for the selected type and filter, or all of them
+    pub(super) fn export_candidates(&mut self, filtered_only: bool) -> Vec<&ServiceEntry> {
+        if filtered_only {
+            let indices = self.get_filtered_services().to_vec();
+            indices.iter().map(|&idx| &self.services[idx]).collect()
+        } else {
+            self.services.iter().collect()
+        }
+    }
+
+    // Writes the services of the export dialog, asks before overwriting a file,
+    // "-" or nothing prints them on exit
+    fn export_services(&mut self) {
+        let Some(dialog) = self.exports.dialog.clone() else {
+            return;
+        };
+        let path = dialog.path.text.trim().to_string();
+        let to_stdout = path.is_empty() || path == "-";
+        if !to_stdout && !dialog.confirm_overwrite && std::path::Path::new(&path).exists() {
+            if let Some(dialog) = &mut self.exports.dialog {
+                dialog.confirm_overwrite = true;
+            }
+            return;
+        }
+        self.exports.dialog = None;
+        let services = self.export_candidates(dialog.filtered_only);
+        let count = services.len();
+        let rendered = match export::render(dialog.format, &services) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                self.status_message = Some(format!("Export failed: {}", e));
+                return;
+            }
+        };
+        if to_stdout {
+            self.stdout_on_exit.push(rendered);
+            self.status_message = Some(format!("{} services will be printed on exit", count));
+            return;
+        }
+        self.status_message = Some(match std::fs::write(&path, rendered) {
+            Ok(()) => format!(
+                "Exported {} services as {} to {}",
+                count,
+                dialog.format.label(),
+                path
+            ),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+}
@@ -0,0 +1,845 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// Compare strings so that embedded numbers sort by value, e.g. "host2" < "host10"
+pub(super) fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_ch), Some(b_ch)) if a_ch.is_ascii_digit() && b_ch.is_ascii_digit() => {
+                let a_run = take_digit_run(&mut a_chars);
+                let b_run = take_digit_run(&mut b_chars);
+                let a_digits = a_run.trim_start_matches('0');
+                let b_digits = b_run.trim_start_matches('0');
+
+                // Longer significant run means a bigger number; equal lengths compare digit-wise.
+                // On equal values, fewer leading zeros sort first to keep the order total.
+                let ordering = a_digits
+                    .len()
+                    .cmp(&b_digits.len())
+                    .then_with(|| a_digits.cmp(b_digits))
+                    .then_with(|| a_run.len().cmp(&b_run.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a_ch), Some(b_ch)) => {
+                if a_ch != b_ch {
+                    return a_ch.cmp(&b_ch);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_digit_run(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut run = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        run.push(ch);
+    }
+    run
+}
+
+// Addresses of the preferred family first, then routable ones if asked for,
+// falling back to the first address at all
+pub(super) fn preferred_address(
+    service: &ServiceEntry,
+    preference: AddressPreference,
+) -> Option<&str> {
+    service
+        .addrs
+        .iter()
+        .min_by_key(|addr| {
+            let family_mismatch = match (preference.family, parse_address(addr)) {
+                (AddressFamily::Ipv4, Some(ip)) => !ip.is_ipv4(),
+                (AddressFamily::Ipv6, Some(ip)) => !ip.is_ipv6(),
+                (AddressFamily::Any, _) => false,
+                (_, None) => true,
+            };
+            let scope_rank = if preference.routable_first {
+                classify_address(addr).rank()
+            } else {
+                0
+            };
+            (family_mismatch, scope_rank)
+        })
+        .map(String::as_str)
+}
+
+// Scoped IPv6 addresses carry their zone id after a '%'
+pub(super) fn parse_address(addr: &str) -> Option<std::net::IpAddr> {
+    addr.split('%').next()?.parse().ok()
+}
+
+pub(super) fn classify_address(addr: &str) -> AddressScope {
+    use std::net::IpAddr;
+
+    match parse_address(addr) {
+        Some(IpAddr::V4(ip)) => {
+            let octets = ip.octets();
+            if ip.is_loopback() {
+                AddressScope::Loopback
+            } else if ip.is_link_local() {
+                AddressScope::LinkLocal
+            } else if ip.is_private() || (octets[0] == 100 && (octets[1] & 0xc0) == 64) {
+                AddressScope::Private
+            } else {
+                AddressScope::Global
+            }
+        }
+        Some(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            if ip.is_loopback() {
+                AddressScope::Loopback
+            } else if (first & 0xffc0) == 0xfe80 {
+                AddressScope::LinkLocal
+            } else if (first & 0xfe00) == 0xfc00 {
+                AddressScope::UniqueLocal
+            } else if let Some(v4) = ip.to_ipv4_mapped() {
+                classify_address(&v4.to_string())
+            } else {
+                AddressScope::Global
+            }
+        }
+        None => AddressScope::Unknown,
+    }
+}
+
+pub(super) fn format_host_port(service: &ServiceEntry) -> String {
+    format!("{}:{}", service.host.trim_end_matches('.'), service.port)
+}
+
+// Builds the URL of an _http._tcp or _https._tcp service from its address, port and
+// the path= TXT key, None for any other service type
+pub(super) fn service_url(service: &ServiceEntry, preference: AddressPreference) -> Option<String> {
+    let scheme = http_scheme(service)?;
+
+    let host = match preferred_address(service, preference) {
+        // IPv6 literals need brackets, and the '%' of a zone id must be escaped
+        Some(addr) if addr.contains(':') => format!("[{}]", addr.replace('%', "%25")),
+        Some(addr) => addr.to_string(),
+        None => service.host.trim_end_matches('.').to_string(),
+    };
+
+    Some(format!(
+        "{}://{}:{}{}",
+        scheme,
+        host,
+        service.port,
+        service_path(service)
+    ))
+}
+
+pub(super) fn http_scheme(service: &ServiceEntry) -> Option<&'static str> {
+    if service.service_type.starts_with("_http._tcp.") {
+        Some("http")
+    } else if service.service_type.starts_with("_https._tcp.") {
+        Some("https")
+    } else {
+        None
+    }
+}
+
+// The encoded path= TXT value, "/" if there is none
+pub(super) fn service_path(service: &ServiceEntry) -> String {
+    let path = service
+        .txt
+        .iter()
+        .find_map(|entry| entry.strip_prefix("path="))
+        .unwrap_or("/");
+    if path.starts_with('/') {
+        encode_url_path(path)
+    } else {
+        format!("/{}", encode_url_path(path))
+    }
+}
+
+// Builds the ssh invocation for an _ssh._tcp or _sftp-ssh._tcp service, taking the
+// user name from the u= TXT key if present
+pub(super) fn ssh_command(
+    service: &ServiceEntry,
+    preference: AddressPreference,
+) -> Option<ExternalCommand> {
+    if !service.service_type.starts_with("_ssh._tcp.")
+        && !service.service_type.starts_with("_sftp-ssh._tcp.")
+    {
+        return None;
+    }
+
+    let host = preferred_address(service, preference)
+        .map(str::to_string)
+        .unwrap_or_else(|| service.host.trim_end_matches('.').to_string());
+
+    let mut args = vec!["-p".to_string(), service.port.to_string()];
+    if let Some(user) = service
+        .txt
+        .iter()
+        .find_map(|entry| entry.strip_prefix("u="))
+        .filter(|user| !user.is_empty())
+    {
+        args.push("-l".to_string());
+        args.push(user.to_string());
+    }
+    // Ends option parsing, so a hostile host name cannot inject ssh options
+    args.push("--".to_string());
+    args.push(host);
+
+    Some(ExternalCommand {
+        program: "ssh".to_string(),
+        args,
+    })
+}
+
+// Percent-encodes everything that is not allowed verbatim in a URL path and query,
+// so TXT values cannot smuggle whitespace or control characters into the URL
+pub(super) fn encode_url_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/?%#".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+// Turns an action command template into a command line, substituting the
+// placeholders per argument so service data can never split or inject arguments
+pub(super) fn expand_action_command(
+    template: &str,
+    service: &ServiceEntry,
+    preference: AddressPreference,
+) -> Result<ExternalCommand, String> {
+    let host = service.host.trim_end_matches('.');
+    let address = preferred_address(service, preference).unwrap_or(host);
+    let port = service.port.to_string();
+    let service_type = service.service_type.trim_end_matches('.');
+
+    let mut args = shell_words::split(template)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{host}", host)
+                .replace("{address}", address)
+                .replace("{port}", &port)
+                .replace("{fullname}", &service.fullname)
+                .replace("{type}", service_type)
+        });
+    let program = args.next().ok_or("empty command")?;
+    Ok(ExternalCommand {
+        program,
+        args: args.collect(),
+    })
+}
+
+// "_http._tcp.example.com." -> "example.com."
+pub(super) fn service_domain(service_type: &str) -> &str {
+    ["._tcp.", "._udp."]
+        .iter()
+        .filter_map(|protocol| {
+            service_type
+                .find(protocol)
+                .map(|start| &service_type[start + protocol.len()..])
+        })
+        .next()
+        .unwrap_or("local.")
+}
+
+// Enumerating this name lists all service types announced in the domain
+pub(super) fn meta_query_name(domain: &str) -> String {
+    format!("_services._dns-sd._udp.{}", domain)
+}
+
+// "_workstation._tcp" -> "_workstation._tcp.local.", None unless it names a TCP or UDP type
+pub(super) fn normalize_service_type(input: &str) -> Option<String> {
+    let service_type = format!("{}.", input.trim().trim_end_matches('.'));
+    let labels: Vec<&str> = service_type.split('.').collect();
+    let protocol_idx = labels
+        .iter()
+        .position(|label| *label == "_tcp" || *label == "_udp")?;
+    if protocol_idx == 0 || labels[..protocol_idx].iter().any(|label| label.is_empty()) {
+        return None;
+    }
+    if protocol_idx + 2 == labels.len() {
+        Some(format!("{}local.", service_type))
+    } else {
+        Some(service_type)
+    }
+}
+
+// "_printer._sub._http._tcp.local." is a subtype of "_http._tcp.local."
+pub(super) fn is_subtype(service_type: &str) -> bool {
+    service_type.contains("._sub.")
+}
+
+pub(super) fn parent_service_type(service_type: &str) -> &str {
+    service_type
+        .split_once("._sub.")
+        .map_or(service_type, |(_, parent)| parent)
+}
+
+pub(super) fn service_type_sort_key(service_type: &str) -> (&str, bool, &str) {
+    (
+        parent_service_type(service_type),
+        is_subtype(service_type),
+        service_type,
+    )
+}
+
+// "192.168.1.20:5353 printer.local. 120s A 192.168.1.20"
+pub(super) fn format_mdns_answer(answer: &mdns_query::MdnsAnswer) -> String {
+    format!(
+        "{} {} {}s {} {}",
+        answer.source,
+        answer.record.name(),
+        answer.record.ttl(),
+        answer.record.record_type(),
+        answer.record.data()
+    )
+}
+
+// "octopi" and "octopi.local" -> "octopi.local.", None for empty input
+pub(super) fn normalize_hostname(input: &str) -> Option<String> {
+    let hostname = input.trim().trim_end_matches('.');
+    if hostname.is_empty() {
+        return None;
+    }
+    Some(if hostname.contains('.') {
+        format!("{}.", hostname)
+    } else {
+        format!("{}.local.", hostname)
+    })
+}
+
+pub(super) fn well_known_port_name(port: u16) -> Option<&'static str> {
+    Some(match port {
+        21 => "FTP",
+        22 => "SSH",
+        23 => "Telnet",
+        25 => "SMTP",
+        53 => "DNS",
+        80 => "HTTP",
+        110 => "POP3",
+        139 => "NetBIOS",
+        143 => "IMAP",
+        443 => "HTTPS",
+        445 => "SMB",
+        515 => "LPD",
+        548 => "AFP",
+        554 => "RTSP",
+        631 => "IPP",
+        1883 => "MQTT",
+        3389 => "RDP",
+        5000 | 5001 => "UPnP / Synology DSM",
+        5900 => "VNC",
+        8008 | 8009 => "Google Cast",
+        8080 | 8000 => "HTTP alternate",
+        8443 => "HTTPS alternate",
+        8883 => "MQTT over TLS",
+        9100 => "JetDirect printing",
+        _ => return None,
+    })
+}
+
+// Helper functions for formatting
+pub(super) fn format_sort_field_for_display(field: SortField) -> &'static str {
+    match field {
+        SortField::Host => "Host",
+        SortField::ServiceType => "Type",
+        SortField::Fullname => "Name",
+        SortField::Port => "Port",
+        SortField::Address => "Addr",
+        SortField::AddressCount => "Addrs#",
+        SortField::Timestamp => "Time",
+        SortField::FirstSeen => "First",
+        SortField::Refreshes => "Refreshes",
+        SortField::Priority => "Prio",
+    }
+}
+
+pub(super) fn format_sort_direction_for_display(direction: SortDirection) -> &'static str {
+    match direction {
+        SortDirection::Ascending => "↑",
+        SortDirection::Descending => "↓",
+    }
+}
+
+pub(super) fn format_address_family_for_display(family: AddressFamily) -> Option<&'static str> {
+    match family {
+        AddressFamily::Any => None,
+        AddressFamily::Ipv4 => Some("IPv4"),
+        AddressFamily::Ipv6 => Some("IPv6"),
+    }
+}
+
+pub(super) fn format_service_type_for_display(service_type: &str) -> String {
+    service_type
+        .trim_start_matches('_')
+        .trim_end_matches(".local.")
+        .trim_end_matches(".")
+        .replace("._tcp", ".tcp")
+        .replace("._udp", ".udp")
+}
+
+// "_printer._sub._http._tcp.local." -> "printer"
+pub(super) fn format_subtype_for_display(subtype: &str) -> String {
+    subtype
+        .split_once("._sub.")
+        .map_or(subtype, |(label, _)| label)
+        .trim_start_matches('_')
+        .to_string()
+}
+
+pub(super) fn format_reachability_badge(reachable: Option<bool>) -> (&'static str, Color) {
+    match reachable {
+        Some(true) => (" ✓ open", Color::Green),
+        Some(false) => (" ✗ closed", Color::Red),
+        None => (" … probing", Color::DarkGray),
+    }
+}
+
+pub(super) fn format_latency(latency_micros: u64) -> String {
+    if latency_micros < 10_000 {
+        format!("{:.1} ms", latency_micros as f64 / 1000.0)
+    } else {
+        format!("{} ms", latency_micros / 1000)
+    }
+}
+
+pub(super) fn format_service_markers(pinned: bool, marked: bool, watched: bool) -> String {
+    format!(
+        "{}{}{} ",
+        if pinned { "⚑" } else { " " },
+        if marked { "✔" } else { " " },
+        if watched { "◉" } else { " " }
+    )
+}
+
+pub(super) fn format_service_for_display(
+    service: &ServiceEntry,
+    preference: AddressPreference,
+) -> String {
+    let display_name = service
+        .fullname
+        .trim_end_matches(&*service.service_type)
+        .trim_end_matches(".");
+    let display_host = service
+        .host
+        .trim_end_matches(".local.")
+        .trim_end_matches(".");
+    let address = preferred_address(service, preference)
+        .map(|a| a.to_string())
+        .unwrap_or_else(|| "<no-addr>".into());
+    format!(
+        "{} - {} - {}:{}",
+        display_name, display_host, address, service.port
+    )
+}
+
+pub(super) fn format_timestamp_micros(timestamp_micros: u64) -> String {
+    use chrono::{DateTime, Local, Utc};
+
+    let seconds = timestamp_micros / 1_000_000;
+    let nanoseconds = (timestamp_micros % 1_000_000) * 1000;
+
+    let datetime = DateTime::<Utc>::from_timestamp(seconds as i64, nanoseconds as u32)
+        .unwrap_or_default()
+        .with_timezone(&Local);
+
+    datetime.format("%Y-%m-%d %H:%M:%S%.6f").to_string()
+}
+
+pub(super) fn format_txt_interpretation(service: &ServiceEntry) -> Option<String> {
+    let lines = txt_schema::interpret_txt(&service.service_type, &service.txt);
+    if lines.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|(label, value)| format!("{}: {}", label, value))
+        .collect();
+    Some(format!("Interpretation:\n{}", lines.join("\n")))
+}
+
+pub(super) fn format_decoded_txt(service: &ServiceEntry) -> String {
+    let decoded: Vec<String> = service
+        .txt
+        .iter()
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            let (encoding, decoded) = txt_decode::decode_txt_value(value)?;
+            Some(format!("{} ({}): {}", key, encoding, decoded))
+        })
+        .collect();
+    if decoded.is_empty() {
+        "Decoded TXT Values:\nNone".to_string()
+    } else {
+        format!("Decoded TXT Values:\n{}", decoded.join("\n"))
+    }
+}
+
+pub(super) fn format_reverse_dns(
+    service: &ServiceEntry,
+    reverse_dns: &HashMap<String, Option<Vec<String>>>,
+) -> String {
+    let host = reverse_dns::normalize_name(&service.host);
+    let mut text = String::from("Reverse DNS:");
+    if service.addrs.is_empty() {
+        text.push_str("\nNone");
+    }
+    for address in &service.addrs {
+        let names = match reverse_dns.get(address) {
+            None | Some(None) => "resolving...".to_string(),
+            Some(Some(names)) if names.is_empty() => "no PTR record".to_string(),
+            Some(Some(names)) if !names.contains(&host) => {
+                format!("{} (does not match {})", names.join(", "), service.host)
+            }
+            Some(Some(names)) => names.join(", "),
+        };
+        text.push_str(&format!("\n{} → {}", address, names));
+    }
+    text
+}
+
+pub(super) fn format_printer_query(query: Option<&Result<PrinterStatus, String>>) -> String {
+    let status = match query {
+        None => return "Printer Status:\nQuerying...".to_string(),
+        Some(Err(e)) => return format!("Printer Status:\nQuery failed: {}", e),
+        Some(Ok(status)) => status,
+    };
+
+    let mut text = String::from("Printer Status:");
+    if let Some(make_and_model) = &status.make_and_model {
+        text.push_str(&format!("\nModel: {}", make_and_model));
+    }
+    if let Some(state) = &status.state {
+        text.push_str(&format!("\nState: {}", state));
+    }
+    if !status.state_reasons.is_empty() {
+        text.push_str(&format!("\nReasons: {}", status.state_reasons.join(", ")));
+    }
+    if !status.document_formats.is_empty() {
+        text.push_str(&format!(
+            "\nFormats: {}",
+            status.document_formats.join(", ")
+        ));
+    }
+    for marker in &status.markers {
+        let level = match marker.level {
+            level if level >= 0 => format!("{}%", level),
+            -3 => "some remaining".to_string(),
+            _ => "unknown".to_string(),
+        };
+        match &marker.color {
+            Some(color) => text.push_str(&format!("\n{} ({}): {}", marker.name, color, level)),
+            None => text.push_str(&format!("\n{}: {}", marker.name, level)),
+        }
+    }
+    text
+}
+
+pub(super) fn format_cast_query(
+    query: Option<&Result<CastDeviceInfo, String>>,
+    service: &ServiceEntry,
+) -> String {
+    let info = match query {
+        None => return "Cast Device:\nQuerying...".to_string(),
+        Some(Err(e)) => return format!("Cast Device:\nQuery failed: {}", e),
+        Some(Ok(info)) => info,
+    };
+
+    let mut text = String::from("Cast Device:");
+    if let Some(name) = &info.name {
+        text.push_str(&format!("\nName: {}", name));
+    }
+    let model = [&info.manufacturer, &info.model]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+    if !model.is_empty() {
+        text.push_str(&format!("\nModel: {}", model.join(" ")));
+    }
+    if let Some(build) = &info.build {
+        text.push_str(&format!("\nFirmware: {}", build));
+    }
+    if let Some(uptime_secs) = info.uptime_secs {
+        text.push_str(&format!("\nUptime: {}", format_duration_secs(uptime_secs)));
+    }
+    // The setup API doesn't tell, but the device announces its status text
+    let current_app = service
+        .txt
+        .iter()
+        .find_map(|entry| entry.strip_prefix("rs="))
+        .filter(|status| !status.is_empty());
+    text.push_str(&format!("\nCurrent app: {}", current_app.unwrap_or("None")));
+    text
+}
+
+// The PTR, SRV and TXT records of the service and the address records of its host,
+// with the time left until they expire, None if none of them were seen yet
+pub(super) fn format_record_ttls(
+    service: &ServiceEntry,
+    observed_records: &ObservedRecords,
+    now: std::time::Instant,
+) -> Option<String> {
+    let fullname = service.fullname.to_lowercase();
+    let pointers = observed_records
+        .of_name(&service.service_type.to_lowercase())
+        .filter(|&(record_type, data, _)| record_type == RecordType::PTR && data == fullname);
+    let instance = observed_records
+        .of_name(&fullname)
+        .filter(|(record_type, _, _)| matches!(record_type, RecordType::SRV | RecordType::TXT));
+    let host = service.host.to_lowercase();
+    let addresses = observed_records
+        .of_name(&host)
+        .filter(|(record_type, _, _)| matches!(record_type, RecordType::A | RecordType::AAAA));
+    let mut records: Vec<_> = pointers.chain(instance).chain(addresses).collect();
+    if records.is_empty() {
+        return None;
+    }
+    records.sort_by(|a, b| {
+        let order = |record_type: &RecordType| match record_type {
+            RecordType::PTR => 0,
+            RecordType::SRV => 1,
+            RecordType::TXT => 2,
+            RecordType::A => 3,
+            _ => 4,
+        };
+        (order(&a.0), a.1).cmp(&(order(&b.0), b.1))
+    });
+
+    let mut text = String::from("Records:");
+    for (record_type, data, record) in records {
+        let label = match record_type {
+            RecordType::A | RecordType::AAAA => format!("{} {}", record_type, data),
+            _ => record_type.to_string(),
+        };
+        let age = now.duration_since(record.received).as_secs();
+        let ttl = u64::from(record.ttl);
+        let state = if record.ttl == 0 {
+            "goodbye".to_string()
+        } else if age < ttl {
+            format!("expires in {}", format_duration_secs(ttl - age))
+        } else {
+            format!("expired {} ago", format_duration_secs(age - ttl))
+        };
+        text.push_str(&format!("\n{} TTL {}s, {}", label, record.ttl, state));
+    }
+    Some(text)
+}
+
+// Newest change first
+pub(super) fn format_txt_history(history: &[TxtChange]) -> String {
+    let mut text = String::from("TXT History:");
+    for change in history.iter().rev() {
+        text.push_str(&format!(
+            "\n{}",
+            format_timestamp_micros(change.timestamp_micros)
+        ));
+        for (key, old, new) in &change.changes {
+            text.push_str(&match (old, new) {
+                (Some(old), Some(new)) => format!("\n  {}: {} → {}", key, old, new),
+                (None, Some(new)) => format!("\n  + {}={}", key, new),
+                (Some(old), None) => format!("\n  - {}={}", key, old),
+                (None, None) => continue,
+            });
+        }
+    }
+    text
+}
+
+pub(super) fn format_duration_secs(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, secs % 60)
+    }
+}
+
+pub(super) fn format_certificate_inspection(
+    inspection: Option<&Result<Vec<CertificateInfo>, String>>,
+    now: i64,
+) -> String {
+    let chain = match inspection {
+        None => return "TLS Certificates:\nInspecting...".to_string(),
+        Some(Err(e)) => return format!("TLS Certificates:\nInspection failed: {}", e),
+        Some(Ok(chain)) if chain.is_empty() => return "TLS Certificates:\nNone".to_string(),
+        Some(Ok(chain)) => chain,
+    };
+
+    let mut text = String::from("TLS Certificates:");
+    for (i, certificate) in chain.iter().enumerate() {
+        let mut notes = Vec::new();
+        if certificate.is_expired_at(now) {
+            notes.push("EXPIRED");
+        }
+        if certificate.is_self_signed() {
+            notes.push("self-signed");
+        }
+        let notes = if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", notes.join(", "))
+        };
+        let subject_alt_names = if certificate.subject_alt_names.is_empty() {
+            "None".to_string()
+        } else {
+            certificate.subject_alt_names.join(", ")
+        };
+        text.push_str(&format!(
+            "\n[{}] Subject: {}\n    Issuer: {}\n    SANs: {}\n    Valid: {} to {}{}",
+            i,
+            certificate.subject,
+            certificate.issuer,
+            subject_alt_names,
+            format_timestamp_micros(certificate.not_before.max(0) as u64 * 1_000_000),
+            format_timestamp_micros(certificate.not_after.max(0) as u64 * 1_000_000),
+            notes
+        ));
+    }
+    text
+}
+
+pub(super) fn create_service_details_text(
+    service: &ServiceEntry,
+    type_name: Option<&str>,
+) -> String {
+    let subtype_text = service
+        .subtype
+        .as_ref()
+        .map(|s| format!("\nSubtype: {}", s))
+        .unwrap_or_default();
+    let subtype_text = match (type_name, service_names::description(&service.service_type)) {
+        (Some(name), Some(description)) => {
+            format!(" ({}: {}){}", name, description, subtype_text)
+        }
+        (Some(name), None) => format!(" ({}){}", name, subtype_text),
+        (None, _) => subtype_text,
+    };
+
+    let status_text = if service.online {
+        "Status: online".to_string()
+    } else {
+        format!(
+            "Offline since: {}",
+            format_timestamp_micros(
+                service
+                    .offline_since_micros
+                    .unwrap_or(service.last_updated_micros)
+            )
+        )
+    };
+    let status_text = format!(
+        "{}\nFirst seen: {}\nLast updated: {}\nLast seen: {}\nRefreshes: {}",
+        status_text,
+        format_timestamp_micros(service.first_seen_micros),
+        format_timestamp_micros(service.last_updated_micros),
+        format_timestamp_micros(service.last_seen_micros),
+        service.refresh_count
+    );
+
+    let status_text = match service.probe.reachable {
+        Some(true) => format!("{}\nTCP probe: reachable", status_text),
+        Some(false) => format!("{}\nTCP probe: unreachable", status_text),
+        None => status_text,
+    };
+    let status_text = match service.probe.latency_micros {
+        Some(latency_micros) => format!(
+            "{}\nLatency: {}",
+            status_text,
+            format_latency(latency_micros)
+        ),
+        None => status_text,
+    };
+    let status_text = match &service.probe.http {
+        Some(Ok(response)) => format!(
+            "{}\nHTTP status: {}{}",
+            status_text,
+            response.status,
+            response
+                .title
+                .as_ref()
+                .map(|title| format!("\nPage title: {}", title))
+                .unwrap_or_default()
+        ),
+        Some(Err(e)) => format!("{}\nHTTP probe failed: {}", status_text, e),
+        None => status_text,
+    };
+
+    let addresses_text = if service.addrs.is_empty() {
+        "None".to_string()
+    } else {
+        service
+            .addrs
+            .iter()
+            .map(|addr| format!("{} [{}]", addr, classify_address(addr)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let txt_text = if service.txt.is_empty() {
+        "None".to_string()
+    } else {
+        service.txt.join("\n")
+    };
+
+    let interfaces_text = if service.interfaces.is_empty() {
+        String::new()
+    } else {
+        format!("\nInterfaces: {}", service.interfaces.join(", "))
+    };
+
+    let agent_text = service
+        .agent
+        .as_ref()
+        .map(|agent| format!("\nAgent: {}", agent))
+        .unwrap_or_default();
+
+    let category_text = device_category::classify(&service.service_type, &service.txt)
+        .map(|category| format!("\nCategory: {}", category.label()))
+        .unwrap_or_default();
+
+    let srv_text = match (service.srv_priority, service.srv_weight) {
+        (Some(priority), Some(weight)) => {
+            format!("\nSRV priority: {}, weight: {}", priority, weight)
+        }
+        _ => String::new(),
+    };
+
+    format!(
+        "{}\n\nFullname: {}\nHostname: {}\nType: {}{}\nDomain: {}{}\nPort: {}{}{}{}\n\nAddresses:\n{}\n\nTXT Records:\n{}",
+        status_text,
+        service.fullname,
+        service.host,
+        service.service_type,
+        subtype_text,
+        service_domain(&service.service_type),
+        category_text,
+        service.port,
+        srv_text,
+        interfaces_text,
+        agent_text,
+        addresses_text,
+        txt_text
+    )
+}
+
+pub(super) fn spinner_frame(elapsed: Duration) -> char {
+    let frame = elapsed.as_millis() / SPINNER_INTERVAL.as_millis();
+    SPINNER_FRAMES[frame as usize % SPINNER_FRAMES.len()]
+}
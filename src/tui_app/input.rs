@@ -0,0 +1,1054 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// Single-line text buffer with a cursor, used for interactive prompts like the quick filter
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct InputLine {
+    pub(super) text: String,
+    // Byte offset into `text`, always on a char boundary
+    pub(super) cursor: usize,
+}
+
+impl InputLine {
+    pub(super) fn before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    pub(super) fn after_cursor(&self) -> &str {
+        &self.text[self.cursor..]
+    }
+
+    pub(super) fn insert(&mut self, ch: char) {
+        self.text.insert(self.cursor, ch);
+        self.cursor += ch.len_utf8();
+    }
+
+    pub(super) fn backspace(&mut self) -> bool {
+        if let Some(ch) = self.before_cursor().chars().next_back() {
+            self.cursor -= ch.len_utf8();
+            self.text.remove(self.cursor);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(super) fn delete(&mut self) -> bool {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(super) fn move_left(&mut self) {
+        if let Some(ch) = self.before_cursor().chars().next_back() {
+            self.cursor -= ch.len_utf8();
+        }
+    }
+
+    pub(super) fn move_right(&mut self) {
+        if let Some(ch) = self.after_cursor().chars().next() {
+            self.cursor += ch.len_utf8();
+        }
+    }
+
+    pub(super) fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub(super) fn move_to_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    // Ctrl+W: delete trailing whitespace before the cursor, then the word before it
+    pub(super) fn delete_word_before_cursor(&mut self) -> bool {
+        let before = self.before_cursor();
+        let word_end = before.trim_end().len();
+        let word_start = before[..word_end]
+            .rfind(char::is_whitespace)
+            .map(|idx| idx + before[idx..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0);
+        if word_start == self.cursor {
+            return false;
+        }
+        self.text.replace_range(word_start..self.cursor, "");
+        self.cursor = word_start;
+        true
+    }
+
+    // Ctrl+U: delete everything before the cursor
+    pub(super) fn delete_to_start(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+        true
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+}
+
+impl From<&str> for InputLine {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_string())
+    }
+}
+
+impl From<String> for InputLine {
+    fn from(text: String) -> Self {
+        let cursor = text.len();
+        Self { text, cursor }
+    }
+}
+
+impl std::ops::Deref for InputLine {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq<&str> for InputLine {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+impl std::fmt::Display for InputLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl AppState {
+    fn handle_hidden_types_popup_key(&mut self, key: KeyEvent) -> bool {
+        let service_types = self.managed_service_types();
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.hidden_types_cursor = self.hidden_types_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.hidden_types_cursor + 1 < service_types.len() {
+                    self.hidden_types_cursor += 1;
+                }
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some(service_type) = service_types.get(self.hidden_types_cursor) {
+                    self.toggle_service_type_hidden(service_type);
+                }
+            }
+            _ => self.show_hidden_types_popup = false,
+        }
+        true
+    }
+
+    fn handle_inventory_popup_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.inventory_cursor = self.inventory_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.inventory_cursor + 1 < self.inventory_checklist().len() {
+                    self.inventory_cursor += 1;
+                }
+            }
+            _ => self.show_inventory_popup = false,
+        }
+        true
+    }
+
+    fn handle_interfaces_popup_key(&mut self, key: KeyEvent) -> bool {
+        let names: Vec<String> = self.interface_addresses().into_keys().collect();
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.interfaces_cursor = self.interfaces_cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.interfaces_cursor + 1 < names.len() {
+                    self.interfaces_cursor += 1;
+                }
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if let Some(name) = names.get(self.interfaces_cursor) {
+                    self.toggle_interface_enabled(name);
+                }
+            }
+            _ => self.show_interfaces_popup = false,
+        }
+        true
+    }
+
+    // Key handling methods
+    pub(super) fn handle_key_event(&mut self, key: KeyEvent) -> bool {
+        self.status_message = None;
+
+        if self.show_help_popup {
+            self.handle_help_popup_key(key)
+        } else if self.show_metrics_popup {
+            self.handle_metrics_popup_key(key)
+        } else if self.show_errors_popup {
+            self.handle_errors_popup_key(key)
+        } else if self.show_bulk_actions_popup {
+            self.handle_bulk_actions_popup_key(key)
+        } else if self.show_actions_popup {
+            self.handle_actions_popup_key(key)
+        } else if self.show_copy_commands_popup {
+            self.handle_copy_commands_popup_key(key)
+        } else if self.show_hidden_types_popup {
+            self.handle_hidden_types_popup_key(key)
+        } else if self.show_interfaces_popup {
+            self.handle_interfaces_popup_key(key)
+        } else if self.show_inventory_popup {
+            self.handle_inventory_popup_key(key)
+        } else if self.pins.show_popup() {
+            self.handle_pins_popup_key(key)
+        } else if self.probes.show_port_scan_popup() {
+            // Any key closes the popup
+            self.probes.close_port_scan_popup();
+            true
+        } else if self.queries.show_host_lookup_popup() {
+            self.queries.close_host_lookup_popup();
+            true
+        } else if self.queries.show_record_query_popup() {
+            self.handle_record_query_popup_key(key)
+        } else if self.filter_input_mode {
+            self.handle_filter_input_key(key)
+        } else if self.search_input_mode {
+            self.handle_search_input_key(key)
+        } else if self.exports.service_path_input().is_some() {
+            self.handle_export_path_input_key(key)
+        } else if self.exports.dialog().is_some() {
+            self.handle_export_dialog_key(key)
+        } else if self.add_type_input.is_some() {
+            self.handle_add_type_input_key(key)
+        } else if self.queries.hostname_input().is_some() {
+            self.handle_hostname_input_key(key)
+        } else if self.queries.question_input().is_some() {
+            self.handle_question_input_key(key)
+        } else {
+            self.handle_normal_mode_key(key)
+        }
+    }
+
+    fn handle_help_popup_key(&mut self, _key: KeyEvent) -> bool {
+        // Any key just closes the help popup and returns to normal mode
+        self.show_help_popup = false;
+        true // Continue running
+    }
+
+    fn handle_errors_popup_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('c') => self.errors.clear(),
+            // Any other key closes the error console
+            _ => self.show_errors_popup = false,
+        }
+        true
+    }
+
+    fn handle_metrics_popup_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('v') => {
+                daemon_log::cycle_level();
+            }
+            KeyCode::Char('f') => self.pending_requests.push(BackgroundRequest::VerifyCache),
+            // Any other key just closes the metrics popup and returns to normal mode
+            _ => self.show_metrics_popup = false,
+        }
+        true // Continue running
+    }
+
+    fn handle_bulk_actions_popup_key(&mut self, key: KeyEvent) -> bool {
+        // Any key closes the popup, action keys run on the marked services first
+        self.show_bulk_actions_popup = false;
+        match key.code {
+            KeyCode::Char('e') => self.export_marked_services(),
+            KeyCode::Char('c') => self.copy_marked_addresses(),
+            KeyCode::Char('d') => self.remove_marked_offline_services(),
+            KeyCode::Char('w') => self.watch_marked_services(true),
+            KeyCode::Char('W') => self.watch_marked_services(false),
+            KeyCode::Char('u') => self.marked_services.clear(),
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_actions_popup_key(&mut self, key: KeyEvent) -> bool {
+        self.show_actions_popup = false;
+        if let KeyCode::Char(ch) = key.code
+            && let Some(number) = ch.to_digit(10)
+            && number > 0
+        {
+            self.run_selected_service_action(number as usize - 1);
+        }
+        true
+    }
+
+    fn handle_copy_commands_popup_key(&mut self, key: KeyEvent) -> bool {
+        self.show_copy_commands_popup = false;
+        if let KeyCode::Char(ch) = key.code
+            && let Some(number) = ch.to_digit(10)
+            && number > 0
+        {
+            self.copy_selected_service_command(number as usize - 1);
+        }
+        true
+    }
+
+    fn handle_filter_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                self.apply_filter();
+                true
+            }
+            KeyCode::Esc => {
+                self.cancel_filter_input();
+                true
+            }
+            KeyCode::Backspace => {
+                self.remove_from_filter();
+                true
+            }
+            KeyCode::Char(ch)
+                if !key
+                    .modifiers
+                    .contains(crossterm::event::KeyModifiers::CONTROL) =>
+            {
+                self.add_to_filter(ch);
+                true
+            }
+            _ => {
+                if edit_input_line(&mut self.filter_query, key) {
+                    // Invalidate cache to trigger real-time filtering
+                    self.invalidate_cache_and_validate();
+                }
+                true
+            }
+        }
+    }
+
+    fn handle_search_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                self.search_input_mode = false;
+                self.search_origin = None;
+            }
+            KeyCode::Esc => self.cancel_search(),
+            _ => {
+                if edit_input_line(&mut self.search_query, key) {
+                    // Incremental search starts over from where the search was started
+                    if let Some(origin) = self.search_origin {
+                        self.selected_service = origin;
+                    }
+                    self.jump_to_search_match(true, true);
+                }
+            }
+        }
+        true
+    }
+
+    fn handle_add_type_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(service_type) = self.add_type_input.take() {
+                    self.add_browsed_service_type(&service_type);
+                }
+            }
+            KeyCode::Esc => self.add_type_input = None,
+            _ => {
+                if let Some(service_type) = &mut self.add_type_input {
+                    edit_input_line(service_type, key);
+                }
+            }
+        }
+        true
+    }
+
+    fn handle_normal_mode_key(&mut self, key: KeyEvent) -> bool {
+        // Vim-style count prefix, e.g. "5j"; a leading '0' is not a count
+        if let KeyCode::Char(ch) = key.code
+            && let Some(digit) = ch.to_digit(10)
+            && (digit != 0 || self.pending_count.is_some())
+        {
+            let count = self.pending_count.unwrap_or(0) * 10 + digit as usize;
+            self.pending_count = Some(count.min(MAX_COUNT_PREFIX));
+            return true;
+        }
+        let explicit_count = self.pending_count.take();
+        let count = explicit_count.unwrap_or(1);
+
+        // Second key of a two-key sequence like "gg", unknown sequences fall through
+        // and the key is handled on its own
+        if let Some(prefix) = self.pending_key.take() {
+            match (prefix, key.code) {
+                ('g', KeyCode::Char('g')) => {
+                    self.navigate_services_to_position(explicit_count.unwrap_or(1));
+                    return true;
+                }
+                ('y', KeyCode::Char('c')) => {
+                    self.open_copy_commands_popup();
+                    return true;
+                }
+                ('y', KeyCode::Char('v')) => {
+                    self.copy_view_snapshot();
+                    return true;
+                }
+                ('y', KeyCode::Char(field)) if self.copy_selected_service_field(field) => {
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        match key.code {
+            // Quit actions
+            KeyCode::Char('q') => {
+                false // Signal to quit
+            }
+            KeyCode::Char('c')
+                if key
+                    .modifiers
+                    .contains(crossterm::event::KeyModifiers::CONTROL) =>
+            {
+                false // Signal to quit
+            }
+
+            // Help toggle
+            KeyCode::Char('?') => {
+                self.toggle_help();
+                true
+            }
+
+            // Metrics toggle
+            KeyCode::Char('m') => {
+                self.toggle_metrics();
+                true
+            }
+
+            KeyCode::Char('!') => {
+                self.toggle_errors();
+                true
+            }
+
+            // Service navigation
+            KeyCode::Char('k') | KeyCode::Up => {
+                for _ in 0..count {
+                    self.navigate_services_up();
+                }
+                true
+            }
+
+            KeyCode::Char('j') | KeyCode::Down => {
+                for _ in 0..count {
+                    self.navigate_services_down();
+                }
+                true
+            }
+
+            KeyCode::Char('h') | KeyCode::Left => {
+                for _ in 0..count {
+                    self.navigate_service_types_up();
+                }
+                true
+            }
+
+            KeyCode::Char('l') | KeyCode::Right => {
+                for _ in 0..count {
+                    self.navigate_service_types_down();
+                }
+                true
+            }
+
+            // Page navigation
+            KeyCode::PageUp | KeyCode::Char('b') => {
+                for _ in 0..count {
+                    self.navigate_services_page_up();
+                }
+                true
+            }
+
+            KeyCode::PageDown | KeyCode::Char('f') | KeyCode::Char(' ') => {
+                for _ in 0..count {
+                    self.navigate_services_page_down();
+                }
+                true
+            }
+
+            KeyCode::Home => {
+                self.navigate_services_to_first();
+                true
+            }
+
+            KeyCode::End => {
+                self.navigate_services_to_last();
+                true
+            }
+
+            KeyCode::Char('g') => {
+                self.pending_key = Some('g');
+                self.pending_count = explicit_count;
+                true
+            }
+
+            KeyCode::Char('y') => {
+                self.pending_key = Some('y');
+                true
+            }
+
+            KeyCode::Char('w') => {
+                self.open_selected_service_in_browser();
+                true
+            }
+
+            KeyCode::Char('L') => {
+                self.ssh_to_selected_service();
+                true
+            }
+
+            KeyCode::Enter => {
+                self.open_actions_popup();
+                true
+            }
+
+            KeyCode::Char('p') => {
+                self.toggle_tcp_probe();
+                true
+            }
+
+            KeyCode::Char('C') => {
+                self.inspect_selected_service_certificates();
+                true
+            }
+
+            KeyCode::Char('R') => {
+                self.reverse_dns_enabled = !self.reverse_dns_enabled;
+                true
+            }
+
+            KeyCode::Char('r') => {
+                self.pending_requests.push(BackgroundRequest::Rescan);
+                true
+            }
+
+            KeyCode::Char('u') => {
+                self.re_resolve_selected_service();
+                true
+            }
+
+            KeyCode::Char('M') => {
+                self.queries.open_hostname_input();
+                true
+            }
+
+            KeyCode::Char('Q') => {
+                self.queries.open_question_input();
+                true
+            }
+
+            KeyCode::Char('E') => {
+                self.open_interfaces_popup();
+                true
+            }
+            KeyCode::Char('H') => {
+                self.show_hidden_types_popup = true;
+                self.hidden_types_cursor = 0;
+                true
+            }
+            KeyCode::Char('a') => {
+                self.add_type_input = Some(InputLine::default());
+                true
+            }
+            KeyCode::Char('J') => {
+                self.start_service_export();
+                true
+            }
+            KeyCode::Char('e') => {
+                self.open_export_dialog();
+                true
+            }
+            KeyCode::Char('Y') => {
+                self.write_view_snapshot();
+                true
+            }
+            KeyCode::Char('X') => {
+                self.open_inventory_popup();
+                true
+            }
+            KeyCode::Char('U') => {
+                self.save_baseline();
+                true
+            }
+            KeyCode::Char('B') => {
+                self.cycle_baseline_filter();
+                true
+            }
+
+            KeyCode::Char('I') => {
+                self.query_selected_device();
+                true
+            }
+
+            KeyCode::Char('T') => {
+                self.txt_decoding_enabled = !self.txt_decoding_enabled;
+                true
+            }
+
+            KeyCode::Char('W') => {
+                self.wake_selected_service_host();
+                true
+            }
+
+            KeyCode::Char('P') => {
+                self.scan_selected_service_ports();
+                true
+            }
+
+            KeyCode::Char('t') => {
+                self.jump_to_selected_service_type();
+                true
+            }
+
+            // Marks and bulk actions
+            KeyCode::Char('v') => {
+                for _ in 0..count {
+                    self.toggle_selected_service_mark();
+                }
+                true
+            }
+
+            KeyCode::Char('V') => {
+                self.toggle_all_visible_service_marks();
+                true
+            }
+
+            KeyCode::Char('*') => {
+                self.toggle_selected_service_pin();
+                true
+            }
+
+            KeyCode::Char('\'') => {
+                self.open_pins_popup();
+                true
+            }
+
+            KeyCode::Char('x') => {
+                self.show_bulk_actions_popup = true;
+                true
+            }
+
+            KeyCode::Char('G') => {
+                match explicit_count {
+                    Some(position) => self.navigate_services_to_position(position),
+                    None => self.navigate_services_to_last(),
+                }
+                true
+            }
+
+            // Sorting
+            KeyCode::Char('s') => {
+                self.cycle_sort_field(true);
+                true
+            }
+
+            KeyCode::Char('S') => {
+                self.cycle_sort_field(false);
+                true
+            }
+
+            KeyCode::Char('o') => {
+                self.toggle_sort_direction();
+                true
+            }
+
+            KeyCode::Char('O') => {
+                self.toggle_online_first();
+                true
+            }
+
+            KeyCode::Char('A') => {
+                self.cycle_preferred_address_family();
+                true
+            }
+            KeyCode::Char('K') => {
+                self.cycle_category_filter();
+                true
+            }
+            KeyCode::Char('Z') => {
+                self.cycle_domain_filter();
+                true
+            }
+            KeyCode::Char('i') => {
+                self.cycle_interface_filter();
+                true
+            }
+            KeyCode::Char('@') => {
+                self.cycle_agent_filter();
+                true
+            }
+
+            // Actions
+            KeyCode::Char('d') => {
+                self.remove_offline_services();
+                true
+            }
+            KeyCode::Char('D') => {
+                self.clear_stale_service_types();
+                true
+            }
+
+            // Filter controls
+            KeyCode::Char('/') => {
+                self.start_filter_input();
+                true
+            }
+
+            KeyCode::Char('\\') => {
+                self.start_new_filter_input();
+                true
+            }
+
+            KeyCode::Char('c') => {
+                self.clear_filter();
+                true
+            }
+
+            // Search controls
+            KeyCode::Char('F') => {
+                self.start_search_input();
+                true
+            }
+
+            KeyCode::Char('n') => {
+                for _ in 0..count {
+                    self.jump_to_search_match(true, false);
+                }
+                true
+            }
+
+            KeyCode::Char('N') => {
+                for _ in 0..count {
+                    self.jump_to_search_match(false, false);
+                }
+                true
+            }
+
+            _ => true,
+        }
+    }
+
+    pub(super) fn navigate_services_up(&mut self) {
+        if self.selected_service > 0 {
+            self.selected_service -= 1;
+            self.update_services_scroll_offset();
+        }
+    }
+
+    pub(super) fn navigate_services_down(&mut self) {
+        let filtered = self.get_filtered_services();
+        let filtered_len = filtered.len();
+        if self.selected_service < filtered_len.saturating_sub(1) {
+            self.selected_service += 1;
+            self.update_services_scroll_offset();
+        }
+    }
+
+    pub(super) fn navigate_service_types_up(&mut self) {
+        let new_type = match self.selected_type {
+            None => None,               // Already at "All Types", can't go further left
+            Some(0) => None,            // Move from first service type to "All Types"
+            Some(idx) => Some(idx - 1), // Move to previous service type
+        };
+
+        if new_type.is_none() {
+            // Moving to "All Types" - ensure it's visible at visual index 0
+            self.types_scroll_offset = 0;
+        } else if let Some(new_idx) = new_type {
+            // Update scroll offset for types list using actual visible count
+            if new_idx < self.types_scroll_offset {
+                self.types_scroll_offset = new_idx;
+            }
+        }
+        self.update_service_type_selection(new_type);
+    }
+
+    pub(super) fn navigate_service_types_down(&mut self) {
+        let new_type = match self.selected_type {
+            None => {
+                // Move from "All Types" to first service type (index 0)
+                if !self.service_types.is_empty() {
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+            Some(idx) if idx < self.service_types.len().saturating_sub(1) => Some(idx + 1),
+            Some(idx) => Some(idx), // Stay at last service type, don't wrap to "All Types"
+        };
+
+        if new_type.is_none() {
+            // Moving to "All Types" - ensure it's visible at visual index 0
+            self.types_scroll_offset = 0;
+        } else if let Some(new_idx) = new_type {
+            // Update scroll offset for types list using actual visible count
+            if self.visible_types > 0 && new_idx >= self.types_scroll_offset + self.visible_types {
+                self.types_scroll_offset = new_idx - self.visible_types + 1;
+            }
+        }
+        self.update_service_type_selection(new_type);
+    }
+
+    pub(super) fn navigate_services_page_up(&mut self) {
+        let scroll_amount = self.visible_services.saturating_sub(1);
+        if self.selected_service >= scroll_amount {
+            self.selected_service -= scroll_amount;
+        } else {
+            self.selected_service = 0;
+        }
+        self.update_services_scroll_offset();
+    }
+
+    pub(super) fn navigate_services_page_down(&mut self) {
+        let filtered = self.get_filtered_services();
+        let filtered_len = filtered.len();
+        let scroll_amount = self.visible_services.saturating_sub(1);
+        if self.selected_service + scroll_amount < filtered_len.saturating_sub(1) {
+            self.selected_service += scroll_amount;
+        } else {
+            self.selected_service = filtered_len.saturating_sub(1);
+        }
+        self.update_services_scroll_offset();
+    }
+
+    pub(super) fn navigate_services_to_first(&mut self) {
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+    }
+
+    // Moves to the 1-based position in the services list, clamped to its bounds
+    fn navigate_services_to_position(&mut self, position: usize) {
+        let filtered_len = self.get_filtered_services().len();
+        self.selected_service = position
+            .saturating_sub(1)
+            .min(filtered_len.saturating_sub(1));
+        self.update_services_scroll_offset();
+    }
+
+    pub(super) fn navigate_services_to_last(&mut self) {
+        let filtered = self.get_filtered_services();
+        let filtered_len = filtered.len();
+        self.selected_service = filtered_len.saturating_sub(1);
+        self.update_services_scroll_offset();
+    }
+
+    // Filter methods
+    pub(super) fn start_filter_input(&mut self) {
+        // Keep the current query so it can be refined, with the cursor at the end
+        self.filter_input_mode = true;
+        self.filter_before_input = self.filter_query.clone();
+        self.filter_query.move_to_end();
+    }
+
+    fn start_new_filter_input(&mut self) {
+        self.filter_input_mode = true;
+        self.filter_before_input = self.filter_query.clone();
+        if !self.filter_query.is_empty() {
+            self.filter_query.clear();
+            self.invalidate_cache_and_validate();
+        }
+    }
+
+    // Leaves the edit with the query it started from
+    pub(super) fn cancel_filter_input(&mut self) {
+        self.filter_input_mode = false;
+        let query = std::mem::take(&mut self.filter_before_input);
+        if query != self.filter_query {
+            self.filter_query = query;
+            self.invalidate_cache_and_validate();
+        }
+    }
+
+    pub(super) fn clear_filter(&mut self) {
+        let had_filter = !self.filter_query.is_empty();
+        self.filter_query.clear();
+        self.filter_input_mode = false;
+        // Only reset selection and scroll when there was actually a filter
+        if had_filter {
+            self.selected_service = 0;
+            self.services_scroll_offset = 0;
+        }
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn apply_filter(&mut self) {
+        self.filter_input_mode = false;
+        // Reset selection and scroll when exiting filter mode
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn add_to_filter(&mut self, ch: char) {
+        self.filter_query.insert(ch);
+        // Invalidate cache to trigger real-time filtering
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn remove_from_filter(&mut self) {
+        self.filter_query.backspace();
+        // Invalidate cache to trigger real-time filtering
+        self.invalidate_cache_and_validate();
+    }
+
+    // Search methods
+    fn start_search_input(&mut self) {
+        self.search_input_mode = true;
+        self.search_origin = Some(self.selected_service);
+        self.search_query.clear();
+    }
+
+    fn cancel_search(&mut self) {
+        self.search_input_mode = false;
+        self.search_query.clear();
+        if let Some(origin) = self.search_origin.take() {
+            self.selected_service = origin;
+            self.update_services_scroll_offset();
+        }
+    }
+
+    // Moves the selection to the next (or previous) service matching the search query,
+    // wrapping around the list. Nothing is hidden, unlike the quick filter.
+    pub(super) fn jump_to_search_match(&mut self, forward: bool, include_current: bool) -> bool {
+        if self.search_query.is_empty() {
+            return false;
+        }
+        let query = self.search_query.to_lowercase();
+        let start = self.selected_service;
+        let filtered = self.get_filtered_services().to_vec();
+        let len = filtered.len();
+        if len == 0 {
+            return false;
+        }
+
+        let first_step = if include_current { 0 } else { 1 };
+        let found = (first_step..len + first_step)
+            .map(|step| {
+                if forward {
+                    (start + step) % len
+                } else {
+                    (start + len * 2 - step) % len
+                }
+            })
+            .find(|&pos| service_matches_query(&self.services[filtered[pos]], &query));
+
+        if let Some(pos) = found {
+            self.selected_service = pos;
+            self.update_services_scroll_offset();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Applies the cursor movement and editing keys shared by all text prompts,
+// returns whether the text changed
+pub(super) fn edit_input_line(line: &mut InputLine, key: KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Backspace => line.backspace(),
+        KeyCode::Delete => line.delete(),
+        KeyCode::Left => {
+            line.move_left();
+            false
+        }
+        KeyCode::Right => {
+            line.move_right();
+            false
+        }
+        KeyCode::Home => {
+            line.move_to_start();
+            false
+        }
+        KeyCode::End => {
+            line.move_to_end();
+            false
+        }
+        // Readline-style editing shortcuts
+        KeyCode::Char(ch)
+            if key
+                .modifiers
+                .contains(crossterm::event::KeyModifiers::CONTROL) =>
+        {
+            match ch {
+                'w' => line.delete_word_before_cursor(),
+                'u' => line.delete_to_start(),
+                'a' => {
+                    line.move_to_start();
+                    false
+                }
+                'e' => {
+                    line.move_to_end();
+                    false
+                }
+                _ => false,
+            }
+        }
+        KeyCode::Char(ch) => {
+            line.insert(ch);
+            true
+        }
+        _ => false,
+    }
+}
+
+// Runs a command detached from the terminal without waiting for it
+pub(super) fn spawn_background_command(command: &ExternalCommand) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    Command::new(&command.program)
+        .args(&command.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(reap_when_done)
+}
+
+// Launches the platform's default handler for the URL without waiting for it
+pub(super) fn open_url(url: &str) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+
+    // Keep the child's output from messing up the TUI
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(reap_when_done)
+}
+
+// Waits for a detached child on a thread of its own, so it doesn't linger as a
+// zombie once it exits; the thread ends with it
+fn reap_when_done(mut child: std::process::Child) {
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
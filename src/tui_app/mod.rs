@@ -0,0 +1,686 @@
+#![forbid(unsafe_code)]
+
+use crossterm::{
+    event::{Event, KeyCode, KeyEvent},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+};
+use mdns_sd::{ResolvedService, ServiceDaemon, ServiceEvent};
+use ratatui::{
+    Frame, Terminal,
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+use crate::backend::{BackendKind, ConfiguredBackend, DiscoveryBackend, ServiceEvents};
+use crate::baseline::{Baseline, BaselineService, BaselineStatus};
+#[cfg(feature = "bonjour")]
+use crate::bonjour::BonjourBackend;
+use crate::browse_manager::{self, BrowseManager, Retry};
+use crate::cast::{self, CastDeviceInfo};
+use crate::config::{ActionConfig, Config, CopyCommandConfig, LimitsConfig};
+use crate::daemon_log;
+use crate::data_file;
+use crate::desktop_notify;
+use crate::device_category::{self, DeviceCategory};
+use crate::discovery_events::{self, DiscoveryEvent, EventKind};
+use crate::error::{self, Error};
+use crate::event_log;
+use crate::export::{self, ExportFormat};
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::home_assistant;
+use crate::http_probe::{self, CertificateInfo};
+use crate::interfaces::{self, LocalInterface};
+use crate::inventory::{CheckStatus, ChecklistEntry, Inventory, ObservedService};
+use crate::ipp::{self, PrinterStatus};
+use crate::mdns_query;
+#[cfg(any(test, feature = "mock-discovery"))]
+use crate::mock_discovery::MockDaemon;
+use crate::mqtt;
+use crate::parsable_output::{self, OutputFormat};
+use crate::pins::{PinStore, Sighting};
+use crate::remote::RemoteAgent;
+use crate::report;
+use crate::reverse_dns::{self, ReverseResolver};
+use crate::runtime;
+use crate::schema::SchemaVersion;
+use crate::server;
+use crate::service_history::{self, HistoryEntry};
+use crate::service_names;
+use crate::session::{self, RecordedService, Replay, SessionEvent};
+use crate::txt_decode;
+use crate::txt_schema;
+use crate::unicast_dns_sd::{self, UnicastBrowser, UnicastService};
+use crate::wake_on_lan::{self, MacAddress, MacStore};
+use hickory_resolver::proto::rr::{RData, RecordType};
+
+// Hooks into the service list for the benchmarks
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+// The discovery without the TUI, for embedding
+mod browser;
+// Browsing, probing and the other background tasks feeding the state
+mod discovery;
+// The prompts exporting services to files
+mod export_dialog;
+// Text of services, addresses and query results, free of the state
+mod format;
+// Key handling and the prompts it edits
+mod input;
+// The pinned services and the popup listing them
+mod pinned;
+// The probes of services and hosts started from the list and their results
+mod probes;
+// The hostname lookups and record questions typed in
+mod queries;
+// The view settings kept from one session to the next
+mod saved_view;
+// The services and types found, the view settings and how discoveries change them
+mod state;
+// The filtered services in list order
+mod sorted_list;
+// The services found, indexed by fullname
+mod store;
+// The background tasks of a session and their shutdown
+mod tasks;
+// The terminal the event loop draws on and reads its input from
+mod terminal;
+#[cfg(test)]
+mod tests;
+// Drawing of the lists, details and popups
+mod ui;
+
+pub use browser::Browser;
+pub use discovery::DiscoverySource;
+use discovery::*;
+use export_dialog::*;
+use format::*;
+use input::*;
+use pinned::*;
+use probes::*;
+use queries::*;
+use saved_view::*;
+use sorted_list::*;
+pub use state::ServiceEntry;
+use state::*;
+use store::*;
+use tasks::*;
+use terminal::*;
+use ui::*;
+
+const MAX_COUNT_PREFIX: usize = 9999;
+const TCP_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+const TCP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const HTTP_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+// Time between starting two port probes, keeps scans from flooding small devices
+const PORT_PROBE_SPACING: Duration = Duration::from_millis(5);
+const PORT_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+// Unicast DNS has no announcements, so wide-area domains are polled
+const UNICAST_BROWSE_INTERVAL: Duration = Duration::from_secs(60);
+// How long responders get to answer a re-resolve over mDNS
+const RE_RESOLVE_TIMEOUT: Duration = Duration::from_secs(2);
+// Wait before reconnecting to a remote agent that went away
+const REMOTE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+// MACs are looked up and saved in batches at most this often
+const MAC_LEARN_INTERVAL: Duration = Duration::from_secs(5);
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+// Top bit of the class of mDNS records, set for unique records
+const MDNS_CACHE_FLUSH: u16 = 0x8000;
+// How long two hosts announcing the same instance count as a conflict, an SRV TTL
+const CONFLICT_WINDOW: Duration = Duration::from_secs(120);
+// TXT changes kept per service, oldest ones are dropped first
+const MAX_TXT_HISTORY: usize = 20;
+// Daemon log lines shown below the metrics
+const DAEMON_LOG_LINES: usize = 10;
+// How long expired records keep being shown in the details
+const OBSERVED_RECORD_RETENTION: Duration = Duration::from_secs(60);
+// Updates of the browses waiting for the aggregation task, browses wait while it is full
+const DISCOVERY_QUEUE_CAPACITY: usize = 1024;
+// Updates the aggregation task applies under one write lock
+const MAX_UPDATE_BATCH: usize = 256;
+const _: () = assert!(discovery_events::EVENT_CAPACITY > MAX_UPDATE_BATCH);
+// Services kept at most, new ones beyond are dropped so a flooding network
+// can't exhaust the memory
+const MAX_SERVICES: usize = 10_000;
+// Runtime errors kept for the error console, oldest ones are dropped first
+const MAX_ERRORS: usize = 100;
+// How long shutting down waits for the background tasks to end
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+#[derive(Debug, Clone)]
+enum Notification {
+    ServiceChanged,
+    MetricsUpdated,
+}
+
+// A pending notification covers any number of changes, so senders use try_send
+// and a burst of changes costs a single redraw
+fn notification_channel() -> (flume::Sender<Notification>, flume::Receiver<Notification>) {
+    flume::bounded(1)
+}
+
+fn current_timestamp_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+// Shows the TUI, discovering services on the network or, given a replay, from
+// a recorded session
+pub async fn run_tui(config: Config, source: DiscoverySource) -> Result<(), Error> {
+    // Initialize app state
+    let mut app_state = AppState::new();
+    app_state.config = config;
+    app_state.mac_store = MacStore::load_default();
+    app_state.pins = PinState::new(PinStore::load_default());
+    app_state.baseline = Baseline::load_default();
+    app_state.local_interfaces = interfaces::local_interfaces();
+    app_state.load_inventory();
+    if !app_state.config.fresh_view
+        && let Some(path) = SavedView::default_path()
+    {
+        app_state.restore_view(SavedView::load(&path));
+    }
+    if app_state.config.history
+        && matches!(source, DiscoverySource::Network)
+        && let Some(path) = service_history::default_path()
+    {
+        app_state.restore_history(service_history::load(&path));
+    }
+    if matches!(source, DiscoverySource::Network) {
+        app_state.restore_baseline();
+    }
+
+    // The mock daemon replaces the mDNS one, also for the user's actions
+    #[cfg(feature = "mock-discovery")]
+    if let DiscoverySource::Mock(script) = &source {
+        let mock = MockDaemon::new();
+        let player = mock.clone();
+        let script = script.clone();
+        tokio::spawn(async move { player.play(script).await });
+        return run_tui_on(mock, app_state, source).await;
+    }
+    let mdns = start_backend(&mut app_state).await?;
+    run_tui_on(mdns, app_state, source).await
+}
+
+async fn run_tui_on<B: DiscoveryBackend>(
+    mdns: B,
+    mut app_state: AppState,
+    source: DiscoverySource,
+) -> Result<(), Error> {
+    // Setup terminal for full TUI
+    let mut terminal = CrosstermTerminal::enter().map_err(Error::Terminal)?;
+    // Replayed and remote services are not this network's
+    let on_network = matches!(source, DiscoverySource::Network);
+    let keeps_history = app_state.config.history && on_network;
+    app_state.on_network = on_network;
+
+    let state = Arc::new(RwLock::new(app_state));
+
+    // Create notification channels
+    let (notification_sender, notification_receiver) = notification_channel();
+    let tasks = BackgroundTasks::default();
+
+    let mdns_for_metrics = mdns.clone();
+
+    // Other sources only keep the daemon for the queries of the user's actions
+    if matches!(source, DiscoverySource::Network) {
+        spawn_record_monitor(&tasks, Arc::clone(&state), notification_sender.clone());
+    }
+    spawn_event_outputs(&tasks, Arc::clone(&state), notification_sender.clone()).await;
+
+    // Start background task to periodically collect the backend's metrics
+    let state_for_metrics = Arc::clone(&state);
+    let notification_sender_for_metrics = notification_sender.clone();
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+
+            let daemon_metrics = mdns_for_metrics.metrics().await;
+            let mut state = state_for_metrics.write().await;
+            if state.update_daemon_metrics(&daemon_metrics) {
+                // Metrics changed, trigger UI refresh
+                let _ = notification_sender_for_metrics.try_send(Notification::MetricsUpdated);
+            }
+        }
+    });
+
+    // Start background task to remove services offline for longer than the grace period
+    let state_for_expiry = Arc::clone(&state);
+    let notification_sender_for_expiry = notification_sender.clone();
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+
+            if state_for_expiry
+                .write()
+                .await
+                .expire_offline_services(current_timestamp_micros())
+            {
+                let _ = notification_sender_for_expiry.try_send(Notification::ServiceChanged);
+            }
+        }
+    });
+
+    // Start background task to resolve new addresses back to names while enabled
+    let state_for_reverse_dns = Arc::clone(&state);
+    let notification_sender_for_reverse_dns = notification_sender.clone();
+    let lookup_tasks = tasks.clone();
+    tasks.spawn(async move {
+        let resolver = Arc::new(ReverseResolver::new());
+        let permits = LimitsConfig::permits(
+            state_for_reverse_dns
+                .read()
+                .await
+                .config
+                .limits
+                .max_reverse_lookups,
+        );
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(permits));
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+
+            let addresses = state_for_reverse_dns
+                .write()
+                .await
+                .take_reverse_dns_lookups();
+            for address in addresses {
+                let resolver = Arc::clone(&resolver);
+                let semaphore = Arc::clone(&semaphore);
+                let state = Arc::clone(&state_for_reverse_dns);
+                let notification_sender = notification_sender_for_reverse_dns.clone();
+                lookup_tasks.spawn(async move {
+                    let Ok(_permit) = semaphore.acquire().await else {
+                        return;
+                    };
+                    // Scoped IPv6 addresses carry their zone id after a '%'
+                    let names = match address.split('%').next().unwrap_or_default().parse() {
+                        Ok(ip) => resolver.lookup(ip).await,
+                        Err(_) => Vec::new(),
+                    };
+                    state.write().await.reverse_dns.insert(address, Some(names));
+                    let _ = notification_sender.try_send(Notification::ServiceChanged);
+                });
+            }
+        }
+    });
+
+    // Start background task to look the MACs of new hosts up in the neighbour
+    // table and save the learned ones, both blocking and kept off the state lock
+    let state_for_macs = Arc::clone(&state);
+    let notification_sender_for_macs = notification_sender.clone();
+    tasks.spawn(async move {
+        let mut interval = tokio::time::interval(MAC_LEARN_INTERVAL);
+        loop {
+            interval.tick().await;
+
+            let lookups = state_for_macs.write().await.take_mac_lookups();
+            if !lookups.is_empty() {
+                let found = tokio::task::spawn_blocking(move || {
+                    let table = wake_on_lan::read_arp_table();
+                    lookups
+                        .into_iter()
+                        .filter_map(|(host, addrs)| {
+                            let mac = addrs
+                                .iter()
+                                .find_map(|addr| wake_on_lan::mac_from_arp_table(&table, addr))?;
+                            Some((host, mac))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await
+                .unwrap_or_default();
+                if !found.is_empty() {
+                    state_for_macs.write().await.learn_mac_addresses(found);
+                    let _ = notification_sender_for_macs.try_send(Notification::ServiceChanged);
+                }
+            }
+
+            let unsaved = state_for_macs.write().await.take_unsaved_macs();
+            if let Some(macs) = unsaved {
+                // Losing a MAC is not worth interrupting the user for
+                let _ = tokio::task::spawn_blocking(move || macs.save()).await;
+            }
+        }
+    });
+
+    // Start background task to probe reachability and latency of the services while enabled
+    let state_for_probe = Arc::clone(&state);
+    let notification_sender_for_probe = notification_sender.clone();
+    let probe_tasks = tasks.clone();
+    tasks.spawn(async move {
+        // Shared by all rounds, the probes of slow hosts may outlast theirs
+        let permits =
+            LimitsConfig::permits(state_for_probe.read().await.config.limits.max_tcp_probes);
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(permits));
+        // Held by the probes of a round, a round is skipped while the last one runs
+        let in_flight = Arc::new(());
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        let mut last_probe: Option<std::time::Instant> = None;
+        loop {
+            interval.tick().await;
+
+            let targets = {
+                let state = state_for_probe.read().await;
+                if !state.probes.tcp_enabled() {
+                    // Probe right away once enabled again
+                    last_probe = None;
+                    continue;
+                }
+                if last_probe.is_some_and(|at| at.elapsed() < TCP_PROBE_INTERVAL)
+                    || Arc::strong_count(&in_flight) > 1
+                {
+                    continue;
+                }
+                state.probe_targets()
+            };
+            last_probe = Some(std::time::Instant::now());
+
+            for target in targets {
+                let semaphore = Arc::clone(&semaphore);
+                let round = Arc::clone(&in_flight);
+                let state = Arc::clone(&state_for_probe);
+                let notification_sender = notification_sender_for_probe.clone();
+                probe_tasks.spawn(async move {
+                    let _round = round;
+                    let Ok(_permit) = semaphore.acquire().await else {
+                        return;
+                    };
+                    let probe = probe_service(&target).await;
+                    if state.write().await.update_probe_result(
+                        target.agent.as_deref(),
+                        &target.fullname,
+                        probe,
+                    ) {
+                        let _ = notification_sender.try_send(Notification::ServiceChanged);
+                    }
+                });
+            }
+        }
+    });
+
+    match source {
+        DiscoverySource::Network => {
+            start_browsing(&tasks, mdns.clone(), &state, &notification_sender).await
+        }
+        DiscoverySource::Replay(replay) => {
+            state.write().await.status_message = Some(format!(
+                "Replaying {} events at {}x",
+                replay.events.len(),
+                replay.speed
+            ));
+            spawn_replay(
+                &tasks,
+                replay,
+                Arc::clone(&state),
+                notification_sender.clone(),
+            );
+        }
+        DiscoverySource::Remote(agents) => {
+            {
+                let mut state = state.write().await;
+                state.agents = agents.iter().map(RemoteAgent::label).collect();
+                let merge_agents = state.config.merge_agents;
+                state.services.set_merge_agents(merge_agents);
+                state.status_message = Some(format!("Connecting to {}", state.agents.join(", ")));
+            }
+            for agent in agents {
+                spawn_remote(
+                    &tasks,
+                    agent,
+                    Arc::clone(&state),
+                    notification_sender.clone(),
+                );
+            }
+        }
+        #[cfg(feature = "mock-discovery")]
+        DiscoverySource::Mock(_) => {
+            // Played on the backend by run_tui
+            start_browsing(&tasks, mdns.clone(), &state, &notification_sender).await
+        }
+    }
+
+    let result = run_event_loop(
+        &mut terminal,
+        &mdns,
+        &state,
+        &tasks,
+        (&notification_sender, &notification_receiver),
+    )
+    .await;
+    // Restored even after a failure, so the error is readable once printed
+    let left = terminal.leave().map_err(Error::Terminal);
+    // Replays and remote sessions view other networks than the one restored
+    if on_network
+        && let Some(path) = SavedView::default_path()
+        && let Err(e) = state.read().await.saved_view().save(&path)
+    {
+        eprintln!("Failed to save the view to {}: {}", path.display(), e);
+    }
+    if keeps_history
+        && let Some(path) = service_history::default_path()
+        && let Err(e) = service_history::save(&path, state.read().await.history_entries())
+    {
+        eprintln!("Failed to save the history to {}: {}", path.display(), e);
+    }
+    if let Some(macs) = state.write().await.take_unsaved_macs() {
+        let _ = macs.save();
+    }
+    if on_network && let Err(e) = state.write().await.save_pins() {
+        eprintln!("Failed to save the pins: {}", e);
+    }
+
+    shut_down(&mdns, &tasks).await;
+    for text in std::mem::take(&mut state.write().await.stdout_on_exit) {
+        println!("{}", text);
+    }
+
+    result.and(left)
+}
+
+// Prints discovery events as lines of the given format instead of showing the
+// TUI, until interrupted; status messages go to stderr
+pub async fn run_headless(config: Config, format: OutputFormat) -> Result<(), Error> {
+    let mut app_state = AppState::new();
+    app_state.config = config;
+    app_state.local_interfaces = interfaces::local_interfaces();
+    let local_interfaces = app_state.local_interfaces.clone();
+
+    let mdns = start_backend(&mut app_state).await?;
+    // Subscribe before browsing so no service is missed
+    let mut events = app_state.discovery_events.subscribe();
+    let state = Arc::new(RwLock::new(app_state));
+    let (notification_sender, notification_receiver) = notification_channel();
+
+    let tasks = BackgroundTasks::default();
+    spawn_event_outputs(&tasks, Arc::clone(&state), notification_sender.clone()).await;
+    start_browsing(&tasks, mdns.clone(), &state, &notification_sender).await;
+
+    let mut stdout = std::io::stdout();
+    for line in parsable_output::header(format, chrono::Local::now()) {
+        writeln!(stdout, "{}", line).map_err(Error::Output)?;
+    }
+    let result = loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                        eprintln!("Missed {} events", missed);
+                        continue;
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break Ok(()),
+                };
+                let lines = parsable_output::format_event(format, &event, &local_interfaces);
+                if let Err(e) = lines
+                    .iter()
+                    .try_for_each(|line| writeln!(stdout, "{}", line))
+                    .and_then(|_| stdout.flush())
+                {
+                    // A closed pipe ends the output, like with other line tools
+                    break if e.kind() == std::io::ErrorKind::BrokenPipe {
+                        Ok(())
+                    } else {
+                        Err(Error::Output(e))
+                    };
+                }
+            }
+            _ = notification_receiver.recv_async() => {
+                if let Some(message) = state.write().await.status_message.take() {
+                    eprintln!("{}", message);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    shut_down(&mdns, &tasks).await;
+    result
+}
+
+impl server::Catalog for Arc<RwLock<AppState>> {
+    async fn services(&self) -> serde_json::Value {
+        serde_json::to_value(&self.read().await.services).unwrap_or_default()
+    }
+
+    async fn service_types(&self) -> Vec<String> {
+        self.read().await.service_types.clone()
+    }
+}
+
+// Keeps discovering and serves the services over HTTP until interrupted,
+// status messages go to stderr
+pub async fn run_serve(
+    config: Config,
+    listen: std::net::SocketAddr,
+    grpc_listen: Option<std::net::SocketAddr>,
+    cors_origin: Option<String>,
+) -> Result<(), Error> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .map_err(|source| Error::Listen {
+            address: listen,
+            source,
+        })?;
+    #[cfg(not(feature = "grpc"))]
+    if grpc_listen.is_some() {
+        return Err(Error::Unsupported(
+            "gRPC needs a build with --features grpc",
+        ));
+    }
+    #[cfg(feature = "grpc")]
+    let grpc_listener = match grpc_listen {
+        Some(address) => Some((
+            address,
+            tokio::net::TcpListener::bind(address)
+                .await
+                .map_err(|source| Error::Listen { address, source })?,
+        )),
+        None => None,
+    };
+    let mut app_state = AppState::new();
+    app_state.config = config;
+    app_state.local_interfaces = interfaces::local_interfaces();
+
+    let mdns = start_backend(&mut app_state).await?;
+    let events = app_state.discovery_events.subscribe();
+    let state = Arc::new(RwLock::new(app_state));
+    let (notification_sender, notification_receiver) = notification_channel();
+
+    let tasks = BackgroundTasks::default();
+    spawn_event_outputs(&tasks, Arc::clone(&state), notification_sender.clone()).await;
+    start_browsing(&tasks, mdns.clone(), &state, &notification_sender).await;
+    eprintln!(
+        "Serving the API on http://{}",
+        listen_address(&listener, listen)?
+    );
+    #[cfg(feature = "grpc")]
+    if let Some((address, grpc_listener)) = grpc_listener {
+        eprintln!(
+            "Serving gRPC on {}",
+            listen_address(&grpc_listener, address)?
+        );
+        let events = state.read().await.discovery_events.clone();
+        let catalog = Arc::clone(&state);
+        tasks.spawn(async move {
+            if let Err(e) = grpc::serve(grpc_listener, catalog, events).await {
+                eprintln!("gRPC server failed: {}", e);
+            }
+        });
+    }
+
+    let server = server::serve(listener, Arc::clone(&state), events, cors_origin);
+    tokio::pin!(server);
+    let mut expiry = tokio::time::interval(Duration::from_secs(1));
+    let result = loop {
+        tokio::select! {
+            // Serves until interrupted, failed accepts are retried
+            () = &mut server => break Ok(()),
+            _ = expiry.tick() => {
+                state.write().await.expire_offline_services(current_timestamp_micros());
+            }
+            notification = notification_receiver.recv_async() => {
+                if notification.is_ok()
+                    && let Some(message) = state.write().await.status_message.take()
+                {
+                    eprintln!("{}", message);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+    shut_down(&mdns, &tasks).await;
+    result
+}
+
+// Browses for the given time or until interrupted, then prints a report of the
+// services found
+pub async fn run_report(
+    config: Config,
+    format: report::ReportFormat,
+    grouping: report::ReportGrouping,
+    duration: Duration,
+) -> Result<(), Error> {
+    let browser = Browser::start(config).await?;
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    let services = browser.services().await;
+    browser.shutdown().await;
+
+    let report =
+        report::render(format, grouping, &services, chrono::Local::now()).map_err(Error::Report)?;
+    let mut stdout = std::io::stdout();
+    stdout
+        .write_all(report.as_bytes())
+        .and_then(|()| stdout.flush())
+        .map_err(Error::Output)
+}
+
+// The bound address, the port the system picked for port 0 included
+fn listen_address(
+    listener: &tokio::net::TcpListener,
+    address: std::net::SocketAddr,
+) -> Result<std::net::SocketAddr, Error> {
+    listener
+        .local_addr()
+        .map_err(|source| Error::Listen { address, source })
+}
@@ -0,0 +1,192 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// The services pinned to the top of the list and the popup listing them
+pub(super) struct PinState {
+    store: PinStore,
+    show_popup: bool,
+    cursor: usize,
+    // Note being written for the pin under the cursor of the popup
+    note_input: Option<InputLine>,
+}
+
+impl PinState {
+    pub(super) fn new(store: PinStore) -> Self {
+        Self {
+            store,
+            show_popup: false,
+            cursor: 0,
+            note_input: None,
+        }
+    }
+
+    pub(super) fn store(&self) -> &PinStore {
+        &self.store
+    }
+
+    pub(super) fn show_popup(&self) -> bool {
+        self.show_popup
+    }
+
+    // Index of the pin under the cursor in the order of the popup
+    pub(super) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub(super) fn note_input(&self) -> Option<&InputLine> {
+        self.note_input.as_ref()
+    }
+
+    // The popup lists the pins in the order of the store
+    fn fullname_at_cursor(&self) -> Option<String> {
+        self.store
+            .iter()
+            .nth(self.cursor)
+            .map(|(fullname, _)| fullname.clone())
+    }
+}
+
+impl AppState {
+    // Pins or unpins the selected service, which stays selected wherever it moves
+    pub(super) fn toggle_selected_service_pin(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let fullname = self.services[service_idx].fullname.clone();
+        let pinned = self
+            .pins
+            .store
+            .toggle(&fullname, current_timestamp_micros());
+        self.status_message = Some(match self.save_pins() {
+            Err(e) => format!("Failed to save the pins: {}", e),
+            Ok(()) if pinned => format!("Pinned {}", fullname),
+            Ok(()) => format!("Unpinned {}", fullname),
+        });
+        self.invalidate_cache_and_validate();
+        if !self.select_service_by_fullname(&fullname) {
+            // Unpinned and filtered out, the next one takes its place
+            let len = self.get_filtered_services().len();
+            self.selected_service = self.selected_service.min(len.saturating_sub(1));
+            self.update_services_scroll_offset();
+        }
+    }
+
+    // Keeps how the pinned services were last listed with the pins, so they can
+    // be told about while they are not; only the local network's count
+    pub(super) fn save_pins(&mut self) -> Result<(), String> {
+        for service in self.services.iter() {
+            if self.on_network
+                && self.pins.store.contains(&service.fullname)
+                && !self.remembered_services.contains(&service.fullname)
+            {
+                self.pins.store.note_sighting(
+                    &service.fullname,
+                    Sighting {
+                        host: service.host.to_string(),
+                        port: service.port,
+                        online: service.online,
+                        last_seen_micros: service.last_seen_micros,
+                    },
+                );
+            }
+        }
+        self.pins.store.save()
+    }
+
+    pub(super) fn open_pins_popup(&mut self) {
+        if self.pins.store.is_empty() {
+            self.status_message = Some("No pinned services, pin one with *".to_string());
+            return;
+        }
+        self.pins.show_popup = true;
+        self.pins.cursor = 0;
+    }
+
+    pub(super) fn handle_pins_popup_key(&mut self, key: KeyEvent) -> bool {
+        if self.pins.note_input.is_some() {
+            match key.code {
+                KeyCode::Enter => {
+                    if let Some(note) = self.pins.note_input.take() {
+                        self.set_pin_note_at_cursor(&note);
+                    }
+                }
+                KeyCode::Esc => self.pins.note_input = None,
+                _ => {
+                    if let Some(note) = &mut self.pins.note_input {
+                        edit_input_line(note, key);
+                    }
+                }
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.pins.cursor = self.pins.cursor.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.pins.cursor + 1 < self.pins.store.len() {
+                    self.pins.cursor += 1;
+                }
+            }
+            KeyCode::Enter => self.jump_to_pin(),
+            KeyCode::Char('d') => self.unpin_at_cursor(),
+            KeyCode::Char('n') => self.start_pin_note_input(),
+            _ => self.pins.show_popup = false,
+        }
+        true
+    }
+
+    // Closes the pins popup and selects the pinned service under its cursor
+    fn jump_to_pin(&mut self) {
+        self.pins.show_popup = false;
+        let Some(fullname) = self.pins.fullname_at_cursor() else {
+            return;
+        };
+        if !self.select_service_by_fullname(&fullname) {
+            self.status_message = Some(format!("{} has not been seen this session", fullname));
+        }
+    }
+
+    fn unpin_at_cursor(&mut self) {
+        let Some(fullname) = self.pins.fullname_at_cursor() else {
+            return;
+        };
+        self.pins.store.remove(&fullname);
+        self.status_message = Some(match self.save_pins() {
+            Ok(()) => format!("Unpinned {}", fullname),
+            Err(e) => format!("Failed to save the pins: {}", e),
+        });
+        self.invalidate_cache_and_validate();
+        self.pins.cursor = self
+            .pins
+            .cursor
+            .min(self.pins.store.len().saturating_sub(1));
+        if self.pins.store.is_empty() {
+            self.pins.show_popup = false;
+        }
+    }
+
+    fn start_pin_note_input(&mut self) {
+        let Some(fullname) = self.pins.fullname_at_cursor() else {
+            return;
+        };
+        let note = self
+            .pins
+            .store
+            .get(&fullname)
+            .map(|pin| pin.note.clone())
+            .unwrap_or_default();
+        self.pins.note_input = Some(InputLine::from(note));
+    }
+
+    fn set_pin_note_at_cursor(&mut self, note: &str) {
+        let Some(fullname) = self.pins.fullname_at_cursor() else {
+            return;
+        };
+        self.pins.store.set_note(&fullname, note);
+        if let Err(e) = self.save_pins() {
+            self.status_message = Some(format!("Failed to save the pins: {}", e));
+        }
+    }
+}
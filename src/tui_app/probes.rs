@@ -0,0 +1,285 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// The probes of services and their hosts: the periodic TCP probe and the
+// inspections, device queries and port scans started from the list
+#[derive(Debug, Default)]
+pub(super) struct ProbeState {
+    tcp_enabled: bool,
+    // TLS certificate chains by service fullname, None while the inspection runs
+    certificate_inspections: HashMap<String, Option<Result<Vec<CertificateInfo>, String>>>,
+    // Printer attributes by service fullname, None while the query runs
+    printer_queries: HashMap<String, Option<Result<PrinterStatus, String>>>,
+    // Cast device info by service fullname, None while the query runs
+    cast_queries: HashMap<String, Option<Result<CastDeviceInfo, String>>>,
+    port_scan: Option<PortScan>,
+    show_port_scan_popup: bool,
+}
+
+// Progress and results of the last port scan
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct PortScan {
+    pub(super) scan_id: u64,
+    pub(super) host: String,
+    pub(super) address: String,
+    pub(super) total: usize,
+    pub(super) scanned: usize,
+    pub(super) open_ports: Vec<u16>,
+}
+
+impl ProbeState {
+    pub(super) fn tcp_enabled(&self) -> bool {
+        self.tcp_enabled
+    }
+
+    // Outer None if never inspected, inner None while the inspection runs
+    pub(super) fn certificate_inspection(
+        &self,
+        fullname: &str,
+    ) -> Option<Option<&Result<Vec<CertificateInfo>, String>>> {
+        self.certificate_inspections
+            .get(fullname)
+            .map(Option::as_ref)
+    }
+
+    pub(super) fn set_certificate_inspection(
+        &mut self,
+        fullname: String,
+        result: Result<Vec<CertificateInfo>, String>,
+    ) {
+        self.certificate_inspections.insert(fullname, Some(result));
+    }
+
+    pub(super) fn printer_query(
+        &self,
+        fullname: &str,
+    ) -> Option<Option<&Result<PrinterStatus, String>>> {
+        self.printer_queries.get(fullname).map(Option::as_ref)
+    }
+
+    pub(super) fn set_printer_query(
+        &mut self,
+        fullname: String,
+        result: Result<PrinterStatus, String>,
+    ) {
+        self.printer_queries.insert(fullname, Some(result));
+    }
+
+    pub(super) fn cast_query(
+        &self,
+        fullname: &str,
+    ) -> Option<Option<&Result<CastDeviceInfo, String>>> {
+        self.cast_queries.get(fullname).map(Option::as_ref)
+    }
+
+    pub(super) fn set_cast_query(
+        &mut self,
+        fullname: String,
+        result: Result<CastDeviceInfo, String>,
+    ) {
+        self.cast_queries.insert(fullname, Some(result));
+    }
+
+    pub(super) fn port_scan(&self) -> Option<&PortScan> {
+        self.port_scan.as_ref()
+    }
+
+    pub(super) fn show_port_scan_popup(&self) -> bool {
+        self.show_port_scan_popup
+    }
+
+    // The scan carries on in the background
+    pub(super) fn close_port_scan_popup(&mut self) {
+        self.show_port_scan_popup = false;
+    }
+
+    pub(super) fn update_port_scan(&mut self, scan_id: u64, port: u16, open: bool) {
+        let Some(scan) = &mut self.port_scan else {
+            return;
+        };
+        // Results of a scan that got replaced meanwhile
+        if scan.scan_id != scan_id {
+            return;
+        }
+        scan.scanned += 1;
+        if open {
+            scan.open_ports.push(port);
+            scan.open_ports.sort_unstable();
+        }
+    }
+}
+
+impl AppState {
+    pub(super) fn toggle_tcp_probe(&mut self) {
+        self.probes.tcp_enabled = !self.probes.tcp_enabled;
+        if !self.probes.tcp_enabled {
+            // Results go stale quickly, don't show them once probing stopped
+            self.services
+                .update_all(|service| service.probe = ProbeResult::default());
+        }
+    }
+
+    pub(super) fn probe_targets(&self) -> Vec<ProbeTarget> {
+        self.services
+            .iter()
+            .filter(|s| s.online)
+            .filter_map(|s| {
+                let address = preferred_address(s, self.address_preference())?;
+                Some(ProbeTarget {
+                    agent: s.agent.clone(),
+                    fullname: s.fullname.clone(),
+                    address: address.to_string(),
+                    port: s.port,
+                    host: s.host.trim_end_matches('.').to_string(),
+                    http: http_scheme(s).map(|scheme| (scheme == "https", service_path(s))),
+                })
+            })
+            .collect()
+    }
+
+    pub(super) fn update_probe_result(
+        &mut self,
+        agent: Option<&str>,
+        fullname: &str,
+        probe: ProbeResult,
+    ) -> bool {
+        if !self.probes.tcp_enabled {
+            return false;
+        }
+        match self.services.get_mut_at(agent, fullname) {
+            Some(mut service) if service.probe != probe => {
+                service.probe = probe;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn inspect_selected_service_certificates(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        if http_scheme(service) != Some("https") {
+            self.status_message = Some(format!(
+                "Cannot inspect certificates of {} services",
+                service.service_type
+            ));
+            return;
+        }
+        let Some(address) = preferred_address(service, self.address_preference()) else {
+            self.status_message = Some("No address to connect to".to_string());
+            return;
+        };
+        self.pending_requests
+            .push(BackgroundRequest::InspectCertificates {
+                fullname: service.fullname.clone(),
+                address: address.to_string(),
+                port: service.port,
+                host: service.host.trim_end_matches('.').to_string(),
+            });
+        self.probes
+            .certificate_inspections
+            .insert(service.fullname.clone(), None);
+    }
+
+    // Asks the device behind the selected service about itself, in the way its
+    // service type allows
+    pub(super) fn query_selected_device(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        let Some(address) = preferred_address(service, self.address_preference()) else {
+            self.status_message = Some("No address to connect to".to_string());
+            return;
+        };
+        let address = address.to_string();
+        match service.service_type.split('.').next() {
+            Some("_ipp") => self.query_printer(service_idx, address, false),
+            Some("_ipps") => self.query_printer(service_idx, address, true),
+            Some("_googlecast") => self.query_cast_device(service_idx, address),
+            _ => {
+                self.status_message = Some(format!(
+                    "Cannot query device info of {} services",
+                    service.service_type
+                ));
+            }
+        }
+    }
+
+    fn query_cast_device(&mut self, service_idx: usize, address: String) {
+        let service = &self.services[service_idx];
+        self.pending_requests
+            .push(BackgroundRequest::QueryCastDevice {
+                fullname: service.fullname.clone(),
+                address,
+                host: service.host.trim_end_matches('.').to_string(),
+            });
+        self.probes
+            .cast_queries
+            .insert(service.fullname.clone(), None);
+    }
+
+    pub(super) fn query_printer(&mut self, service_idx: usize, address: String, tls: bool) {
+        let service = &self.services[service_idx];
+        // Printers without an rp key usually listen on the path of the IPP Everywhere spec
+        let path = service
+            .txt
+            .iter()
+            .find_map(|entry| entry.strip_prefix("rp="))
+            .unwrap_or("ipp/print");
+        self.pending_requests.push(BackgroundRequest::QueryPrinter {
+            fullname: service.fullname.clone(),
+            address,
+            port: service.port,
+            host: service.host.trim_end_matches('.').to_string(),
+            path: path.to_string(),
+            tls,
+        });
+        self.probes
+            .printer_queries
+            .insert(service.fullname.clone(), None);
+    }
+
+    // Scans the configured ports of the selected service's host, or shows the
+    // running scan again if it is for the same host
+    pub(super) fn scan_selected_service_ports(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        let Some(address) = preferred_address(service, self.address_preference()) else {
+            self.status_message = Some("No address to scan".to_string());
+            return;
+        };
+        self.probes.show_port_scan_popup = true;
+        if let Some(scan) = &self.probes.port_scan
+            && scan.address == address
+            && scan.scanned < scan.total
+        {
+            return;
+        }
+
+        let scan_id = self
+            .probes
+            .port_scan
+            .as_ref()
+            .map_or(0, |scan| scan.scan_id + 1);
+        let ports = self.config.port_scan_ports.clone();
+        self.probes.port_scan = Some(PortScan {
+            scan_id,
+            host: service.host.to_string(),
+            address: address.to_string(),
+            total: ports.len(),
+            scanned: 0,
+            open_ports: Vec::new(),
+        });
+        self.pending_requests.push(BackgroundRequest::ScanPorts {
+            scan_id,
+            address: address.to_string(),
+            ports,
+        });
+    }
+}
@@ -0,0 +1,196 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// The ad-hoc mDNS lookups typed in, independent of any browsed service: a
+// hostname to resolve and raw record questions
+#[derive(Debug, Default)]
+pub(super) struct QueryState {
+    hostname_input: Option<InputLine>,
+    host_lookup: Option<HostLookup>,
+    show_host_lookup_popup: bool,
+    question_input: Option<InputLine>,
+    record_query: Option<RecordQuery>,
+    show_record_query_popup: bool,
+}
+
+// An ad-hoc hostname lookup over mDNS
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct HostLookup {
+    pub(super) hostname: String,
+    pub(super) started: std::time::Instant,
+    // None while the query runs
+    pub(super) result: Option<Result<Vec<std::net::IpAddr>, String>>,
+}
+
+// Raw records received for a question typed into the query tool
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct RecordQuery {
+    pub(super) query_id: u64,
+    pub(super) question: String,
+    pub(super) started: std::time::Instant,
+    // None while responders still get to answer
+    pub(super) result: Option<Result<Vec<mdns_query::MdnsAnswer>, String>>,
+    pub(super) scroll: usize,
+}
+
+impl QueryState {
+    pub(super) fn hostname_input(&self) -> Option<&InputLine> {
+        self.hostname_input.as_ref()
+    }
+
+    pub(super) fn open_hostname_input(&mut self) {
+        self.hostname_input = Some(InputLine::default());
+    }
+
+    pub(super) fn host_lookup(&self) -> Option<&HostLookup> {
+        self.host_lookup.as_ref()
+    }
+
+    // Ignored if a newer lookup replaced the one of the hostname
+    pub(super) fn set_host_lookup_result(
+        &mut self,
+        hostname: &str,
+        result: Result<Vec<std::net::IpAddr>, String>,
+    ) {
+        if let Some(lookup) = &mut self.host_lookup
+            && lookup.hostname == hostname
+        {
+            lookup.result = Some(result);
+        }
+    }
+
+    pub(super) fn show_host_lookup_popup(&self) -> bool {
+        self.show_host_lookup_popup
+    }
+
+    pub(super) fn close_host_lookup_popup(&mut self) {
+        self.show_host_lookup_popup = false;
+    }
+
+    pub(super) fn question_input(&self) -> Option<&InputLine> {
+        self.question_input.as_ref()
+    }
+
+    pub(super) fn open_question_input(&mut self) {
+        self.question_input = Some(InputLine::default());
+    }
+
+    pub(super) fn record_query(&self) -> Option<&RecordQuery> {
+        self.record_query.as_ref()
+    }
+
+    // Ignored if a newer query replaced the one of the id
+    pub(super) fn set_record_query_result(
+        &mut self,
+        query_id: u64,
+        result: Result<Vec<mdns_query::MdnsAnswer>, String>,
+    ) {
+        if let Some(query) = &mut self.record_query
+            && query.query_id == query_id
+        {
+            query.result = Some(result);
+        }
+    }
+
+    pub(super) fn show_record_query_popup(&self) -> bool {
+        self.show_record_query_popup
+    }
+}
+
+impl AppState {
+    pub(super) fn handle_hostname_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(hostname) = self.queries.hostname_input.take() {
+                    self.start_host_lookup(&hostname.text);
+                }
+            }
+            KeyCode::Esc => self.queries.hostname_input = None,
+            _ => {
+                if let Some(hostname) = &mut self.queries.hostname_input {
+                    edit_input_line(hostname, key);
+                }
+            }
+        }
+        true
+    }
+
+    fn start_host_lookup(&mut self, input: &str) {
+        let Some(hostname) = normalize_hostname(input) else {
+            return;
+        };
+        self.pending_requests
+            .push(BackgroundRequest::ResolveHostname {
+                hostname: hostname.clone(),
+            });
+        self.queries.host_lookup = Some(HostLookup {
+            hostname,
+            started: std::time::Instant::now(),
+            result: None,
+        });
+        self.queries.show_host_lookup_popup = true;
+    }
+
+    pub(super) fn handle_question_input_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(question) = self.queries.question_input.take() {
+                    self.start_record_query(&question.text);
+                }
+            }
+            KeyCode::Esc => self.queries.question_input = None,
+            _ => {
+                if let Some(question) = &mut self.queries.question_input {
+                    edit_input_line(question, key);
+                }
+            }
+        }
+        true
+    }
+
+    fn start_record_query(&mut self, question: &str) {
+        if let Err(e) = mdns_query::parse_question(question) {
+            self.status_message = Some(e);
+            return;
+        }
+        let query_id = self
+            .queries
+            .record_query
+            .as_ref()
+            .map_or(0, |query| query.query_id + 1);
+        self.pending_requests.push(BackgroundRequest::QueryRecords {
+            query_id,
+            question: question.trim().to_string(),
+        });
+        self.queries.record_query = Some(RecordQuery {
+            query_id,
+            question: question.trim().to_string(),
+            started: std::time::Instant::now(),
+            result: None,
+            scroll: 0,
+        });
+        self.queries.show_record_query_popup = true;
+    }
+
+    // j/k scroll through the answers, any other key closes the popup
+    pub(super) fn handle_record_query_popup_key(&mut self, key: KeyEvent) -> bool {
+        let queries = &mut self.queries;
+        let Some(query) = &mut queries.record_query else {
+            queries.show_record_query_popup = false;
+            return true;
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                let answers = match &query.result {
+                    Some(Ok(answers)) => answers.len(),
+                    _ => 0,
+                };
+                query.scroll = (query.scroll + 1).min(answers.saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => query.scroll = query.scroll.saturating_sub(1),
+            _ => queries.show_record_query_popup = false,
+        }
+        true
+    }
+}
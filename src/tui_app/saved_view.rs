@@ -0,0 +1,39 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// How the list was sorted and filtered when the TUI was left, restored on the
+// next start unless --fresh is given
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct SavedView {
+    pub(super) sort_field: Option<SortField>,
+    pub(super) sort_direction: Option<SortDirection>,
+    pub(super) online_first: bool,
+    pub(super) address_family: Option<AddressFamily>,
+    pub(super) filter: String,
+    // By name, the type is selected again once it is discovered
+    pub(super) selected_type: Option<String>,
+    pub(super) hidden_service_types: BTreeSet<String>,
+    pub(super) category_filter: Option<DeviceCategory>,
+    pub(super) domain_filter: Option<String>,
+    pub(super) interface_filter: Option<String>,
+    pub(super) baseline_filter: Option<BaselineStatus>,
+}
+
+impl SavedView {
+    pub(super) fn default_path() -> Option<PathBuf> {
+        data_file::path("view.json")
+    }
+
+    // A missing or unreadable file starts with the default view
+    pub(super) fn load(path: &Path) -> Self {
+        data_file::load_json(path)
+    }
+
+    pub(super) fn save(&self, path: &Path) -> Result<(), String> {
+        data_file::save_json(path, self)
+    }
+}
@@ -0,0 +1,166 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+
+// The filtered services in list order, kept sorted as they come and change.
+// Each is filed under its sort key and its index in the store, which stays put
+// while it is listed, so placing one takes O(log n) and moves no others. The
+// rows the UI reads are copied out of the set once after changes, so a burst
+// of events costs one O(n) copy per frame instead of one per event
+#[derive(Clone, Debug, Default)]
+pub(super) struct SortedList {
+    order: BTreeSet<(ListKey, usize)>,
+    // The key each listed service is filed under, to find it once it changed
+    keys: HashMap<usize, ListKey>,
+    rows: Vec<usize>,
+    rows_stale: bool,
+}
+
+impl SortedList {
+    pub(super) fn clear(&mut self) {
+        self.order.clear();
+        self.keys.clear();
+        self.rows.clear();
+        self.rows_stale = false;
+    }
+
+    #[cfg(feature = "bench")]
+    pub(super) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    // Lists the service at the place of the key, moving it there if listed already
+    pub(super) fn insert(&mut self, idx: usize, key: ListKey) {
+        self.remove(idx);
+        self.order.insert((key.clone(), idx));
+        self.keys.insert(idx, key);
+        self.rows_stale = true;
+    }
+
+    pub(super) fn remove(&mut self, idx: usize) {
+        if let Some(key) = self.keys.remove(&idx) {
+            self.order.remove(&(key, idx));
+            self.rows_stale = true;
+        }
+    }
+
+    // The listed services in no particular order
+    pub(super) fn rows_unordered(&self) -> Vec<usize> {
+        self.keys.keys().copied().collect()
+    }
+
+    // Copies the order out to the rows if it changed since
+    pub(super) fn update_rows(&mut self) {
+        if self.rows_stale {
+            self.rows.clear();
+            self.rows.extend(self.order.iter().map(|&(_, idx)| idx));
+            self.rows_stale = false;
+        }
+    }
+
+    // Indices into the store in list order, as of the last update_rows
+    pub(super) fn rows(&self) -> &[usize] {
+        debug_assert!(!self.rows_stale, "rows read before they were updated");
+        &self.rows
+    }
+}
+
+// Where a service goes in the list: pinned ones first, then the online ones
+// and the ones with an address of the preferred family if those go first, then
+// by the sort field; services equal in all of it are ordered by host
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct ListKey {
+    pinned: Reverse<bool>,
+    online: Reverse<bool>,
+    preferred_family: Reverse<bool>,
+    field: Directed,
+    host: Natural,
+}
+
+impl ListKey {
+    pub(super) fn new(
+        service: &ServiceEntry,
+        pinned: bool,
+        online_first: bool,
+        preferred_family: AddressFamily,
+        sort_field: SortField,
+        sort_direction: SortDirection,
+    ) -> Self {
+        let field = FieldKey::new(service, sort_field);
+        Self {
+            pinned: Reverse(pinned),
+            online: Reverse(online_first && service.online),
+            preferred_family: Reverse(
+                preferred_family != AddressFamily::Any
+                    && has_address_of_family(service, preferred_family),
+            ),
+            field: match sort_direction {
+                SortDirection::Ascending => Directed::Ascending(field),
+                SortDirection::Descending => Directed::Descending(Reverse(field)),
+            },
+            host: Natural(Arc::clone(&service.host)),
+        }
+    }
+}
+
+// All keys of a list have the same direction
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Directed {
+    Ascending(FieldKey),
+    Descending(Reverse<FieldKey>),
+}
+
+// The value of a service sorted by a field
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum FieldKey {
+    Name(Natural),
+    Text(Arc<str>),
+    Number(u64),
+    // Addresses that parse come first in numeric order, the others as text
+    Address(Result<IpAddr, String>),
+    // Like a client picks targets (RFC 2782): lowest priority, then highest
+    // weight, services without a seen SRV record last
+    Srv(u16, Reverse<Option<u16>>),
+}
+
+impl FieldKey {
+    pub(super) fn new(service: &ServiceEntry, field: SortField) -> Self {
+        match field {
+            SortField::Host => Self::Name(Natural(Arc::clone(&service.host))),
+            SortField::ServiceType => Self::Text(Arc::clone(&service.service_type)),
+            SortField::Fullname => Self::Name(Natural(Arc::from(service.fullname.as_str()))),
+            SortField::Port => Self::Number(u64::from(service.port)),
+            SortField::Address => {
+                let address = service.addrs.first().map_or("<no-addr>", String::as_str);
+                Self::Address(address.parse().map_err(|_| address.to_string()))
+            }
+            SortField::AddressCount => Self::Number(service.addrs.len() as u64),
+            SortField::Timestamp => Self::Number(service.last_updated_micros),
+            SortField::FirstSeen => Self::Number(service.first_seen_micros),
+            SortField::Refreshes => Self::Number(service.refresh_count),
+            SortField::Priority => Self::Srv(
+                service.srv_priority.unwrap_or(u16::MAX),
+                Reverse(service.srv_weight),
+            ),
+        }
+    }
+}
+
+// Text compared naturally, "printer2" before "printer12"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Natural(Arc<str>);
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        natural_cmp(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -0,0 +1,2570 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum SortField {
+    Host,
+    ServiceType,
+    Fullname,
+    Port,
+    Address,
+    AddressCount,
+    Timestamp,
+    FirstSeen,
+    Refreshes,
+    Priority,
+}
+
+impl SortField {
+    // Time based fields are most useful newest first, everything else reads best A→Z
+    pub(super) fn default_direction(self) -> SortDirection {
+        match self {
+            SortField::Timestamp | SortField::FirstSeen | SortField::Refreshes => {
+                SortDirection::Descending
+            }
+            _ => SortDirection::Ascending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum AddressFamily {
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+// How to pick the address used for display, copy, open and connect actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct AddressPreference {
+    pub(super) family: AddressFamily,
+    // Rank routable addresses before link-local and loopback ones
+    pub(super) routable_first: bool,
+}
+
+impl From<AddressFamily> for AddressPreference {
+    fn from(family: AddressFamily) -> Self {
+        Self {
+            family,
+            routable_first: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AddressScope {
+    Loopback,
+    LinkLocal,
+    // IPv6 fc00::/7
+    UniqueLocal,
+    // IPv4 RFC 1918 and shared address space
+    Private,
+    Global,
+    Unknown,
+}
+
+impl AddressScope {
+    pub(super) fn is_routable(self) -> bool {
+        matches!(
+            self,
+            AddressScope::UniqueLocal | AddressScope::Private | AddressScope::Global
+        )
+    }
+
+    // Lower is better for connecting to
+    pub(super) fn rank(self) -> u8 {
+        match self {
+            _ if self.is_routable() => 0,
+            AddressScope::LinkLocal => 1,
+            AddressScope::Loopback => 2,
+            _ => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for AddressScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AddressScope::Loopback => "loopback",
+            AddressScope::LinkLocal => "link-local",
+            AddressScope::UniqueLocal => "ULA",
+            AddressScope::Private => "private",
+            AddressScope::Global => "global",
+            AddressScope::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// A discovered service, as [`crate::Browser`] lists it and the exports write it
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ServiceEntry {
+    pub(super) schema_version: SchemaVersion,
+    pub fullname: String,
+    /// Shared with the other services of the host
+    pub host: Arc<str>,
+    /// Shared with the other services of the type
+    pub service_type: Arc<str>,
+    pub subtype: Option<String>,
+    pub addrs: Vec<String>,
+    pub port: u16,
+    pub txt: Vec<String>,
+    pub online: bool,
+    /// When this service was first discovered, kept across updates and going offline
+    pub first_seen_micros: u64,
+    /// When this service last changed, including going offline
+    pub last_updated_micros: u64,
+    /// When this service was last announced or resolved, also without changes
+    pub last_seen_micros: u64,
+    /// When this service went offline, None while online
+    pub offline_since_micros: Option<u64>,
+    /// How often this service was resolved or announced during the session
+    pub refresh_count: u64,
+    /// From the SRV record, None until one was seen as the daemon drops them
+    pub srv_priority: Option<u16>,
+    pub srv_weight: Option<u16>,
+    /// Results of the last probe, kept across updates until the next probe
+    pub(super) probe: ProbeResult,
+    /// Local interfaces the addresses are reachable on
+    pub interfaces: Vec<String>,
+    /// Remote agent that reported the service, None for local discoveries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub(super) struct ProbeResult {
+    // Whether a TCP connect succeeded, None while not probed yet
+    pub(super) reachable: Option<bool>,
+    // Round trip time, ICMP echo if permitted, TCP connect otherwise
+    pub(super) latency_micros: Option<u64>,
+    // Only probed for _http._tcp and _https._tcp services
+    pub(super) http: Option<Result<http_probe::HttpResponse, String>>,
+}
+
+impl ServiceEntry {
+    pub(super) fn go_offline_at(&mut self, timestamp_micros: u64) {
+        self.online = false;
+        self.last_updated_micros = timestamp_micros;
+        self.offline_since_micros = Some(timestamp_micros);
+    }
+}
+
+impl From<ResolvedService> for ServiceEntry {
+    fn from(resolved_service: ResolvedService) -> Self {
+        let now = current_timestamp_micros();
+        Self {
+            fullname: resolved_service.get_fullname().to_string(),
+            host: resolved_service.get_hostname().into(),
+            service_type: resolved_service.ty_domain.as_str().into(),
+            subtype: resolved_service
+                .get_subtype()
+                .as_ref()
+                .map(|s| s.to_string()),
+            addrs: {
+                let mut addrs: Vec<String> = resolved_service
+                    .get_addresses()
+                    .iter()
+                    .map(|ip| ip.to_string())
+                    .collect();
+                addrs.sort();
+                addrs
+            },
+            port: resolved_service.get_port(),
+            txt: {
+                let mut txt: Vec<String> = resolved_service
+                    .get_properties()
+                    .iter()
+                    .filter_map(|prop| {
+                        prop.val().map(|val| {
+                            format!("{}={}", prop.key(), txt_decode::format_txt_value(val))
+                        })
+                    })
+                    .collect();
+                sort_txt_by_key(&mut txt);
+                txt
+            },
+            online: true,
+            first_seen_micros: now,
+            last_updated_micros: now,
+            last_seen_micros: now,
+            offline_since_micros: None,
+            refresh_count: 0,
+            srv_priority: None,
+            srv_weight: None,
+            probe: ProbeResult::default(),
+            interfaces: Vec::new(),
+            agent: None,
+            schema_version: SchemaVersion,
+        }
+    }
+}
+
+impl From<RecordedService> for ServiceEntry {
+    fn from(service: RecordedService) -> Self {
+        let now = current_timestamp_micros();
+        Self {
+            fullname: service.fullname,
+            host: service.host.into(),
+            service_type: service.service_type.into(),
+            subtype: service.subtype,
+            addrs: service.addrs,
+            port: service.port,
+            txt: service.txt,
+            online: true,
+            first_seen_micros: now,
+            last_updated_micros: now,
+            last_seen_micros: now,
+            offline_since_micros: None,
+            refresh_count: 0,
+            srv_priority: service.srv_priority,
+            srv_weight: service.srv_weight,
+            probe: ProbeResult::default(),
+            interfaces: Vec::new(),
+            agent: None,
+            schema_version: SchemaVersion,
+        }
+    }
+}
+
+impl From<UnicastService> for ServiceEntry {
+    fn from(service: UnicastService) -> Self {
+        let now = current_timestamp_micros();
+        let mut txt: Vec<String> = service
+            .txt
+            .iter()
+            .filter_map(|entry| {
+                let separator = entry.iter().position(|&b| b == b'=')?;
+                Some(format!(
+                    "{}={}",
+                    String::from_utf8_lossy(&entry[..separator]),
+                    txt_decode::format_txt_value(&entry[separator + 1..])
+                ))
+            })
+            .collect();
+        sort_txt_by_key(&mut txt);
+        Self {
+            fullname: service.fullname,
+            host: service.host.into(),
+            service_type: service.service_type.into(),
+            subtype: None,
+            addrs: service.addrs.iter().map(|ip| ip.to_string()).collect(),
+            port: service.port,
+            txt,
+            online: true,
+            first_seen_micros: now,
+            last_updated_micros: now,
+            last_seen_micros: now,
+            offline_since_micros: None,
+            refresh_count: 0,
+            srv_priority: Some(service.priority),
+            srv_weight: Some(service.weight),
+            probe: ProbeResult::default(),
+            interfaces: Vec::new(),
+            agent: None,
+            schema_version: SchemaVersion,
+        }
+    }
+}
+
+fn sort_txt_by_key(txt: &mut [String]) {
+    txt.sort_by(|a, b| {
+        let a_key = a.split('=').next().unwrap_or(a);
+        let b_key = b.split('=').next().unwrap_or(b);
+        a_key.cmp(b_key)
+    });
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum BackgroundRequest {
+    InspectCertificates {
+        fullname: String,
+        address: String,
+        port: u16,
+        host: String,
+    },
+    QueryPrinter {
+        fullname: String,
+        address: String,
+        port: u16,
+        host: String,
+        // Resource path from the rp TXT key
+        path: String,
+        tls: bool,
+    },
+    QueryCastDevice {
+        fullname: String,
+        address: String,
+        host: String,
+    },
+    ScanPorts {
+        scan_id: u64,
+        address: String,
+        ports: Vec<u16>,
+    },
+    ReResolve {
+        fullname: String,
+        service_type: String,
+    },
+    BrowseServiceType {
+        service_type: String,
+    },
+    SetInterfaceEnabled {
+        name: String,
+        enabled: bool,
+    },
+    Rescan,
+    ResolveHostname {
+        hostname: String,
+    },
+    QueryRecords {
+        query_id: u64,
+        question: String,
+    },
+    VerifyCache,
+}
+
+// A runtime error as the error console lists it
+#[derive(Clone, Debug)]
+pub(super) struct ErrorEntry {
+    pub(super) at: chrono::DateTime<chrono::Local>,
+    pub(super) message: String,
+}
+
+// One change of a service's TXT record
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct TxtChange {
+    pub(super) timestamp_micros: u64,
+    // Key with its old and new value, None where the key was added or removed
+    pub(super) changes: Vec<(String, Option<String>, Option<String>)>,
+}
+
+// The TTL a record was last seen with on the network and when
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct RecordTtl {
+    pub(super) ttl: u32,
+    pub(super) received: std::time::Instant,
+}
+
+// Every record seen in multicast responses, by lowercase name so the ones of a
+// service are looked up directly, then by type and lowercase data
+#[derive(Debug, Default)]
+pub(super) struct ObservedRecords {
+    by_name: HashMap<String, HashMap<(RecordType, String), RecordTtl>>,
+}
+
+impl ObservedRecords {
+    pub(super) fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    #[cfg(test)]
+    pub(super) fn len(&self) -> usize {
+        self.by_name.values().map(HashMap::len).sum()
+    }
+
+    // The type, data and TTL of the records of a lowercase name
+    pub(super) fn of_name<'a>(
+        &'a self,
+        name: &str,
+    ) -> impl Iterator<Item = (RecordType, &'a str, &'a RecordTtl)> + use<'a> {
+        self.by_name
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|((record_type, data), record)| (*record_type, data.as_str(), record))
+    }
+
+    // A cache flush record replaces all others of its name and type received
+    // more than a second before (RFC 6762 section 10.2)
+    fn insert(
+        &mut self,
+        name: String,
+        record_type: RecordType,
+        data: String,
+        record: RecordTtl,
+        cache_flush: bool,
+    ) {
+        let records = self.by_name.entry(name).or_default();
+        if cache_flush {
+            records.retain(|(other_type, _), other| {
+                *other_type != record_type
+                    || record.received.duration_since(other.received) <= Duration::from_secs(1)
+            });
+        }
+        records.insert((record_type, data), record);
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&RecordTtl) -> bool) {
+        self.by_name.retain(|_, records| {
+            records.retain(|_, record| keep(record));
+            !records.is_empty()
+        });
+    }
+}
+
+// Interactive program to run in the foreground while the TUI is suspended
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct ExternalCommand {
+    pub(super) program: String,
+    pub(super) args: Vec<String>,
+}
+
+impl ExternalCommand {
+    // Quoted so it can be pasted into a shell as is
+    pub(super) fn to_shell_line(&self) -> String {
+        shell_words::join(std::iter::once(&self.program).chain(&self.args))
+    }
+}
+
+impl std::fmt::Display for ExternalCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) struct AppState {
+    pub(super) services: ServiceStore,
+    pub(super) service_types: Vec<String>,
+    pub(super) selected_service: usize,
+    pub(super) selected_type: Option<usize>,
+    pub(super) types_scroll_offset: usize,
+    pub(super) services_scroll_offset: usize,
+    pub(super) visible_types: usize,
+    pub(super) visible_services: usize,
+    pub(super) cached_filtered_services: SortedList,
+    pub(super) cache_dirty: bool,
+    pub(super) cached_sorted: bool,
+    pub(super) show_help_popup: bool,
+    pub(super) show_metrics_popup: bool,
+    // Runtime errors, oldest first, and how many came since the console was opened
+    pub(super) errors: VecDeque<ErrorEntry>,
+    pub(super) unseen_errors: usize,
+    pub(super) show_errors_popup: bool,
+    pub(super) metrics: BTreeMap<String, u64>,
+    pub(super) sort_field: SortField,
+    pub(super) sort_direction: SortDirection,
+    // Last direction used for each sort field, restored when cycling back to it
+    pub(super) sort_directions: HashMap<SortField, SortDirection>,
+    pub(super) online_first: bool,
+    pub(super) preferred_address_family: AddressFamily,
+    pub(super) filter_query: InputLine,
+    pub(super) filter_input_mode: bool,
+    // The query before editing it, restored when the edit is cancelled
+    pub(super) filter_before_input: InputLine,
+    pub(super) search_query: InputLine,
+    pub(super) search_input_mode: bool,
+    pub(super) exports: ExportState,
+    // Service type to browse while asking for it
+    pub(super) add_type_input: Option<InputLine>,
+    // Printed once the terminal is restored on exit
+    pub(super) stdout_on_exit: Vec<String>,
+    // Selection when the search prompt was opened, restored on cancel
+    pub(super) search_origin: Option<usize>,
+    // Count typed before a navigation key, e.g. the 5 in "5j"
+    pub(super) pending_count: Option<usize>,
+    // First key of a two-key sequence, e.g. the first 'g' of "gg"
+    pub(super) pending_key: Option<char>,
+    // Fullnames of services marked for bulk actions
+    pub(super) marked_services: HashSet<String>,
+    // Fullnames of services on the watch list
+    pub(super) watched_services: HashSet<String>,
+    // Services kept at the top of the list whatever the filters
+    pub(super) pins: PinState,
+    pub(super) show_bulk_actions_popup: bool,
+    // One-shot feedback for the last action, cleared on the next key press
+    pub(super) status_message: Option<String>,
+    // Created on first use and kept alive, X11 clipboards lose their content when dropped
+    pub(super) clipboard: Option<arboard::Clipboard>,
+    // Picked up by the event loop, which suspends the TUI while it runs
+    pub(super) pending_external_command: Option<ExternalCommand>,
+    pub(super) config: Config,
+    pub(super) show_actions_popup: bool,
+    pub(super) show_copy_commands_popup: bool,
+    pub(super) probes: ProbeState,
+    // Work that needs the runtime, spawned by the event loop
+    pub(super) pending_requests: Vec<BackgroundRequest>,
+    pub(super) reverse_dns_enabled: bool,
+    // Show values that look encoded decoded in the details
+    pub(super) txt_decoding_enabled: bool,
+    // Only show services classified into this device category
+    pub(super) category_filter: Option<DeviceCategory>,
+    // Only show services of this browse domain
+    pub(super) domain_filter: Option<String>,
+    // Only show services reachable on this local interface
+    pub(super) interface_filter: Option<String>,
+    // Labels of the remote agents the TUI is attached to
+    pub(super) agents: Vec<String>,
+    // Whether the services are the ones of the local network, not replayed or
+    // of remote agents
+    pub(super) on_network: bool,
+    // Only show services reported by this remote agent
+    pub(super) agent_filter: Option<String>,
+    pub(super) local_interfaces: Vec<LocalInterface>,
+    // PTR names by address, None while the lookup runs, kept for the whole session
+    pub(super) reverse_dns: HashMap<String, Option<Vec<String>>>,
+    // MAC addresses by host name, for waking hosts once they went offline
+    pub(super) mac_store: MacStore,
+    // Addresses by host of the hosts to look the MAC up for in the neighbour table
+    pub(super) mac_lookups: HashMap<String, Vec<String>>,
+    // MACs learned since the store was last saved
+    pub(super) unsaved_macs: bool,
+    pub(super) queries: QueryState,
+    // Every record seen in a multicast response, for the TTL countdowns in the details
+    pub(super) observed_records: ObservedRecords,
+    // "host:port" targets each instance was resolved to and when, to spot name conflicts
+    // that the daemon settles by letting the last announcement win
+    pub(super) instance_targets: HashMap<String, HashMap<String, std::time::Instant>>,
+    // TXT changes by service fullname, devices often encode their state in TXT
+    pub(super) txt_history: HashMap<String, Vec<TxtChange>>,
+    // Types left out of the types panel and the all types list, still browsed
+    pub(super) hidden_service_types: HashSet<String>,
+    // Type selected when the last session ended, selected once it is discovered
+    pub(super) pending_selected_type: Option<String>,
+    // Fullnames of the services of earlier sessions not seen again yet
+    pub(super) remembered_services: HashSet<String>,
+    // The history loaded at the start, kept whole so services expired or removed
+    // from the list meanwhile are still remembered on saving it
+    pub(super) loaded_history: Vec<HistoryEntry>,
+    pub(super) show_hidden_types_popup: bool,
+    pub(super) hidden_types_cursor: usize,
+    // Interfaces the daemon was told to leave out, all others take part in mDNS
+    pub(super) disabled_interfaces: BTreeSet<String>,
+    pub(super) show_interfaces_popup: bool,
+    pub(super) interfaces_cursor: usize,
+    // Expected services, loaded from the inventory file at the start
+    pub(super) inventory: Option<Inventory>,
+    pub(super) show_inventory_popup: bool,
+    pub(super) inventory_cursor: usize,
+    // Services online when the baseline was saved, to spot drift
+    pub(super) baseline: Baseline,
+    // Only show services new or missing compared to the baseline
+    pub(super) baseline_filter: Option<BaselineStatus>,
+    // Appends what the discovery backends report to the session file of --record
+    pub(super) session_recorder: Option<session::Recorder>,
+    // Starts the mDNS browses of service types once each and at a polite pace
+    pub(super) browse_manager: BrowseManager,
+    // Services added, updated and gone offline, for the event outputs
+    pub(super) discovery_events: tokio::sync::broadcast::Sender<DiscoveryEvent>,
+    // Wakes the wide-area polls up for a rescan
+    pub(super) unicast_rescan: Arc<tokio::sync::Notify>,
+    // Services being re-resolved by fullname, with when that started for the spinner
+    pub(super) re_resolving: HashMap<String, std::time::Instant>,
+}
+
+impl AppState {
+    pub(super) fn new() -> Self {
+        let mut state = Self {
+            services: ServiceStore::default(),
+            service_types: Vec::new(),
+            selected_service: 0,
+            selected_type: None,
+            types_scroll_offset: 0,
+            services_scroll_offset: 0,
+            visible_types: 0,
+            visible_services: 0,
+            cached_filtered_services: SortedList::default(),
+            cache_dirty: true,
+            cached_sorted: false,
+            show_help_popup: false,
+            show_metrics_popup: false,
+            errors: VecDeque::new(),
+            unseen_errors: 0,
+            show_errors_popup: false,
+            metrics: BTreeMap::new(),
+            sort_field: SortField::Host,
+            sort_direction: SortField::Host.default_direction(),
+            sort_directions: HashMap::new(),
+            online_first: false,
+            preferred_address_family: AddressFamily::Any,
+            filter_query: InputLine::default(),
+            filter_input_mode: false,
+            filter_before_input: InputLine::default(),
+            search_query: InputLine::default(),
+            search_input_mode: false,
+            exports: ExportState::default(),
+            add_type_input: None,
+            stdout_on_exit: Vec::new(),
+            search_origin: None,
+            pending_count: None,
+            pending_key: None,
+            marked_services: HashSet::new(),
+            watched_services: HashSet::new(),
+            pins: PinState::new(PinStore::in_memory()),
+            show_bulk_actions_popup: false,
+            status_message: None,
+            clipboard: None,
+            pending_external_command: None,
+            config: Config::default(),
+            show_actions_popup: false,
+            show_copy_commands_popup: false,
+            probes: ProbeState::default(),
+            pending_requests: Vec::new(),
+            reverse_dns_enabled: false,
+            txt_decoding_enabled: false,
+            category_filter: None,
+            domain_filter: None,
+            interface_filter: None,
+            agents: Vec::new(),
+            on_network: true,
+            agent_filter: None,
+            local_interfaces: Vec::new(),
+            reverse_dns: HashMap::new(),
+            mac_store: MacStore::in_memory(),
+            mac_lookups: HashMap::new(),
+            unsaved_macs: false,
+            queries: QueryState::default(),
+            observed_records: ObservedRecords::default(),
+            instance_targets: HashMap::new(),
+            txt_history: HashMap::new(),
+            hidden_service_types: HashSet::new(),
+            pending_selected_type: None,
+            remembered_services: HashSet::new(),
+            loaded_history: Vec::new(),
+            show_hidden_types_popup: false,
+            hidden_types_cursor: 0,
+            disabled_interfaces: BTreeSet::new(),
+            show_interfaces_popup: false,
+            inventory: None,
+            show_inventory_popup: false,
+            inventory_cursor: 0,
+            baseline: Baseline::in_memory(),
+            baseline_filter: None,
+            session_recorder: None,
+            interfaces_cursor: 0,
+            browse_manager: BrowseManager::new(std::time::Instant::now()),
+            discovery_events: tokio::sync::broadcast::channel(discovery_events::EVENT_CAPACITY).0,
+            unicast_rescan: Arc::new(tokio::sync::Notify::new()),
+            re_resolving: HashMap::new(),
+        };
+        state.validate_selected_type();
+        state
+    }
+
+    pub(super) fn filter_service(&self, service: &ServiceEntry) -> bool {
+        if self.pins.store().contains(&service.fullname) {
+            return true;
+        }
+
+        if self.selected_type.is_none()
+            && (self.hidden_service_types.contains(&*service.service_type)
+                || service
+                    .subtype
+                    .as_ref()
+                    .is_some_and(|subtype| self.hidden_service_types.contains(subtype)))
+        {
+            return false;
+        }
+
+        // First filter by service type if one is selected
+        if let Some(selected_type_idx) = self.selected_type {
+            if let Some(selected_type) = self.service_types.get(selected_type_idx) {
+                // A subtype narrows its parent type down to the instances announcing it
+                let matches = if is_subtype(selected_type) {
+                    service.subtype.as_ref() == Some(selected_type)
+                } else {
+                    *service.service_type == **selected_type
+                };
+                if !matches {
+                    return false;
+                }
+            }
+        }
+
+        if let Some(category) = self.category_filter
+            && device_category::classify(&service.service_type, &service.txt) != Some(category)
+        {
+            return false;
+        }
+
+        if let Some(domain) = &self.domain_filter
+            && service_domain(&service.service_type) != domain
+        {
+            return false;
+        }
+
+        if let Some(interface) = &self.interface_filter
+            && !service.interfaces.contains(interface)
+        {
+            return false;
+        }
+
+        if let Some(agent) = &self.agent_filter
+            && service.agent.as_ref() != Some(agent)
+        {
+            return false;
+        }
+
+        if let Some(status) = self.baseline_filter
+            && self.baseline_status(service) != Some(status)
+        {
+            return false;
+        }
+
+        // Then filter by text query if present
+        if !self.filter_query.is_empty() {
+            service_matches_query(service, &self.filter_query.to_lowercase())
+        } else {
+            true // Show all services if query is empty
+        }
+    }
+
+    pub(super) fn update_filtered_cache(&mut self) -> bool {
+        if self.cache_dirty {
+            self.cached_filtered_services.clear();
+            for (idx, service) in self.services.iter().enumerate() {
+                if self.filter_service(service) {
+                    let key = self.list_key(idx);
+                    self.cached_filtered_services.insert(idx, key);
+                }
+            }
+            self.cache_dirty = false;
+            // Filed under the sort settings of now
+            self.cached_sorted = true;
+            true // Cache was rebuilt
+        } else {
+            false // Cache was not rebuilt
+        }
+    }
+
+    pub(super) fn mark_cache_dirty(&mut self) {
+        self.cache_dirty = true;
+    }
+
+    pub(super) fn validate_selected_type(&mut self) {
+        // Ensure selected_type is always valid
+        if let Some(idx) = self.selected_type
+            && idx >= self.service_types.len()
+        {
+            if self.service_types.is_empty() {
+                self.selected_type = None;
+            } else {
+                self.selected_type = Some(self.service_types.len().saturating_sub(1));
+            }
+        }
+    }
+
+    pub(super) fn get_filtered_services(&mut self) -> &[usize] {
+        let cache_was_rebuilt = self.update_filtered_cache();
+        if !cache_was_rebuilt && !self.cached_sorted {
+            self.sort_filtered_services();
+        }
+        self.cached_filtered_services.update_rows();
+        self.cached_filtered_services.rows()
+    }
+
+    // Files the listed services under the sort settings of now
+    pub(super) fn sort_filtered_services(&mut self) {
+        let listed = self.cached_filtered_services.rows_unordered();
+        for idx in listed {
+            let key = self.list_key(idx);
+            self.cached_filtered_services.insert(idx, key);
+        }
+        self.cached_sorted = true;
+    }
+
+    // Where the service goes in the list by the sort settings
+    fn list_key(&self, idx: usize) -> ListKey {
+        let service = &self.services[idx];
+        ListKey::new(
+            service,
+            self.pins.store().contains(&service.fullname),
+            self.online_first,
+            self.preferred_address_family,
+            self.sort_field,
+            self.sort_direction,
+        )
+    }
+
+    // Moves the changed service to its place in the sorted filtered list, or out
+    // of it if no longer matching, in O(log n) instead of sorting the whole list
+    fn place_in_filtered_list(&mut self, idx: usize) {
+        if self.cache_dirty || !self.cached_sorted {
+            // Rebuilt and sorted on the next read anyway
+            return;
+        }
+        if self.filter_service(&self.services[idx]) {
+            let key = self.list_key(idx);
+            self.cached_filtered_services.insert(idx, key);
+        } else {
+            self.cached_filtered_services.remove(idx);
+        }
+    }
+
+    // Helper methods for service type management
+    pub(super) fn add_service_type(&mut self, service_type: &str) -> bool {
+        if self.hidden_service_types.contains(service_type) {
+            return false;
+        }
+        if !self.service_types.contains(&service_type.to_string()) {
+            // Capture currently selected value before mutation
+            let selected_value = self
+                .selected_type
+                .and_then(|idx| self.service_types.get(idx).cloned());
+
+            tracing::debug!(service_type, "service type added");
+            self.service_types.push(service_type.to_string());
+            // Subtypes are listed right after their parent type
+            self.service_types
+                .sort_by(|a, b| service_type_sort_key(a).cmp(&service_type_sort_key(b)));
+
+            // Re-anchor selection by finding the captured value's new index
+            if let Some(selected_value) = selected_value {
+                if let Some(new_idx) = self.service_types.iter().position(|s| s == &selected_value)
+                {
+                    self.selected_type = Some(new_idx);
+                } else {
+                    // Fallback: if somehow the value is gone, go to None (All Types)
+                    self.selected_type = None;
+                }
+            }
+            if self.pending_selected_type.as_deref() == Some(service_type) {
+                self.pending_selected_type = None;
+                self.selected_type = self.service_types.iter().position(|s| s == service_type);
+            }
+
+            self.invalidate_cache_and_validate();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(super) fn remove_service_type(&mut self, service_type: &str) -> bool {
+        if self
+            .services
+            .iter()
+            .any(|s| &*s.service_type == service_type || s.subtype.as_deref() == Some(service_type))
+        {
+            return false; // Still in use
+        }
+        let initial_len = self.service_types.len();
+
+        // Capture currently selected value before mutation
+        let selected_value = self
+            .selected_type
+            .and_then(|idx| self.service_types.get(idx).cloned());
+
+        self.service_types.retain(|s| s != service_type);
+        let removed = self.service_types.len() < initial_len;
+
+        if removed {
+            tracing::debug!(service_type, "service type removed");
+            // Re-anchor selection by finding the captured value's new index
+            if let Some(selected_value) = selected_value {
+                if let Some(new_idx) = self.service_types.iter().position(|s| s == &selected_value)
+                {
+                    self.selected_type = Some(new_idx);
+                } else if selected_value == service_type {
+                    // The selected item was removed - pick nearest valid index
+                    if self.service_types.is_empty() {
+                        self.selected_type = None;
+                    } else {
+                        // Try to use the same index, or clamp to last valid index
+                        let fallback_idx = self
+                            .selected_type
+                            .unwrap_or(0)
+                            .min(self.service_types.len().saturating_sub(1));
+                        self.selected_type = Some(fallback_idx);
+                    }
+                } else {
+                    // Selected value is gone for some other reason
+                    self.selected_type = None;
+                }
+            }
+
+            self.invalidate_cache_and_validate();
+        }
+
+        removed
+    }
+
+    // Visible and hidden types in panel order
+    pub(super) fn managed_service_types(&self) -> Vec<String> {
+        let mut service_types: Vec<String> = self
+            .service_types
+            .iter()
+            .chain(&self.hidden_service_types)
+            .cloned()
+            .collect();
+        service_types.sort_by(|a, b| service_type_sort_key(a).cmp(&service_type_sort_key(b)));
+        service_types
+    }
+
+    pub(super) fn toggle_service_type_hidden(&mut self, service_type: &str) {
+        if self.hidden_service_types.remove(service_type) {
+            self.add_service_type(service_type);
+            return;
+        }
+        let selected_value = self
+            .selected_type
+            .and_then(|idx| self.service_types.get(idx).cloned());
+        self.hidden_service_types.insert(service_type.to_string());
+        self.service_types.retain(|t| t != service_type);
+        self.selected_type = selected_value
+            .and_then(|selected| self.service_types.iter().position(|t| *t == selected));
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    // Local interface names with their addresses, disabled ones included
+    pub(super) fn interface_addresses(&self) -> BTreeMap<String, Vec<std::net::IpAddr>> {
+        let mut interfaces: BTreeMap<String, Vec<std::net::IpAddr>> = self
+            .disabled_interfaces
+            .iter()
+            .map(|name| (name.clone(), Vec::new()))
+            .collect();
+        for interface in &self.local_interfaces {
+            interfaces
+                .entry(interface.name.clone())
+                .or_default()
+                .push(interface.ip);
+        }
+        interfaces
+    }
+
+    // Disables all local interfaces but the configured multicast ones, returns the
+    // names to disable on the daemon
+    pub(super) fn restrict_to_multicast_interfaces(&mut self) -> Vec<String> {
+        let allowed = &self.config.multicast_interfaces;
+        if allowed.is_empty() {
+            return Vec::new();
+        }
+        let unknown: Vec<&str> = allowed
+            .iter()
+            .filter(|name| !self.local_interfaces.iter().any(|i| i.name == **name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            self.status_message = Some(format!("Unknown interface: {}", unknown.join(", ")));
+        }
+        let disabled: BTreeSet<String> = self
+            .local_interfaces
+            .iter()
+            .filter(|interface| !allowed.contains(&interface.name))
+            .map(|interface| interface.name.clone())
+            .collect();
+        self.disabled_interfaces.extend(disabled.iter().cloned());
+        disabled.into_iter().collect()
+    }
+
+    // The IPv4 address of the first configured multicast interface
+    pub(super) fn multicast_if(&self) -> Option<std::net::Ipv4Addr> {
+        let name = self.config.multicast_interfaces.first()?;
+        self.local_interfaces
+            .iter()
+            .filter(|interface| interface.name == *name)
+            .find_map(|interface| match interface.ip {
+                std::net::IpAddr::V4(ip) => Some(ip),
+                std::net::IpAddr::V6(_) => None,
+            })
+    }
+
+    // Loads the configured inventory, failures are reported in the status line
+    pub(super) fn load_inventory(&mut self) {
+        let Some(path) = &self.config.inventory else {
+            return;
+        };
+        match Inventory::load(path) {
+            Ok(inventory) => self.inventory = Some(inventory),
+            Err(e) => self.status_message = Some(format!("Cannot load inventory: {}", e)),
+        }
+    }
+
+    pub(super) fn baseline_status(&self, service: &ServiceEntry) -> Option<BaselineStatus> {
+        self.baseline.status(&service.fullname, service.online)
+    }
+
+    // The new and missing services among the listed ones, the same ones the
+    // baseline filter shows
+    pub(super) fn baseline_drift(&self) -> (usize, usize) {
+        let mut drift = (0, 0);
+        for service in &self.services {
+            match self.baseline_status(service) {
+                Some(BaselineStatus::New) => drift.0 += 1,
+                Some(BaselineStatus::Missing) => drift.1 += 1,
+                None => {}
+            }
+        }
+        drift
+    }
+
+    // Lists the baseline services not known yet as offline, as they were when
+    // the baseline was saved, so the missing ones show up without being seen
+    pub(super) fn restore_baseline(&mut self) {
+        let saved_micros = self.baseline.saved_micros();
+        let placeholders: Vec<RecordedService> = self
+            .baseline
+            .services()
+            .iter()
+            .filter(|(fullname, _)| !self.services.contains(fullname))
+            .map(|(fullname, service)| RecordedService {
+                fullname: fullname.clone(),
+                service_type: service.service_type.clone(),
+                subtype: None,
+                host: service.host.clone(),
+                port: service.port,
+                addrs: Vec::new(),
+                txt: Vec::new(),
+                srv_priority: None,
+                srv_weight: None,
+            })
+            .collect();
+        for recorded in placeholders {
+            if self.services.len() >= MAX_SERVICES {
+                break;
+            }
+            let mut service = ServiceEntry {
+                first_seen_micros: saved_micros,
+                last_seen_micros: saved_micros,
+                ..ServiceEntry::from(recorded)
+            };
+            service.go_offline_at(saved_micros);
+            self.add_service_type(&service.service_type);
+            self.remembered_services.insert(service.fullname.clone());
+            self.services.push(service);
+        }
+        self.invalidate_cache_and_validate();
+    }
+
+    // Takes the online services as the new baseline
+    pub(super) fn save_baseline(&mut self) {
+        let services: BTreeMap<String, BaselineService> = self
+            .services
+            .iter()
+            .filter(|service| service.online)
+            .map(|service| {
+                (
+                    service.fullname.clone(),
+                    BaselineService {
+                        service_type: service.service_type.to_string(),
+                        host: service.host.to_string(),
+                        port: service.port,
+                    },
+                )
+            })
+            .collect();
+        let count = services.len();
+        self.status_message = Some(
+            match self.baseline.save(services, current_timestamp_micros()) {
+                Ok(()) => format!("Saved {} online services as the baseline", count),
+                Err(e) => format!("Cannot save baseline: {}", e),
+            },
+        );
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn cycle_baseline_filter(&mut self) {
+        if self.baseline.is_empty() {
+            self.status_message = Some("No baseline saved yet, press U to save one".to_string());
+            return;
+        }
+        self.baseline_filter = match self.baseline_filter {
+            None => Some(BaselineStatus::New),
+            Some(BaselineStatus::New) => Some(BaselineStatus::Missing),
+            Some(BaselineStatus::Missing) => None,
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn inventory_checklist(&self) -> Vec<ChecklistEntry> {
+        let Some(inventory) = &self.inventory else {
+            return Vec::new();
+        };
+        let observed: Vec<ObservedService> = self
+            .services
+            .iter()
+            .map(|service| ObservedService {
+                fullname: &service.fullname,
+                service_type: &service.service_type,
+                host: &service.host,
+                online: service.online,
+            })
+            .collect();
+        inventory.check(&observed)
+    }
+
+    pub(super) fn open_inventory_popup(&mut self) {
+        if self.inventory.is_none() {
+            self.status_message =
+                Some("No inventory loaded, set inventory or pass --inventory".to_string());
+            return;
+        }
+        self.show_inventory_popup = true;
+        self.inventory_cursor = 0;
+    }
+
+    pub(super) fn open_interfaces_popup(&mut self) {
+        // Interfaces may have come and gone since the start
+        self.local_interfaces = interfaces::local_interfaces();
+        self.show_interfaces_popup = true;
+        self.interfaces_cursor = 0;
+    }
+
+    pub(super) fn toggle_interface_enabled(&mut self, name: &str) {
+        let enabled = self.disabled_interfaces.remove(name);
+        if !enabled {
+            self.disabled_interfaces.insert(name.to_string());
+        }
+        self.pending_requests
+            .push(BackgroundRequest::SetInterfaceEnabled {
+                name: name.to_string(),
+                enabled,
+            });
+    }
+
+    pub(super) fn update_service_type_selection(&mut self, new_type: Option<usize>) {
+        // The user's choice wins over the one of the last session
+        self.pending_selected_type = None;
+        self.selected_type = new_type;
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn update_sort_field(&mut self, field: SortField) {
+        self.sort_field = field;
+        self.sort_direction = self
+            .sort_directions
+            .get(&field)
+            .copied()
+            .unwrap_or_else(|| field.default_direction());
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn update_sort_direction(&mut self, direction: SortDirection) {
+        self.sort_direction = direction;
+        self.sort_directions.insert(self.sort_field, direction);
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn toggle_sort_direction(&mut self) {
+        match self.sort_direction {
+            SortDirection::Ascending => self.update_sort_direction(SortDirection::Descending),
+            SortDirection::Descending => self.update_sort_direction(SortDirection::Ascending),
+        }
+    }
+
+    // The view settings to restore on the next start
+    pub(super) fn saved_view(&self) -> SavedView {
+        SavedView {
+            sort_field: Some(self.sort_field),
+            sort_direction: Some(self.sort_direction),
+            online_first: self.online_first,
+            address_family: Some(self.preferred_address_family),
+            filter: self.filter_query.text.clone(),
+            selected_type: self
+                .selected_type
+                .and_then(|idx| self.service_types.get(idx).cloned())
+                .or_else(|| self.pending_selected_type.clone()),
+            hidden_service_types: self.hidden_service_types.iter().cloned().collect(),
+            category_filter: self.category_filter,
+            domain_filter: self.domain_filter.clone(),
+            interface_filter: self.interface_filter.clone(),
+            baseline_filter: self.baseline_filter,
+        }
+    }
+
+    pub(super) fn restore_view(&mut self, view: SavedView) {
+        if let Some(field) = view.sort_field {
+            self.update_sort_field(field);
+        }
+        if let Some(direction) = view.sort_direction {
+            self.update_sort_direction(direction);
+        }
+        self.online_first = view.online_first;
+        if let Some(family) = view.address_family {
+            self.preferred_address_family = family;
+        }
+        self.filter_query = InputLine::from(view.filter);
+        self.pending_selected_type = view.selected_type;
+        self.hidden_service_types = view.hidden_service_types.into_iter().collect();
+        self.category_filter = view.category_filter;
+        self.domain_filter = view.domain_filter;
+        self.interface_filter = view.interface_filter;
+        // Without a baseline the filter would hide every service
+        if !self.baseline.is_empty() {
+            self.baseline_filter = view.baseline_filter;
+        }
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn toggle_online_first(&mut self) {
+        self.online_first = !self.online_first;
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn address_preference(&self) -> AddressPreference {
+        AddressPreference {
+            family: self.preferred_address_family,
+            routable_first: self.config.prefer_routable_addresses,
+        }
+    }
+
+    pub(super) fn cycle_preferred_address_family(&mut self) {
+        self.preferred_address_family = match self.preferred_address_family {
+            AddressFamily::Any => AddressFamily::Ipv4,
+            AddressFamily::Ipv4 => AddressFamily::Ipv6,
+            AddressFamily::Ipv6 => AddressFamily::Any,
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    // All categories in turn, then no category filter again
+    pub(super) fn cycle_category_filter(&mut self) {
+        let categories = DeviceCategory::ALL;
+        self.category_filter = match self.category_filter {
+            None => categories.first().copied(),
+            Some(category) => categories
+                .iter()
+                .skip_while(|&&c| c != category)
+                .nth(1)
+                .copied(),
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn cycle_domain_filter(&mut self) {
+        let domains = self.config.browse_domains();
+        self.domain_filter = match &self.domain_filter {
+            None => domains.first().cloned(),
+            Some(domain) => domains.iter().skip_while(|d| *d != domain).nth(1).cloned(),
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    // Local interfaces plus the ones scoped addresses of services name
+    pub(super) fn known_interface_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .local_interfaces
+            .iter()
+            .map(|interface| interface.name.clone())
+            .chain(
+                self.services
+                    .iter()
+                    .flat_map(|service| service.interfaces.iter().cloned()),
+            )
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub(super) fn cycle_interface_filter(&mut self) {
+        let names = self.known_interface_names();
+        self.interface_filter = match &self.interface_filter {
+            None => names.first().cloned(),
+            Some(name) => names.iter().skip_while(|n| *n != name).nth(1).cloned(),
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn cycle_agent_filter(&mut self) {
+        self.agent_filter = match &self.agent_filter {
+            None => self.agents.first().cloned(),
+            Some(agent) => self
+                .agents
+                .iter()
+                .skip_while(|a| *a != agent)
+                .nth(1)
+                .cloned(),
+        };
+        self.selected_service = 0;
+        self.services_scroll_offset = 0;
+        self.invalidate_cache_and_validate();
+    }
+
+    pub(super) fn cycle_sort_field(&mut self, forward: bool) {
+        use SortField::*;
+        let fields = [
+            Host,
+            ServiceType,
+            Fullname,
+            Port,
+            Address,
+            AddressCount,
+            Timestamp,
+            FirstSeen,
+            Refreshes,
+            Priority,
+        ];
+        let current_idx = fields
+            .iter()
+            .position(|&f| f == self.sort_field)
+            .unwrap_or(0);
+
+        let new_idx = if forward {
+            (current_idx + 1) % fields.len()
+        } else {
+            current_idx.checked_sub(1).unwrap_or(fields.len() - 1)
+        };
+
+        self.update_sort_field(fields[new_idx]);
+    }
+
+    pub(super) fn clear_stale_service_types(&mut self) {
+        // Find service types that have no services at all (neither online nor offline)
+        let mut types_to_remove = Vec::new();
+
+        for service_type in &self.service_types.clone() {
+            if !self
+                .services
+                .iter()
+                .any(|s| *s.service_type == **service_type)
+            {
+                types_to_remove.push(service_type.clone());
+            }
+        }
+
+        // Remove empty service types
+        let mut removed_count = 0;
+        for service_type in types_to_remove {
+            if self.remove_service_type(&service_type) {
+                removed_count += 1;
+            }
+        }
+
+        if removed_count > 0 {
+            self.update_metric_by("stale_service_types_removed", removed_count as u64);
+            self.invalidate_cache_and_validate();
+        }
+    }
+
+    pub(super) fn remove_offline_services(&mut self) {
+        self.remove_offline_services_matching(|_| true);
+    }
+
+    // Removes services that have been offline for longer than the configured grace period
+    pub(super) fn expire_offline_services(&mut self, now_micros: u64) -> bool {
+        let grace_micros = self.config.offline_grace_secs.saturating_mul(1_000_000);
+        if grace_micros == 0 {
+            return false;
+        }
+        let initial_len = self.services.len();
+        // Services of earlier sessions are listed until seen or removed, they
+        // went offline long before the grace period started
+        let remembered = std::mem::take(&mut self.remembered_services);
+        self.remove_offline_services_matching(|service| {
+            !remembered.contains(&service.fullname)
+                && service
+                    .offline_since_micros
+                    .is_some_and(|since| now_micros.saturating_sub(since) > grace_micros)
+        });
+        self.remembered_services = remembered;
+        let expired_count = initial_len - self.services.len();
+        if expired_count > 0 {
+            tracing::debug!(expired_count, "offline services expired");
+            self.update_metric_by("offline_services_expired", expired_count as u64);
+        }
+        expired_count > 0
+    }
+
+    fn remove_offline_services_matching(&mut self, predicate: impl Fn(&ServiceEntry) -> bool) {
+        // Collect service types that have offline services
+        let mut service_types_to_check: std::collections::HashSet<String> =
+            std::collections::HashSet::new();
+
+        // Capture initial filtered length for scroll logic
+        let initial_filtered_len = self.get_filtered_services().len();
+
+        // Remove offline services and track their types
+        let initial_len = self.services.len();
+        self.services.retain(|service| {
+            if !service.online && predicate(service) {
+                service_types_to_check.insert(service.service_type.to_string());
+                false // Remove this service
+            } else {
+                true // Keep this service
+            }
+        });
+
+        let removed_count = initial_len - self.services.len();
+
+        if removed_count > 0 {
+            self.remembered_services
+                .retain(|fullname| self.services.contains(fullname));
+            self.update_metric_by("offline_services_removed", removed_count as u64);
+            // Refresh cache immediately after retain to ensure filtered services are up-to-date
+            self.invalidate_cache_and_validate();
+
+            // Check if any service types should be removed (no active services of that type)
+            let mut types_to_remove = Vec::new();
+            for service_type in service_types_to_check {
+                if !self
+                    .services
+                    .iter()
+                    .any(|s| *s.service_type == *service_type && s.online)
+                {
+                    types_to_remove.push(service_type);
+                }
+            }
+
+            // Remove empty service types
+            for service_type in types_to_remove {
+                self.remove_service_type(&service_type);
+            }
+
+            let new_filtered_len = self.get_filtered_services().len();
+
+            // Adjust selection indices - if user was at the end, keep them at the end
+            if new_filtered_len > 0 {
+                let was_near_end = initial_filtered_len > 0
+                    && (self.selected_service >= initial_filtered_len.saturating_sub(2)
+                        || self.selected_service >= new_filtered_len);
+                if was_near_end {
+                    self.selected_service = new_filtered_len.saturating_sub(1);
+                } else {
+                    // Otherwise, keep the same position but cap it to the new maximum
+                    self.selected_service = self
+                        .selected_service
+                        .min(new_filtered_len.saturating_sub(1));
+                }
+            } else {
+                self.selected_service = 0;
+            }
+
+            // Adjust scroll offset - if we're at the end, position selected item at bottom of view
+            if new_filtered_len > 0 && self.selected_service >= new_filtered_len.saturating_sub(2) {
+                // Position selected item at or near the bottom of the visible area
+                if self.visible_services > 0 {
+                    self.services_scroll_offset = self
+                        .selected_service
+                        .saturating_sub(self.visible_services - 1);
+                }
+            } else {
+                // Otherwise, just ensure it's visible
+                self.update_services_scroll_offset();
+            }
+        }
+    }
+
+    pub(super) fn invalidate_cache_and_validate(&mut self) {
+        self.mark_cache_dirty();
+        self.cached_sorted = false;
+        self.validate_selected_type();
+    }
+
+    // Marks or unmarks the selected service and moves on to the next one
+    pub(super) fn toggle_selected_service_mark(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let fullname = self.services[service_idx].fullname.clone();
+        if !self.marked_services.remove(&fullname) {
+            self.marked_services.insert(fullname);
+        }
+        self.navigate_services_down();
+    }
+
+    // Marks every service passing the current filter and type selection,
+    // or unmarks them all when they are already marked
+    pub(super) fn toggle_all_visible_service_marks(&mut self) {
+        let visible: Vec<String> = self
+            .get_filtered_services()
+            .to_vec()
+            .into_iter()
+            .map(|idx| self.services[idx].fullname.clone())
+            .collect();
+        if visible.is_empty() {
+            return;
+        }
+
+        if visible.iter().all(|f| self.marked_services.contains(f)) {
+            for fullname in &visible {
+                self.marked_services.remove(fullname);
+            }
+            self.status_message = Some(format!("Unmarked {} services", visible.len()));
+        } else {
+            let count = visible.len();
+            self.marked_services.extend(visible);
+            self.status_message = Some(format!("Marked {} services", count));
+        }
+    }
+
+    fn marked_service_entries(&self) -> Vec<&ServiceEntry> {
+        self.services
+            .iter()
+            .filter(|s| self.marked_services.contains(&s.fullname))
+            .collect()
+    }
+
+    pub(super) fn export_marked_services(&mut self) {
+        let marked = self.marked_service_entries();
+        if marked.is_empty() {
+            self.status_message = Some("No services marked".to_string());
+            return;
+        }
+        let count = marked.len();
+        let path = format!(
+            "mdns-services-{}.json",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        );
+        let result = serde_json::to_string_pretty(&marked)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+        self.status_message = Some(match result {
+            Ok(()) => format!("Exported {} services to {}", count, path),
+            Err(e) => format!("Export failed: {}", e),
+        });
+    }
+
+    // Starts browsing a type that may not answer the meta query, or selects it when known
+    pub(super) fn add_browsed_service_type(&mut self, input: &str) {
+        let Some(service_type) = normalize_service_type(input) else {
+            if !input.trim().is_empty() {
+                self.status_message = Some(format!("Invalid service type: {}", input.trim()));
+            }
+            return;
+        };
+        if let Some(type_idx) = self.service_types.iter().position(|t| *t == service_type) {
+            self.update_service_type_selection(Some(type_idx));
+            return;
+        }
+        self.status_message = Some(format!("Browsing {}", service_type));
+        self.pending_requests
+            .push(BackgroundRequest::BrowseServiceType { service_type });
+    }
+
+    pub(super) fn copy_marked_addresses(&mut self) {
+        let addresses: Vec<String> = self
+            .marked_service_entries()
+            .iter()
+            .flat_map(|s| s.addrs.iter().cloned())
+            .collect();
+        if addresses.is_empty() {
+            self.status_message = Some("No addresses to copy".to_string());
+            return;
+        }
+        let count = addresses.len();
+        self.status_message = Some(match self.copy_to_clipboard(addresses.join("\n")) {
+            Ok(()) => format!("Copied {} addresses to the clipboard", count),
+            Err(e) => format!("Copy failed: {}", e),
+        });
+    }
+
+    // Copies a field of the selected service, returns false for unknown fields
+    pub(super) fn copy_selected_service_field(&mut self, field: char) -> bool {
+        let Some(service_idx) = self.selected_service_index() else {
+            return matches!(field, 'y' | 'a' | 'p' | 'n');
+        };
+        let service = &self.services[service_idx];
+        let (what, text) = match field {
+            'y' => (
+                "details",
+                Some(create_service_details_text(
+                    service,
+                    self.config.service_type_name(&service.service_type),
+                )),
+            ),
+            'a' => (
+                "address",
+                preferred_address(service, self.address_preference()).map(str::to_string),
+            ),
+            'p' => ("host:port", Some(format_host_port(service))),
+            'n' => ("fullname", Some(service.fullname.clone())),
+            _ => return false,
+        };
+        let Some(text) = text else {
+            self.status_message = Some(format!("No {} to copy", what));
+            return true;
+        };
+        self.status_message = Some(match self.copy_to_clipboard(text) {
+            Ok(()) => format!("Copied {} to the clipboard", what),
+            Err(e) => format!("Copy failed: {}", e),
+        });
+        true
+    }
+
+    // The services list as plain text, the title followed by the services shown
+    // with the same columns and badges as on screen
+    pub(super) fn view_snapshot(&mut self) -> String {
+        let shown = self.get_filtered_services().len();
+        let lines = service_list_lines(self, 0..shown);
+        let title = services_list_title(self, lines.len());
+        std::iter::once(&title)
+            .chain(&lines)
+            .map(|line| line_text(line).trim_end().to_string() + "\n")
+            .collect()
+    }
+
+    pub(super) fn copy_view_snapshot(&mut self) {
+        let snapshot = self.view_snapshot();
+        self.status_message = Some(match self.copy_to_clipboard(snapshot) {
+            Ok(()) => "Copied the services list to the clipboard".to_string(),
+            Err(e) => format!("Copy failed: {}", e),
+        });
+    }
+
+    pub(super) fn write_view_snapshot(&mut self) {
+        let path = format!(
+            "mdns-view-{}.txt",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        );
+        let snapshot = self.view_snapshot();
+        self.status_message = Some(match std::fs::write(&path, snapshot) {
+            Ok(()) => format!("Wrote the services list to {}", path),
+            Err(e) => format!("Writing the services list failed: {}", e),
+        });
+    }
+
+    pub(super) fn open_selected_service_in_browser(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        let Some(url) = service_url(service, self.address_preference()) else {
+            self.status_message = Some(format!(
+                "Cannot open {} services in a browser",
+                service.service_type
+            ));
+            return;
+        };
+        self.status_message = Some(match open_url(&url) {
+            Ok(()) => format!("Opened {}", url),
+            Err(e) => format!("Failed to open {}: {}", url, e),
+        });
+    }
+
+    pub(super) fn ssh_to_selected_service(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        match ssh_command(service, self.address_preference()) {
+            Some(command) => self.pending_external_command = Some(command),
+            None => {
+                self.status_message =
+                    Some(format!("Cannot ssh to {} services", service.service_type))
+            }
+        }
+    }
+
+    pub(super) fn selected_service_actions(&mut self) -> Vec<ActionConfig> {
+        self.selected_service_index()
+            .map(|idx| {
+                self.config
+                    .actions_for(&self.services[idx].service_type)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(super) fn open_actions_popup(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        if self.selected_service_actions().is_empty() {
+            self.status_message = Some(format!(
+                "No actions configured for {}",
+                self.services[service_idx].service_type
+            ));
+        } else {
+            self.show_actions_popup = true;
+        }
+    }
+
+    pub(super) fn selected_service_copy_commands(&mut self) -> Vec<CopyCommandConfig> {
+        self.selected_service_index()
+            .map(|idx| {
+                self.config
+                    .copy_commands_for(&self.services[idx].service_type)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub(super) fn open_copy_commands_popup(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        if self.selected_service_copy_commands().is_empty() {
+            self.status_message = Some(format!(
+                "No copy commands configured for {}",
+                self.services[service_idx].service_type
+            ));
+        } else {
+            self.show_copy_commands_popup = true;
+        }
+    }
+
+    pub(super) fn copy_selected_service_command(&mut self, command_idx: usize) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let Some(copy_command) = self
+            .selected_service_copy_commands()
+            .into_iter()
+            .nth(command_idx)
+        else {
+            return;
+        };
+        let command = match expand_action_command(
+            &copy_command.command,
+            &self.services[service_idx],
+            self.address_preference(),
+        ) {
+            Ok(command) => command.to_shell_line(),
+            Err(e) => {
+                self.status_message =
+                    Some(format!("Invalid command for {}: {}", copy_command.name, e));
+                return;
+            }
+        };
+        self.status_message = Some(match self.copy_to_clipboard(command.clone()) {
+            Ok(()) => format!("Copied {}", command),
+            Err(e) => format!("Copy failed: {}", e),
+        });
+    }
+
+    pub(super) fn run_selected_service_action(&mut self, action_idx: usize) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let Some(action) = self.selected_service_actions().into_iter().nth(action_idx) else {
+            return;
+        };
+        let command = match expand_action_command(
+            &action.command,
+            &self.services[service_idx],
+            self.address_preference(),
+        ) {
+            Ok(command) => command,
+            Err(e) => {
+                self.status_message = Some(format!("Invalid command for {}: {}", action.name, e));
+                return;
+            }
+        };
+        if action.terminal {
+            self.pending_external_command = Some(command);
+        } else {
+            self.status_message = Some(match spawn_background_command(&command) {
+                Ok(()) => format!("Started {}", command),
+                Err(e) => format!("Failed to run {}: {}", command.program, e),
+            });
+        }
+    }
+
+    // Addresses of all services not looked up yet, marked as pending
+    pub(super) fn take_reverse_dns_lookups(&mut self) -> Vec<String> {
+        if !self.reverse_dns_enabled {
+            return Vec::new();
+        }
+        let mut addresses = Vec::new();
+        for address in self.services.iter().flat_map(|s| s.addrs.iter()) {
+            if !self.reverse_dns.contains_key(address) {
+                self.reverse_dns.insert(address.clone(), None);
+                addresses.push(address.clone());
+            }
+        }
+        addresses
+    }
+
+    fn copy_to_clipboard(&mut self, text: String) -> Result<(), arboard::Error> {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new()?),
+        };
+        clipboard.set_text(text)
+    }
+
+    pub(super) fn remove_marked_offline_services(&mut self) {
+        let marked = std::mem::take(&mut self.marked_services);
+        self.remove_offline_services_matching(|s| marked.contains(&s.fullname));
+        // Only keep marks of services that are still around
+        let listed: HashSet<&str> = self.services.iter().map(|s| s.fullname.as_str()).collect();
+        self.marked_services = marked
+            .into_iter()
+            .filter(|fullname| listed.contains(fullname.as_str()))
+            .collect();
+    }
+
+    pub(super) fn watch_marked_services(&mut self, watch: bool) {
+        let count = self.marked_services.len();
+        for fullname in &self.marked_services {
+            if watch {
+                self.watched_services.insert(fullname.clone());
+            } else {
+                self.watched_services.remove(fullname);
+            }
+        }
+        self.status_message = Some(if watch {
+            format!("Added {} services to the watch list", count)
+        } else {
+            format!("Removed {} services from the watch list", count)
+        });
+    }
+
+    pub(super) fn toggle_help(&mut self) {
+        self.show_help_popup = !self.show_help_popup;
+    }
+
+    pub(super) fn update_metric(&mut self, key: &str) {
+        *self.metrics.entry(key.to_string()).or_insert(0) += 1;
+    }
+
+    pub(super) fn update_metric_by(&mut self, key: &str, value: u64) {
+        *self.metrics.entry(key.to_string()).or_insert(0) += value;
+    }
+
+    pub(super) fn update_daemon_metrics(
+        &mut self,
+        daemon_metrics: &std::collections::HashMap<String, i64>,
+    ) -> bool {
+        let mut metrics_updated = false;
+        for (key, value) in daemon_metrics.iter() {
+            let metric_key = format!("daemon_{}", key.replace('-', "_"));
+            let current_value = *self.metrics.entry(metric_key.clone()).or_insert(0);
+            if current_value != *value as u64 {
+                *self.metrics.get_mut(&metric_key).unwrap() = *value as u64;
+                metrics_updated = true;
+            }
+        }
+        // Return whether metrics changed
+        metrics_updated
+    }
+
+    pub(super) fn toggle_metrics(&mut self) {
+        self.show_metrics_popup = !self.show_metrics_popup;
+    }
+
+    // Keeps the error for the error console and shows it in the status bar
+    pub(super) fn report_error(&mut self, message: String) {
+        tracing::error!("{}", message);
+        if self.errors.len() == MAX_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(ErrorEntry {
+            at: chrono::Local::now(),
+            message: message.clone(),
+        });
+        self.unseen_errors += 1;
+        self.status_message = Some(message);
+    }
+
+    pub(super) fn toggle_errors(&mut self) {
+        self.show_errors_popup = !self.show_errors_popup;
+        self.unseen_errors = 0;
+    }
+
+    pub(super) fn add_or_update_service(&mut self, mut service_entry: ServiceEntry) -> bool {
+        if service_entry.online {
+            self.remembered_services.remove(&service_entry.fullname);
+        }
+        self.services.intern(&mut service_entry);
+        // Subtypes are found by browsing them, keep them when the parent type's browse
+        // resolves the same instance
+        if let Some(subtype) = &service_entry.subtype {
+            self.add_service_type(&subtype.clone());
+        }
+        service_entry.interfaces =
+            interfaces::interfaces_for_addresses(&service_entry.addrs, &self.local_interfaces);
+        if !service_entry.host.is_empty() {
+            self.instance_targets
+                .entry(service_entry.fullname.to_lowercase())
+                .or_default()
+                .insert(
+                    format!("{}:{}", service_entry.host, service_entry.port).to_lowercase(),
+                    std::time::Instant::now(),
+                );
+        }
+        if let Some(idx) = self
+            .services
+            .position_at(service_entry.agent.as_deref(), &service_entry.fullname)
+        {
+            let mut existing = self.services.get_mut(idx);
+            if service_entry.subtype.is_none() {
+                service_entry.subtype = existing.subtype.clone();
+            }
+
+            let event_kind = if !existing.online && service_entry.online {
+                EventKind::Added
+            } else {
+                EventKind::Updated
+            };
+            // Check if any significant fields have changed
+            let significant_fields_changed = existing.host != service_entry.host
+                || existing.service_type != service_entry.service_type
+                || existing.subtype != service_entry.subtype
+                || existing.addrs != service_entry.addrs
+                || existing.port != service_entry.port
+                || existing.txt != service_entry.txt
+                || existing.agent != service_entry.agent
+                || existing.online != service_entry.online; // Include online in significant changes
+
+            if existing.txt != service_entry.txt {
+                let history = self
+                    .txt_history
+                    .entry(service_entry.fullname.clone())
+                    .or_default();
+                if history.len() == MAX_TXT_HISTORY {
+                    history.remove(0);
+                }
+                history.push(TxtChange {
+                    timestamp_micros: service_entry.last_updated_micros,
+                    changes: diff_txt(&existing.txt, &service_entry.txt),
+                });
+            }
+            existing.last_seen_micros = service_entry.last_seen_micros;
+            existing.refresh_count += 1;
+            service_entry.refresh_count = existing.refresh_count;
+            // Resolutions by the daemon come without SRV priority and weight
+            service_entry.srv_priority = service_entry.srv_priority.or(existing.srv_priority);
+            service_entry.srv_weight = service_entry.srv_weight.or(existing.srv_weight);
+            existing.srv_priority = service_entry.srv_priority;
+            existing.srv_weight = service_entry.srv_weight;
+            if significant_fields_changed {
+                let event = discovery_event(event_kind, &service_entry);
+                let first_seen_micros = existing.first_seen_micros;
+                let probe = std::mem::take(&mut existing.probe);
+                drop(existing);
+                // Replaced where it is listed
+                self.services.push(ServiceEntry {
+                    first_seen_micros,
+                    probe,
+                    ..service_entry
+                });
+                self.update_metric("services_updated");
+                self.learn_mac_address(idx);
+                self.publish_event(event);
+            }
+            true
+        } else {
+            service_entry.refresh_count = 1;
+            // Ensure service type exists for filtering purposes
+            self.add_service_type(&service_entry.service_type);
+            self.publish_event(discovery_event(EventKind::Added, &service_entry));
+            self.services.push(service_entry);
+            self.update_metric("services_discovered");
+            self.learn_mac_address(self.services.len() - 1);
+            false
+        }
+    }
+
+    // Lists the services of earlier sessions as offline since they were last seen
+    pub(super) fn restore_history(&mut self, entries: Vec<HistoryEntry>) {
+        self.loaded_history = entries.clone();
+        for entry in entries.into_iter().take(MAX_SERVICES) {
+            if self.services.contains(&entry.service.fullname) {
+                continue;
+            }
+            let mut service = ServiceEntry {
+                first_seen_micros: entry.first_seen_micros,
+                last_seen_micros: entry.last_seen_micros,
+                ..ServiceEntry::from(entry.service)
+            };
+            service.go_offline_at(entry.last_seen_micros);
+            service.interfaces =
+                interfaces::interfaces_for_addresses(&service.addrs, &self.local_interfaces);
+            self.add_service_type(&service.service_type);
+            if let Some(subtype) = &service.subtype {
+                self.add_service_type(&subtype.clone());
+            }
+            self.remembered_services.insert(service.fullname.clone());
+            self.services.push(service);
+        }
+        self.invalidate_cache_and_validate();
+    }
+
+    // The services to remember for the next session: the loaded history updated
+    // by the services of this session, the ones of remote agents belong to their
+    // networks
+    pub(super) fn history_entries(&self) -> Vec<HistoryEntry> {
+        let mut entries: HashMap<&str, HistoryEntry> = self
+            .loaded_history
+            .iter()
+            .map(|entry| (entry.service.fullname.as_str(), entry.clone()))
+            .collect();
+        for service in self.services.iter().filter(|s| s.agent.is_none()) {
+            let first_seen_micros = entries
+                .get(service.fullname.as_str())
+                .map_or(service.first_seen_micros, |earlier| {
+                    earlier.first_seen_micros.min(service.first_seen_micros)
+                });
+            entries.insert(
+                &service.fullname,
+                HistoryEntry {
+                    service: recorded_service(service),
+                    first_seen_micros,
+                    last_seen_micros: service.last_seen_micros,
+                },
+            );
+        }
+        entries.into_values().collect()
+    }
+
+    pub(super) fn record_session_event(&self, event: SessionEvent) {
+        if let Some(recorder) = &self.session_recorder {
+            recorder.record(event, current_timestamp_micros());
+        }
+    }
+
+    // Applies an event of a recorded session as the live discovery would have
+    pub(super) fn apply_session_event(&mut self, event: SessionEvent) {
+        match event {
+            SessionEvent::TypeFound { service_type } => {
+                if self.add_service_type(&service_type) {
+                    self.update_metric("service_types_discovered");
+                }
+            }
+            SessionEvent::TypeRemoved { service_type } => {
+                self.remove_service_type(&service_type);
+            }
+            SessionEvent::Resolved(service) => {
+                self.ingest_resolved_service(ServiceEntry::from(service))
+            }
+            SessionEvent::Removed { fullname } => {
+                self.mark_service_offline(&fullname);
+            }
+        }
+    }
+
+    // Applies an event of a remote agent, its services are listed apart from the
+    // same named ones of other agents unless merged, and only its own removals
+    // take them offline
+    pub(super) fn apply_remote_event(&mut self, agent: &str, event: SessionEvent) {
+        match event {
+            SessionEvent::Resolved(service) => self.ingest_resolved_service(ServiceEntry {
+                agent: Some(agent.to_string()),
+                ..ServiceEntry::from(service)
+            }),
+            SessionEvent::Removed { fullname } => {
+                if let Some(idx) = self
+                    .services
+                    .position_at(Some(agent), &fullname)
+                    .filter(|&idx| self.services[idx].agent.as_deref() == Some(agent))
+                {
+                    self.mark_listed_service_offline(idx);
+                }
+            }
+            event => self.apply_session_event(event),
+        }
+    }
+
+    // Nobody listening is fine, the outputs are optional
+    fn publish_event(&self, event: DiscoveryEvent) {
+        tracing::debug!(event = event.event.as_str(), fullname = %event.fullname, "service changed");
+        let _ = self.discovery_events.send(event);
+    }
+
+    // Adds or updates a service resolved by any of the discovery backends
+    pub(super) fn ingest_resolved_service(&mut self, entry: ServiceEntry) {
+        let fullname = entry.fullname.clone();
+        let agent = entry.agent.clone();
+        self.add_or_update_service(entry);
+        if let Some(idx) = self.services.position_at(agent.as_deref(), &fullname) {
+            self.place_in_filtered_list(idx);
+        }
+        self.validate_selected_type();
+    }
+
+    // Applies the merged updates of the browses, returns whether any changed the
+    // services. New services beyond the limit are dropped and counted
+    #[tracing::instrument(level = "debug", skip_all, fields(updates = batch.updates.len(), merged = batch.merged))]
+    pub(super) fn apply_discovery_updates(&mut self, batch: UpdateBatch) -> bool {
+        if batch.merged > 0 {
+            self.update_metric_by("discovery_updates_merged", batch.merged);
+        }
+        let mut changed = false;
+        for update in batch.updates {
+            match update {
+                DiscoveryUpdate::Removed(fullname) => {
+                    self.record_session_event(SessionEvent::Removed {
+                        fullname: fullname.clone(),
+                    });
+                    changed |= self.mark_service_offline(&fullname);
+                }
+                DiscoveryUpdate::Resolved(entry) => {
+                    if self.services.len() >= MAX_SERVICES
+                        && !self.services.contains(&entry.fullname)
+                    {
+                        tracing::warn!(fullname = %entry.fullname, "service dropped");
+                        self.update_metric("services_dropped");
+                        self.status_message = Some(format!(
+                            "More than {} services, new ones are ignored",
+                            MAX_SERVICES
+                        ));
+                        continue;
+                    }
+                    self.record_session_event(SessionEvent::Resolved(recorded_service(&entry)));
+                    self.ingest_resolved_service(*entry);
+                    changed = true;
+                }
+                DiscoveryUpdate::BrowseEnded {
+                    service_type,
+                    generation,
+                } => {
+                    let now = std::time::Instant::now();
+                    if let Some(retry) = self.browse_manager.ended(&service_type, generation, now) {
+                        self.update_metric("browses_lost");
+                        self.note_browse_failure(&service_type, "browse ended", retry);
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    // Shows why a browse failed and when it restarts; a type failing again and
+    // again is usually invalid and is removed from the list
+    pub(super) fn note_browse_failure(&mut self, service_type: &str, error: &str, retry: Retry) {
+        self.update_metric("browse_failures");
+        let message = match retry {
+            Retry::After(delay) => format!(
+                "Cannot browse {}: {}, retrying in {}s",
+                service_type,
+                error,
+                delay.as_secs()
+            ),
+            Retry::GiveUp => {
+                self.browse_manager.forget(service_type);
+                self.remove_service_type(service_type);
+                self.update_metric("browses_given_up");
+                format!("Cannot browse {}: {}, giving up", service_type, error)
+            }
+        };
+        match error::socket_hint(error) {
+            Some(hint) => self.report_error(format!("{}; {}", message, hint)),
+            None => self.report_error(message),
+        }
+    }
+
+    // Remembers the MAC address of a host, advertised by any of its services or
+    // taken from the neighbour table, so it can be woken up later
+    // MACs in the records of the service are learned right away, the hosts
+    // without a known one are left to the MAC task to look up in the neighbour
+    // table, off the state lock
+    fn learn_mac_address(&mut self, idx: usize) {
+        let service = &self.services[idx];
+        if let Some(mac) = mac_from_service_record(service) {
+            let host = Arc::clone(&service.host);
+            self.unsaved_macs |= self.mac_store.insert(&host, &mac);
+        } else if !service.addrs.is_empty() && self.mac_store.get(&service.host).is_none() {
+            self.mac_lookups
+                .insert(service.host.to_string(), service.addrs.clone());
+        }
+    }
+
+    pub(super) fn take_mac_lookups(&mut self) -> HashMap<String, Vec<String>> {
+        std::mem::take(&mut self.mac_lookups)
+    }
+
+    // Keeps the MACs the neighbour table had for hosts, the ones of their
+    // records learned meanwhile win
+    pub(super) fn learn_mac_addresses(&mut self, macs: Vec<(String, MacAddress)>) {
+        for (host, mac) in macs {
+            if self.mac_store.get(&host).is_none() {
+                self.unsaved_macs |= self.mac_store.insert(&host, &mac);
+            }
+        }
+    }
+
+    // A copy of the MACs to save when some were learned since the last save
+    pub(super) fn take_unsaved_macs(&mut self) -> Option<MacStore> {
+        std::mem::take(&mut self.unsaved_macs).then(|| self.mac_store.clone())
+    }
+
+    pub(super) fn wake_selected_service_host(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        if service.online {
+            self.status_message = Some(format!("{} is online", service.host));
+            return;
+        }
+        let Some(mac) = self.mac_store.get(&service.host) else {
+            self.status_message = Some(format!("No MAC address known for {}", service.host));
+            return;
+        };
+        self.status_message = Some(match wake_on_lan::send_magic_packet(&mac) {
+            Ok(()) => format!(
+                "Sent wake-on-LAN packet to {} ({})",
+                service.host,
+                wake_on_lan::format_mac(&mac)
+            ),
+            Err(e) => format!("Failed to send wake-on-LAN packet: {}", e),
+        });
+    }
+
+    pub(super) fn mark_service_offline(&mut self, fullname: &str) -> bool {
+        match self.services.position(fullname) {
+            Some(idx) => {
+                self.mark_listed_service_offline(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn mark_listed_service_offline(&mut self, idx: usize) {
+        // Only count as removed if the service was online
+        let was_online = self.services[idx].online;
+        self.services
+            .get_mut(idx)
+            .go_offline_at(current_timestamp_micros());
+        if was_online {
+            self.update_metric("services_marked_offline");
+            self.publish_event(discovery_event(EventKind::Removed, &self.services[idx]));
+        }
+        self.place_in_filtered_list(idx);
+        self.validate_selected_type();
+    }
+
+    pub(super) fn observe_records(
+        &mut self,
+        answers: &[mdns_query::MdnsAnswer],
+        now: std::time::Instant,
+    ) {
+        // Re-announcements and answers to the daemon's queries show the service is alive
+        let now_micros = current_timestamp_micros();
+        let announced: HashSet<String> = answers
+            .iter()
+            .filter(|answer| answer.record.ttl() > 0)
+            .filter_map(|answer| match answer.record.data() {
+                RData::SRV(_) | RData::TXT(_) => Some(answer.record.name()),
+                RData::PTR(ptr) => Some(&ptr.0),
+                _ => None,
+            })
+            .map(|name| unicast_dns_sd::name_to_string(name).to_lowercase())
+            .collect();
+        for name in &announced {
+            for idx in self.services.positions_ignoring_case(name) {
+                let mut service = self.services.get_mut(idx);
+                if service.online {
+                    service.last_seen_micros = now_micros;
+                    service.refresh_count += 1;
+                }
+                drop(service);
+                self.place_in_filtered_list(idx);
+            }
+        }
+        for answer in answers {
+            if let RData::SRV(srv) = answer.record.data()
+                && answer.record.ttl() > 0
+            {
+                let name = unicast_dns_sd::name_to_string(answer.record.name()).to_lowercase();
+                for idx in self.services.positions_ignoring_case(&name) {
+                    let mut service = self.services.get_mut(idx);
+                    service.srv_priority = Some(srv.priority());
+                    service.srv_weight = Some(srv.weight());
+                    drop(service);
+                    self.place_in_filtered_list(idx);
+                }
+            }
+        }
+
+        for answer in answers {
+            let record = &answer.record;
+            // Services are known by their raw names, Display would escape spaces
+            let data = match record.data() {
+                RData::PTR(ptr) => unicast_dns_sd::name_to_string(&ptr.0),
+                data => data.to_string(),
+            };
+            let name = unicast_dns_sd::name_to_string(record.name()).to_lowercase();
+            self.observed_records.insert(
+                name,
+                record.record_type(),
+                data.to_lowercase(),
+                RecordTtl {
+                    ttl: record.ttl(),
+                    received: now,
+                },
+                u16::from(record.dns_class()) & MDNS_CACHE_FLUSH != 0,
+            );
+        }
+    }
+
+    // Forgets records that expired a while ago, so the map doesn't grow forever
+    pub(super) fn prune_observed_records(&mut self, now: std::time::Instant) {
+        self.observed_records.retain(|record| {
+            now.duration_since(record.received)
+                < Duration::from_secs(u64::from(record.ttl)) + OBSERVED_RECORD_RETENTION
+        });
+    }
+
+    // Asks for the SRV, TXT and address records of just the selected instance again
+    pub(super) fn re_resolve_selected_service(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service = &self.services[service_idx];
+        if self.re_resolving.contains_key(&service.fullname) {
+            return;
+        }
+        self.pending_requests.push(BackgroundRequest::ReResolve {
+            fullname: service.fullname.clone(),
+            service_type: service.service_type.to_string(),
+        });
+        self.re_resolving
+            .insert(service.fullname.clone(), std::time::Instant::now());
+    }
+
+    // The different "host:port" targets an online instance was announced with recently,
+    // by different hosts or by one host contradicting itself, empty without a conflict
+    pub(super) fn conflicting_targets(
+        &self,
+        service: &ServiceEntry,
+        now: std::time::Instant,
+    ) -> Vec<String> {
+        if !service.online {
+            return Vec::new();
+        }
+        let fullname = service.fullname.to_lowercase();
+        let mut targets: BTreeSet<String> = self
+            .instance_targets
+            .get(&fullname)
+            .into_iter()
+            .flatten()
+            .filter(|(_, seen)| now.duration_since(**seen) < CONFLICT_WINDOW)
+            .map(|(target, _)| target.clone())
+            .collect();
+        for (record_type, data, record) in self.observed_records.of_name(&fullname) {
+            let alive =
+                now.duration_since(record.received) < Duration::from_secs(u64::from(record.ttl));
+            if record_type == RecordType::SRV && alive {
+                // "priority weight port target"
+                if let [_, _, port, target] = data.split(' ').collect::<Vec<_>>()[..] {
+                    targets.insert(format!("{}:{}", target, port));
+                }
+            }
+        }
+        if targets.len() > 1 {
+            targets.into_iter().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Online but neither announced nor resolved within the configured window, devices
+    // that vanish abruptly never send a goodbye
+    pub(super) fn is_stale(&self, service: &ServiceEntry, now_micros: u64) -> bool {
+        let window_micros = self.config.stale_after_secs.saturating_mul(1_000_000);
+        service.online
+            && window_micros > 0
+            && now_micros.saturating_sub(service.last_seen_micros) > window_micros
+    }
+
+    // Index into `services` of the currently selected entry in the filtered list
+    pub(super) fn selected_service_index(&mut self) -> Option<usize> {
+        let selected = self.selected_service;
+        self.get_filtered_services().get(selected).copied()
+    }
+
+    // Selects the first listed service of the name, of whichever agent
+    pub(super) fn select_service_by_fullname(&mut self, fullname: &str) -> bool {
+        // Refresh the filtered cache before looking the service up
+        self.get_filtered_services();
+        let position = self
+            .cached_filtered_services
+            .rows()
+            .iter()
+            .position(|&idx| self.services[idx].fullname == fullname);
+        if let Some(position) = position {
+            self.selected_service = position;
+            self.update_services_scroll_offset();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Selects the type of the selected service in the left panel, keeping the service selected
+    pub(super) fn jump_to_selected_service_type(&mut self) {
+        let Some(service_idx) = self.selected_service_index() else {
+            return;
+        };
+        let service_type = self.services[service_idx].service_type.clone();
+        let fullname = self.services[service_idx].fullname.clone();
+        let Some(type_idx) = self.service_types.iter().position(|t| **t == *service_type) else {
+            return;
+        };
+
+        if type_idx < self.types_scroll_offset {
+            self.types_scroll_offset = type_idx;
+        } else if self.visible_types > 0
+            && type_idx >= self.types_scroll_offset + self.visible_types
+        {
+            self.types_scroll_offset = type_idx - self.visible_types + 1;
+        }
+        self.update_service_type_selection(Some(type_idx));
+        self.select_service_by_fullname(&fullname);
+    }
+
+    pub(super) fn update_services_scroll_offset(&mut self) {
+        if self.selected_service < self.services_scroll_offset {
+            self.services_scroll_offset = self.selected_service;
+        } else if self.visible_services > 0
+            && self.selected_service >= self.services_scroll_offset + self.visible_services
+        {
+            self.services_scroll_offset = self.selected_service - self.visible_services + 1;
+        }
+    }
+}
+
+// Order of two services by a field alone, as the list files them
+#[cfg(test)]
+pub(super) fn compare_services_by_field(
+    a: &ServiceEntry,
+    b: &ServiceEntry,
+    field: SortField,
+) -> std::cmp::Ordering {
+    FieldKey::new(a, field).cmp(&FieldKey::new(b, field))
+}
+
+// Search in all service fields case-insensitively, `query` must already be lowercase
+pub(super) fn service_matches_query(service: &ServiceEntry, query: &str) -> bool {
+    let search_text = [
+        service.fullname.clone(),
+        service.host.to_string(),
+        service.service_type.to_string(),
+        service.addrs.join(" "),
+        service.port.to_string(),
+        service.txt.join(" "),
+        service.subtype.as_ref().unwrap_or(&String::new()).clone(),
+        service.interfaces.join(" "),
+        match (service.srv_priority, service.srv_weight) {
+            (Some(priority), Some(weight)) => format!("priority={} weight={}", priority, weight),
+            _ => String::new(),
+        },
+        device_category::classify(&service.service_type, &service.txt)
+            .map(|category| category.label().to_string())
+            .unwrap_or_default(),
+    ]
+    .join(" ")
+    .to_lowercase();
+    search_text.contains(query)
+}
+
+// When grouping by status is enabled, online services always sort before offline ones
+pub(super) fn has_address_of_family(service: &ServiceEntry, family: AddressFamily) -> bool {
+    service
+        .addrs
+        .iter()
+        .filter_map(|addr| parse_address(addr))
+        .any(|ip| match family {
+            AddressFamily::Any => true,
+            AddressFamily::Ipv4 => ip.is_ipv4(),
+            AddressFamily::Ipv6 => ip.is_ipv6(),
+        })
+}
+
+// MACs advertised in TXT records, e.g. AirPlay's deviceid, or in instance names
+// like "host [aa:bb:cc:dd:ee:ff]" of _workstation._tcp and "AABBCCDDEEFF@name" of _raop._tcp
+pub(super) fn mac_from_service_record(service: &ServiceEntry) -> Option<MacAddress> {
+    const MAC_KEYS: [&str; 5] = ["mac", "macaddress", "mac_address", "hwaddr", "deviceid"];
+
+    let from_txt = service.txt.iter().find_map(|entry| {
+        let (key, value) = entry.split_once('=')?;
+        if MAC_KEYS.contains(&key.to_lowercase().as_str()) {
+            wake_on_lan::parse_mac(value)
+        } else {
+            None
+        }
+    });
+    let instance = service
+        .fullname
+        .strip_suffix(&*service.service_type)
+        .unwrap_or(&service.fullname)
+        .trim_end_matches('.');
+    from_txt
+        .or_else(|| {
+            let (_, bracketed) = instance.rsplit_once('[')?;
+            wake_on_lan::parse_mac(bracketed.strip_suffix(']')?)
+        })
+        .or_else(|| wake_on_lan::parse_mac(instance.split_once('@')?.0))
+}
+
+// Services with an address of the preferred family sort before the ones without
+// Whether an address resolves back to names, none of which is the advertised host
+pub(super) fn has_reverse_dns_mismatch(
+    service: &ServiceEntry,
+    reverse_dns: &HashMap<String, Option<Vec<String>>>,
+) -> bool {
+    let host = reverse_dns::normalize_name(&service.host);
+    service.addrs.iter().any(|address| {
+        matches!(reverse_dns.get(address), Some(Some(names)) if !names.is_empty() && !names.contains(&host))
+    })
+}
+
+// A resolved service as recorded to the session file, before it is merged
+pub(super) fn recorded_service(entry: &ServiceEntry) -> RecordedService {
+    RecordedService {
+        fullname: entry.fullname.clone(),
+        service_type: entry.service_type.to_string(),
+        subtype: entry.subtype.clone(),
+        host: entry.host.to_string(),
+        port: entry.port,
+        addrs: entry.addrs.clone(),
+        txt: entry.txt.clone(),
+        srv_priority: entry.srv_priority,
+        srv_weight: entry.srv_weight,
+    }
+}
+
+fn discovery_event(event: EventKind, service: &ServiceEntry) -> DiscoveryEvent {
+    DiscoveryEvent {
+        schema_version: SchemaVersion,
+        event,
+        fullname: service.fullname.clone(),
+        service_type: service.service_type.to_string(),
+        host: service.host.to_string(),
+        port: service.port,
+        addrs: service.addrs.clone(),
+        txt: service.txt.clone(),
+        timestamp_micros: service.last_updated_micros,
+    }
+}
+
+// Changed, added and removed keys between two TXT records, sorted by key
+pub(super) fn diff_txt(
+    old: &[String],
+    new: &[String],
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let split = |txt: &[String]| -> BTreeMap<String, String> {
+        txt.iter()
+            .map(|entry| match entry.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (entry.clone(), String::new()),
+            })
+            .collect()
+    };
+    let (old, new) = (split(old), split(new));
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| (key.clone(), old.get(key).cloned(), new.get(key).cloned()))
+        .collect()
+}
@@ -0,0 +1,236 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use std::ops::{Deref, DerefMut, Index};
+
+// The services in the order they are listed in, indexed by agent and fullname
+// so the events of bursty networks find theirs without scanning all of them.
+// Reads go through the slice, changes through the store, which keeps the index
+// in step, also when an entry changed in place got another fullname or agent
+#[derive(Clone, Debug, Default)]
+pub(super) struct ServiceStore {
+    entries: Vec<ServiceEntry>,
+    // Position in entries by agent, then fullname. Local services are kept
+    // under "", like the ones of all agents when they are merged
+    positions: HashMap<String, HashMap<String, usize>>,
+    // Positions by lowercase fullname over all agents, for the records seen on
+    // the network, whose names may differ in case from the resolved ones
+    lowercase_positions: HashMap<String, Vec<usize>>,
+    // Services of the same fullname reported by several agents are one entry
+    merge_agents: bool,
+    // Hosts and types of the entries, shared by all entries with the same one so
+    // they are kept once and compare by pointer
+    strings: HashSet<Arc<str>>,
+}
+
+impl ServiceStore {
+    // Only takes effect for the services added after
+    pub(super) fn set_merge_agents(&mut self, merge: bool) {
+        self.merge_agents = merge;
+        self.reindex();
+    }
+
+    // The local service of the name
+    #[cfg(test)]
+    pub(super) fn get(&self, fullname: &str) -> Option<&ServiceEntry> {
+        self.position(fullname).map(|idx| &self.entries[idx])
+    }
+
+    // Panics if the index is out of bounds, like indexing does
+    pub(super) fn get_mut(&mut self, idx: usize) -> EntryMut<'_> {
+        assert!(idx < self.entries.len(), "no service at {}", idx);
+        EntryMut { store: self, idx }
+    }
+
+    pub(super) fn get_mut_at(
+        &mut self,
+        agent: Option<&str>,
+        fullname: &str,
+    ) -> Option<EntryMut<'_>> {
+        let idx = self.position_at(agent, fullname)?;
+        Some(EntryMut { store: self, idx })
+    }
+
+    // Changes every entry in place
+    pub(super) fn update_all(&mut self, mut update: impl FnMut(&mut ServiceEntry)) {
+        let mut moved = false;
+        for idx in 0..self.entries.len() {
+            update(&mut self.entries[idx]);
+            moved |= self.settle(idx);
+        }
+        if moved {
+            self.reindex();
+        }
+    }
+
+    // Interns the host and type of an entry changed in place again, returns
+    // whether it is no longer where the index has it
+    fn settle(&mut self, idx: usize) -> bool {
+        let host = Arc::clone(&self.entries[idx].host);
+        self.entries[idx].host = self.intern_str(&host);
+        let service_type = Arc::clone(&self.entries[idx].service_type);
+        self.entries[idx].service_type = self.intern_str(&service_type);
+        let entry = &self.entries[idx];
+        self.position_at(entry.agent.as_deref(), &entry.fullname) != Some(idx)
+    }
+
+    pub(super) fn position(&self, fullname: &str) -> Option<usize> {
+        self.position_at(None, fullname)
+    }
+
+    pub(super) fn position_at(&self, agent: Option<&str>, fullname: &str) -> Option<usize> {
+        self.positions
+            .get(agent_key(agent, self.merge_agents))?
+            .get(fullname)
+            .copied()
+    }
+
+    // The entries of a lowercase fullname, of any agent
+    pub(super) fn positions_ignoring_case(&self, lowercase_fullname: &str) -> Vec<usize> {
+        self.lowercase_positions
+            .get(lowercase_fullname)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub(super) fn contains(&self, fullname: &str) -> bool {
+        self.position(fullname).is_some()
+    }
+
+    // Points the host and type of the entry at the ones already kept
+    pub(super) fn intern(&mut self, entry: &mut ServiceEntry) {
+        entry.host = self.intern_str(&entry.host);
+        entry.service_type = self.intern_str(&entry.service_type);
+    }
+
+    fn intern_str(&mut self, value: &Arc<str>) -> Arc<str> {
+        match self.strings.get(value) {
+            Some(interned) => Arc::clone(interned),
+            None => {
+                self.strings.insert(Arc::clone(value));
+                Arc::clone(value)
+            }
+        }
+    }
+
+    // Appends a service, or replaces the one of the same agent and name where it is
+    pub(super) fn push(&mut self, mut entry: ServiceEntry) {
+        self.intern(&mut entry);
+        match self.position_at(entry.agent.as_deref(), &entry.fullname) {
+            Some(idx) => self.entries[idx] = entry,
+            None => {
+                self.positions
+                    .entry(agent_key(entry.agent.as_deref(), self.merge_agents).to_string())
+                    .or_default()
+                    .insert(entry.fullname.clone(), self.entries.len());
+                self.lowercase_positions
+                    .entry(entry.fullname.to_lowercase())
+                    .or_default()
+                    .push(self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+
+    pub(super) fn retain(&mut self, keep: impl FnMut(&ServiceEntry) -> bool) {
+        let len = self.entries.len();
+        self.entries.retain(keep);
+        if self.entries.len() != len {
+            self.reindex();
+            // Strings only the set still holds belonged to removed entries
+            self.strings.retain(|value| Arc::strong_count(value) > 1);
+        }
+    }
+
+    fn reindex(&mut self) {
+        self.positions.clear();
+        self.lowercase_positions.clear();
+        for (idx, entry) in self.entries.iter().enumerate() {
+            self.positions
+                .entry(agent_key(entry.agent.as_deref(), self.merge_agents).to_string())
+                .or_default()
+                .insert(entry.fullname.clone(), idx);
+            self.lowercase_positions
+                .entry(entry.fullname.to_lowercase())
+                .or_default()
+                .push(idx);
+        }
+    }
+}
+
+fn agent_key(agent: Option<&str>, merge_agents: bool) -> &str {
+    match agent {
+        Some(agent) if !merge_agents => agent,
+        _ => "",
+    }
+}
+
+impl From<Vec<ServiceEntry>> for ServiceStore {
+    fn from(entries: Vec<ServiceEntry>) -> Self {
+        let mut store = Self::default();
+        for entry in entries {
+            store.push(entry);
+        }
+        store
+    }
+}
+
+impl Deref for ServiceStore {
+    type Target = [ServiceEntry];
+
+    fn deref(&self) -> &[ServiceEntry] {
+        &self.entries
+    }
+}
+
+// An entry borrowed for changing it in place, the store catches up with the
+// changes once it is dropped
+pub(super) struct EntryMut<'a> {
+    store: &'a mut ServiceStore,
+    idx: usize,
+}
+
+impl Deref for EntryMut<'_> {
+    type Target = ServiceEntry;
+
+    fn deref(&self) -> &ServiceEntry {
+        &self.store.entries[self.idx]
+    }
+}
+
+impl DerefMut for EntryMut<'_> {
+    fn deref_mut(&mut self) -> &mut ServiceEntry {
+        &mut self.store.entries[self.idx]
+    }
+}
+
+impl Drop for EntryMut<'_> {
+    fn drop(&mut self) {
+        if self.store.settle(self.idx) {
+            self.store.reindex();
+        }
+    }
+}
+
+impl Index<usize> for ServiceStore {
+    type Output = ServiceEntry;
+
+    fn index(&self, idx: usize) -> &ServiceEntry {
+        &self.entries[idx]
+    }
+}
+
+impl<'a> IntoIterator for &'a ServiceStore {
+    type Item = &'a ServiceEntry;
+    type IntoIter = std::slice::Iter<'a, ServiceEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl serde::Serialize for ServiceStore {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
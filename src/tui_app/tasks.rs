@@ -0,0 +1,57 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use std::future::Future;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tokio_util::task::TaskTracker;
+
+// The background tasks of a session. Shutting down cancels them together and
+// waits for them to end, instead of leaving them to the end of the process
+#[derive(Clone, Debug, Default)]
+pub(super) struct BackgroundTasks {
+    cancel: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl BackgroundTasks {
+    // Runs the task until it ends or the session shuts down
+    pub(super) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let cancel = self.cancel.clone();
+        runtime::spawn(self.tracker.track_future(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = task => {}
+            }
+        }));
+    }
+
+    // Runs a task that watches cancelled() itself, to finish its work before
+    // ending; shutting down waits for it all the same
+    pub(super) fn spawn_draining(&self, task: impl Future<Output = ()> + Send + 'static) {
+        runtime::spawn(self.tracker.track_future(task));
+    }
+
+    pub(super) fn cancelled(&self) -> WaitForCancellationFutureOwned {
+        self.cancel.clone().cancelled_owned()
+    }
+
+    // Cancels the tasks without waiting for them, e.g. when dropped
+    pub(super) fn cancel(&self) {
+        self.cancel.cancel();
+        self.tracker.close();
+    }
+
+    // Cancels the tasks and waits for them to end, at most SHUTDOWN_TIMEOUT
+    pub(super) async fn shutdown(&self) {
+        self.cancel();
+        let _ = runtime::timeout(SHUTDOWN_TIMEOUT, self.tracker.wait()).await;
+    }
+}
+
+// The shutdown sequence of a session: the tasks end first, the browses dropping
+// their receivers and the aggregation task applying what was still queued, then
+// the backend stops its browses and shuts down
+pub(super) async fn shut_down<B: DiscoveryBackend>(backend: &B, tasks: &BackgroundTasks) {
+    tasks.shutdown().await;
+    backend.shutdown();
+}
@@ -0,0 +1,185 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+use crossterm::event::EventStream;
+use futures_core::Stream;
+use ratatui::backend::Backend;
+use std::future::Future;
+use std::pin::Pin;
+
+// The terminal the event loop draws on and reads its input from, the real one
+// or a scripted one in the tests
+pub(super) trait TerminalIo {
+    type Backend: Backend;
+
+    fn terminal(&mut self) -> &mut Terminal<Self::Backend>;
+
+    fn draw(&mut self, state: &mut AppState) -> std::io::Result<()> {
+        self.terminal()
+            .draw(|f| ui(f, state))
+            .map(|_| ())
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+
+    // Waits for the next input event, None once there is no more input
+    fn next_event(&mut self) -> impl Future<Output = Result<Option<Event>, String>>;
+
+    // Hands the terminal over to an interactive child process and takes it back
+    // once the process has finished
+    fn run_external_command(
+        &mut self,
+        command: &ExternalCommand,
+    ) -> std::io::Result<std::process::ExitStatus>;
+}
+
+// The terminal of the process, in raw mode on the alternate screen until left.
+// Input is read as it arrives, so an idle session doesn't wake up to poll
+pub(super) struct CrosstermTerminal {
+    terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    // Started on the first read, stopped while a child process has the terminal
+    events: Option<EventStream>,
+}
+
+impl CrosstermTerminal {
+    pub(super) fn enter() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        Ok(Self {
+            terminal,
+            events: None,
+        })
+    }
+
+    pub(super) fn leave(mut self) -> std::io::Result<()> {
+        self.events = None;
+        disable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        self.terminal.show_cursor()
+    }
+}
+
+impl TerminalIo for CrosstermTerminal {
+    type Backend = CrosstermBackend<std::io::Stdout>;
+
+    fn terminal(&mut self) -> &mut Terminal<Self::Backend> {
+        &mut self.terminal
+    }
+
+    async fn next_event(&mut self) -> Result<Option<Event>, String> {
+        let events = self.events.get_or_insert_with(EventStream::new);
+        match std::future::poll_fn(|cx| Pin::new(&mut *events).poll_next(cx)).await {
+            Some(Ok(event)) => Ok(Some(event)),
+            Some(Err(e)) => Err(format!("Error reading event: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    fn run_external_command(
+        &mut self,
+        command: &ExternalCommand,
+    ) -> std::io::Result<std::process::ExitStatus> {
+        // Its reader would take the child's input otherwise
+        self.events = None;
+        disable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        self.terminal.show_cursor()?;
+
+        let status = std::process::Command::new(&command.program)
+            .args(&command.args)
+            .status();
+
+        enable_raw_mode()?;
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+        self.terminal.clear()?;
+
+        status
+    }
+}
+
+// Draws the state and handles the input until the user quits. Every handled
+// key is drawn right away, before the next one is read
+pub(super) async fn run_event_loop<T: TerminalIo, B: DiscoveryBackend>(
+    io: &mut T,
+    mdns: &B,
+    state: &Arc<RwLock<AppState>>,
+    tasks: &BackgroundTasks,
+    notifications: (&flume::Sender<Notification>, &flume::Receiver<Notification>),
+) -> Result<(), Error> {
+    let (notification_sender, notification_receiver) = notifications;
+
+    // Initial render to show the UI immediately
+    {
+        let mut state = state.write().await;
+        io.draw(&mut state).map_err(Error::Terminal)?;
+    }
+
+    loop {
+        tokio::select! {
+            // Handle notifications for rendering
+            _notification = notification_receiver.recv_async() => {
+                // Draw UI only when there's a notification
+                let mut state = state.write().await;
+                io.draw(&mut state).map_err(Error::Terminal)?;
+            }
+
+            // Handle user input events
+            event_result = io.next_event() => {
+                // Printing would garble the alternate screen, the console shows them
+                let event = match event_result {
+                    Ok(Some(event)) => event,
+                    // The terminal is gone, nobody is left to quit
+                    Ok(None) => return Ok(()),
+                    Err(message) => {
+                        state.write().await.report_error(message);
+                        let _ = notification_sender.try_send(Notification::ServiceChanged);
+                        continue;
+                    }
+                };
+                match event {
+                    Event::Key(key) => {
+                        #[cfg(target_os = "windows")]
+                        {
+                            // On Windows, ignore key release events to prevent duplicate handling
+                            if key.kind == crossterm::event::KeyEventKind::Release {
+                                continue;
+                            }
+                        }
+
+                        tracing::debug!(code = ?key.code, modifiers = ?key.modifiers, "key");
+                        let mut app_state = state.write().await;
+                        let should_continue = app_state.handle_key_event(key);
+                        if !should_continue {
+                            return Ok(());
+                        }
+                        for request in std::mem::take(&mut app_state.pending_requests) {
+                            tasks.spawn(handle_background_request(
+                                request,
+                                mdns.clone(),
+                                Arc::clone(state),
+                                notification_sender.clone(),
+                                tasks.clone(),
+                            ));
+                        }
+                        if let Some(command) = app_state.pending_external_command.take() {
+                            // Don't block the background tasks for the whole session
+                            drop(app_state);
+                            let message = match io.run_external_command(&command) {
+                                Ok(status) if status.success() => None,
+                                Ok(status) => Some(format!("{} exited with {}", command.program, status)),
+                                Err(e) => Some(format!("Failed to run {}: {}", command.program, e)),
+                            };
+                            state.write().await.status_message = message;
+                        }
+                    }
+                    // Redraw on terminal resize
+                    Event::Resize(_, _) => {}
+                    _ => continue,
+                }
+                let mut state = state.write().await;
+                io.draw(&mut state).map_err(Error::Terminal)?;
+            }
+        }
+    }
+}
@@ -0,0 +1,6037 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+// ServiceEntry tests
+#[test]
+fn test_service_entry_go_offline_at() {
+    let mut service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec!["192.168.1.1".to_string()],
+        port: 8080,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    assert!(service.online);
+    service.go_offline_at(2000);
+    assert!(!service.online);
+    assert_eq!(service.last_updated_micros, 2000);
+    assert_eq!(service.offline_since_micros, Some(2000));
+    assert_eq!(service.first_seen_micros, 1000);
+}
+
+// AppState initialization tests
+#[test]
+fn test_appstate_new() {
+    let state = AppState::new();
+    assert_eq!(state.services.len(), 0);
+    assert_eq!(state.service_types.len(), 0);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.selected_type, None);
+    assert_eq!(state.types_scroll_offset, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+    assert!(state.cache_dirty);
+    assert!(!state.show_help_popup);
+    assert!(!state.show_metrics_popup);
+}
+
+// Filter service tests
+#[test]
+fn test_filter_service_all_types() {
+    let mut state = AppState::new();
+    state.selected_type = None;
+
+    let service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_service_specific_type() {
+    let mut state = AppState::new();
+    state.service_types.push("_http._tcp.local.".to_string());
+    state.service_types.push("_ssh._tcp.local.".to_string());
+    state.selected_type = Some(0);
+
+    let http_service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let ssh_service = ServiceEntry {
+        fullname: "test._ssh._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_ssh._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 22,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    assert!(state.filter_service(&http_service));
+    assert!(!state.filter_service(&ssh_service));
+}
+
+// Service type management tests
+#[test]
+fn test_add_service_type() {
+    let mut state = AppState::new();
+    assert!(state.add_service_type("_http._tcp.local."));
+    assert_eq!(state.service_types.len(), 1);
+    assert_eq!(state.service_types[0], "_http._tcp.local.");
+
+    // Adding duplicate should return false
+    assert!(!state.add_service_type("_http._tcp.local."));
+    assert_eq!(state.service_types.len(), 1);
+}
+
+#[test]
+fn test_add_service_type_maintains_sort_order() {
+    let mut state = AppState::new();
+    state.add_service_type("_ssh._tcp.local.");
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_printer._tcp.local.");
+
+    assert_eq!(state.service_types[0], "_http._tcp.local.");
+    assert_eq!(state.service_types[1], "_printer._tcp.local.");
+    assert_eq!(state.service_types[2], "_ssh._tcp.local.");
+}
+
+#[test]
+fn test_add_service_type_preserves_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_ssh._tcp.local.");
+    state.add_service_type("_http._tcp.local.");
+    state.selected_type = Some(1); // _ssh._tcp.local.
+
+    // Add a new type, selection should still point to _ssh._tcp.local.
+    state.add_service_type("_printer._tcp.local.");
+    assert_eq!(state.selected_type, Some(2)); // _ssh._tcp.local. moved to index 2
+}
+
+#[test]
+fn test_remove_service_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    // Can't remove if still in use
+    state.services.push(ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    });
+
+    assert!(!state.remove_service_type("_http._tcp.local."));
+    assert_eq!(state.service_types.len(), 2);
+
+    // Can remove if not in use
+    assert!(state.remove_service_type("_ssh._tcp.local."));
+    assert_eq!(state.service_types.len(), 1);
+}
+
+#[test]
+fn test_remove_service_type_adjusts_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_printer._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = Some(1); // _printer._tcp.local.
+
+    // Remove the selected type
+    state.remove_service_type("_printer._tcp.local.");
+    // Selection should move to nearest valid index
+    assert!(state.selected_type == Some(1) || state.selected_type == Some(0));
+}
+
+// Navigation tests
+#[test]
+fn test_navigate_services_up() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("test2", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("test3", "_http._tcp.local.", 82));
+    state.selected_service = 2;
+
+    state.navigate_services_up();
+    assert_eq!(state.selected_service, 1);
+
+    state.navigate_services_up();
+    assert_eq!(state.selected_service, 0);
+
+    // Should not go below 0
+    state.navigate_services_up();
+    assert_eq!(state.selected_service, 0);
+}
+
+#[test]
+fn test_navigate_services_down() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("test2", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("test3", "_http._tcp.local.", 82));
+    state.selected_service = 0;
+
+    state.navigate_services_down();
+    assert_eq!(state.selected_service, 1);
+
+    state.navigate_services_down();
+    assert_eq!(state.selected_service, 2);
+
+    // Should not go beyond last service
+    state.navigate_services_down();
+    assert_eq!(state.selected_service, 2);
+}
+
+#[test]
+fn test_navigate_service_types_up() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = Some(1);
+
+    state.navigate_service_types_up();
+    assert_eq!(state.selected_type, Some(0));
+
+    state.navigate_service_types_up();
+    assert_eq!(state.selected_type, None); // "All Types"
+
+    // Should not go beyond "All Types"
+    state.navigate_service_types_up();
+    assert_eq!(state.selected_type, None);
+}
+
+#[test]
+fn test_navigate_service_types_down() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = None;
+
+    state.navigate_service_types_down();
+    assert_eq!(state.selected_type, Some(0));
+
+    state.navigate_service_types_down();
+    assert_eq!(state.selected_type, Some(1));
+
+    // Should not go beyond last type
+    state.navigate_service_types_down();
+    assert_eq!(state.selected_type, Some(1));
+}
+
+#[test]
+fn test_navigate_services_to_first() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("test2", "_http._tcp.local.", 81));
+    state.selected_service = 1;
+    state.services_scroll_offset = 1;
+
+    state.navigate_services_to_first();
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_navigate_services_to_last() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("test2", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("test3", "_http._tcp.local.", 82));
+    state.selected_service = 0;
+
+    state.navigate_services_to_last();
+    assert_eq!(state.selected_service, 2);
+}
+
+#[test]
+fn test_navigate_services_page_up() {
+    let mut state = AppState::new();
+    for i in 0..20 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.visible_services = 5;
+    state.selected_service = 10;
+
+    state.navigate_services_page_up();
+    assert_eq!(state.selected_service, 6); // 10 - (5-1) = 6
+
+    state.navigate_services_page_up();
+    assert_eq!(state.selected_service, 2); // 6 - (5-1) = 2
+
+    state.navigate_services_page_up();
+    assert_eq!(state.selected_service, 0); // Can't go below 0
+}
+
+#[test]
+fn test_navigate_services_page_down() {
+    let mut state = AppState::new();
+    for i in 0..20 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.visible_services = 5;
+    state.selected_service = 0;
+
+    state.navigate_services_page_down();
+    assert_eq!(state.selected_service, 4); // 0 + (5-1) = 4
+
+    state.navigate_services_page_down();
+    assert_eq!(state.selected_service, 8); // 4 + (5-1) = 8
+
+    state.selected_service = 15;
+    state.navigate_services_page_down();
+    assert_eq!(state.selected_service, 19); // Should stop at last item
+}
+
+#[test]
+fn test_count_prefix_repeats_navigation() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ipp._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    for i in 0..20 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('5')));
+    assert_eq!(state.pending_count, Some(5));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    assert_eq!(state.selected_service, 5);
+    assert_eq!(state.pending_count, None);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('1')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('0')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    assert_eq!(state.selected_service, 15);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('3')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('l')));
+    assert_eq!(state.selected_type, Some(2));
+
+    // Without a count keys act once
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('h')));
+    assert_eq!(state.selected_type, Some(1));
+}
+
+#[test]
+fn test_count_prefix_is_capped_and_cleared_by_other_keys() {
+    let mut state = AppState::new();
+    // A leading zero is not a count
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('0')));
+    assert_eq!(state.pending_count, None);
+
+    for _ in 0..6 {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char('9')));
+    }
+    assert_eq!(state.pending_count, Some(MAX_COUNT_PREFIX));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('s')));
+    assert_eq!(state.pending_count, None);
+}
+
+#[test]
+fn test_gg_and_g_jump_to_first_and_last() {
+    let mut state = AppState::new();
+    for i in 0..10 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('G')));
+    assert_eq!(state.selected_service, 9);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    assert_eq!(state.selected_service, 9); // Waiting for the second 'g'
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.pending_key, None);
+
+    // A count selects the nth service
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('4')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('G')));
+    assert_eq!(state.selected_service, 3);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('2')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    assert_eq!(state.selected_service, 1);
+}
+
+#[test]
+fn test_incomplete_g_sequence_handles_next_key() {
+    let mut state = AppState::new();
+    for i in 0..3 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    assert_eq!(state.selected_service, 1);
+    assert_eq!(state.pending_key, None);
+}
+
+#[test]
+fn test_jump_to_selected_service_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ipp._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("beta", "_ssh._tcp.local.", 22));
+    state
+        .services
+        .push(create_test_service("gamma", "_ssh._tcp.local.", 2222));
+    state.visible_types = 2;
+    state.selected_service = 2; // gamma in "All Types"
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('t')));
+    assert_eq!(state.selected_type, Some(2));
+    assert_eq!(state.types_scroll_offset, 1);
+    assert_eq!(state.get_filtered_services().len(), 2);
+    let selected = state.selected_service_index().unwrap();
+    assert_eq!(&*state.services[selected].host, "gamma.local.");
+}
+
+#[test]
+fn test_jump_to_selected_service_type_without_services() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('t')));
+    assert_eq!(state.selected_type, None);
+}
+
+// Marks and bulk action tests
+#[test]
+fn test_toggle_selected_service_mark() {
+    let mut state = AppState::new();
+    for i in 0..3 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('v')));
+    assert!(state.marked_services.contains("test0._http._tcp.local."));
+    assert_eq!(state.selected_service, 1);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('2')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('v')));
+    assert_eq!(state.marked_services.len(), 3);
+
+    state.selected_service = 0;
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('v')));
+    assert!(!state.marked_services.contains("test0._http._tcp.local."));
+}
+
+#[test]
+fn test_toggle_all_visible_service_marks() {
+    let mut state = AppState::new();
+    state.services = vec![
+        create_test_service("alpha", "_http._tcp.local.", 80),
+        create_test_service("beta", "_http._tcp.local.", 81),
+        create_test_service("gamma", "_ssh._tcp.local.", 22),
+    ]
+    .into();
+    state.filter_query = "_http".into();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('V')));
+    assert_eq!(state.marked_services.len(), 2);
+    assert!(!state.marked_services.contains("gamma._ssh._tcp.local."));
+
+    // A partially marked selection gets fully marked first
+    state.marked_services.remove("alpha._http._tcp.local.");
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('V')));
+    assert_eq!(state.marked_services.len(), 2);
+
+    // Marks outside the visible selection are kept when unmarking
+    state
+        .marked_services
+        .insert("gamma._ssh._tcp.local.".to_string());
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('V')));
+    assert_eq!(state.marked_services.len(), 1);
+    assert!(state.marked_services.contains("gamma._ssh._tcp.local."));
+}
+
+#[test]
+fn test_bulk_remove_marked_offline_services() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    let mut marked_offline = create_test_service("test1", "_http._tcp.local.", 80);
+    marked_offline.online = false;
+    let mut unmarked_offline = create_test_service("test2", "_http._tcp.local.", 81);
+    unmarked_offline.online = false;
+    let marked_online = create_test_service("test3", "_http._tcp.local.", 82);
+    state.services = vec![marked_offline, unmarked_offline, marked_online].into();
+    state
+        .marked_services
+        .insert("test1._http._tcp.local.".to_string());
+    state
+        .marked_services
+        .insert("test3._http._tcp.local.".to_string());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    assert!(state.show_bulk_actions_popup);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('d')));
+    assert!(!state.show_bulk_actions_popup);
+
+    let remaining: Vec<&str> = state.services.iter().map(|s| &*s.host).collect();
+    assert_eq!(remaining, vec!["test2.local.", "test3.local."]);
+    assert_eq!(state.marked_services.len(), 1);
+    assert!(state.marked_services.contains("test3._http._tcp.local."));
+}
+
+#[test]
+fn test_bulk_watch_and_unwatch_marked_services() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .marked_services
+        .insert("test1._http._tcp.local.".to_string());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('w')));
+    assert!(state.watched_services.contains("test1._http._tcp.local."));
+    assert!(state.status_message.is_some());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('W')));
+    assert!(state.watched_services.is_empty());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('u')));
+    assert!(state.marked_services.is_empty());
+}
+
+#[test]
+fn test_bulk_export_without_marks_reports_status() {
+    let mut state = AppState::new();
+    state.export_marked_services();
+    assert_eq!(state.status_message.as_deref(), Some("No services marked"));
+}
+
+#[test]
+fn test_preferred_address() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.addrs = vec!["fe80::1".to_string(), "192.168.1.2".to_string()];
+    assert_eq!(
+        preferred_address(&service, AddressFamily::Ipv4.into()),
+        Some("192.168.1.2")
+    );
+    assert_eq!(
+        preferred_address(&service, AddressFamily::Ipv6.into()),
+        Some("fe80::1")
+    );
+    assert_eq!(
+        preferred_address(&service, AddressFamily::Any.into()),
+        Some("fe80::1")
+    );
+    service.addrs.clear();
+    assert_eq!(preferred_address(&service, AddressFamily::Any.into()), None);
+}
+
+#[test]
+fn test_service_url() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 8080);
+    service.addrs = vec!["192.168.1.2".to_string()];
+    assert_eq!(
+        service_url(&service, AddressFamily::Any.into()).as_deref(),
+        Some("http://192.168.1.2:8080/")
+    );
+
+    service.service_type = "_https._tcp.local.".into();
+    service.txt = vec!["path=admin/index.html".to_string()];
+    assert_eq!(
+        service_url(&service, AddressFamily::Any.into()).as_deref(),
+        Some("https://192.168.1.2:8080/admin/index.html")
+    );
+
+    service.addrs = vec!["fe80::1%2".to_string()];
+    service.txt = vec!["path=/a b".to_string()];
+    assert_eq!(
+        service_url(&service, AddressFamily::Any.into()).as_deref(),
+        Some("https://[fe80::1%252]:8080/a%20b")
+    );
+
+    service.addrs.clear();
+    service.txt.clear();
+    assert_eq!(
+        service_url(&service, AddressFamily::Any.into()).as_deref(),
+        Some("https://test.local:8080/")
+    );
+
+    service.service_type = "_ssh._tcp.local.".into();
+    assert_eq!(service_url(&service, AddressFamily::Any.into()), None);
+}
+
+#[test]
+fn test_ssh_command() {
+    let mut service = create_test_service("test", "_ssh._tcp.local.", 22);
+    service.addrs = vec!["192.168.1.2".to_string()];
+    let command = ssh_command(&service, AddressFamily::Any.into()).unwrap();
+    assert_eq!(command.to_string(), "ssh -p 22 -- 192.168.1.2");
+
+    service.service_type = "_sftp-ssh._tcp.local.".into();
+    service.txt = vec!["u=admin".to_string()];
+    service.addrs.clear();
+    let command = ssh_command(&service, AddressFamily::Any.into()).unwrap();
+    assert_eq!(command.to_string(), "ssh -p 22 -l admin -- test.local");
+
+    service.service_type = "_http._tcp.local.".into();
+    assert_eq!(ssh_command(&service, AddressFamily::Any.into()), None);
+}
+
+#[test]
+fn test_ssh_key_queues_external_command() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test", "_ssh._tcp.local.", 22));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('L')));
+    assert!(state.pending_external_command.is_some());
+
+    state.services = vec![create_test_service("test", "_http._tcp.local.", 80)].into();
+    state.pending_external_command = None;
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('L')));
+    assert!(state.pending_external_command.is_none());
+    assert!(state.status_message.is_some());
+}
+
+fn test_action(service_type: &str, command: &str, terminal: bool) -> ActionConfig {
+    ActionConfig {
+        service_type: service_type.to_string(),
+        name: "test action".to_string(),
+        command: command.to_string(),
+        terminal,
+    }
+}
+
+#[test]
+fn test_probe_results() {
+    let mut state = AppState::new();
+    let mut offline = create_test_service("offline", "_http._tcp.local.", 81);
+    offline.online = false;
+    state.services = vec![
+        create_test_service("test", "_http._tcp.local.", 80),
+        create_test_service("ssh", "_ssh._tcp.local.", 22),
+        offline,
+    ]
+    .into();
+    state.services.get_mut(0).txt = vec!["path=status".to_string()];
+    let probe = ProbeResult {
+        reachable: Some(true),
+        latency_micros: Some(3000),
+        http: Some(Ok(http_probe::HttpResponse {
+            status: 200,
+            title: None,
+        })),
+    };
+
+    // Results are ignored while probing is disabled
+    assert!(!state.update_probe_result(None, "test._http._tcp.local.", probe.clone()));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('p')));
+    assert!(state.probes.tcp_enabled());
+    let targets = state.probe_targets();
+    assert_eq!(targets.len(), 2);
+    assert_eq!(targets[0].fullname, "test._http._tcp.local.");
+    assert_eq!(targets[0].port, 80);
+    assert_eq!(&*targets[0].host, "test.local");
+    assert_eq!(targets[0].http, Some((false, "/status".to_string())));
+    assert_eq!(targets[1].http, None);
+
+    assert!(state.update_probe_result(None, "test._http._tcp.local.", probe.clone()));
+    assert!(!state.update_probe_result(None, "test._http._tcp.local.", probe.clone()));
+    assert!(!state.update_probe_result(None, "unknown._http._tcp.local.", probe.clone()));
+    assert_eq!(state.services[0].probe, probe);
+
+    // A changed service keeps its probe result until the next probe
+    let mut updated = create_test_service("test", "_http._tcp.local.", 80);
+    updated.txt = vec!["path=/".to_string()];
+    state.add_or_update_service(updated);
+    assert_eq!(state.services[0].probe, probe);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('p')));
+    assert_eq!(state.services[0].probe, ProbeResult::default());
+}
+
+#[tokio::test]
+async fn test_probe_tcp() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    assert!(probe_tcp("127.0.0.1", port).await.is_some());
+    drop(listener);
+    assert!(probe_tcp("127.0.0.1", port).await.is_none());
+}
+
+#[test]
+fn test_icmp_echo_request() {
+    let packet = icmp_echo_request(0x1234);
+    assert_eq!(packet[0], 8);
+    assert_eq!(&packet[6..8], &[0x12, 0x34]);
+    // A packet including its checksum sums up to zero
+    assert_eq!(internet_checksum(&packet), 0);
+}
+
+#[test]
+fn test_is_icmp_echo_reply() {
+    let mut reply = icmp_echo_request(7);
+    reply[0] = 0;
+    assert!(is_icmp_echo_reply(&reply, 7));
+    assert!(!is_icmp_echo_reply(&reply, 8));
+    assert!(!is_icmp_echo_reply(&icmp_echo_request(7), 7));
+    assert!(!is_icmp_echo_reply(&reply[..4], 7));
+
+    let mut with_ip_header = vec![0x45];
+    with_ip_header.extend_from_slice(&[0; 19]);
+    with_ip_header.extend_from_slice(&reply);
+    assert!(is_icmp_echo_reply(&with_ip_header, 7));
+}
+
+#[test]
+fn test_format_latency() {
+    assert_eq!(format_latency(1234), "1.2 ms");
+    assert_eq!(format_latency(123_456), "123 ms");
+    assert_eq!(latency_color(5_000), Color::Green);
+    assert_eq!(latency_color(50_000), Color::Yellow);
+    assert_eq!(latency_color(500_000), Color::Red);
+}
+
+#[test]
+fn test_inspect_selected_service_certificates() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test", "_http._tcp.local.", 80));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('C')));
+    assert!(state.pending_requests.is_empty());
+    assert!(state.status_message.is_some());
+
+    state.services = vec![create_test_service("test", "_https._tcp.local.", 443)].into();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('C')));
+    assert_eq!(
+        state.pending_requests,
+        vec![BackgroundRequest::InspectCertificates {
+            fullname: "test._https._tcp.local.".to_string(),
+            address: state.services[0].addrs[0].clone(),
+            port: 443,
+            host: "test.local".into(),
+        }]
+    );
+    assert_eq!(
+        state
+            .probes
+            .certificate_inspection("test._https._tcp.local."),
+        Some(None)
+    );
+}
+
+#[test]
+fn test_query_selected_printer() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("printer", "_ipps._tcp.local.", 631);
+    service.txt = vec!["rp=ipp/queue".to_string()];
+    state.services.push(service);
+    state
+        .services
+        .push(create_test_service("web", "_http._tcp.local.", 80));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('I')));
+    assert_eq!(
+        state.pending_requests,
+        vec![BackgroundRequest::QueryPrinter {
+            fullname: "printer._ipps._tcp.local.".to_string(),
+            address: state.services[0].addrs[0].clone(),
+            port: 631,
+            host: "printer.local".into(),
+            path: "ipp/queue".to_string(),
+            tls: true,
+        }]
+    );
+    assert_eq!(
+        state.probes.printer_query("printer._ipps._tcp.local."),
+        Some(None)
+    );
+
+    state.pending_requests.clear();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('I')));
+    assert!(state.pending_requests.is_empty());
+    assert!(state.status_message.is_some());
+}
+
+#[test]
+fn test_query_selected_cast_device() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("tv", "_googlecast._tcp.local.", 8009));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('I')));
+    assert_eq!(
+        state.pending_requests,
+        vec![BackgroundRequest::QueryCastDevice {
+            fullname: "tv._googlecast._tcp.local.".to_string(),
+            address: state.services[0].addrs[0].clone(),
+            host: "tv.local".into(),
+        }]
+    );
+    assert_eq!(
+        state.probes.cast_query("tv._googlecast._tcp.local."),
+        Some(None)
+    );
+}
+
+#[test]
+fn test_format_cast_query() {
+    let mut service = create_test_service("tv", "_googlecast._tcp.local.", 8009);
+    service.txt = vec!["rs=YouTube".to_string()];
+    let info = CastDeviceInfo {
+        name: Some("Living Room TV".to_string()),
+        manufacturer: Some("Google Inc.".to_string()),
+        model: Some("Chromecast".to_string()),
+        build: None,
+        uptime_secs: Some(90061),
+    };
+    assert_eq!(
+        format_cast_query(Some(&Ok(info)), &service),
+        "Cast Device:\nName: Living Room TV\nModel: Google Inc. Chromecast\nUptime: 1d 1h 1m\nCurrent app: YouTube"
+    );
+    assert_eq!(
+        format_cast_query(Some(&Err("timed out".to_string())), &service),
+        "Cast Device:\nQuery failed: timed out"
+    );
+}
+
+#[test]
+fn test_format_duration_secs() {
+    assert_eq!(format_duration_secs(59), "0m 59s");
+    assert_eq!(format_duration_secs(3725), "1h 2m");
+}
+
+#[test]
+fn test_format_printer_query() {
+    assert_eq!(format_printer_query(None), "Printer Status:\nQuerying...");
+    let status = PrinterStatus {
+        make_and_model: Some("Office Laser".to_string()),
+        state: Some("stopped".to_string()),
+        state_reasons: vec!["media-empty-error".to_string()],
+        document_formats: vec!["application/pdf".to_string()],
+        markers: vec![
+            ipp::Marker {
+                name: "Black".to_string(),
+                level: 5,
+                color: None,
+            },
+            ipp::Marker {
+                name: "Waste".to_string(),
+                level: -3,
+                color: None,
+            },
+        ],
+    };
+    assert_eq!(
+        format_printer_query(Some(&Ok(status))),
+        "Printer Status:\nModel: Office Laser\nState: stopped\nReasons: media-empty-error\nFormats: application/pdf\nBlack: 5%\nWaste: some remaining"
+    );
+}
+
+#[test]
+fn test_take_reverse_dns_lookups() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.addrs = vec!["192.168.1.2".to_string(), "fe80::1%eth0".to_string()];
+    state.services.push(service);
+
+    assert!(state.take_reverse_dns_lookups().is_empty());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('R')));
+    assert_eq!(
+        state.take_reverse_dns_lookups(),
+        vec!["192.168.1.2", "fe80::1%eth0"]
+    );
+    assert_eq!(state.reverse_dns.get("192.168.1.2"), Some(&None));
+    // Pending and finished lookups are not repeated
+    assert!(state.take_reverse_dns_lookups().is_empty());
+}
+
+#[test]
+fn test_reverse_dns_mismatch() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.addrs = vec!["192.168.1.2".to_string(), "192.168.1.3".to_string()];
+    let mut reverse_dns = HashMap::new();
+    reverse_dns.insert(
+        "192.168.1.2".to_string(),
+        Some(vec!["test.local.".to_string()]),
+    );
+    reverse_dns.insert("192.168.1.3".to_string(), None);
+    assert!(!has_reverse_dns_mismatch(&service, &reverse_dns));
+
+    reverse_dns.insert("192.168.1.3".to_string(), Some(vec![]));
+    assert!(!has_reverse_dns_mismatch(&service, &reverse_dns));
+    let text = format_reverse_dns(&service, &reverse_dns);
+    assert!(text.contains("192.168.1.2 → test.local."));
+    assert!(text.contains("192.168.1.3 → no PTR record"));
+
+    reverse_dns.insert(
+        "192.168.1.3".to_string(),
+        Some(vec!["other.lan.".to_string()]),
+    );
+    assert!(has_reverse_dns_mismatch(&service, &reverse_dns));
+    assert!(
+        format_reverse_dns(&service, &reverse_dns)
+            .contains("other.lan. (does not match test.local.)")
+    );
+}
+
+#[test]
+fn test_format_txt_interpretation() {
+    let mut service = create_test_service("printer", "_ipp._tcp.local.", 631);
+    assert_eq!(format_txt_interpretation(&service), None);
+
+    service.txt = vec!["ty=Office Laser".to_string(), "note=Hallway".to_string()];
+    assert_eq!(
+        format_txt_interpretation(&service).as_deref(),
+        Some("Interpretation:\nModel: Office Laser\nLocation: Hallway")
+    );
+}
+
+#[test]
+fn test_format_decoded_txt() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    assert_eq!(format_decoded_txt(&service), "Decoded TXT Values:\nNone");
+
+    service.txt = vec![
+        "id=aGVsbG8gd29ybGQ=".to_string(),
+        "name=Living Room".to_string(),
+        "url=http://test.local/a%20b".to_string(),
+    ];
+    assert_eq!(
+        format_decoded_txt(&service),
+        "Decoded TXT Values:\nid (base64): \"hello world\"\nurl (URL): http://test.local/a b"
+    );
+}
+
+#[test]
+fn test_format_certificate_inspection() {
+    let certificate = CertificateInfo {
+        subject: "CN=nas".to_string(),
+        issuer: "CN=nas".to_string(),
+        subject_alt_names: vec!["nas.local".to_string()],
+        not_before: 0,
+        not_after: 100,
+    };
+    let text = format_certificate_inspection(Some(&Ok(vec![certificate.clone()])), 200);
+    assert!(text.contains("[0] Subject: CN=nas"));
+    assert!(text.contains("SANs: nas.local"));
+    assert!(text.contains("(EXPIRED, self-signed)"));
+
+    let text = format_certificate_inspection(Some(&Ok(vec![certificate])), 50);
+    assert!(!text.contains("EXPIRED"));
+
+    assert!(format_certificate_inspection(None, 0).contains("Inspecting"));
+    assert!(
+        format_certificate_inspection(Some(&Err("refused".to_string())), 0)
+            .contains("Inspection failed: refused")
+    );
+}
+
+#[test]
+fn test_expand_action_command() {
+    let mut service = create_test_service("test", "_vnc._tcp.local.", 5900);
+    service.addrs = vec!["192.168.1.2".to_string()];
+    let command = expand_action_command(
+        "vncviewer '{address}:{port}' --title \"{fullname}\" {type} {host}",
+        &service,
+        AddressFamily::Any.into(),
+    )
+    .unwrap();
+    assert_eq!(command.program, "vncviewer");
+    assert_eq!(
+        command.args,
+        vec![
+            "192.168.1.2:5900",
+            "--title",
+            "test._vnc._tcp.local.",
+            "_vnc._tcp.local",
+            "test.local"
+        ]
+    );
+
+    assert!(expand_action_command("", &service, AddressFamily::Any.into()).is_err());
+    assert!(expand_action_command("'unterminated", &service, AddressFamily::Any.into()).is_err());
+}
+
+#[test]
+fn test_actions_popup() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test", "_vnc._tcp.local.", 5900));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(!state.show_actions_popup);
+    assert!(state.status_message.is_some());
+
+    state.config.actions = vec![
+        test_action("_ipp._tcp", "true", false),
+        test_action("_vnc._tcp", "vncviewer {address}", true),
+    ];
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.show_actions_popup);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('1')));
+    assert!(!state.show_actions_popup);
+    assert_eq!(
+        state
+            .pending_external_command
+            .as_ref()
+            .map(|c| c.to_string()),
+        Some("vncviewer 192.168.1.5900".to_string())
+    );
+
+    // Numbers without an action just close the popup
+    state.pending_external_command = None;
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('2')));
+    assert!(!state.show_actions_popup);
+    assert!(state.pending_external_command.is_none());
+}
+
+// Clears the prompt being edited and types the text into it
+fn replace_input(state: &mut AppState, text: &str) {
+    state.handle_key_event(KeyEvent::new(
+        KeyCode::Char('u'),
+        crossterm::event::KeyModifiers::CONTROL,
+    ));
+    for ch in text.chars() {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char(ch)));
+    }
+}
+
+#[test]
+fn test_export_selected_service() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("service.json");
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("my service", "_http._tcp.local.", 80));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('J')));
+    let suggested = state.exports.service_path_input().unwrap();
+    assert!(suggested.starts_with("mdns-service-my-service-"));
+    assert!(suggested.ends_with(".json"));
+
+    replace_input(&mut state, path.to_str().unwrap());
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.exports.service_path_input().is_none());
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(json["fullname"], "my service._http._tcp.local.");
+    assert_eq!(json["port"], 80);
+    assert_eq!(json["schema_version"], crate::schema::VERSION);
+    crate::schema::assert_matches_definition(&json, "service");
+
+    // Without a path the service is kept for printing on exit
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('J')));
+    replace_input(&mut state, "-");
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(state.stdout_on_exit.len(), 1);
+    assert!(state.stdout_on_exit[0].contains("\"txt\": []"));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('J')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(state.exports.service_path_input().is_none());
+    assert_eq!(state.stdout_on_exit.len(), 1);
+}
+
+#[test]
+fn test_export_dialog() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("services.json");
+    let mut state = AppState::new();
+    state.services = vec![
+        create_test_service("alpha", "_http._tcp.local.", 80),
+        create_test_service("gamma", "_ssh._tcp.local.", 22),
+    ]
+    .into();
+    state.filter_query = "_http".into();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('e')));
+    let dialog = state.exports.dialog().unwrap();
+    assert!(dialog.path.text.ends_with(".json"));
+    replace_input(&mut state, path.to_str().unwrap());
+    state.handle_key_event(KeyEvent::from(KeyCode::Tab));
+    let csv_path = dir.path().join("services.csv");
+    assert_eq!(
+        state.exports.dialog().unwrap().path.text,
+        csv_path.to_str().unwrap()
+    );
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.exports.dialog().is_none());
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.contains("alpha._http._tcp.local."));
+
+    // An existing file is only overwritten after confirming
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('e')));
+    replace_input(&mut state, csv_path.to_str().unwrap());
+    state.handle_key_event(KeyEvent::from(KeyCode::Tab));
+    state.handle_key_event(KeyEvent::from(KeyCode::BackTab));
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.exports.dialog().unwrap().confirm_overwrite);
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.exports.dialog().is_none());
+    let csv = std::fs::read_to_string(&csv_path).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('e')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(state.exports.dialog().is_none());
+}
+
+#[test]
+fn test_service_list_lines_only_builds_the_range() {
+    let mut state = AppState::new();
+    state.services = (0..50)
+        .map(|i| create_test_service(&format!("s{:02}", i), "_http._tcp.local.", 80))
+        .collect::<Vec<_>>()
+        .into();
+
+    let lines = service_list_lines(&mut state, 10..13);
+    assert_eq!(lines.len(), 3);
+    assert!(line_text(&lines[0]).contains("s10"));
+    assert!(line_text(&lines[2]).contains("s12"));
+
+    // Ranges past the end are cut to the services shown
+    assert_eq!(service_list_lines(&mut state, 48..60).len(), 2);
+    assert!(service_list_lines(&mut state, 70..80).is_empty());
+}
+
+#[test]
+fn test_view_snapshot() {
+    let mut state = AppState::new();
+    state.services = vec![
+        create_test_service("alpha", "_http._tcp.local.", 80),
+        create_test_service("beta", "_http._tcp.local.", 81),
+        create_test_service("gamma", "_ssh._tcp.local.", 22),
+    ]
+    .into();
+    state.filter_query = "_http".into();
+    state
+        .marked_services
+        .insert("beta._http._tcp.local.".to_string());
+
+    let snapshot = state.view_snapshot();
+    let lines: Vec<&str> = snapshot.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("Services [2/3 1 marked]"));
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.ends_with("alpha - alpha - 192.168.1.80:80"))
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.ends_with("beta - beta - 192.168.1.81:81"))
+    );
+}
+
+#[test]
+fn test_inventory_popup() {
+    let mut state = AppState::new();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('X')));
+    assert!(!state.show_inventory_popup);
+    assert!(state.status_message.is_some());
+
+    state.inventory = Some(
+        Inventory::parse(
+            "[[services]]\nname = \"alpha\"\n\n[[services]]\nservice_type = \"_ssh._tcp\"",
+        )
+        .unwrap(),
+    );
+    state.services = vec![
+        create_test_service("alpha", "_http._tcp.local.", 80),
+        create_test_service("beta", "_http._tcp.local.", 81),
+    ]
+    .into();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('X')));
+    assert!(state.show_inventory_popup);
+    let statuses: Vec<CheckStatus> = state
+        .inventory_checklist()
+        .iter()
+        .map(|entry| entry.status)
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            CheckStatus::Present,
+            CheckStatus::Missing,
+            CheckStatus::Unexpected
+        ]
+    );
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    assert_eq!(state.inventory_cursor, 1);
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.show_inventory_popup);
+}
+
+// Runs the browse pipeline against the mock daemon until the state satisfies done
+async fn wait_for_state(state: &Arc<RwLock<AppState>>, done: impl Fn(&AppState) -> bool) -> bool {
+    for _ in 0..200 {
+        if done(&*state.read().await) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_mock_discovery_pipeline() {
+    let script = session::parse(concat!(
+        r#"{"timestamp_micros":0,"event":"resolved","fullname":"Office._ipp._tcp.local.","service_type":"_ipp._tcp.local.","subtype":null,"host":"printer.local.","port":631,"addrs":["192.168.1.20"],"txt":["rp=ipp/print"]}"#,
+        "\n",
+        r#"{"timestamp_micros":1000,"event":"resolved","fullname":"NAS._smb._tcp.local.","service_type":"_smb._tcp.local.","subtype":null,"host":"nas.local.","port":445,"addrs":["192.168.1.30"],"txt":[]}"#,
+    ))
+    .unwrap();
+    let state = Arc::new(RwLock::new(AppState::new()));
+    let mut events = state.read().await.discovery_events.subscribe();
+    let (notification_sender, _notification_receiver) = flume::unbounded();
+    let mock = MockDaemon::new();
+    start_browsing(
+        &BackgroundTasks::default(),
+        mock.clone(),
+        &state,
+        &notification_sender,
+    )
+    .await;
+    mock.play(script).await;
+
+    assert!(wait_for_state(&state, |state| state.services.len() == 2).await);
+    {
+        let state = state.read().await;
+        assert_eq!(
+            state.service_types,
+            ["_ipp._tcp.local.", "_smb._tcp.local."]
+        );
+        assert_eq!(&*state.services[1].host, "nas.local.");
+        assert_eq!(state.services[0].txt, ["rp=ipp/print"]);
+    }
+    assert_eq!(events.recv().await.unwrap().event, EventKind::Added);
+
+    mock.remove("NAS._smb._tcp.local.");
+    assert!(
+        wait_for_state(&state, |state| state
+            .services
+            .iter()
+            .any(|service| !service.online))
+        .await
+    );
+}
+
+#[tokio::test]
+async fn test_browser_on_backend() {
+    let mock = MockDaemon::new();
+    let browser = Browser::start_with(Config::default(), mock.clone()).await;
+    mock.announce(RecordedService {
+        fullname: "Office._ipp._tcp.local.".to_string(),
+        service_type: "_ipp._tcp.local.".into(),
+        subtype: None,
+        host: "printer.local.".into(),
+        port: 631,
+        addrs: vec!["192.168.1.20".to_string()],
+        txt: Vec::new(),
+        srv_priority: None,
+        srv_weight: None,
+    });
+    let mut found = false;
+    for _ in 0..200 {
+        if !browser.services().await.is_empty() {
+            found = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(found);
+    assert_eq!(browser.service_types().await, ["_ipp._tcp.local."]);
+
+    let (services, mut events) = browser.watch().await;
+    assert_eq!(services[0].port, 631);
+    mock.remove("Office._ipp._tcp.local.");
+    let event = events.recv().await.unwrap();
+    assert_eq!(
+        (event.event, event.fullname.as_str()),
+        (EventKind::Removed, "Office._ipp._tcp.local.")
+    );
+}
+
+// The engine needs no Tokio runtime with the smol feature
+#[cfg(feature = "smol")]
+#[test]
+fn test_browser_without_tokio() {
+    smol::block_on(async {
+        let mock = MockDaemon::new();
+        let browser = Browser::start_with(Config::default(), mock.clone()).await;
+        mock.announce(RecordedService {
+            fullname: "Office._ipp._tcp.local.".to_string(),
+            service_type: "_ipp._tcp.local.".into(),
+            subtype: None,
+            host: "printer.local.".into(),
+            port: 631,
+            addrs: vec!["192.168.1.20".to_string()],
+            txt: Vec::new(),
+            srv_priority: None,
+            srv_weight: None,
+        });
+        let mut found = false;
+        for _ in 0..200 {
+            if !browser.services().await.is_empty() {
+                found = true;
+                break;
+            }
+            runtime::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(found);
+        browser.shutdown().await;
+    });
+}
+
+#[cfg(feature = "smol")]
+#[test]
+fn test_wide_area_browsing_needs_tokio() {
+    let config = Config {
+        browse_domains: vec!["local.".to_string(), "example.com.".to_string()],
+        ..Config::default()
+    };
+    let error = smol::block_on(Browser::start(config)).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Browsing example.com. needs a Tokio runtime"
+    );
+}
+
+#[cfg(all(feature = "smol", feature = "bonjour"))]
+#[test]
+fn test_bonjour_backend_needs_tokio() {
+    let config = Config {
+        backend: BackendKind::Bonjour,
+        ..Config::default()
+    };
+    let error = smol::block_on(Browser::start(config)).err().unwrap();
+    assert!(matches!(error, Error::NoTokio { .. }));
+    assert!(smol::block_on(BonjourBackend::start(&LimitsConfig::default())).is_err());
+}
+
+#[tokio::test]
+async fn test_ended_browse_is_restarted() {
+    let state = Arc::new(RwLock::new(AppState::new()));
+    let (notification_sender, _notification_receiver) = flume::unbounded();
+    let tasks = BackgroundTasks::default();
+    let mock = MockDaemon::new();
+    start_browsing(&tasks, mock.clone(), &state, &notification_sender).await;
+    mock.announce(RecordedService {
+        fullname: "Office._ipp._tcp.local.".to_string(),
+        service_type: "_ipp._tcp.local.".into(),
+        subtype: None,
+        host: "printer.local.".into(),
+        port: 631,
+        addrs: vec!["192.168.1.20".to_string()],
+        txt: Vec::new(),
+        srv_priority: None,
+        srv_weight: None,
+    });
+    assert!(wait_for_state(&state, |state| state.services.len() == 1).await);
+
+    // The backend ends the browse on its own
+    mock.stop_browse("_ipp._tcp.local.");
+    assert!(
+        wait_for_state(&state, |state| state
+            .browse_manager
+            .failures()
+            .contains_key("_ipp._tcp.local."))
+        .await
+    );
+    assert_eq!(state.read().await.metrics["browses_lost"], 1);
+    assert!(wait_for_state(&state, |state| state.metrics["browses_started"] == 2).await);
+    tasks.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_shutdown_ends_the_background_tasks() {
+    let state = Arc::new(RwLock::new(AppState::new()));
+    let (notification_sender, _notification_receiver) = flume::unbounded();
+    let tasks = BackgroundTasks::default();
+    let mock = MockDaemon::new();
+    start_browsing(&tasks, mock.clone(), &state, &notification_sender).await;
+    mock.announce(RecordedService {
+        fullname: "Office._ipp._tcp.local.".to_string(),
+        service_type: "_ipp._tcp.local.".into(),
+        subtype: None,
+        host: "printer.local.".into(),
+        port: 631,
+        addrs: vec!["192.168.1.20".to_string()],
+        txt: Vec::new(),
+        srv_priority: None,
+        srv_weight: None,
+    });
+    assert!(wait_for_state(&state, |state| state.services.len() == 1).await);
+
+    // Well before the timeout, nothing is left running
+    let started = std::time::Instant::now();
+    shut_down(&mock, &tasks).await;
+    assert!(started.elapsed() < SHUTDOWN_TIMEOUT);
+}
+
+#[tokio::test]
+async fn test_remote_agent() {
+    let service = |name: &str| {
+        SessionEvent::Resolved(RecordedService {
+            fullname: format!("{}._ipp._tcp.local.", name),
+            service_type: "_ipp._tcp.local.".into(),
+            subtype: None,
+            host: "printer.local.".into(),
+            port: 631,
+            addrs: vec!["192.168.1.20".to_string()],
+            txt: Vec::new(),
+            srv_priority: None,
+            srv_weight: None,
+        })
+    };
+    let agent_state = Arc::new(RwLock::new(AppState::new()));
+    agent_state
+        .write()
+        .await
+        .apply_session_event(service("Office"));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let agent = RemoteAgent::parse(&listener.local_addr().unwrap().to_string()).unwrap();
+    let events = agent_state.read().await.discovery_events.subscribe();
+    tokio::spawn(server::serve(
+        listener,
+        Arc::clone(&agent_state),
+        events,
+        None,
+    ));
+
+    let state = Arc::new(RwLock::new(AppState::new()));
+    let (notification_sender, _notification_receiver) = flume::unbounded();
+    spawn_remote(
+        &BackgroundTasks::default(),
+        agent,
+        Arc::clone(&state),
+        notification_sender,
+    );
+    assert!(wait_for_state(&state, |state| state.services.len() == 1).await);
+    assert_eq!(state.read().await.service_types, ["_ipp._tcp.local."]);
+    let label = state.read().await.services[0].agent.clone().unwrap();
+    assert!(label.starts_with("127.0.0.1:"));
+
+    // Give the client a moment to start polling
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    {
+        let mut agent_state = agent_state.write().await;
+        agent_state.apply_session_event(service("Lobby"));
+        agent_state.apply_session_event(SessionEvent::Removed {
+            fullname: "Office._ipp._tcp.local.".to_string(),
+        });
+    }
+    assert!(
+        wait_for_state(&state, |state| state.services.len() == 2
+            && state
+                .services
+                .iter()
+                .any(|s| s.fullname == "Office._ipp._tcp.local." && !s.online))
+        .await
+    );
+}
+
+#[test]
+fn test_agent_attribution_and_filter() {
+    let mut state = AppState::new();
+    state.agents = vec!["hq:8080".to_string(), "branch:8080".to_string()];
+    for (agent, name) in [("hq:8080", "Office"), ("branch:8080", "Lobby")] {
+        state.apply_remote_event(
+            agent,
+            SessionEvent::Resolved(RecordedService {
+                fullname: format!("{}._ipp._tcp.local.", name),
+                service_type: "_ipp._tcp.local.".into(),
+                subtype: None,
+                host: "printer.local.".into(),
+                port: 631,
+                addrs: Vec::new(),
+                txt: Vec::new(),
+                srv_priority: None,
+                srv_weight: None,
+            }),
+        );
+    }
+    // Only the agent that reported a service takes it offline
+    state.apply_remote_event(
+        "hq:8080",
+        SessionEvent::Removed {
+            fullname: "Lobby._ipp._tcp.local.".to_string(),
+        },
+    );
+    assert!(state.services.iter().all(|s| s.online));
+
+    state.cycle_agent_filter();
+    assert_eq!(state.agent_filter.as_deref(), Some("hq:8080"));
+    let shown = state.get_filtered_services().to_vec();
+    assert_eq!(shown.len(), 1);
+    assert_eq!(state.services[shown[0]].fullname, "Office._ipp._tcp.local.");
+    state.cycle_agent_filter();
+    assert_eq!(state.agent_filter.as_deref(), Some("branch:8080"));
+    state.cycle_agent_filter();
+    assert_eq!(state.agent_filter, None);
+}
+
+#[test]
+fn test_same_named_services_of_agents_stay_apart_unless_merged() {
+    let resolved = |addr: &str| {
+        SessionEvent::Resolved(RecordedService {
+            fullname: "Living Room._googlecast._tcp.local.".to_string(),
+            service_type: "_googlecast._tcp.local.".into(),
+            subtype: None,
+            host: "chromecast.local.".into(),
+            port: 8009,
+            addrs: vec![addr.to_string()],
+            txt: Vec::new(),
+            srv_priority: None,
+            srv_weight: None,
+        })
+    };
+    let removed = || SessionEvent::Removed {
+        fullname: "Living Room._googlecast._tcp.local.".to_string(),
+    };
+
+    let mut state = AppState::new();
+    state.apply_remote_event("hq:8080", resolved("10.0.0.5"));
+    state.apply_remote_event("branch:8080", resolved("10.1.0.5"));
+    assert_eq!(state.services.len(), 2);
+    state.apply_remote_event("hq:8080", removed());
+    let online: Vec<_> = state
+        .services
+        .iter()
+        .map(|s| (s.agent.as_deref().unwrap(), s.addrs[0].as_str(), s.online))
+        .collect();
+    assert!(online.contains(&("hq:8080", "10.0.0.5", false)));
+    assert!(online.contains(&("branch:8080", "10.1.0.5", true)));
+    state.agent_filter = Some("branch:8080".to_string());
+    state.invalidate_cache_and_validate();
+    assert_eq!(state.get_filtered_services().len(), 1);
+
+    let mut state = AppState::new();
+    state.services.set_merge_agents(true);
+    state.apply_remote_event("hq:8080", resolved("10.0.0.5"));
+    state.apply_remote_event("branch:8080", resolved("10.1.0.5"));
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].agent.as_deref(), Some("branch:8080"));
+    state.apply_remote_event("hq:8080", removed());
+    assert!(state.services[0].online);
+}
+
+#[test]
+fn test_apply_session_events() {
+    let mut state = AppState::new();
+    let events = session::parse(concat!(
+        r#"{"timestamp_micros":1,"event":"type_found","service_type":"_ipp._tcp.local."}"#,
+        "\n",
+        r#"{"timestamp_micros":2,"event":"resolved","fullname":"Office._ipp._tcp.local.","service_type":"_ipp._tcp.local.","subtype":null,"host":"printer.local.","port":631,"addrs":["192.168.1.20"],"txt":[]}"#,
+        "\n",
+        r#"{"timestamp_micros":3,"event":"removed","fullname":"Office._ipp._tcp.local."}"#,
+    ))
+    .unwrap();
+    for event in events {
+        state.apply_session_event(event.event);
+    }
+    assert_eq!(state.service_types, ["_ipp._tcp.local."]);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].port, 631);
+    assert!(!state.services[0].online);
+}
+
+#[test]
+fn test_baseline_drift() {
+    let mut state = AppState::new();
+    state.services = vec![
+        create_test_service("alpha", "_http._tcp.local.", 80),
+        create_test_service("beta", "_http._tcp.local.", 81),
+    ]
+    .into();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('B')));
+    assert_eq!(state.baseline_filter, None);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('U')));
+    assert_eq!(state.baseline_drift(), (0, 0));
+    state.services.get_mut(1).online = false;
+    state
+        .services
+        .push(create_test_service("gamma", "_http._tcp.local.", 82));
+    state.invalidate_cache_and_validate();
+    assert_eq!(state.baseline_drift(), (1, 1));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('B')));
+    assert_eq!(state.baseline_filter, Some(BaselineStatus::New));
+    assert_eq!(state.get_filtered_services(), &[2]);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('B')));
+    assert_eq!(state.get_filtered_services(), &[1]);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('B')));
+    assert_eq!(state.get_filtered_services().len(), 3);
+}
+
+#[test]
+fn test_restored_baseline_lists_missing_services() {
+    let mut state = AppState::new();
+    state.services = vec![create_test_service("alpha", "_http._tcp.local.", 80)].into();
+    let services = BTreeMap::from([
+        (
+            state.services[0].fullname.clone(),
+            BaselineService {
+                service_type: "_http._tcp.local.".to_string(),
+                host: "alpha.local.".to_string(),
+                port: 80,
+            },
+        ),
+        (
+            "Printer._ipp._tcp.local.".to_string(),
+            BaselineService {
+                service_type: "_ipp._tcp.local.".to_string(),
+                host: "printer.local.".to_string(),
+                port: 631,
+            },
+        ),
+    ]);
+    state.baseline.save(services, 1_000).unwrap();
+
+    state.restore_baseline();
+    assert_eq!(state.services.len(), 2);
+    assert_eq!(state.baseline_drift(), (0, 1));
+    state.baseline_filter = Some(BaselineStatus::Missing);
+    state.invalidate_cache_and_validate();
+    let missing = state.get_filtered_services().to_vec();
+    assert_eq!(missing.len(), 1);
+    let placeholder = &state.services[missing[0]];
+    assert_eq!(placeholder.fullname, "Printer._ipp._tcp.local.");
+    assert!(!placeholder.online);
+    assert_eq!(placeholder.last_seen_micros, 1_000);
+}
+
+#[test]
+fn test_copy_commands_popup() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test", "_foo._tcp.local.", 1234));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('y')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('c')));
+    assert!(!state.show_copy_commands_popup);
+    assert!(state.status_message.is_some());
+
+    state.config.copy_commands = vec![CopyCommandConfig {
+        service_type: "_foo._tcp".into(),
+        name: "nc".to_string(),
+        command: "nc {host} {port}".to_string(),
+    }];
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('y')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('c')));
+    assert!(state.show_copy_commands_popup);
+    // Numbers without a command just close the popup
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('2')));
+    assert!(!state.show_copy_commands_popup);
+}
+
+#[test]
+fn test_external_command_to_shell_line() {
+    let command = ExternalCommand {
+        program: "curl".to_string(),
+        args: vec!["http://my host:80/".to_string(), "-k".to_string()],
+    };
+    assert_eq!(command.to_shell_line(), "curl 'http://my host:80/' -k");
+}
+
+#[test]
+fn test_encode_url_path() {
+    assert_eq!(
+        encode_url_path("/index.html?a=1&b=2"),
+        "/index.html?a=1&b=2"
+    );
+    assert_eq!(encode_url_path("/a b\n"), "/a%20b%0A");
+    assert_eq!(encode_url_path("/ü"), "/%C3%BC");
+}
+
+#[test]
+fn test_preferred_address_routable_first() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.addrs = vec![
+        "169.254.1.1".to_string(),
+        "fe80::1%eth0".to_string(),
+        "fd00::1".to_string(),
+        "192.168.1.2".to_string(),
+    ];
+    let routable_first = |family| AddressPreference {
+        family,
+        routable_first: true,
+    };
+    assert_eq!(
+        preferred_address(&service, routable_first(AddressFamily::Any)),
+        Some("fd00::1")
+    );
+    assert_eq!(
+        preferred_address(&service, routable_first(AddressFamily::Ipv4)),
+        Some("192.168.1.2")
+    );
+    assert_eq!(
+        preferred_address(&service, routable_first(AddressFamily::Ipv6)),
+        Some("fd00::1")
+    );
+    assert_eq!(
+        preferred_address(&service, AddressFamily::Ipv6.into()),
+        Some("fe80::1%eth0")
+    );
+}
+
+#[test]
+fn test_classify_address() {
+    assert_eq!(classify_address("127.0.0.1"), AddressScope::Loopback);
+    assert_eq!(classify_address("::1"), AddressScope::Loopback);
+    assert_eq!(classify_address("169.254.3.4"), AddressScope::LinkLocal);
+    assert_eq!(classify_address("fe80::1%eth0"), AddressScope::LinkLocal);
+    assert_eq!(classify_address("fd12:3456::1"), AddressScope::UniqueLocal);
+    assert_eq!(classify_address("10.0.0.1"), AddressScope::Private);
+    assert_eq!(classify_address("100.64.0.1"), AddressScope::Private);
+    assert_eq!(
+        classify_address("::ffff:192.168.1.1"),
+        AddressScope::Private
+    );
+    assert_eq!(classify_address("8.8.8.8"), AddressScope::Global);
+    assert_eq!(classify_address("2001:db8::1"), AddressScope::Global);
+    assert_eq!(classify_address("not an address"), AddressScope::Unknown);
+    assert!(AddressScope::UniqueLocal.is_routable());
+    assert!(!AddressScope::LinkLocal.is_routable());
+}
+
+#[test]
+fn test_mac_from_service_record() {
+    let mut service = create_test_service("test", "_airplay._tcp.local.", 7000);
+    assert_eq!(mac_from_service_record(&service), None);
+
+    service.txt = vec!["deviceid=AA:BB:CC:01:02:03".to_string()];
+    assert_eq!(
+        mac_from_service_record(&service),
+        Some([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03])
+    );
+
+    let mut service = create_test_service("test", "_workstation._tcp.local.", 9);
+    service.fullname = "nas [aa:bb:cc:01:02:03]._workstation._tcp.local.".to_string();
+    assert_eq!(
+        mac_from_service_record(&service),
+        Some([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03])
+    );
+
+    let mut service = create_test_service("test", "_raop._tcp.local.", 7000);
+    service.fullname = "AABBCC010203@Living Room._raop._tcp.local.".to_string();
+    assert_eq!(
+        mac_from_service_record(&service),
+        Some([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03])
+    );
+}
+
+#[test]
+fn test_learn_mac_address_and_wake() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("test", "_workstation._tcp.local.", 9);
+    service.fullname = "test [aa:bb:cc:01:02:03]._workstation._tcp.local.".to_string();
+    state.add_or_update_service(service);
+    assert_eq!(
+        state.mac_store.get("test.local."),
+        Some([0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03])
+    );
+
+    // Online hosts are not woken
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('W')));
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("test.local. is online")
+    );
+
+    let mut unknown = create_test_service("other", "_http._tcp.local.", 80);
+    unknown.online = false;
+    state.services = vec![unknown].into();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('W')));
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("No MAC address known for other.local.")
+    );
+}
+
+#[test]
+fn test_mac_lookups_are_batched_off_the_resolve() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("nas", "_smb._tcp.local.", 445);
+    service.addrs = vec!["192.168.1.30".to_string()];
+    state.add_or_update_service(service);
+    assert_eq!(state.mac_store.get("nas.local."), None);
+    let lookups = state.take_mac_lookups();
+    assert_eq!(
+        lookups.get("nas.local."),
+        Some(&vec!["192.168.1.30".to_string()])
+    );
+    assert!(state.take_mac_lookups().is_empty());
+    assert!(state.take_unsaved_macs().is_none());
+
+    const MAC: MacAddress = [0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03];
+    state.learn_mac_addresses(vec![("nas.local.".to_string(), MAC)]);
+    assert_eq!(state.mac_store.get("nas.local."), Some(MAC));
+    assert!(state.take_unsaved_macs().is_some());
+    assert!(state.take_unsaved_macs().is_none());
+
+    // Known hosts are not looked up again
+    state.add_or_update_service(create_test_service("nas", "_http._tcp.local.", 80));
+    assert!(state.take_mac_lookups().is_empty());
+}
+
+#[test]
+fn test_cycle_domain_filter() {
+    let mut state = AppState::new();
+    state.config.browse_domains = vec!["local.".to_string(), "corp.example.com".to_string()];
+    state
+        .services
+        .push(create_test_service("web", "_http._tcp.local.", 80));
+    state.services.push(create_test_service(
+        "intranet",
+        "_http._tcp.corp.example.com.",
+        8080,
+    ));
+    state.mark_cache_dirty();
+    assert_eq!(state.get_filtered_services().len(), 2);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('Z')));
+    assert_eq!(state.domain_filter.as_deref(), Some("local."));
+    assert_eq!(state.get_filtered_services(), &[0]);
+
+    state.cycle_domain_filter();
+    assert_eq!(state.domain_filter.as_deref(), Some("corp.example.com."));
+    assert_eq!(state.get_filtered_services(), &[1]);
+
+    state.cycle_domain_filter();
+    assert_eq!(state.domain_filter, None);
+    assert_eq!(state.get_filtered_services().len(), 2);
+}
+
+#[test]
+fn test_service_entry_from_unicast_service() {
+    let entry = ServiceEntry::from(UnicastService {
+        fullname: "printer._ipp._tcp.example.com.".to_string(),
+        service_type: "_ipp._tcp.example.com.".into(),
+        host: "printer.example.com.".into(),
+        port: 631,
+        priority: 10,
+        weight: 5,
+        addrs: vec!["192.0.2.7".parse().unwrap()],
+        txt: vec![
+            b"rp=ipp/print".to_vec(),
+            b"ty=\x01".to_vec(),
+            b"flag".to_vec(),
+        ],
+    });
+    assert_eq!(entry.fullname, "printer._ipp._tcp.example.com.");
+    assert_eq!(entry.addrs, vec!["192.0.2.7"]);
+    assert_eq!(entry.txt, vec!["rp=ipp/print", "ty=\\x01"]);
+    assert_eq!((entry.srv_priority, entry.srv_weight), (Some(10), Some(5)));
+    assert!(entry.online);
+    assert_eq!(service_domain(&entry.service_type), "example.com.");
+}
+
+#[test]
+fn test_cycle_interface_filter() {
+    let mut state = AppState::new();
+    state.local_interfaces = vec![
+        LocalInterface {
+            name: "eth0".to_string(),
+            index: Some(2),
+            ip: "192.168.1.10".parse().unwrap(),
+            prefix_len: 24,
+        },
+        LocalInterface {
+            name: "vlan20".to_string(),
+            index: Some(5),
+            ip: "10.20.0.1".parse().unwrap(),
+            prefix_len: 16,
+        },
+    ];
+    let mut lab = create_test_service("lab", "_http._tcp.local.", 80);
+    lab.addrs = vec!["10.20.3.4".to_string()];
+    state.ingest_resolved_service(lab);
+    state.ingest_resolved_service(create_test_service("office", "_http._tcp.local.", 81));
+    let lab_idx = state.services.iter().position(|s| &*s.host == "lab.local.");
+    assert_eq!(state.services[lab_idx.unwrap()].interfaces, vec!["vlan20"]);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('i')));
+    assert_eq!(state.interface_filter.as_deref(), Some("eth0"));
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(&*state.services[filtered[0]].host, "office.local.");
+
+    state.cycle_interface_filter();
+    assert_eq!(state.interface_filter.as_deref(), Some("vlan20"));
+    state.cycle_interface_filter();
+    assert_eq!(state.interface_filter, None);
+    assert_eq!(state.get_filtered_services().len(), 2);
+}
+
+#[test]
+fn test_service_domain() {
+    assert_eq!(service_domain("_http._tcp.local."), "local.");
+    assert_eq!(
+        service_domain("_ipp._tcp.corp.example.com."),
+        "corp.example.com."
+    );
+    assert_eq!(service_domain("_sleep-proxy._udp.local."), "local.");
+    assert_eq!(
+        meta_query_name("example.com."),
+        "_services._dns-sd._udp.example.com."
+    );
+}
+
+#[test]
+fn test_cycle_category_filter() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("printer", "_ipp._tcp.local.", 631));
+    state
+        .services
+        .push(create_test_service("nas", "_smb._tcp.local.", 445));
+    state
+        .services
+        .push(create_test_service("web", "_http._tcp.local.", 80));
+    state.mark_cache_dirty();
+    assert_eq!(state.get_filtered_services().len(), 3);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('K')));
+    assert_eq!(state.category_filter, Some(DeviceCategory::Printer));
+    assert_eq!(state.get_filtered_services(), &[0]);
+
+    for _ in 1..DeviceCategory::ALL.len() {
+        state.cycle_category_filter();
+    }
+    assert_eq!(state.category_filter, Some(DeviceCategory::Iot));
+    assert!(state.get_filtered_services().is_empty());
+
+    state.cycle_category_filter();
+    assert_eq!(state.category_filter, None);
+    assert_eq!(state.get_filtered_services().len(), 3);
+}
+
+#[test]
+fn test_quick_filter_matches_category() {
+    let service = create_test_service("office", "_ipp._tcp.local.", 631);
+    assert!(service_matches_query(&service, "printer"));
+    assert!(!service_matches_query(&service, "camera"));
+}
+
+#[test]
+fn test_scan_selected_service_ports() {
+    let mut state = AppState::new();
+    state.config.port_scan_ports = vec![22, 80];
+    state
+        .services
+        .push(create_test_service("test", "_http._tcp.local.", 80));
+    let address = state.services[0].addrs[0].clone();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('P')));
+    assert!(state.probes.show_port_scan_popup());
+    assert_eq!(
+        state.pending_requests,
+        vec![BackgroundRequest::ScanPorts {
+            scan_id: 0,
+            address: address.clone(),
+            ports: vec![22, 80],
+        }]
+    );
+
+    // Reopening during a running scan of the same host doesn't restart it
+    state.pending_requests.clear();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    assert!(!state.probes.show_port_scan_popup());
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('P')));
+    assert!(state.pending_requests.is_empty());
+
+    state.probes.update_port_scan(0, 80, true);
+    state.probes.update_port_scan(0, 22, false);
+    // Results of replaced scans are ignored
+    state.probes.update_port_scan(7, 443, true);
+    let scan = state.probes.port_scan().unwrap();
+    assert_eq!(scan.scanned, 2);
+    assert_eq!(scan.open_ports, vec![80]);
+
+    // A finished scan is started again
+    state.probes.close_port_scan_popup();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('P')));
+    assert_eq!(state.probes.port_scan().unwrap().scan_id, 1);
+    assert_eq!(state.pending_requests.len(), 1);
+}
+
+#[test]
+fn test_well_known_port_name() {
+    assert_eq!(well_known_port_name(22), Some("SSH"));
+    assert_eq!(well_known_port_name(12345), None);
+}
+
+#[test]
+fn test_format_host_port() {
+    let service = create_test_service("test", "_http._tcp.local.", 8080);
+    assert_eq!(format_host_port(&service), "test.local:8080");
+}
+
+#[test]
+fn test_copy_sequence_without_selection() {
+    let mut state = AppState::new();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('y')));
+    assert_eq!(state.pending_key, Some('y'));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('a')));
+    assert_eq!(state.pending_key, None);
+    assert!(!state.show_bulk_actions_popup);
+
+    // Unknown copy fields fall through to the regular key handling
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('y')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    assert!(state.show_bulk_actions_popup);
+}
+
+#[test]
+fn test_format_service_markers() {
+    assert_eq!(format_service_markers(false, true, false), " ✔  ");
+    assert_eq!(format_service_markers(false, false, true), "  ◉ ");
+    assert_eq!(format_service_markers(true, false, false), "⚑   ");
+    assert_eq!(format_service_markers(false, false, false), "    ");
+}
+
+// Remove offline services tests
+#[test]
+fn test_remove_offline_services() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.online = false;
+    let service2 = create_test_service("test2", "_http._tcp.local.", 81);
+    let mut service3 = create_test_service("test3", "_http._tcp.local.", 82);
+    service3.online = false;
+
+    state.services.push(service1);
+    state.services.push(service2);
+    state.services.push(service3);
+
+    state.remove_offline_services();
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].fullname, "test2._http._tcp.local.");
+}
+
+#[test]
+fn test_service_store_index() {
+    let mut store = ServiceStore::from(vec![
+        create_test_service("gamma", "_http._tcp.local.", 80),
+        create_test_service("alpha", "_http._tcp.local.", 81),
+    ]);
+    store.push(create_test_service("beta", "_ssh._tcp.local.", 22));
+    assert_eq!(store.position("beta._ssh._tcp.local."), Some(2));
+    let mut shouting = create_test_service("BETA", "_ssh._tcp.local.", 22);
+    shouting.agent = Some("lab".to_string());
+    store.push(shouting);
+    assert_eq!(
+        store.positions_ignoring_case("beta._ssh._tcp.local."),
+        [2, 3]
+    );
+    store.retain(|service| service.agent.is_none());
+
+    assert_eq!(store.position("alpha._http._tcp.local."), Some(1));
+    assert_eq!(store.get("gamma._http._tcp.local.").unwrap().port, 80);
+
+    // Same name, replaced where it is
+    store.push(create_test_service("alpha", "_http._tcp.local.", 8080));
+    assert_eq!(store.len(), 3);
+    assert_eq!(store[1].port, 8080);
+
+    store.retain(|service| service.port != 8080);
+    assert!(!store.contains("alpha._http._tcp.local."));
+    assert_eq!(store.position("beta._ssh._tcp.local."), Some(1));
+    store
+        .get_mut_at(None, "beta._ssh._tcp.local.")
+        .unwrap()
+        .online = false;
+    assert!(!store[1].online);
+
+    // Changed keys are indexed again
+    store.get_mut(1).fullname = "delta._http._tcp.local.".to_string();
+    assert!(!store.contains("beta._ssh._tcp.local."));
+    assert_eq!(store.position("delta._http._tcp.local."), Some(1));
+    assert_eq!(
+        store.positions_ignoring_case("delta._http._tcp.local."),
+        [1]
+    );
+    assert!(
+        store
+            .positions_ignoring_case("beta._ssh._tcp.local.")
+            .is_empty()
+    );
+    store.update_all(|service| service.agent = Some("lab".to_string()));
+    assert!(!store.contains("delta._http._tcp.local."));
+    assert_eq!(
+        store.position_at(Some("lab"), "delta._http._tcp.local."),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_changed_services_are_placed_like_a_full_sort() {
+    let mut state = AppState::new();
+    for (name, port) in [("delta", 80), ("alpha", 443), ("charlie", 80)] {
+        state.ingest_resolved_service(create_test_service(name, "_http._tcp.local.", port));
+    }
+    state.sort_field = SortField::Port;
+    state.sort_direction = SortDirection::Descending;
+    for ch in "local".chars() {
+        state.add_to_filter(ch);
+    }
+    state.get_filtered_services();
+
+    let mut moved_offline = create_test_service("alpha", "_http._tcp.local.", 443);
+    moved_offline.port = 80;
+    let changes = [
+        create_test_service("bravo", "_http._tcp.local.", 80),
+        create_test_service("aardvark", "_http._tcp.local.", 8080),
+        moved_offline,
+        create_test_service("echo", "_http._tcp.local.", 443),
+    ];
+    for service in changes {
+        state.ingest_resolved_service(service);
+        // Placed without rebuilding the list
+        assert!(!state.cache_dirty);
+        let placed = state.get_filtered_services().to_vec();
+        state.invalidate_cache_and_validate();
+        assert_eq!(placed, state.get_filtered_services());
+    }
+
+    state.mark_service_offline("bravo._http._tcp.local.");
+    assert!(!state.cache_dirty);
+    let placed = state.get_filtered_services().to_vec();
+    state.invalidate_cache_and_validate();
+    assert_eq!(placed, state.get_filtered_services());
+    // Services keep their place in the store as others come
+    assert_eq!(state.services[0].host.as_ref(), "delta.local.");
+}
+
+#[test]
+fn test_service_store_interns_hosts_and_types() {
+    let mut store = ServiceStore::default();
+    store.push(create_test_service("alpha", "_http._tcp.local.", 80));
+    let mut beta = create_test_service("beta", "_http._tcp.local.", 81);
+    beta.host = "alpha.local.".into();
+    store.push(beta);
+    assert!(Arc::ptr_eq(&store[0].service_type, &store[1].service_type));
+    assert!(Arc::ptr_eq(&store[0].host, &store[1].host));
+
+    store.retain(|service| service.port == 81);
+    store.push(create_test_service("gamma", "_http._tcp.local.", 82));
+    assert!(Arc::ptr_eq(&store[0].service_type, &store[1].service_type));
+    assert_eq!(&*store[1].host, "gamma.local.");
+}
+
+#[test]
+fn test_update_batch_keeps_latest_per_service() {
+    let mut batch = UpdateBatch::default();
+    batch.push(DiscoveryUpdate::Resolved(Box::new(create_test_service(
+        "alpha",
+        "_http._tcp.local.",
+        80,
+    ))));
+    batch.push(DiscoveryUpdate::Removed(
+        "beta._ssh._tcp.local.".to_string(),
+    ));
+    batch.push(DiscoveryUpdate::Resolved(Box::new(create_test_service(
+        "alpha",
+        "_http._tcp.local.",
+        8080,
+    ))));
+    assert_eq!(batch.len(), 2);
+    assert_eq!(batch.merged, 1);
+    assert!(matches!(&batch.updates[0], DiscoveryUpdate::Resolved(entry) if entry.port == 8080));
+
+    let mut state = AppState::new();
+    assert!(state.apply_discovery_updates(batch));
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.metrics["discovery_updates_merged"], 1);
+}
+
+#[test]
+fn test_apply_discovery_updates_drops_services_beyond_limit() {
+    let mut state = AppState::new();
+    state.services = (0..MAX_SERVICES)
+        .map(|i| create_test_service(&format!("s{}", i), "_http._tcp.local.", 80))
+        .collect::<Vec<_>>()
+        .into();
+
+    let mut batch = UpdateBatch::default();
+    batch.push(DiscoveryUpdate::Resolved(Box::new(create_test_service(
+        "new",
+        "_http._tcp.local.",
+        80,
+    ))));
+    assert!(!state.apply_discovery_updates(batch));
+    assert_eq!(state.services.len(), MAX_SERVICES);
+    assert_eq!(state.metrics["services_dropped"], 1);
+
+    // Known services still get their updates
+    let mut batch = UpdateBatch::default();
+    batch.push(DiscoveryUpdate::Resolved(Box::new(create_test_service(
+        "s1",
+        "_http._tcp.local.",
+        8080,
+    ))));
+    assert!(state.apply_discovery_updates(batch));
+    assert_eq!(
+        state.services.get("s1._http._tcp.local.").unwrap().port,
+        8080
+    );
+}
+
+#[test]
+fn test_remove_offline_services_removes_empty_types() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    let mut http_service = create_test_service("test1", "_http._tcp.local.", 80);
+    http_service.online = false;
+    let ssh_service = create_test_service("test2", "_ssh._tcp.local.", 22);
+
+    state.services.push(http_service);
+    state.services.push(ssh_service);
+
+    state.remove_offline_services();
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.service_types.len(), 1);
+    assert_eq!(state.service_types[0], "_ssh._tcp.local.");
+}
+
+#[test]
+fn test_remove_offline_services_adjusts_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 81);
+    service2.online = false;
+    let service3 = create_test_service("test3", "_http._tcp.local.", 82);
+
+    state.services.push(service1);
+    state.services.push(service2);
+    state.services.push(service3);
+    state.selected_service = 2;
+
+    state.remove_offline_services();
+    assert_eq!(state.services.len(), 2);
+    // Selection should be adjusted to stay within bounds
+    assert!(state.selected_service <= 1);
+}
+
+// Key handling tests
+#[test]
+fn test_handle_key_event_quit() {
+    let mut state = AppState::new();
+    let key = KeyEvent::from(KeyCode::Char('q'));
+    assert!(!state.handle_key_event(key)); // Should return false to quit
+}
+
+#[test]
+fn test_handle_key_event_toggle_help() {
+    let mut state = AppState::new();
+    assert!(!state.show_help_popup);
+
+    let key = KeyEvent::from(KeyCode::Char('?'));
+    assert!(state.handle_key_event(key)); // Should return true to continue
+    assert!(state.show_help_popup);
+
+    assert!(state.handle_key_event(key));
+    assert!(!state.show_help_popup);
+}
+
+#[test]
+fn test_handle_key_event_toggle_metrics() {
+    let mut state = AppState::new();
+    assert!(!state.show_metrics_popup);
+
+    let key = KeyEvent::from(KeyCode::Char('m'));
+    assert!(state.handle_key_event(key));
+    assert!(state.show_metrics_popup);
+
+    assert!(state.handle_key_event(key));
+    assert!(!state.show_metrics_popup);
+}
+
+#[test]
+fn test_handle_help_popup_key() {
+    let mut state = AppState::new();
+    state.show_help_popup = true;
+
+    let key = KeyEvent::from(KeyCode::Char('a'));
+    assert!(state.handle_key_event(key)); // Any key should close popup
+    assert!(!state.show_help_popup);
+}
+
+#[test]
+fn test_handle_metrics_popup_key() {
+    let mut state = AppState::new();
+    state.show_metrics_popup = true;
+
+    let key = KeyEvent::from(KeyCode::Char('x'));
+    assert!(state.handle_key_event(key)); // Any key should close popup
+    assert!(!state.show_metrics_popup);
+}
+
+#[test]
+fn test_error_console() {
+    let mut state = AppState::new();
+    for i in 0..MAX_ERRORS + 2 {
+        state.report_error(format!("error {}", i));
+    }
+    assert_eq!(state.errors.len(), MAX_ERRORS);
+    assert_eq!(state.errors[0].message, "error 2");
+    assert_eq!(state.unseen_errors, MAX_ERRORS + 2);
+    assert_eq!(state.status_message.as_deref(), Some("error 101"));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('!')));
+    assert!(state.show_errors_popup);
+    assert_eq!(state.unseen_errors, 0);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('c')));
+    assert!(state.errors.is_empty());
+    assert!(state.show_errors_popup);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    assert!(!state.show_errors_popup);
+}
+
+// Metrics tests
+#[test]
+fn test_update_metric() {
+    let mut state = AppState::new();
+    state.update_metric("test_metric");
+    assert_eq!(state.metrics.get("test_metric"), Some(&1));
+
+    state.update_metric("test_metric");
+    assert_eq!(state.metrics.get("test_metric"), Some(&2));
+}
+
+#[test]
+fn test_update_daemon_metrics() {
+    let mut state = AppState::new();
+    let mut daemon_metrics = std::collections::HashMap::new();
+    daemon_metrics.insert("queries-sent".to_string(), 10);
+    daemon_metrics.insert("responses-recv".to_string(), 5);
+
+    let updated = state.update_daemon_metrics(&daemon_metrics);
+    assert!(updated);
+    assert_eq!(state.metrics.get("daemon_queries_sent"), Some(&10));
+    assert_eq!(state.metrics.get("daemon_responses_recv"), Some(&5));
+
+    // Same metrics should not trigger update
+    let updated = state.update_daemon_metrics(&daemon_metrics);
+    assert!(!updated);
+
+    // Changed metrics should trigger update
+    daemon_metrics.insert("queries-sent".to_string(), 15);
+    let updated = state.update_daemon_metrics(&daemon_metrics);
+    assert!(updated);
+    assert_eq!(state.metrics.get("daemon_queries_sent"), Some(&15));
+}
+
+// Cache tests
+#[test]
+fn test_filter_cache_invalidation() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    // Populate cache
+    let filtered = state.get_filtered_services();
+    assert_eq!(filtered.len(), 1);
+    assert!(!state.cache_dirty);
+
+    // Mark cache dirty
+    state.mark_cache_dirty();
+    assert!(state.cache_dirty);
+
+    // Next call should rebuild cache
+    let filtered = state.get_filtered_services();
+    assert_eq!(filtered.len(), 1);
+    assert!(!state.cache_dirty);
+}
+
+#[test]
+fn test_validate_selected_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = Some(1);
+
+    // Remove both types
+    state.service_types.clear();
+    state.validate_selected_type();
+    assert_eq!(state.selected_type, None);
+
+    // Add types back
+    state.add_service_type("_http._tcp.local.");
+    state.selected_type = Some(5); // Invalid index
+    state.validate_selected_type();
+    assert_eq!(state.selected_type, Some(0)); // Should clamp to last valid index
+}
+
+// Utility function tests
+#[test]
+fn test_rescan_key_queues_request() {
+    let mut state = AppState::new();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('r')));
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::Rescan]
+    ));
+}
+
+#[test]
+fn test_re_resolve_key_queues_request_once() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("printer", "_ipp._tcp.local.", 631));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('u')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('u')));
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::ReResolve { fullname, service_type }]
+            if fullname == "printer._ipp._tcp.local." && service_type == "_ipp._tcp.local."
+    ));
+    assert!(state.re_resolving.contains_key("printer._ipp._tcp.local."));
+}
+
+#[test]
+fn test_hostname_prompt_starts_lookup() {
+    let mut state = AppState::new();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('M')));
+    for ch in "octopi".chars() {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char(ch)));
+    }
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.queries.hostname_input().is_none());
+    assert!(state.queries.show_host_lookup_popup());
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::ResolveHostname { hostname }] if hostname == "octopi.local."
+    ));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('x')));
+    assert!(!state.queries.show_host_lookup_popup());
+}
+
+#[test]
+fn test_record_query_prompt() {
+    let mut state = AppState::new();
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('Q')));
+    for ch in "_ipp._tcp.local PTR".chars() {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char(ch)));
+    }
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.queries.show_record_query_popup());
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::QueryRecords { query_id: 0, question }]
+            if question == "_ipp._tcp.local PTR"
+    ));
+
+    state.queries.set_record_query_result(0, Ok(Vec::new()));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('j')));
+    assert_eq!(state.queries.record_query().unwrap().scroll, 0);
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.queries.show_record_query_popup());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('Q')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert_eq!(state.status_message.as_deref(), Some("Empty name"));
+    assert!(!state.queries.show_record_query_popup());
+}
+
+#[test]
+fn test_format_mdns_answer() {
+    use hickory_resolver::proto::rr::rdata::A;
+    use hickory_resolver::proto::rr::{Name, RData, Record};
+
+    let answer = mdns_query::MdnsAnswer {
+        source: "192.168.1.20:5353".parse().unwrap(),
+        record: Record::from_rdata(
+            Name::from_utf8("printer.local.").unwrap(),
+            120,
+            RData::A(A::new(192, 168, 1, 20)),
+        ),
+    };
+    assert_eq!(
+        format_mdns_answer(&answer),
+        "192.168.1.20:5353 printer.local. 120s A 192.168.1.20"
+    );
+}
+
+#[test]
+fn test_format_record_ttls() {
+    use hickory_resolver::proto::rr::rdata::{A, PTR, SRV};
+    use hickory_resolver::proto::rr::{Name, Record};
+
+    let mut state = AppState::new();
+    let mut service = create_test_service("Office Printer", "_ipp._tcp.local.", 631);
+    service.host = "Printer.local.".into();
+    let now = std::time::Instant::now();
+    assert_eq!(
+        format_record_ttls(&service, &state.observed_records, now),
+        None
+    );
+
+    let instance = mdns_query::instance_name("Office Printer", "_ipp._tcp.local.").unwrap();
+    let host = Name::from_utf8("printer.local.").unwrap();
+    let answer = |record| mdns_query::MdnsAnswer {
+        source: "192.168.1.20:5353".parse().unwrap(),
+        record,
+    };
+    state.observe_records(
+        &[
+            answer(Record::from_rdata(
+                Name::from_utf8("_ipp._tcp.local.").unwrap(),
+                4500,
+                RData::PTR(PTR(instance.clone())),
+            )),
+            answer(Record::from_rdata(
+                instance,
+                120,
+                RData::SRV(SRV::new(0, 0, 631, host.clone())),
+            )),
+            answer(Record::from_rdata(
+                host,
+                0,
+                RData::A(A::new(192, 168, 1, 20)),
+            )),
+        ],
+        now,
+    );
+    assert_eq!(
+        format_record_ttls(&service, &state.observed_records, now + Duration::from_secs(30)),
+        Some(
+            "Records:\nPTR TTL 4500s, expires in 1h 14m\nSRV TTL 120s, expires in 1m 30s\nA 192.168.1.20 TTL 0s, goodbye"
+                .to_string()
+        )
+    );
+    assert!(
+        format_record_ttls(
+            &service,
+            &state.observed_records,
+            now + Duration::from_secs(150)
+        )
+        .unwrap()
+        .contains("SRV TTL 120s, expired 0m 30s ago")
+    );
+
+    state.prune_observed_records(now + Duration::from_secs(181));
+    assert_eq!(state.observed_records.len(), 1);
+}
+
+#[test]
+fn test_stale_services() {
+    use hickory_resolver::proto::rr::rdata::TXT;
+    use hickory_resolver::proto::rr::{Name, Record};
+
+    let mut state = AppState::new();
+    let mut service = create_test_service("printer", "_ipp._tcp.local.", 631);
+    let now_micros = service.last_seen_micros;
+    assert!(!state.is_stale(&service, now_micros + 600_000_000));
+    assert!(state.is_stale(&service, now_micros + 601_000_000));
+
+    state.config.stale_after_secs = 0;
+    assert!(!state.is_stale(&service, now_micros + 601_000_000));
+    state.config.stale_after_secs = 600;
+    service.online = false;
+    assert!(!state.is_stale(&service, now_micros + 601_000_000));
+
+    // A re-announced TXT record counts as seen
+    service.online = true;
+    service.last_seen_micros = 0;
+    state.services.push(service);
+    state.observe_records(
+        &[mdns_query::MdnsAnswer {
+            source: "192.168.1.20:5353".parse().unwrap(),
+            record: Record::from_rdata(
+                Name::from_utf8("Printer._ipp._tcp.local.").unwrap(),
+                4500,
+                RData::TXT(TXT::new(vec!["rp=ipp/print".to_string()])),
+            ),
+        }],
+        std::time::Instant::now(),
+    );
+    assert!(state.services[0].last_seen_micros > 0);
+}
+
+#[test]
+fn test_expire_offline_services() {
+    let mut state = AppState::new();
+    let mut expired = create_test_service("old", "_ipp._tcp.local.", 631);
+    expired.go_offline_at(1_000_000);
+    // Updated since, the grace period counts from going offline
+    expired.last_updated_micros = 90_000_000;
+    let mut recent = create_test_service("recent", "_http._tcp.local.", 80);
+    recent.go_offline_at(50_000_000);
+    let mut online = create_test_service("online", "_ipp._tcp.local.", 632);
+    online.last_updated_micros = 1_000_000;
+    for service in [expired, recent, online] {
+        state.add_service_type(&service.service_type.clone());
+        state.services.push(service);
+    }
+
+    // Keeping them forever is the default
+    assert!(!state.expire_offline_services(100_000_000));
+    assert_eq!(state.services.len(), 3);
+
+    state.config.offline_grace_secs = 60;
+    assert!(state.expire_offline_services(100_000_000));
+    let names: Vec<&str> = state.services.iter().map(|s| s.fullname.as_str()).collect();
+    assert_eq!(
+        names,
+        ["recent._http._tcp.local.", "online._ipp._tcp.local."]
+    );
+    assert_eq!(state.metrics.get("offline_services_expired"), Some(&1));
+    assert!(!state.expire_offline_services(100_000_000));
+}
+
+#[test]
+fn test_conflicting_targets() {
+    let mut state = AppState::new();
+    let first = create_test_service("printer", "_ipp._tcp.local.", 631);
+    let mut second = first.clone();
+    second.host = "other.local.".into();
+    let now = std::time::Instant::now();
+
+    state.ingest_resolved_service(first.clone());
+    assert!(state.conflicting_targets(&first, now).is_empty());
+    state.ingest_resolved_service(second);
+    let service = state.services[0].clone();
+    assert_eq!(
+        state.conflicting_targets(&service, std::time::Instant::now()),
+        vec!["other.local.:631", "printer.local.:631"]
+    );
+    assert!(
+        state
+            .conflicting_targets(&service, now + CONFLICT_WINDOW + Duration::from_secs(1))
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_conflicting_srv_records() {
+    use hickory_resolver::proto::rr::rdata::SRV;
+    use hickory_resolver::proto::rr::{DNSClass, Name, Record};
+
+    let mut state = AppState::new();
+    let service = create_test_service("printer", "_ipp._tcp.local.", 631);
+    let instance = Name::from_utf8("printer._ipp._tcp.local.").unwrap();
+    let srv = |port, cache_flush: bool| {
+        let mut record = Record::from_rdata(
+            instance.clone(),
+            120,
+            RData::SRV(SRV::new(
+                0,
+                0,
+                port,
+                Name::from_utf8("printer.local.").unwrap(),
+            )),
+        );
+        if cache_flush {
+            record.set_dns_class(DNSClass::Unknown(MDNS_CACHE_FLUSH | 1));
+        }
+        mdns_query::MdnsAnswer {
+            source: "192.168.1.20:5353".parse().unwrap(),
+            record,
+        }
+    };
+    let now = std::time::Instant::now();
+    state.observe_records(&[srv(631, false), srv(632, false)], now);
+    assert_eq!(
+        state.conflicting_targets(&service, now),
+        vec!["printer.local.:631", "printer.local.:632"]
+    );
+
+    // A cache flush replaces the older record instead of conflicting with it
+    state.observe_records(&[srv(633, true)], now + Duration::from_secs(5));
+    assert!(
+        state
+            .conflicting_targets(&service, now + Duration::from_secs(5))
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_metrics_popup_keys() {
+    let mut state = AppState::new();
+    state.show_metrics_popup = true;
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('f')));
+    assert!(state.show_metrics_popup);
+    assert_eq!(state.pending_requests, vec![BackgroundRequest::VerifyCache]);
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.show_metrics_popup);
+}
+
+#[test]
+fn test_refresh_count() {
+    let mut state = AppState::new();
+    let service = create_test_service("printer", "_ipp._tcp.local.", 631);
+    state.ingest_resolved_service(service.clone());
+    assert_eq!(state.services[0].refresh_count, 1);
+    state.ingest_resolved_service(service.clone());
+    let mut changed = service;
+    changed.port = 632;
+    state.ingest_resolved_service(changed);
+    assert_eq!(state.services[0].refresh_count, 3);
+    assert_eq!(state.services[0].port, 632);
+
+    let mut chatty = create_test_service("chatty", "_ipp._tcp.local.", 631);
+    chatty.refresh_count = 10;
+    assert_eq!(
+        compare_services_by_field(&chatty, &state.services[0], SortField::Refreshes),
+        std::cmp::Ordering::Greater
+    );
+}
+
+#[test]
+fn test_srv_priority_and_weight() {
+    use hickory_resolver::proto::rr::rdata::SRV;
+    use hickory_resolver::proto::rr::{Name, Record};
+
+    let mut state = AppState::new();
+    state.ingest_resolved_service(create_test_service("web", "_http._tcp.local.", 80));
+    state.observe_records(
+        &[mdns_query::MdnsAnswer {
+            source: "192.168.1.20:5353".parse().unwrap(),
+            record: Record::from_rdata(
+                Name::from_utf8("web._http._tcp.local.").unwrap(),
+                120,
+                RData::SRV(SRV::new(10, 60, 80, Name::from_utf8("web.local.").unwrap())),
+            ),
+        }],
+        std::time::Instant::now(),
+    );
+    assert_eq!(state.services[0].srv_priority, Some(10));
+    assert_eq!(state.services[0].srv_weight, Some(60));
+
+    // The daemon's resolutions don't carry them, the seen values stay
+    let mut changed = create_test_service("web", "_http._tcp.local.", 8080);
+    changed.txt = vec!["path=/".to_string()];
+    state.ingest_resolved_service(changed);
+    let service = &state.services[0];
+    assert_eq!(
+        (service.srv_priority, service.srv_weight),
+        (Some(10), Some(60))
+    );
+    assert!(create_service_details_text(service, None).contains("SRV priority: 10, weight: 60"));
+    assert!(service_matches_query(service, "priority=10"));
+
+    let mut backup = service.clone();
+    backup.srv_weight = Some(20);
+    let unknown = create_test_service("other", "_http._tcp.local.", 80);
+    assert_eq!(
+        compare_services_by_field(service, &backup, SortField::Priority),
+        std::cmp::Ordering::Less
+    );
+    assert_eq!(
+        compare_services_by_field(&unknown, service, SortField::Priority),
+        std::cmp::Ordering::Greater
+    );
+}
+
+#[test]
+fn test_discovery_events() {
+    let mut state = AppState::new();
+    let mut events = state.discovery_events.subscribe();
+    let service = create_test_service("web", "_http._tcp.local.", 80);
+    state.ingest_resolved_service(service.clone());
+    state.ingest_resolved_service(service.clone());
+    let mut moved = service.clone();
+    moved.port = 8080;
+    state.ingest_resolved_service(moved);
+    state.mark_service_offline(&service.fullname);
+    state.mark_service_offline(&service.fullname);
+    state.ingest_resolved_service(service);
+
+    let mut kinds = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        kinds.push((event.event, event.port));
+    }
+    assert_eq!(
+        kinds,
+        [
+            (EventKind::Added, 80),
+            (EventKind::Updated, 8080),
+            (EventKind::Removed, 8080),
+            (EventKind::Added, 80)
+        ]
+    );
+}
+
+#[test]
+fn test_txt_history() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("cast", "_googlecast._tcp.local.", 8009);
+    service.txt = vec!["fn=Living Room".to_string(), "rs=Netflix".to_string()];
+    state.ingest_resolved_service(service.clone());
+    state.ingest_resolved_service(service.clone());
+    assert!(state.txt_history.is_empty());
+
+    service.txt = vec![
+        "fn=Living Room".to_string(),
+        "rs=YouTube".to_string(),
+        "st=1".to_string(),
+    ];
+    service.last_updated_micros = 0;
+    state.ingest_resolved_service(service);
+    let history = &state.txt_history["cast._googlecast._tcp.local."];
+    assert_eq!(
+        history[0].changes,
+        vec![
+            (
+                "rs".to_string(),
+                Some("Netflix".to_string()),
+                Some("YouTube".to_string())
+            ),
+            ("st".to_string(), None, Some("1".to_string())),
+        ]
+    );
+    assert!(format_txt_history(history).ends_with("\n  rs: Netflix → YouTube\n  + st=1"));
+}
+
+#[test]
+fn test_diff_txt_removed_key() {
+    let old = vec!["a=1".to_string(), "flag".to_string()];
+    assert_eq!(
+        diff_txt(&old, &["a=1".to_string()]),
+        vec![("flag".to_string(), Some(String::new()), None)]
+    );
+}
+
+#[test]
+fn test_normalize_hostname() {
+    assert_eq!(
+        normalize_hostname(" octopi "),
+        Some("octopi.local.".to_string())
+    );
+    assert_eq!(
+        normalize_hostname("nas.local"),
+        Some("nas.local.".to_string())
+    );
+    assert_eq!(
+        normalize_hostname("nas.local."),
+        Some("nas.local.".to_string())
+    );
+    assert_eq!(normalize_hostname("  "), None);
+}
+
+#[test]
+fn test_spinner_frame() {
+    assert_eq!(spinner_frame(Duration::ZERO), '⠋');
+    assert_eq!(spinner_frame(Duration::from_millis(250)), '⠹');
+    assert_eq!(spinner_frame(Duration::from_millis(1000)), '⠋');
+}
+
+#[test]
+fn test_toggle_interfaces() {
+    let mut state = AppState::new();
+    state.local_interfaces = vec![LocalInterface {
+        name: "eth0".to_string(),
+        index: Some(2),
+        ip: "192.168.1.10".parse().unwrap(),
+        prefix_len: 24,
+    }];
+    state.show_interfaces_popup = true;
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char(' ')));
+    assert!(state.disabled_interfaces.contains("eth0"));
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::SetInterfaceEnabled { name, enabled: false }] if name == "eth0"
+    ));
+
+    // Disabled interfaces stay listed even when gone from the system
+    state.local_interfaces.clear();
+    assert_eq!(
+        state.interface_addresses().into_keys().collect::<Vec<_>>(),
+        vec!["eth0"]
+    );
+    state.pending_requests.clear();
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.disabled_interfaces.is_empty());
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::SetInterfaceEnabled { enabled: true, .. }]
+    ));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.show_interfaces_popup);
+}
+
+#[test]
+fn test_restrict_to_multicast_interfaces() {
+    let interface = |name: &str, ip: &str| LocalInterface {
+        name: name.to_string(),
+        index: Some(2),
+        ip: ip.parse().unwrap(),
+        prefix_len: 24,
+    };
+    let mut state = AppState::new();
+    state.local_interfaces = vec![
+        interface("eth0", "192.168.1.10"),
+        interface("eth0.20", "fe80::1"),
+        interface("eth0.20", "10.0.20.5"),
+        interface("docker0", "172.17.0.1"),
+    ];
+    assert!(state.restrict_to_multicast_interfaces().is_empty());
+    assert_eq!(state.multicast_if(), None);
+
+    state.config.multicast_interfaces = vec!["eth0.20".to_string(), "wg0".to_string()];
+    assert_eq!(
+        state.restrict_to_multicast_interfaces(),
+        ["docker0", "eth0"]
+    );
+    assert!(state.disabled_interfaces.contains("eth0"));
+    assert_eq!(state.multicast_if(), Some("10.0.20.5".parse().unwrap()));
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Unknown interface: wg0")
+    );
+}
+
+#[test]
+fn test_hidden_service_types() {
+    let mut state = AppState::new();
+    state.ingest_resolved_service(create_test_service("web", "_http._tcp.local.", 80));
+    state.ingest_resolved_service(create_test_service("nas", "_smb._tcp.local.", 445));
+    state.update_service_type_selection(Some(1));
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('H')));
+    assert!(state.show_hidden_types_popup);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char(' ')));
+    assert!(state.hidden_service_types.contains("_http._tcp.local."));
+    assert_eq!(state.service_types, vec!["_smb._tcp.local."]);
+    assert_eq!(state.selected_type, Some(0));
+
+    // Hidden types stay hidden when their services update and drop out of all types
+    state.ingest_resolved_service(create_test_service("web2", "_http._tcp.local.", 81));
+    assert_eq!(state.service_types, vec!["_smb._tcp.local."]);
+    state.update_service_type_selection(None);
+    assert_eq!(state.get_filtered_services().len(), 1);
+    assert_eq!(
+        state.managed_service_types(),
+        vec!["_http._tcp.local.", "_smb._tcp.local."]
+    );
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.hidden_service_types.is_empty());
+    assert_eq!(state.service_types.len(), 2);
+    assert_eq!(state.get_filtered_services().len(), 3);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.show_hidden_types_popup);
+}
+
+#[test]
+fn test_normalize_service_type() {
+    assert_eq!(
+        normalize_service_type(" _workstation._tcp "),
+        Some("_workstation._tcp.local.".to_string())
+    );
+    assert_eq!(
+        normalize_service_type("_http._tcp.example.com"),
+        Some("_http._tcp.example.com.".to_string())
+    );
+    assert_eq!(
+        normalize_service_type("_printer._sub._http._tcp.local."),
+        Some("_printer._sub._http._tcp.local.".to_string())
+    );
+    assert_eq!(normalize_service_type("_tcp.local."), None);
+    assert_eq!(normalize_service_type("workstation"), None);
+    assert_eq!(normalize_service_type(""), None);
+}
+
+#[test]
+fn test_add_browsed_service_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('a')));
+    for ch in "_workstation._tcp".chars() {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char(ch)));
+    }
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(state.add_type_input.is_none());
+    assert!(matches!(
+        state.pending_requests.as_slice(),
+        [BackgroundRequest::BrowseServiceType { service_type }]
+            if service_type == "_workstation._tcp.local."
+    ));
+
+    // Known types are selected instead of browsed again
+    state.pending_requests.clear();
+    state.add_browsed_service_type("_http._tcp");
+    assert!(state.pending_requests.is_empty());
+    assert_eq!(state.selected_type, Some(0));
+
+    state.add_browsed_service_type("nonsense");
+    assert!(state.pending_requests.is_empty());
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Invalid service type: nonsense")
+    );
+}
+
+#[test]
+fn test_subtype_helpers() {
+    assert!(!is_subtype("_http._tcp.local."));
+    assert!(is_subtype("_printer._sub._http._tcp.local."));
+    assert_eq!(
+        parent_service_type("_printer._sub._http._tcp.local."),
+        "_http._tcp.local."
+    );
+    assert_eq!(
+        parent_service_type("_http._tcp.local."),
+        "_http._tcp.local."
+    );
+}
+
+#[test]
+fn test_subtypes_nest_under_parent_and_narrow_results() {
+    let mut state = AppState::new();
+    state.add_service_type("_ipp._tcp.local.");
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_printer._sub._http._tcp.local.");
+    assert_eq!(
+        state.service_types,
+        vec![
+            "_http._tcp.local.",
+            "_printer._sub._http._tcp.local.",
+            "_ipp._tcp.local."
+        ]
+    );
+
+    let mut printer = create_test_service("printer", "_http._tcp.local.", 80);
+    printer.subtype = Some("_printer._sub._http._tcp.local.".to_string());
+    state.ingest_resolved_service(printer);
+    state.ingest_resolved_service(create_test_service("web", "_http._tcp.local.", 8080));
+    // Resolved again by the parent browse without the subtype
+    state.ingest_resolved_service(create_test_service("printer", "_http._tcp.local.", 80));
+
+    state.update_service_type_selection(Some(0));
+    assert_eq!(state.get_filtered_services().len(), 2);
+    state.update_service_type_selection(Some(1));
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(&*state.services[filtered[0]].host, "printer.local.");
+    assert!(!state.remove_service_type("_printer._sub._http._tcp.local."));
+}
+
+#[test]
+fn test_current_timestamp_micros() {
+    let ts1 = current_timestamp_micros();
+    let ts2 = current_timestamp_micros();
+    assert!(ts2 >= ts1);
+    assert!(ts1 > 0);
+}
+
+// Formatting tests
+#[test]
+fn test_format_service_type_for_display() {
+    assert_eq!(
+        format_service_type_for_display("_http._tcp.local."),
+        "http.tcp"
+    );
+    assert_eq!(
+        format_service_type_for_display("_ssh._tcp.local."),
+        "ssh.tcp"
+    );
+    assert_eq!(
+        format_service_type_for_display("_printer._tcp."),
+        "printer.tcp"
+    );
+}
+
+#[test]
+fn test_format_service_for_display() {
+    let service = ServiceEntry {
+        fullname: "MyPrinter._printer._tcp.local.".to_string(),
+        host: "printer.local.".into(),
+        service_type: "_printer._tcp.local.".into(),
+        subtype: None,
+        addrs: vec!["192.168.1.100".to_string()],
+        port: 631,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let display = format_service_for_display(&service, AddressFamily::Any.into());
+    assert!(display.contains("MyPrinter"));
+    assert!(display.contains("printer"));
+    assert!(display.contains("192.168.1.100"));
+    assert!(display.contains("631"));
+}
+
+#[test]
+fn test_format_service_for_display_no_address() {
+    let service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let display = format_service_for_display(&service, AddressFamily::Any.into());
+    assert!(display.contains("<no-addr>"));
+}
+
+#[test]
+fn test_create_service_details_text() {
+    let service = ServiceEntry {
+        fullname: "MyService._http._tcp.local.".to_string(),
+        host: "myhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: Some("_printer".to_string()),
+        addrs: vec!["192.168.1.1".to_string(), "192.168.1.2".to_string()],
+        port: 8080,
+        txt: vec!["key1=value1".to_string(), "key2=value2".to_string()],
+        online: true,
+        first_seen_micros: 1000000000,
+        last_updated_micros: 1000000000,
+        last_seen_micros: 1000000000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let details = create_service_details_text(&service, None);
+    assert!(details.contains("MyService._http._tcp.local."));
+    assert!(details.contains("myhost.local."));
+    assert!(details.contains("_http._tcp.local."));
+    assert!(details.contains("_printer"));
+    assert!(details.contains("8080"));
+    assert!(details.contains("192.168.1.1"));
+    assert!(details.contains("192.168.1.2"));
+    assert!(details.contains("key1=value1"));
+    assert!(details.contains("key2=value2"));
+    assert!(details.contains("Status: online"));
+    assert!(details.contains("Last updated:"));
+    assert!(details.contains("Last seen:"));
+}
+
+#[test]
+fn test_create_service_details_text_type_name() {
+    let service = create_test_service("printer", "_ipp._tcp.local.", 631);
+    let details = create_service_details_text(&service, Some("Printers (IPP)"));
+    assert!(
+        details.contains("Type: _ipp._tcp.local. (Printers (IPP): Internet Printing Protocol)\n")
+    );
+
+    let service = create_test_service("foo", "_foo._tcp.local.", 1234);
+    let details = create_service_details_text(&service, Some("Foo"));
+    assert!(details.contains("Type: _foo._tcp.local. (Foo)\n"));
+}
+
+#[test]
+fn test_create_service_details_text_offline_service() {
+    let service = ServiceEntry {
+        fullname: "OfflineService._http._tcp.local.".to_string(),
+        host: "offlinehost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: false,
+        first_seen_micros: 2000000000,
+        last_updated_micros: 2000000000,
+        last_seen_micros: 2000000000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let details = create_service_details_text(&service, None);
+    assert!(details.contains("Offline since:"));
+    assert!(details.contains("None")); // No addresses
+    assert!(!details.contains("Subtype:")); // No subtype
+}
+
+#[test]
+fn test_format_timestamp_micros() {
+    let timestamp = format_timestamp_micros(1609459200000000); // 2021-01-01 00:00:00 UTC
+    // Just verify it's a valid formatted string with expected components
+    assert!(timestamp.contains("-"));
+    assert!(timestamp.contains(":"));
+    assert!(timestamp.len() > 20); // Should include date, time, and microseconds
+}
+
+// Layout tests
+#[test]
+fn test_create_main_layout() {
+    let area = ratatui::layout::Rect::new(0, 0, 100, 50);
+    let layout = create_main_layout(area);
+
+    assert!(layout.left_panel.width > 0);
+    assert!(layout.services_area.width > 0);
+    assert!(layout.details_area.width > 0);
+    assert!(layout.services_area.height > 0);
+    assert!(layout.details_area.height > 0);
+}
+
+#[test]
+fn test_calculate_visible_counts() {
+    let area = ratatui::layout::Rect::new(0, 0, 100, 50);
+    let layout = create_main_layout(area);
+    let counts = calculate_visible_counts(&layout);
+
+    assert!(counts.types > 0);
+    assert!(counts.services > 0);
+}
+
+#[test]
+fn test_create_centered_popup() {
+    let parent = ratatui::layout::Rect::new(0, 0, 100, 50);
+    let popup = create_centered_popup(parent, 50, 50);
+
+    // Popup should be smaller than parent
+    assert!(popup.width <= parent.width);
+    assert!(popup.height <= parent.height);
+
+    // Popup should be centered (roughly)
+    let center_x = parent.width / 2;
+    let center_y = parent.height / 2;
+    let popup_center_x = popup.x + popup.width / 2;
+    let popup_center_y = popup.y + popup.height / 2;
+
+    // Allow some margin of error due to rounding and margins
+    assert!((popup_center_x as i32 - center_x as i32).abs() < 10);
+    assert!((popup_center_y as i32 - center_y as i32).abs() < 10);
+}
+
+#[test]
+fn test_create_service_list_item_style() {
+    let online_service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let offline_service = ServiceEntry {
+        fullname: "test._http._tcp.local.".to_string(),
+        host: "testhost.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: false,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    // Test selected online service
+    let style = create_service_list_item_style(0, 0, &online_service);
+    assert_eq!(style.fg, Some(Color::White));
+    assert_eq!(style.bg, Some(Color::DarkGray));
+
+    // Test unselected online service
+    let style = create_service_list_item_style(0, 1, &online_service);
+    assert_eq!(style.fg, Some(Color::White));
+    assert_eq!(style.bg, None);
+
+    // Test offline service
+    let style = create_service_list_item_style(0, 0, &offline_service);
+    assert_eq!(style.fg, Some(Color::LightMagenta));
+    assert!(style.add_modifier.contains(Modifier::ITALIC));
+}
+
+// Edge case tests
+#[test]
+fn test_empty_service_list_navigation() {
+    let mut state = AppState::new();
+
+    state.navigate_services_up();
+    assert_eq!(state.selected_service, 0);
+
+    state.navigate_services_down();
+    assert_eq!(state.selected_service, 0);
+
+    state.navigate_services_to_first();
+    assert_eq!(state.selected_service, 0);
+
+    state.navigate_services_to_last();
+    assert_eq!(state.selected_service, 0);
+}
+
+#[test]
+fn test_empty_service_types_navigation() {
+    let mut state = AppState::new();
+
+    state.navigate_service_types_up();
+    assert_eq!(state.selected_type, None);
+
+    state.navigate_service_types_down();
+    assert_eq!(state.selected_type, None);
+}
+
+#[test]
+fn test_filter_with_no_matching_services() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = Some(1); // Select _ssh._tcp.local.
+
+    // Add only http service
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    let filtered = state.get_filtered_services();
+    assert_eq!(filtered.len(), 0); // No ssh services
+}
+
+#[test]
+fn test_scroll_offset_boundary_conditions() {
+    let mut state = AppState::new();
+    for i in 0..3 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.visible_services = 10; // More visible space than services
+
+    state.selected_service = 2;
+    state.update_services_scroll_offset();
+    assert_eq!(state.services_scroll_offset, 0); // Should stay at 0 since all fit
+}
+
+#[test]
+fn test_update_service_type_selection_resets_scroll() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    for i in 0..10 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    state.selected_service = 5;
+    state.services_scroll_offset = 3;
+
+    state.update_service_type_selection(Some(1));
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_notification_enum() {
+    // Test that notification enum variants can be created
+    let _service_changed = Notification::ServiceChanged;
+    let _metrics_updated = Notification::MetricsUpdated;
+}
+
+// Sorting tests
+#[test]
+fn test_compare_services_by_field_host() {
+    let service1 = create_test_service("alpha", "_http._tcp.local.", 80);
+    let service2 = create_test_service("beta", "_http._tcp.local.", 80);
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Host);
+    assert_eq!(result, std::cmp::Ordering::Less);
+
+    let result = compare_services_by_field(&service2, &service1, SortField::Host);
+    assert_eq!(result, std::cmp::Ordering::Greater);
+
+    let result = compare_services_by_field(&service1, &service1, SortField::Host);
+    assert_eq!(result, std::cmp::Ordering::Equal);
+}
+
+#[test]
+fn test_natural_cmp() {
+    use std::cmp::Ordering;
+
+    assert_eq!(natural_cmp("host2", "host10"), Ordering::Less);
+    assert_eq!(natural_cmp("host10", "host2"), Ordering::Greater);
+    assert_eq!(natural_cmp("printer-1", "printer-12"), Ordering::Less);
+    assert_eq!(natural_cmp("a1b2", "a1b10"), Ordering::Less);
+    assert_eq!(natural_cmp("host", "host1"), Ordering::Less);
+    assert_eq!(natural_cmp("alpha", "beta"), Ordering::Less);
+    assert_eq!(natural_cmp("host007", "host7"), Ordering::Greater);
+    assert_eq!(natural_cmp("host7", "host7"), Ordering::Equal);
+}
+
+#[test]
+fn test_compare_services_by_field_host_natural_order() {
+    let printer2 = create_test_service("printer-2", "_ipp._tcp.local.", 631);
+    let printer12 = create_test_service("printer-12", "_ipp._tcp.local.", 631);
+
+    assert_eq!(
+        compare_services_by_field(&printer2, &printer12, SortField::Host),
+        std::cmp::Ordering::Less
+    );
+    assert_eq!(
+        compare_services_by_field(&printer2, &printer12, SortField::Fullname),
+        std::cmp::Ordering::Less
+    );
+}
+
+#[test]
+fn test_compare_services_by_field_service_type() {
+    let http_service = create_test_service("test", "_http._tcp.local.", 80);
+    let ssh_service = create_test_service("test", "_ssh._tcp.local.", 22);
+
+    let result = compare_services_by_field(&http_service, &ssh_service, SortField::ServiceType);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_fullname() {
+    let service1 = ServiceEntry {
+        fullname: "aaa._http._tcp.local.".to_string(),
+        host: "host1.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+    let service2 = ServiceEntry {
+        fullname: "zzz._http._tcp.local.".to_string(),
+        host: "host2.local.".into(),
+        service_type: "_http._tcp.local.".into(),
+        subtype: None,
+        addrs: vec![],
+        port: 80,
+        txt: vec![],
+        online: true,
+        first_seen_micros: 1000,
+        last_updated_micros: 1000,
+        last_seen_micros: 1000,
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    };
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Fullname);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_port() {
+    let service1 = create_test_service("test", "_http._tcp.local.", 80);
+    let service2 = create_test_service("test", "_http._tcp.local.", 8080);
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Port);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_timestamp() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.last_updated_micros = 1000;
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.last_updated_micros = 2000;
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Timestamp);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_first_seen() {
+    let mut older = create_test_service("test1", "_http._tcp.local.", 80);
+    older.first_seen_micros = 1000;
+    older.last_updated_micros = 5000;
+    let mut newer = create_test_service("test2", "_http._tcp.local.", 80);
+    newer.first_seen_micros = 2000;
+    newer.last_updated_micros = 3000;
+
+    let result = compare_services_by_field(&older, &newer, SortField::FirstSeen);
+    assert_eq!(result, std::cmp::Ordering::Less);
+    let result = compare_services_by_field(&older, &newer, SortField::Timestamp);
+    assert_eq!(result, std::cmp::Ordering::Greater);
+}
+
+#[test]
+fn test_add_or_update_service_preserves_first_seen() {
+    let mut state = AppState::new();
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.first_seen_micros = 1000;
+    service.last_updated_micros = 1000;
+    state.add_or_update_service(service.clone());
+
+    state.mark_service_offline(&service.fullname);
+    assert_eq!(state.services[0].first_seen_micros, 1000);
+    assert!(state.services[0].offline_since_micros.is_some());
+
+    let mut updated = service.clone();
+    updated.first_seen_micros = 9000;
+    updated.last_updated_micros = 9000;
+    state.add_or_update_service(updated);
+    assert!(state.services[0].online);
+    assert_eq!(state.services[0].first_seen_micros, 1000);
+    assert_eq!(state.services[0].last_updated_micros, 9000);
+    assert_eq!(state.services[0].offline_since_micros, None);
+}
+
+#[test]
+fn test_compare_services_by_field_address_ip() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.addrs = vec!["192.168.1.10".to_string()];
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.addrs = vec!["192.168.1.20".to_string()];
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Address);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_address_ipv6() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.addrs = vec!["2001:db8::1".to_string()];
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.addrs = vec!["2001:db8::2".to_string()];
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Address);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_address_mixed_ipv4_ipv6() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.addrs = vec!["192.168.1.1".to_string()];
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.addrs = vec!["2001:db8::1".to_string()];
+
+    // IPv4 should come before IPv6 (lexicographic comparison of IP types)
+    let result = compare_services_by_field(&service1, &service2, SortField::Address);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_address_no_addr() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.addrs = vec![];
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.addrs = vec!["192.168.1.1".to_string()];
+
+    let result = compare_services_by_field(&service1, &service2, SortField::Address);
+    // "<no-addr>" should be compared as string ("<no-addr>" > "192.168.1.1")
+    assert_eq!(result, std::cmp::Ordering::Greater);
+}
+
+#[test]
+fn test_compare_services_by_field_address_string_fallback() {
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.addrs = vec!["invalid-ip-1".to_string()];
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 80);
+    service2.addrs = vec!["invalid-ip-2".to_string()];
+
+    // Falls back to string comparison when IP parsing fails
+    let result = compare_services_by_field(&service1, &service2, SortField::Address);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_compare_services_by_field_address_count() {
+    let mut no_addrs = create_test_service("test1", "_http._tcp.local.", 80);
+    no_addrs.addrs = vec![];
+    let mut two_addrs = create_test_service("test2", "_http._tcp.local.", 80);
+    two_addrs.addrs = vec!["192.168.1.2".to_string(), "fe80::2".to_string()];
+
+    let result = compare_services_by_field(&no_addrs, &two_addrs, SortField::AddressCount);
+    assert_eq!(result, std::cmp::Ordering::Less);
+}
+
+#[test]
+fn test_preferred_address_family_sorts_matching_services_first() {
+    let mut state = AppState::new();
+    let mut v4_only = create_test_service("alpha", "_http._tcp.local.", 80);
+    v4_only.addrs = vec!["192.168.1.1".to_string()];
+    let mut v6_only = create_test_service("beta", "_http._tcp.local.", 80);
+    v6_only.addrs = vec!["fe80::1".to_string()];
+    let mut no_addrs = create_test_service("gamma", "_http._tcp.local.", 80);
+    no_addrs.addrs = vec![];
+    state.services = vec![v4_only, v6_only, no_addrs].into();
+
+    let first_host = |state: &mut AppState| -> String {
+        let idx = state.get_filtered_services()[0];
+        state.services[idx].host.to_string()
+    };
+
+    assert_eq!(first_host(&mut state), "alpha.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('A')));
+    assert_eq!(state.preferred_address_family, AddressFamily::Ipv4);
+    assert_eq!(first_host(&mut state), "alpha.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('A')));
+    assert_eq!(state.preferred_address_family, AddressFamily::Ipv6);
+    assert_eq!(first_host(&mut state), "beta.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('A')));
+    assert_eq!(state.preferred_address_family, AddressFamily::Any);
+}
+
+#[test]
+fn test_toggle_sort_direction() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+
+    state.toggle_sort_direction();
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.toggle_sort_direction();
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+}
+
+#[test]
+fn test_sort_field_default_directions() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+
+    state.update_sort_field(SortField::Timestamp);
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.update_sort_field(SortField::FirstSeen);
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.update_sort_field(SortField::Port);
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+}
+
+#[test]
+fn test_sort_direction_remembered_per_field() {
+    let mut state = AppState::new();
+    state.toggle_sort_direction();
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::ServiceType);
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Host);
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.update_sort_field(SortField::Timestamp);
+    state.toggle_sort_direction();
+    state.update_sort_field(SortField::Host);
+    state.update_sort_field(SortField::Timestamp);
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+}
+
+#[test]
+fn test_view_restored_from_last_session() {
+    let mut state = AppState::new();
+    state.update_sort_field(SortField::Port);
+    state.toggle_sort_direction();
+    state.toggle_online_first();
+    state.filter_query = InputLine::from("printer");
+    state.category_filter = Some(DeviceCategory::Printer);
+    state.domain_filter = Some("local.".to_string());
+    state.interface_filter = Some("eth0".to_string());
+    state.baseline_filter = Some(BaselineStatus::Missing);
+    state
+        .hidden_service_types
+        .insert("_sleep-proxy._udp.local.".to_string());
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ipp._tcp.local.");
+    state.update_service_type_selection(Some(1));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("view.json");
+    state.saved_view().save(&path).unwrap();
+    let view = SavedView::load(&path);
+    assert_eq!(view.selected_type.as_deref(), Some("_ipp._tcp.local."));
+
+    let mut restored = AppState::new();
+    restored.restore_view(view);
+    assert_eq!(restored.sort_field, SortField::Port);
+    assert_eq!(restored.sort_direction, SortDirection::Descending);
+    assert!(restored.online_first);
+    assert_eq!(restored.filter_query.text, "printer");
+    assert_eq!(restored.category_filter, Some(DeviceCategory::Printer));
+    assert_eq!(restored.domain_filter.as_deref(), Some("local."));
+    assert_eq!(restored.interface_filter.as_deref(), Some("eth0"));
+    // No baseline to compare to
+    assert_eq!(restored.baseline_filter, None);
+    assert!(!restored.add_service_type("_sleep-proxy._udp.local."));
+    // Selected once discovered, wherever it ends up in the list
+    restored.add_service_type("_ipp._tcp.local.");
+    restored.add_service_type("_http._tcp.local.");
+    assert_eq!(restored.selected_type, Some(1));
+    // Still saved while it wasn't discovered again
+    let mut unseen = AppState::new();
+    unseen.restore_view(restored.saved_view());
+    assert_eq!(
+        unseen.saved_view().selected_type.as_deref(),
+        Some("_ipp._tcp.local.")
+    );
+}
+
+#[test]
+fn test_services_of_earlier_sessions_are_remembered() {
+    let mut earlier = AppState::new();
+    let mut sensor = create_test_service("sensor", "_http._tcp.local.", 80);
+    sensor.first_seen_micros = 1_000;
+    sensor.last_seen_micros = 5_000;
+    earlier.ingest_resolved_service(sensor);
+    earlier.ingest_resolved_service(ServiceEntry {
+        agent: Some("hq:8080".to_string()),
+        ..create_test_service("remote", "_http._tcp.local.", 81)
+    });
+    let entries = earlier.history_entries();
+    assert_eq!(entries.len(), 1);
+
+    let mut state = AppState::new();
+    state.restore_history(entries);
+    let sensor = state.services.get("sensor._http._tcp.local.").unwrap();
+    assert!(!sensor.online);
+    assert_eq!(sensor.offline_since_micros, Some(5_000));
+    assert_eq!(state.service_types, ["_http._tcp.local."]);
+    assert!(
+        state
+            .remembered_services
+            .contains("sensor._http._tcp.local.")
+    );
+    assert_eq!(state.get_filtered_services().len(), 1);
+
+    // Seen again, it is a known service coming back online
+    state.ingest_resolved_service(create_test_service("sensor", "_http._tcp.local.", 80));
+    let sensor = state.services.get("sensor._http._tcp.local.").unwrap();
+    assert!(sensor.online);
+    assert_eq!(sensor.first_seen_micros, 1_000);
+    assert!(state.remembered_services.is_empty());
+}
+
+#[test]
+fn test_history_survives_expiry_and_removal() {
+    let entry = |name: &str, last_seen_micros: u64| HistoryEntry {
+        service: recorded_service(&create_test_service(name, "_http._tcp.local.", 80)),
+        first_seen_micros: 1_000,
+        last_seen_micros,
+    };
+    let mut state = AppState::new();
+    state.config.offline_grace_secs = 60;
+    state.restore_history(vec![entry("sensor", 5_000), entry("camera", 6_000)]);
+
+    // Remembered services went offline long before the grace period started
+    assert!(!state.expire_offline_services(1_000_000_000));
+    assert_eq!(state.services.len(), 2);
+
+    state
+        .marked_services
+        .insert("camera._http._tcp.local.".to_string());
+    state.remove_marked_offline_services();
+    assert!(
+        !state
+            .remembered_services
+            .contains("camera._http._tcp.local.")
+    );
+    state.ingest_resolved_service(create_test_service("door", "_http._tcp.local.", 80));
+
+    let mut saved: Vec<String> = state
+        .history_entries()
+        .into_iter()
+        .map(|entry| entry.service.fullname)
+        .collect();
+    saved.sort();
+    assert_eq!(
+        saved,
+        [
+            "camera._http._tcp.local.",
+            "door._http._tcp.local.",
+            "sensor._http._tcp.local."
+        ]
+    );
+}
+
+#[test]
+fn test_pinned_services_stay_on_top_whatever_the_filters() {
+    let mut state = AppState::new();
+    for (name, port) in [("alpha", 1), ("beta", 2), ("gamma", 3)] {
+        state.ingest_resolved_service(create_test_service(name, "_http._tcp.local.", port));
+    }
+    state.ingest_resolved_service(create_test_service("printer", "_ipp._tcp.local.", 4));
+    let listed = |state: &mut AppState| -> Vec<String> {
+        state
+            .get_filtered_services()
+            .to_vec()
+            .into_iter()
+            .map(|idx| state.services[idx].host.to_string())
+            .collect()
+    };
+
+    state.selected_service = 2;
+    state.toggle_selected_service_pin();
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Pinned gamma._http._tcp.local.")
+    );
+    assert_eq!(
+        listed(&mut state),
+        [
+            "gamma.local.",
+            "alpha.local.",
+            "beta.local.",
+            "printer.local."
+        ]
+    );
+    assert_eq!(state.selected_service, 0);
+
+    state.filter_query = InputLine::from("printer");
+    state.invalidate_cache_and_validate();
+    assert_eq!(listed(&mut state), ["gamma.local.", "printer.local."]);
+    state.update_sort_direction(SortDirection::Descending);
+    assert_eq!(listed(&mut state), ["gamma.local.", "printer.local."]);
+
+    state.selected_service = 0;
+    state.toggle_selected_service_pin();
+    assert_eq!(listed(&mut state), ["printer.local."]);
+    assert_eq!(state.selected_service, 0);
+}
+
+#[test]
+fn test_pins_keep_no_sightings_of_other_networks() {
+    let mut state = AppState::new();
+    state.on_network = false;
+    state.ingest_resolved_service(create_test_service("alpha", "_http._tcp.local.", 1));
+    state.selected_service = 0;
+    state.toggle_selected_service_pin();
+    let pin = state.pins.store().get("alpha._http._tcp.local.").unwrap();
+    assert_eq!(pin.last_seen, None);
+}
+
+#[test]
+fn test_pins_popup() {
+    let mut state = AppState::new();
+    let key = |state: &mut AppState, code: KeyCode| state.handle_key_event(KeyEvent::from(code));
+    key(&mut state, KeyCode::Char('\''));
+    assert!(!state.pins.show_popup());
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("No pinned services, pin one with *")
+    );
+
+    // Pinned in an earlier session and not seen in this one
+    let mut store = PinStore::in_memory();
+    store.toggle("ghost._http._tcp.local.", 1);
+    state.pins = PinState::new(store);
+    for (name, port) in [("alpha", 1), ("beta", 2), ("gamma", 3)] {
+        state.ingest_resolved_service(create_test_service(name, "_http._tcp.local.", port));
+    }
+    state.selected_service = 0;
+    state.toggle_selected_service_pin();
+    state.selected_service = 2;
+    state.toggle_selected_service_pin();
+    let sighting = state
+        .pins
+        .store()
+        .get("alpha._http._tcp.local.")
+        .unwrap()
+        .last_seen
+        .clone();
+    assert_eq!(
+        sighting.map(|s| (s.host, s.port)),
+        Some(("alpha.local.".to_string(), 1))
+    );
+
+    // Jumping selects the service in the list
+    key(&mut state, KeyCode::Char('\''));
+    assert!(state.pins.show_popup());
+    key(&mut state, KeyCode::Char('j'));
+    key(&mut state, KeyCode::Enter);
+    assert!(!state.pins.show_popup());
+    assert_eq!(
+        state
+            .selected_service_index()
+            .map(|idx| state.services[idx].fullname.clone()),
+        Some("gamma._http._tcp.local.".to_string())
+    );
+
+    key(&mut state, KeyCode::Char('\''));
+    for _ in 0..3 {
+        key(&mut state, KeyCode::Char('j'));
+    }
+    assert_eq!(state.pins.cursor(), 2);
+    key(&mut state, KeyCode::Enter);
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("ghost._http._tcp.local. has not been seen this session")
+    );
+
+    // Notes are edited in place, Esc drops the edit
+    key(&mut state, KeyCode::Char('\''));
+    key(&mut state, KeyCode::Char('n'));
+    for ch in "rack 2".chars() {
+        key(&mut state, KeyCode::Char(ch));
+    }
+    key(&mut state, KeyCode::Enter);
+    assert!(state.pins.note_input().is_none());
+    assert!(state.pins.show_popup());
+    key(&mut state, KeyCode::Char('n'));
+    key(&mut state, KeyCode::Backspace);
+    key(&mut state, KeyCode::Esc);
+    assert_eq!(
+        state
+            .pins
+            .store()
+            .get("alpha._http._tcp.local.")
+            .unwrap()
+            .note,
+        "rack 2"
+    );
+
+    key(&mut state, KeyCode::Char('d'));
+    assert_eq!(
+        state.status_message.as_deref(),
+        Some("Unpinned alpha._http._tcp.local.")
+    );
+    assert_eq!(state.pins.store().len(), 2);
+    assert_eq!(state.pins.cursor(), 0);
+    key(&mut state, KeyCode::Esc);
+    assert!(!state.pins.show_popup());
+}
+
+#[test]
+fn test_missing_or_broken_view_file_is_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("view.json");
+    assert_eq!(SavedView::load(&path), SavedView::default());
+    std::fs::write(&path, "{").unwrap();
+    assert_eq!(SavedView::load(&path), SavedView::default());
+}
+
+#[test]
+fn test_cycle_sort_field_forward() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_field, SortField::Host);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::ServiceType);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Fullname);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Port);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Address);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::AddressCount);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Timestamp);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::FirstSeen);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Refreshes);
+
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Priority);
+
+    // Should wrap around
+    state.cycle_sort_field(true);
+    assert_eq!(state.sort_field, SortField::Host);
+}
+
+#[test]
+fn test_cycle_sort_field_backward() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_field, SortField::Host);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Priority);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Refreshes);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::FirstSeen);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Timestamp);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::AddressCount);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Address);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Port);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Fullname);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::ServiceType);
+
+    state.cycle_sort_field(false);
+    assert_eq!(state.sort_field, SortField::Host);
+}
+
+#[test]
+fn test_update_sort_field_resets_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    for i in 0..5 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.selected_service = 3;
+    state.services_scroll_offset = 2;
+
+    state.update_sort_field(SortField::Port);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_update_sort_direction_resets_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    for i in 0..5 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.selected_service = 3;
+    state.services_scroll_offset = 2;
+
+    state.update_sort_direction(SortDirection::Descending);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_sort_filtered_services_ascending() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    // Add services in reverse alphabetical order
+    state
+        .services
+        .push(create_test_service("zebra", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("beta", "_http._tcp.local.", 82));
+
+    state.sort_field = SortField::Host;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(filtered.len(), 3);
+
+    // Verify services are sorted by host in ascending order
+    assert_eq!(&*state.services[filtered[0]].host, "alpha.local.");
+    assert_eq!(&*state.services[filtered[1]].host, "beta.local.");
+    assert_eq!(&*state.services[filtered[2]].host, "zebra.local.");
+}
+
+#[test]
+fn test_sort_filtered_services_descending() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("beta", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("zebra", "_http._tcp.local.", 82));
+
+    state.sort_field = SortField::Host;
+    state.sort_direction = SortDirection::Descending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(filtered.len(), 3);
+
+    // Verify services are sorted by host in descending order
+    assert_eq!(&*state.services[filtered[0]].host, "zebra.local.");
+    assert_eq!(&*state.services[filtered[1]].host, "beta.local.");
+    assert_eq!(&*state.services[filtered[2]].host, "alpha.local.");
+}
+
+#[test]
+fn test_sort_by_port_ascending() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    state
+        .services
+        .push(create_test_service("service1", "_http._tcp.local.", 8080));
+    state
+        .services
+        .push(create_test_service("service2", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("service3", "_http._tcp.local.", 443));
+
+    state.sort_field = SortField::Port;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(state.services[filtered[0]].port, 80);
+    assert_eq!(state.services[filtered[1]].port, 443);
+    assert_eq!(state.services[filtered[2]].port, 8080);
+}
+
+#[test]
+fn test_sort_by_timestamp() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service1 = create_test_service("service1", "_http._tcp.local.", 80);
+    service1.last_updated_micros = 3000;
+    let mut service2 = create_test_service("service2", "_http._tcp.local.", 81);
+    service2.last_updated_micros = 1000;
+    let mut service3 = create_test_service("service3", "_http._tcp.local.", 82);
+    service3.last_updated_micros = 2000;
+
+    state.services.push(service1);
+    state.services.push(service2);
+    state.services.push(service3);
+
+    state.sort_field = SortField::Timestamp;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(state.services[filtered[0]].last_updated_micros, 1000);
+    assert_eq!(state.services[filtered[1]].last_updated_micros, 2000);
+    assert_eq!(state.services[filtered[2]].last_updated_micros, 3000);
+}
+
+#[test]
+fn test_sort_with_filtering() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    state
+        .services
+        .push(create_test_service("http-zebra", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("ssh-alpha", "_ssh._tcp.local.", 22));
+    state
+        .services
+        .push(create_test_service("http-alpha", "_http._tcp.local.", 8080));
+    state
+        .services
+        .push(create_test_service("ssh-zebra", "_ssh._tcp.local.", 2222));
+
+    // Filter to only HTTP services and sort by host
+    state.selected_type = Some(0); // _http._tcp.local.
+    state.sort_field = SortField::Host;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    assert_eq!(filtered.len(), 2); // Only HTTP services
+    assert_eq!(&*state.services[filtered[0]].host, "http-alpha.local.");
+    assert_eq!(&*state.services[filtered[1]].host, "http-zebra.local.");
+}
+
+#[test]
+fn test_sort_mixed_online_offline() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service1 = create_test_service("online1", "_http._tcp.local.", 80);
+    service1.online = true;
+    let mut service2 = create_test_service("offline1", "_http._tcp.local.", 81);
+    service2.online = false;
+    let mut service3 = create_test_service("online2", "_http._tcp.local.", 82);
+    service3.online = true;
+
+    state.services.push(service1);
+    state.services.push(service2);
+    state.services.push(service3);
+
+    state.sort_field = SortField::Host;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    // All services should be included and sorted, regardless of online status
+    assert_eq!(filtered.len(), 3);
+    assert!(state.services[filtered[0]].host < state.services[filtered[1]].host);
+    assert!(state.services[filtered[1]].host < state.services[filtered[2]].host);
+}
+
+#[test]
+fn test_sort_online_first_groups_by_status() {
+    let mut state = AppState::new();
+    let mut offline_alpha = create_test_service("alpha", "_http._tcp.local.", 80);
+    offline_alpha.online = false;
+    state.services.push(offline_alpha);
+    state
+        .services
+        .push(create_test_service("beta", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("gamma", "_http._tcp.local.", 82));
+
+    let order = |state: &mut AppState| -> Vec<String> {
+        let services = state.services.clone();
+        state
+            .get_filtered_services()
+            .iter()
+            .map(|&idx| services[idx].host.to_string())
+            .collect()
+    };
+    assert_eq!(order(&mut state)[0], "alpha.local.");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('O')));
+    assert!(state.online_first);
+    assert_eq!(
+        order(&mut state),
+        vec!["beta.local.", "gamma.local.", "alpha.local."]
+    );
+
+    // Direction only applies within each status group
+    state.toggle_sort_direction();
+    assert_eq!(
+        order(&mut state),
+        vec!["gamma.local.", "beta.local.", "alpha.local."]
+    );
+}
+
+#[test]
+fn test_format_sort_field_display() {
+    assert_eq!(format_sort_field_for_display(SortField::Host), "Host");
+    assert_eq!(
+        format_sort_field_for_display(SortField::ServiceType),
+        "Type"
+    );
+    assert_eq!(format_sort_field_for_display(SortField::Fullname), "Name");
+    assert_eq!(format_sort_field_for_display(SortField::Port), "Port");
+    assert_eq!(format_sort_field_for_display(SortField::Address), "Addr");
+    assert_eq!(
+        format_sort_field_for_display(SortField::AddressCount),
+        "Addrs#"
+    );
+    assert_eq!(format_sort_field_for_display(SortField::Timestamp), "Time");
+    assert_eq!(format_sort_field_for_display(SortField::FirstSeen), "First");
+}
+
+#[test]
+fn test_format_sort_direction_display() {
+    assert_eq!(
+        format_sort_direction_for_display(SortDirection::Ascending),
+        "↑"
+    );
+    assert_eq!(
+        format_sort_direction_for_display(SortDirection::Descending),
+        "↓"
+    );
+}
+
+#[test]
+fn test_sort_field_enum_equality() {
+    assert_eq!(SortField::Host, SortField::Host);
+    assert_ne!(SortField::Host, SortField::Port);
+}
+
+#[test]
+fn test_sort_direction_enum_equality() {
+    assert_eq!(SortDirection::Ascending, SortDirection::Ascending);
+    assert_ne!(SortDirection::Ascending, SortDirection::Descending);
+}
+
+#[test]
+fn test_key_event_cycle_sort_forward() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_field, SortField::Host);
+
+    let key = KeyEvent::from(KeyCode::Char('s'));
+    state.handle_key_event(key);
+    assert_eq!(state.sort_field, SortField::ServiceType);
+}
+
+#[test]
+fn test_key_event_cycle_sort_backward() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_field, SortField::Host);
+
+    let key = KeyEvent::from(KeyCode::Char('S'));
+    state.handle_key_event(key);
+    assert_eq!(state.sort_field, SortField::Priority);
+}
+
+#[test]
+fn test_key_event_toggle_sort_direction() {
+    let mut state = AppState::new();
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+
+    let key = KeyEvent::from(KeyCode::Char('o'));
+    state.handle_key_event(key);
+    assert_eq!(state.sort_direction, SortDirection::Descending);
+
+    state.handle_key_event(key);
+    assert_eq!(state.sort_direction, SortDirection::Ascending);
+}
+
+#[test]
+fn test_cache_invalidation_on_sort_change() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test", "_http._tcp.local.", 80));
+
+    // Populate cache
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+    assert!(state.cached_sorted);
+
+    // Changing sort field should invalidate sorted flag
+    state.update_sort_field(SortField::Port);
+    assert!(state.cache_dirty);
+    assert!(!state.cached_sorted);
+}
+
+#[test]
+fn test_sort_stability_with_equal_values() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    // Create services with same port but different names
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("beta", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("gamma", "_http._tcp.local.", 80));
+
+    state.sort_field = SortField::Port;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+    // All should have same port, so order is determined by the stable sort
+    assert_eq!(filtered.len(), 3);
+    for idx in filtered {
+        assert_eq!(state.services[idx].port, 80);
+    }
+}
+
+// Filter functionality tests
+#[test]
+fn test_appstate_new_with_filter() {
+    let state = AppState::new();
+    assert_eq!(state.filter_query, "");
+    assert!(!state.filter_input_mode);
+}
+
+#[test]
+fn test_start_filter_input() {
+    let mut state = AppState::new();
+    state.start_filter_input();
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "");
+}
+
+#[test]
+fn test_start_filter_input_keeps_existing_query() {
+    let mut state = AppState::new();
+    state.filter_query = "printer".into();
+    state.filter_query.move_to_start();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('/')));
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "printer");
+    assert_eq!(state.filter_query.cursor, "printer".len());
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('s')));
+    assert_eq!(state.filter_query, "printers");
+}
+
+#[test]
+fn test_start_new_filter_input_clears_query() {
+    let mut state = AppState::new();
+    state.filter_query = "printer".into();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('\\')));
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "");
+}
+
+#[test]
+fn test_cancel_filter_input_restores_query() {
+    let mut state = AppState::new();
+    state.filter_query = "printer".into();
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('/')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('s')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.filter_query, "printer");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('\\')));
+    assert_eq!(state.filter_query, "");
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert_eq!(state.filter_query, "printer");
+}
+
+#[test]
+fn test_clear_filter() {
+    let mut state = AppState::new();
+    state.filter_query = "test".into();
+    state.filter_input_mode = true;
+    state.selected_service = 5;
+    state.services_scroll_offset = 2;
+
+    state.clear_filter();
+
+    assert_eq!(state.filter_query, "");
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_apply_filter() {
+    let mut state = AppState::new();
+    state.filter_query = "test".into();
+    state.filter_input_mode = true;
+    state.selected_service = 5;
+    state.services_scroll_offset = 2;
+
+    state.apply_filter();
+
+    assert_eq!(state.filter_query, "test");
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_add_to_filter() {
+    let mut state = AppState::new();
+    state.add_to_filter('a');
+    state.add_to_filter('b');
+    state.add_to_filter('c');
+    assert_eq!(state.filter_query, "abc");
+}
+
+#[test]
+fn test_add_to_filter_invalidates_cache() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test", "_http._tcp.local.", 80));
+
+    // Populate cache first
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+
+    // Adding to filter should invalidate cache
+    state.add_to_filter('t');
+    assert!(state.cache_dirty);
+}
+
+#[test]
+fn test_remove_from_filter() {
+    let mut state = AppState::new();
+    state.filter_query = "abc".into();
+    state.remove_from_filter();
+    assert_eq!(state.filter_query, "ab");
+    state.remove_from_filter();
+    assert_eq!(state.filter_query, "a");
+    state.remove_from_filter();
+    assert_eq!(state.filter_query, "");
+    state.remove_from_filter(); // Removing from empty string should be safe
+    assert_eq!(state.filter_query, "");
+}
+
+#[test]
+fn test_remove_from_filter_invalidates_cache() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test", "_http._tcp.local.", 80));
+
+    // Populate cache first
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+
+    // Removing from filter should invalidate cache
+    state.filter_query = "test".into();
+    state.remove_from_filter();
+    assert!(state.cache_dirty);
+}
+
+#[test]
+fn test_filter_service_no_filter() {
+    let mut state = AppState::new();
+    state.selected_type = None;
+
+    let service = create_test_service("test", "_http._tcp.local.", 80);
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_service_with_text_query() {
+    let mut state = AppState::new();
+    state.filter_query = "test".into();
+
+    let matching_service = create_test_service("test", "_http._tcp.local.", 80);
+    let non_matching_service = create_test_service("other", "_http._tcp.local.", 80);
+
+    assert!(state.filter_service(&matching_service));
+    assert!(!state.filter_service(&non_matching_service));
+}
+
+#[test]
+fn test_filter_service_case_insensitive() {
+    let mut state = AppState::new();
+    state.filter_query = "TEST".into();
+
+    let service = create_test_service("test", "_http._tcp.local.", 80);
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_service_searches_all_fields() {
+    let mut state = AppState::new();
+
+    // Test fullname search
+    state.filter_query = "MyService".into();
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.fullname = "MyService._http._tcp.local.".to_string();
+    assert!(state.filter_service(&service));
+
+    // Test host search
+    state.filter_query = "myhost".into();
+    service.host = "myhost.local.".into();
+    assert!(state.filter_service(&service));
+
+    // Test service type search
+    state.filter_query = "http".into();
+    service.service_type = "_http._tcp.local.".into();
+    assert!(state.filter_service(&service));
+
+    // Test address search
+    state.filter_query = "192.168.1.100".into();
+    service.addrs = vec!["192.168.1.100".to_string()];
+    assert!(state.filter_service(&service));
+
+    // Test port search
+    state.filter_query = "8080".into();
+    service.port = 8080;
+    assert!(state.filter_service(&service));
+
+    // Test TXT record search
+    state.filter_query = "key1=value1".into();
+    service.txt = vec!["key1=value1".to_string()];
+    assert!(state.filter_service(&service));
+
+    // Test subtype search
+    state.filter_query = "printer".into();
+    service.subtype = Some("_printer".to_string());
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_service_combined_with_type_filter() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.selected_type = Some(0); // Select _http._tcp.local.
+    state.filter_query = "test".into();
+
+    let http_service = create_test_service("test", "_http._tcp.local.", 80);
+    let ssh_service = create_test_service("test", "_ssh._tcp.local.", 22);
+
+    assert!(state.filter_service(&http_service)); // Matches both type and text
+    assert!(!state.filter_service(&ssh_service)); // Matches text but wrong type
+}
+
+#[test]
+fn test_handle_filter_input_key_enter() {
+    let mut state = AppState::new();
+    state.filter_input_mode = true;
+    state.filter_query = "test".into();
+
+    let key = KeyEvent::from(KeyCode::Enter);
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.filter_query, "test");
+}
+
+#[test]
+fn test_handle_filter_input_key_escape() {
+    let mut state = AppState::new();
+    state.filter_input_mode = true;
+    state.filter_query = "test".into();
+
+    let key = KeyEvent::from(KeyCode::Esc);
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.filter_query, "");
+}
+
+#[test]
+fn test_handle_filter_input_key_backspace() {
+    let mut state = AppState::new();
+    state.filter_input_mode = true;
+    state.filter_query = "test".into();
+
+    let key = KeyEvent::from(KeyCode::Backspace);
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "tes");
+}
+
+#[test]
+fn test_handle_filter_input_key_char() {
+    let mut state = AppState::new();
+    state.filter_input_mode = true;
+
+    let key = KeyEvent::from(KeyCode::Char('a'));
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "a");
+}
+
+#[test]
+fn test_handle_normal_mode_key_slash() {
+    let mut state = AppState::new();
+
+    let key = KeyEvent::from(KeyCode::Char('/'));
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert!(state.filter_input_mode);
+    assert_eq!(state.filter_query, "");
+}
+
+#[test]
+fn test_handle_normal_mode_key_c() {
+    let mut state = AppState::new();
+    state.filter_query = "test".into();
+    // Note: not in filter_input_mode so 'c' is handled by normal mode
+    state.selected_service = 5;
+    state.services_scroll_offset = 2;
+
+    let key = KeyEvent::from(KeyCode::Char('c'));
+    let should_continue = state.handle_key_event(key);
+
+    assert!(should_continue);
+    assert_eq!(state.filter_query, "");
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+}
+
+#[test]
+fn test_search_jumps_to_matches_without_filtering() {
+    let mut state = AppState::new();
+    for name in ["alpha", "beta", "gamma", "beta-2"] {
+        state
+            .services
+            .push(create_test_service(name, "_http._tcp.local.", 80));
+    }
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('F')));
+    assert!(state.search_input_mode);
+    for ch in "beta".chars() {
+        state.handle_key_event(KeyEvent::from(KeyCode::Char(ch)));
+    }
+    assert_eq!(state.selected_service, 1);
+    assert_eq!(state.get_filtered_services().len(), 4);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Enter));
+    assert!(!state.search_input_mode);
+    assert_eq!(state.search_query, "beta");
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('n')));
+    assert_eq!(state.selected_service, 2); // "beta-2" sorts after "beta"
+
+    // Wraps around to the first match
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('n')));
+    assert_eq!(state.selected_service, 1);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('N')));
+    assert_eq!(state.selected_service, 2);
+}
+
+#[test]
+fn test_search_escape_restores_selection() {
+    let mut state = AppState::new();
+    for name in ["alpha", "beta", "gamma"] {
+        state
+            .services
+            .push(create_test_service(name, "_http._tcp.local.", 80));
+    }
+    state.selected_service = 0;
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('F')));
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('g')));
+    assert_eq!(state.selected_service, 2);
+
+    state.handle_key_event(KeyEvent::from(KeyCode::Esc));
+    assert!(!state.search_input_mode);
+    assert_eq!(state.search_query, "");
+    assert_eq!(state.selected_service, 0);
+}
+
+#[test]
+fn test_search_without_match_keeps_selection() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("beta", "_http._tcp.local.", 81));
+    state.selected_service = 1;
+    state.search_query = "nonexistent".into();
+
+    assert!(!state.jump_to_search_match(true, false));
+    assert_eq!(state.selected_service, 1);
+}
+
+#[test]
+fn test_filter_empty_query_shows_all() {
+    let mut state = AppState::new();
+    state.filter_query = InputLine::default(); // Empty query
+    state.selected_type = Some(0); // Specific type selected
+    state.add_service_type("_http._tcp.local.");
+
+    let service = create_test_service("test", "_http._tcp.local.", 80);
+    assert!(state.filter_service(&service)); // Should show all since empty query
+}
+
+#[test]
+fn test_filter_with_special_characters() {
+    let mut state = AppState::new();
+    state.filter_query = "key=value".into();
+
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.txt = vec!["key=value".to_string()];
+    assert!(state.filter_service(&service));
+}
+
+// Test for the filter clear bug fix (regression test)
+#[test]
+fn test_clear_filter_when_empty_doesnt_reset_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    // Add multiple services
+    for i in 0..10 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    // Navigate to a specific service
+    state.selected_service = 5;
+    state.services_scroll_offset = 3;
+
+    // Clear filter when it's already empty should NOT reset selection
+    state.clear_filter();
+
+    assert_eq!(state.selected_service, 5);
+    assert_eq!(state.services_scroll_offset, 3);
+}
+
+#[test]
+fn test_clear_filter_when_not_empty_resets_selection() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    // Add multiple services
+    for i in 0..10 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+
+    // Navigate to a specific service and set a filter
+    state.selected_service = 5;
+    state.services_scroll_offset = 3;
+    state.filter_query = "test5".into();
+
+    // Clear filter when it has content SHOULD reset selection
+    state.clear_filter();
+
+    assert_eq!(state.selected_service, 0);
+    assert_eq!(state.services_scroll_offset, 0);
+    assert_eq!(state.filter_query, "");
+}
+
+// Test add_or_update_service edge cases
+#[test]
+fn test_add_or_update_service_returns_false_for_new_service() {
+    let mut state = AppState::new();
+    let service = create_test_service("test1", "_http._tcp.local.", 80);
+
+    let was_updated = state.add_or_update_service(service);
+
+    assert!(!was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.metrics.get("services_discovered"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_returns_true_for_existing_service() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    // Add initial service
+    state.add_or_update_service(service1.clone());
+
+    // Update same service with different port
+    let mut service2 = service1.clone();
+    service2.port = 8080;
+
+    let was_updated = state.add_or_update_service(service2);
+
+    assert!(was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].port, 8080);
+    assert_eq!(state.metrics.get("services_updated"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_only_updates_on_significant_changes() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    state.add_or_update_service(service1.clone());
+
+    // Update with same service (no significant changes)
+    let was_updated = state.add_or_update_service(service1.clone());
+
+    assert!(was_updated); // Returns true for existing service
+    assert_eq!(state.services.len(), 1);
+    // Metrics should not increment for non-significant changes
+    assert_eq!(state.metrics.get("services_updated"), None);
+}
+
+#[test]
+fn test_add_or_update_service_detects_online_status_change() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    state.add_or_update_service(service1.clone());
+
+    // Update service to be offline
+    let mut service2 = service1.clone();
+    service2.online = false;
+
+    let was_updated = state.add_or_update_service(service2);
+
+    assert!(was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert!(!state.services[0].online);
+    assert_eq!(state.metrics.get("services_updated"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_detects_address_change() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    state.add_or_update_service(service1.clone());
+
+    // Update service with different address
+    let mut service2 = service1.clone();
+    service2.addrs = vec!["192.168.2.100".to_string()];
+
+    let was_updated = state.add_or_update_service(service2);
+
+    assert!(was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].addrs[0], "192.168.2.100");
+    assert_eq!(state.metrics.get("services_updated"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_detects_txt_change() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    state.add_or_update_service(service1.clone());
+
+    // Update service with different TXT records
+    let mut service2 = service1.clone();
+    service2.txt = vec!["newkey=newvalue".to_string()];
+
+    let was_updated = state.add_or_update_service(service2);
+
+    assert!(was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].txt[0], "newkey=newvalue");
+    assert_eq!(state.metrics.get("services_updated"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_detects_subtype_change() {
+    let mut state = AppState::new();
+    let service1 = create_test_service("test1", "_http._tcp.local.", 80);
+
+    state.add_or_update_service(service1.clone());
+
+    // Update service with subtype
+    let mut service2 = service1.clone();
+    service2.subtype = Some("_printer".to_string());
+
+    let was_updated = state.add_or_update_service(service2);
+
+    assert!(was_updated);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.services[0].subtype, Some("_printer".to_string()));
+    assert_eq!(state.metrics.get("services_updated"), Some(&1));
+}
+
+#[test]
+fn test_add_or_update_service_ensures_service_type_exists() {
+    let mut state = AppState::new();
+
+    // Start with no service types
+    assert_eq!(state.service_types.len(), 0);
+
+    // Add a service with a new type
+    let service = create_test_service("test1", "_newtype._tcp.local.", 8080);
+    let was_existing = state.add_or_update_service(service);
+
+    // Service should be added and service type should be created
+    assert!(!was_existing);
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.service_types.len(), 1);
+    assert!(
+        state
+            .service_types
+            .contains(&"_newtype._tcp.local.".to_string())
+    );
+
+    // Add another service with the same type
+    let service2 = create_test_service("test2", "_newtype._tcp.local.", 8081);
+    let was_existing2 = state.add_or_update_service(service2);
+
+    // Service should be added but service type count should stay the same
+    assert!(!was_existing2);
+    assert_eq!(state.services.len(), 2);
+    assert_eq!(state.service_types.len(), 1);
+
+    // Update an existing service
+    let mut service3 = create_test_service("test1", "_newtype._tcp.local.", 8082);
+    service3.online = false; // Change to trigger update
+    let was_existing3 = state.add_or_update_service(service3);
+
+    // Service should be updated but service type count should stay the same
+    assert!(was_existing3);
+    assert_eq!(state.services.len(), 2);
+    assert_eq!(state.service_types.len(), 1);
+}
+
+// Test cache invalidation scenarios
+#[test]
+fn test_cache_invalidation_on_service_removal() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    // Populate cache
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+
+    // Remove services should invalidate cache
+    state.remove_offline_services();
+    // After remove_offline_services completes, cache should be clean again
+    assert!(!state.cache_dirty);
+}
+
+#[test]
+fn test_cache_invalidation_on_add_service_type() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    // Populate cache
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+
+    // Adding service type should invalidate cache
+    state.add_service_type("_http._tcp.local.");
+    assert!(state.cache_dirty);
+}
+
+#[test]
+fn test_cache_invalidation_on_remove_service_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    // Populate cache
+    let _ = state.get_filtered_services();
+    assert!(!state.cache_dirty);
+
+    // Removing service type should invalidate cache
+    state.remove_service_type("_ssh._tcp.local.");
+    assert!(state.cache_dirty);
+}
+
+#[test]
+fn test_cache_sorted_flag_on_sort_field_change() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    // Populate and sort cache
+    let _ = state.get_filtered_services();
+    assert!(state.cached_sorted);
+
+    // Changing sort field should invalidate sorted flag
+    state.update_sort_field(SortField::Port);
+    assert!(!state.cached_sorted);
+}
+
+// Boundary condition tests
+#[test]
+fn test_navigate_services_with_single_service() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+
+    state.selected_service = 0;
+
+    state.navigate_services_up();
+    assert_eq!(state.selected_service, 0);
+
+    state.navigate_services_down();
+    assert_eq!(state.selected_service, 0);
+}
+
+#[test]
+fn test_navigate_service_types_with_single_type() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.selected_type = Some(0);
+
+    state.navigate_service_types_down();
+    assert_eq!(state.selected_type, Some(0));
+}
+
+#[test]
+fn test_page_navigation_with_fewer_items_than_page_size() {
+    let mut state = AppState::new();
+    state
+        .services
+        .push(create_test_service("test1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("test2", "_http._tcp.local.", 81));
+    state.visible_services = 10; // Page size larger than item count
+
+    state.selected_service = 0;
+    state.navigate_services_page_down();
+    assert_eq!(state.selected_service, 1); // Should go to last item
+
+    state.navigate_services_page_up();
+    assert_eq!(state.selected_service, 0); // Should go to first item
+}
+
+#[test]
+fn test_remove_offline_services_with_all_offline() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.online = false;
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 81);
+    service2.online = false;
+
+    state.services.push(service1);
+    state.services.push(service2);
+
+    state.remove_offline_services();
+
+    assert_eq!(state.services.len(), 0);
+    assert_eq!(state.service_types.len(), 0); // Type should be removed too
+}
+
+#[test]
+fn test_remove_offline_services_updates_metrics() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service1 = create_test_service("test1", "_http._tcp.local.", 80);
+    service1.online = false;
+    let mut service2 = create_test_service("test2", "_http._tcp.local.", 81);
+    service2.online = false;
+    let mut service3 = create_test_service("test3", "_http._tcp.local.", 82);
+    service3.online = false;
+
+    state.services.push(service1);
+    state.services.push(service2);
+    state.services.push(service3);
+
+    state.remove_offline_services();
+
+    assert_eq!(state.metrics.get("offline_services_removed"), Some(&3));
+}
+
+#[test]
+fn test_update_metric_by() {
+    let mut state = AppState::new();
+
+    state.update_metric_by("test_metric", 5);
+    assert_eq!(state.metrics.get("test_metric"), Some(&5));
+
+    state.update_metric_by("test_metric", 3);
+    assert_eq!(state.metrics.get("test_metric"), Some(&8));
+}
+
+#[test]
+fn test_filter_query_with_multiple_words() {
+    let mut state = AppState::new();
+    state.filter_query = "192.168".into();
+
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.addrs = vec!["192.168.1.100".to_string()];
+
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_query_partial_match() {
+    let mut state = AppState::new();
+    state.filter_query = "http".into();
+
+    let service = create_test_service("test", "_http._tcp.local.", 80);
+
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_filter_with_port_as_string() {
+    let mut state = AppState::new();
+    state.filter_query = "8080".into();
+
+    let service = create_test_service("test", "_http._tcp.local.", 8080);
+
+    assert!(state.filter_service(&service));
+}
+
+#[test]
+fn test_scroll_offset_updates_correctly_on_navigation() {
+    let mut state = AppState::new();
+    for i in 0..10 {
+        state.services.push(create_test_service(
+            &format!("test{}", i),
+            "_http._tcp.local.",
+            80 + i,
+        ));
+    }
+    state.visible_services = 5;
+
+    // Navigate down beyond visible area
+    for _ in 0..7 {
+        state.navigate_services_down();
+    }
+
+    // Scroll offset should be adjusted to keep selected item visible
+    assert!(state.services_scroll_offset > 0);
+    assert!(state.selected_service >= state.services_scroll_offset);
+    assert!(state.selected_service < state.services_scroll_offset + state.visible_services);
+}
+
+#[test]
+fn test_validate_selected_type_with_invalid_index() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.selected_type = Some(10); // Invalid index
+
+    state.validate_selected_type();
+
+    assert_eq!(state.selected_type, Some(0)); // Should clamp to valid index
+}
+
+#[test]
+fn test_service_entry_go_offline_updates_timestamp() {
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    let original_timestamp = service.last_updated_micros;
+    let new_timestamp = original_timestamp + 1000000;
+
+    service.go_offline_at(new_timestamp);
+
+    assert!(!service.online);
+    assert_eq!(service.last_updated_micros, new_timestamp);
+    assert_ne!(service.last_updated_micros, original_timestamp);
+}
+
+#[test]
+fn test_filter_and_sort_together() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    // Add services with different types and names
+    state
+        .services
+        .push(create_test_service("zebra", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("alpha", "_http._tcp.local.", 81));
+    state
+        .services
+        .push(create_test_service("beta", "_ssh._tcp.local.", 22));
+
+    // Filter to HTTP and sort by host
+    state.selected_type = Some(0); // _http._tcp.local.
+    state.sort_field = SortField::Host;
+    state.sort_direction = SortDirection::Ascending;
+    state.mark_cache_dirty();
+
+    let filtered = state.get_filtered_services().to_vec();
+
+    assert_eq!(filtered.len(), 2);
+    assert_eq!(&*state.services[filtered[0]].host, "alpha.local.");
+    assert_eq!(&*state.services[filtered[1]].host, "zebra.local.");
+}
+
+#[test]
+fn test_key_event_ctrl_c() {
+    let mut state = AppState::new();
+
+    let mut key = KeyEvent::from(KeyCode::Char('c'));
+    key.modifiers = crossterm::event::KeyModifiers::CONTROL;
+
+    let should_continue = state.handle_key_event(key);
+
+    assert!(!should_continue); // Should quit
+}
+
+#[test]
+fn test_key_event_remove_offline() {
+    let mut state = AppState::new();
+    state.add_service_type("_http._tcp.local.");
+
+    let mut service = create_test_service("test", "_http._tcp.local.", 80);
+    service.online = false;
+    state.services.push(service);
+
+    let key = KeyEvent::from(KeyCode::Char('d'));
+    state.handle_key_event(key);
+
+    assert_eq!(state.services.len(), 0);
+}
+
+#[test]
+fn test_key_event_clear_stale_service_types() {
+    let mut state = AppState::new();
+
+    // Add multiple service types but only services for some
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.add_service_type("_printer._tcp.local.");
+
+    state
+        .services
+        .push(create_test_service("http1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("ssh1", "_ssh._tcp.local.", 22));
+
+    let key = KeyEvent::from(KeyCode::Char('D'));
+    state.handle_key_event(key);
+
+    // Should remove _printer._tcp.local. but keep the others
+    assert_eq!(state.service_types.len(), 2);
+    assert!(
+        state
+            .service_types
+            .contains(&"_http._tcp.local.".to_string())
+    );
+    assert!(
+        state
+            .service_types
+            .contains(&"_ssh._tcp.local.".to_string())
+    );
+    assert!(
+        !state
+            .service_types
+            .contains(&"_printer._tcp.local.".to_string())
+    );
+}
+
+#[test]
+fn test_multiple_filter_operations() {
+    let mut state = AppState::new();
+
+    // Start filter
+    state.start_filter_input();
+    assert!(state.filter_input_mode);
+
+    // Add characters
+    state.add_to_filter('t');
+    state.add_to_filter('e');
+    state.add_to_filter('s');
+    state.add_to_filter('t');
+    assert_eq!(state.filter_query, "test");
+
+    // Remove one character
+    state.remove_from_filter();
+    assert_eq!(state.filter_query, "tes");
+
+    // Apply filter
+    state.apply_filter();
+    assert!(!state.filter_input_mode);
+    assert_eq!(state.filter_query, "tes");
+}
+
+#[test]
+fn test_empty_services_with_filter() {
+    let mut state = AppState::new();
+    state.filter_query = "nonexistent".into();
+
+    let filtered = state.get_filtered_services();
+
+    assert_eq!(filtered.len(), 0);
+}
+
+#[test]
+fn test_service_type_sorting_order() {
+    let mut state = AppState::new();
+
+    state.add_service_type("_ssh._tcp.local.");
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_printer._tcp.local.");
+
+    // Service types should be sorted alphabetically
+    assert_eq!(state.service_types[0], "_http._tcp.local.");
+    assert_eq!(state.service_types[1], "_printer._tcp.local.");
+    assert_eq!(state.service_types[2], "_ssh._tcp.local.");
+}
+
+// InputLine tests
+#[test]
+fn test_input_line_insert_and_move() {
+    let mut line = InputLine::from("helo");
+    assert_eq!(line.cursor, 4);
+
+    line.move_left();
+    line.insert('l');
+    assert_eq!(line, "hello");
+    assert_eq!(line.before_cursor(), "hell");
+
+    line.move_to_start();
+    line.insert('>');
+    assert_eq!(line, ">hello");
+
+    line.move_to_end();
+    line.move_right();
+    assert_eq!(line.cursor, line.len());
+}
+
+#[test]
+fn test_input_line_multibyte_editing() {
+    let mut line = InputLine::from("aé");
+    line.move_left();
+    assert_eq!(line.after_cursor(), "é");
+    assert!(line.delete());
+    assert_eq!(line, "a");
+    assert!(line.backspace());
+    assert_eq!(line, "");
+    assert!(!line.backspace());
+}
+
+#[test]
+fn test_input_line_delete_word_before_cursor() {
+    let mut line = InputLine::from("foo bar  ");
+    assert!(line.delete_word_before_cursor());
+    assert_eq!(line, "foo ");
+    assert!(line.delete_word_before_cursor());
+    assert_eq!(line, "");
+    assert!(!line.delete_word_before_cursor());
+
+    let mut line = InputLine::from("one two three");
+    line.move_left();
+    line.move_left();
+    assert!(line.delete_word_before_cursor());
+    assert_eq!(line, "one two ee");
+}
+
+#[test]
+fn test_input_line_delete_to_start() {
+    let mut line = InputLine::from("hello world");
+    for _ in 0..5 {
+        line.move_left();
+    }
+    assert!(line.delete_to_start());
+    assert_eq!(line, "world");
+    assert_eq!(line.cursor, 0);
+    assert!(!line.delete_to_start());
+}
+
+#[test]
+fn test_handle_filter_input_readline_shortcuts() {
+    use crossterm::event::KeyModifiers;
+
+    let mut state = AppState::new();
+    state.filter_input_mode = true;
+    state.filter_query = "http printer".into();
+
+    state.handle_key_event(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL));
+    assert_eq!(state.filter_query, "http ");
+
+    state.handle_key_event(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL));
+    assert_eq!(state.filter_query.cursor, 0);
+    state.handle_key_event(KeyEvent::from(KeyCode::Char('_')));
+    assert_eq!(state.filter_query, "_http ");
+
+    state.handle_key_event(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+    state.handle_key_event(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+    assert_eq!(state.filter_query, "");
+    assert!(state.filter_input_mode);
+}
+
+// Helper function for creating test services
+fn create_test_service(name: &str, service_type: &str, port: u16) -> ServiceEntry {
+    ServiceEntry {
+        fullname: format!("{}.{}", name, service_type),
+        host: format!("{}.local.", name).into(),
+        service_type: service_type.into(),
+        subtype: None,
+        addrs: vec![format!("192.168.1.{}", port)],
+        port,
+        txt: vec![],
+        online: true,
+        first_seen_micros: current_timestamp_micros(),
+        last_updated_micros: current_timestamp_micros(),
+        last_seen_micros: current_timestamp_micros(),
+        offline_since_micros: None,
+        refresh_count: 0,
+        srv_priority: None,
+        srv_weight: None,
+        probe: ProbeResult::default(),
+        interfaces: Vec::new(),
+        agent: None,
+        schema_version: SchemaVersion,
+    }
+}
+
+// Tests for service removal metric fix
+#[test]
+fn test_remove_service_only_counts_online_services() {
+    let mut state = AppState::new();
+
+    // Add an online service
+    let online_service = create_test_service("test-service", "_http._tcp.local.", 8080);
+    state.services.push(online_service);
+
+    // Add an offline service
+    let mut offline_service = create_test_service("offline-service", "_http._tcp.local.", 8081);
+    offline_service.online = false;
+    state.services.push(offline_service);
+
+    // Initially no services removed
+    assert_eq!(state.metrics.get("services_marked_offline"), None);
+
+    // Remove online service - should increment metric
+    let removed = state.mark_service_offline("test-service._http._tcp.local.");
+    assert!(removed);
+    assert_eq!(state.metrics.get("services_marked_offline"), Some(&1));
+    assert!(!state.services[0].online); // Service should now be offline
+
+    // Remove offline service - should not increment metric
+    let removed = state.mark_service_offline("offline-service._http._tcp.local.");
+    assert!(removed);
+    assert_eq!(state.metrics.get("services_marked_offline"), Some(&1)); // Still 1, not 2
+    assert!(!state.services[1].online); // Service should still be offline
+}
+
+#[test]
+fn test_remove_service_prevents_double_counting() {
+    let mut state = AppState::new();
+
+    // Add an online service
+    let service = create_test_service("duplicate-service", "_http._tcp.local.", 8080);
+    state.services.push(service);
+
+    // First removal - should increment metric
+    let removed1 = state.mark_service_offline("duplicate-service._http._tcp.local.");
+    assert!(removed1);
+    assert_eq!(state.metrics.get("services_marked_offline"), Some(&1));
+    assert!(!state.services[0].online);
+
+    // Second removal of same service - should not increment metric
+    let removed2 = state.mark_service_offline("duplicate-service._http._tcp.local.");
+    assert!(removed2);
+    assert_eq!(state.metrics.get("services_marked_offline"), Some(&1)); // Still 1, not 2
+}
+
+#[test]
+fn test_remove_service_nonexistent_service() {
+    let mut state = AppState::new();
+
+    // Try to remove a service that doesn't exist
+    let removed = state.mark_service_offline("nonexistent._http._tcp.local.");
+    assert!(!removed);
+    assert_eq!(state.metrics.get("services_marked_offline"), None);
+}
+
+#[test]
+fn test_remove_service_updates_timestamp() {
+    let mut state = AppState::new();
+
+    let service = create_test_service("timestamp-service", "_http._tcp.local.", 8080);
+    let original_timestamp = service.last_updated_micros;
+    state.services.push(service);
+
+    // Wait a bit to ensure different timestamp
+    std::thread::sleep(std::time::Duration::from_millis(1));
+
+    let removed = state.mark_service_offline("timestamp-service._http._tcp.local.");
+    assert!(removed);
+
+    let updated_service = &state.services[0];
+    assert!(!updated_service.online);
+    assert!(updated_service.last_updated_micros > original_timestamp);
+}
+
+#[test]
+fn test_clear_stale_service_types() {
+    let mut state = AppState::new();
+
+    // Add multiple service types
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+    state.add_service_type("_printer._tcp.local.");
+
+    // Add services for only some types
+    state
+        .services
+        .push(create_test_service("http1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("ssh1", "_ssh._tcp.local.", 22));
+
+    // _printer._tcp.local. has no services - it's stale
+
+    assert_eq!(state.service_types.len(), 3);
+
+    // Clear stale service types
+    state.clear_stale_service_types();
+
+    // Should remove _printer._tcp.local. but keep the others
+    assert_eq!(state.service_types.len(), 2);
+    assert!(
+        state
+            .service_types
+            .contains(&"_http._tcp.local.".to_string())
+    );
+    assert!(
+        state
+            .service_types
+            .contains(&"_ssh._tcp.local.".to_string())
+    );
+    assert!(
+        !state
+            .service_types
+            .contains(&"_printer._tcp.local.".to_string())
+    );
+}
+
+#[test]
+fn test_clear_stale_service_types_empty() {
+    let mut state = AppState::new();
+
+    // Add a service type but no services
+    state.add_service_type("_test._tcp.local.");
+
+    assert_eq!(state.service_types.len(), 1);
+
+    // Clear should remove the empty type
+    state.clear_stale_service_types();
+
+    assert_eq!(state.service_types.len(), 0);
+}
+
+#[test]
+fn test_clear_stale_service_types_no_stale() {
+    let mut state = AppState::new();
+
+    // Add service types and services for all
+    state.add_service_type("_http._tcp.local.");
+    state.add_service_type("_ssh._tcp.local.");
+
+    state
+        .services
+        .push(create_test_service("http1", "_http._tcp.local.", 80));
+    state
+        .services
+        .push(create_test_service("ssh1", "_ssh._tcp.local.", 22));
+
+    assert_eq!(state.service_types.len(), 2);
+
+    // Clear should not remove anything since all types have services
+    state.clear_stale_service_types();
+
+    assert_eq!(state.service_types.len(), 2);
+}
+
+// Snapshot tests of the event loop, drawn on a TestBackend with scripted input.
+// Run with UPDATE_SNAPSHOTS=1 to write the expected screens anew
+struct ScriptedTerminal {
+    terminal: Terminal<ratatui::backend::TestBackend>,
+    events: VecDeque<Event>,
+    // The screen once all events were handled
+    snapshot: Option<String>,
+}
+
+impl ScriptedTerminal {
+    // Types the keys of the text, '\n' being Enter
+    fn typing(text: &str) -> Self {
+        let events = text
+            .chars()
+            .map(|c| match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            })
+            .map(|code| Event::Key(KeyEvent::from(code)))
+            .collect();
+        Self {
+            terminal: Terminal::new(ratatui::backend::TestBackend::new(100, 30)).unwrap(),
+            events,
+            snapshot: None,
+        }
+    }
+
+    fn screen(&self) -> String {
+        let buffer = self.terminal.backend().buffer();
+        let mut screen = String::new();
+        for y in 0..buffer.area.height {
+            let line: String = (0..buffer.area.width)
+                .map(|x| buffer[(x, y)].symbol())
+                .collect();
+            screen.push_str(&redact_timestamps(line.trim_end()));
+            screen.push('\n');
+        }
+        screen
+    }
+}
+
+impl TerminalIo for ScriptedTerminal {
+    type Backend = ratatui::backend::TestBackend;
+
+    fn terminal(&mut self) -> &mut Terminal<Self::Backend> {
+        &mut self.terminal
+    }
+
+    // Every key is drawn before the next one is read, so the screen is final
+    // once the script ran out; Esc and Ctrl+C then close what is open and quit
+    async fn next_event(&mut self) -> Result<Option<Event>, String> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(Some(event));
+        }
+        if self.snapshot.is_none() {
+            self.snapshot = Some(self.screen());
+            self.events
+                .push_back(Event::Key(KeyEvent::from(KeyCode::Esc)));
+        }
+        Ok(Some(Event::Key(KeyEvent::new(
+            KeyCode::Char('c'),
+            crossterm::event::KeyModifiers::CONTROL,
+        ))))
+    }
+
+    fn run_external_command(
+        &mut self,
+        _command: &ExternalCommand,
+    ) -> std::io::Result<std::process::ExitStatus> {
+        Err(std::io::Error::other("not run in tests"))
+    }
+}
+
+// Local times differ between machines, they are replaced by a placeholder
+fn redact_timestamps(line: &str) -> String {
+    const SHAPE: &str = "0000-00-00 00:00:00";
+    let chars: Vec<char> = line.chars().collect();
+    let mut redacted = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let matches = chars.len() - i >= SHAPE.len()
+            && SHAPE.chars().zip(&chars[i..]).all(|(shape, c)| {
+                if shape == '0' {
+                    c.is_ascii_digit()
+                } else {
+                    shape == *c
+                }
+            });
+        if !matches {
+            redacted.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        i += SHAPE.len();
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            while chars.get(i).is_some_and(char::is_ascii_digit) {
+                i += 1;
+            }
+        }
+        redacted.push_str("<timestamp>");
+    }
+    redacted
+}
+
+// Runs the event loop on a few services with the keys and compares the screen
+// to the expected one in src/tui_app/snapshots
+async fn assert_screen(name: &str, keys: &str) {
+    let mut app_state = AppState::new();
+    for service in [
+        create_test_service("office", "_ipp._tcp.local.", 631),
+        create_test_service("nas", "_smb._tcp.local.", 445),
+        create_test_service("web", "_http._tcp.local.", 80),
+    ] {
+        app_state.add_service_type(&service.service_type);
+        app_state.add_or_update_service(service);
+    }
+    let state = Arc::new(RwLock::new(app_state));
+    let (notification_sender, notification_receiver) = notification_channel();
+    let tasks = BackgroundTasks::default();
+    let mut terminal = ScriptedTerminal::typing(keys);
+    run_event_loop(
+        &mut terminal,
+        &MockDaemon::new(),
+        &state,
+        &tasks,
+        (&notification_sender, &notification_receiver),
+    )
+    .await
+    .unwrap();
+    tasks.shutdown().await;
+
+    let screen = terminal.snapshot.unwrap();
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tui_app/snapshots")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &screen).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        screen == expected,
+        "screen {} differs from {}:\n{}",
+        name,
+        path.display(),
+        screen
+    );
+}
+
+#[test]
+fn test_redact_timestamps() {
+    assert_eq!(
+        redact_timestamps("│First seen: 2026-10-18 04:25:47.982503   │"),
+        "│First seen: <timestamp>   │"
+    );
+    assert_eq!(redact_timestamps("at 12:00:00"), "at 12:00:00");
+}
+
+#[tokio::test]
+async fn test_snapshot_service_list() {
+    assert_screen("service_list", "").await;
+}
+
+#[tokio::test]
+async fn test_snapshot_navigation() {
+    assert_screen("navigation", "jl").await;
+}
+
+#[tokio::test]
+async fn test_snapshot_help_popup() {
+    assert_screen("help_popup", "?").await;
+}
+
+#[tokio::test]
+async fn test_snapshot_error_console() {
+    assert_screen("error_console", "!").await;
+}
+
+#[tokio::test]
+async fn test_snapshot_filter_input() {
+    assert_screen("filter_input", "/off").await;
+}
+
+#[tokio::test]
+async fn test_snapshot_applied_filter() {
+    assert_screen("applied_filter", "/nas\n").await;
+}
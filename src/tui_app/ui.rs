@@ -0,0 +1,1528 @@
+#![forbid(unsafe_code)]
+
+use super::*;
+
+pub(super) fn ui(f: &mut Frame, app_state: &mut AppState) {
+    let _span = tracing::debug_span!("draw").entered();
+    // Ensure state is consistent before rendering
+    app_state.validate_selected_type();
+
+    let layout = if app_state.filter_input_mode
+        || app_state.search_input_mode
+        || app_state.exports.service_path_input().is_some()
+        || app_state.exports.dialog().is_some()
+        || app_state.add_type_input.is_some()
+        || app_state.queries.hostname_input().is_some()
+        || app_state.queries.question_input().is_some()
+    {
+        create_filter_input_layout(f.area())
+    } else {
+        create_main_layout(f.area())
+    };
+    let visible_counts = calculate_visible_counts(&layout);
+
+    // Update state with current visible counts
+    app_state.visible_types = visible_counts.types;
+    app_state.visible_services = visible_counts.services;
+
+    if app_state.filter_input_mode {
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_filter_input(f, app_state, f.area());
+    } else if app_state.search_input_mode {
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_search_input(f, app_state, f.area());
+    } else if let Some(path) = app_state.exports.service_path_input() {
+        let path = path.clone();
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_input_line(
+            f,
+            f.area(),
+            "J:",
+            "Export service as JSON to file, - prints it on exit (Enter to export, Esc to cancel)",
+            &path,
+        );
+    } else if let Some(dialog) = app_state.exports.dialog() {
+        let dialog = dialog.clone();
+        let count = app_state.export_candidates(dialog.filtered_only).len();
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        let title = if dialog.confirm_overwrite {
+            "File exists (Enter to overwrite, Esc to cancel)".to_string()
+        } else {
+            format!(
+                "Export {} {} services as {}, - prints them on exit (Tab: format, Shift+Tab: shown/all, Enter to export, Esc to cancel)",
+                count,
+                if dialog.filtered_only { "shown" } else { "all" },
+                dialog.format.label()
+            )
+        };
+        render_input_line(f, f.area(), "e:", &title, &dialog.path);
+    } else if let Some(service_type) = &app_state.add_type_input {
+        let service_type = service_type.clone();
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_input_line(
+            f,
+            f.area(),
+            "a:",
+            "Browse service type, e.g. _workstation._tcp (Enter to browse, Esc to cancel)",
+            &service_type,
+        );
+    } else if let Some(hostname) = app_state.queries.hostname_input() {
+        let hostname = hostname.clone();
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_input_line(
+            f,
+            f.area(),
+            "M:",
+            "Resolve hostname over mDNS, e.g. octopi.local (Enter to resolve, Esc to cancel)",
+            &hostname,
+        );
+    } else if let Some(question) = app_state.queries.question_input() {
+        let question = question.clone();
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+        render_input_line(
+            f,
+            f.area(),
+            "Q:",
+            "Query mDNS, name and PTR/SRV/TXT/A/AAAA/ANY, e.g. _ipp._tcp.local PTR (Enter to query, Esc to cancel)",
+            &question,
+        );
+    } else {
+        render_service_types_list(f, app_state, layout.left_panel, visible_counts.types);
+        render_services_list(f, app_state, layout.services_area, visible_counts.services);
+        render_service_details(f, app_state, layout.details_area);
+
+        // Render filter/search status if not empty
+        if !app_state.filter_query.is_empty()
+            || !app_state.search_query.is_empty()
+            || app_state.pending_count.is_some()
+            || app_state.status_message.is_some()
+            || app_state.unseen_errors > 0
+        {
+            render_filter_status(f, app_state);
+        }
+    }
+
+    // Render popups if active
+    if app_state.show_help_popup {
+        render_help_popup(f);
+    } else if app_state.show_metrics_popup {
+        render_metrics_popup(f, app_state);
+    } else if app_state.show_errors_popup {
+        render_errors_popup(f, app_state);
+    } else if app_state.show_bulk_actions_popup {
+        render_bulk_actions_popup(f, app_state);
+    } else if app_state.show_actions_popup {
+        render_actions_popup(f, app_state);
+    } else if app_state.show_copy_commands_popup {
+        render_copy_commands_popup(f, app_state);
+    } else if app_state.probes.show_port_scan_popup() {
+        render_port_scan_popup(f, app_state);
+    } else if app_state.show_hidden_types_popup {
+        render_hidden_types_popup(f, app_state);
+    } else if app_state.show_interfaces_popup {
+        render_interfaces_popup(f, app_state);
+    } else if app_state.show_inventory_popup {
+        render_inventory_popup(f, app_state);
+    } else if app_state.pins.show_popup() {
+        render_pins_popup(f, app_state);
+    } else if app_state.queries.show_host_lookup_popup() {
+        render_host_lookup_popup(f, app_state);
+    } else if app_state.queries.show_record_query_popup() {
+        render_record_query_popup(f, app_state);
+    }
+}
+
+pub(super) struct MainLayout {
+    pub(super) left_panel: ratatui::layout::Rect,
+    pub(super) services_area: ratatui::layout::Rect,
+    pub(super) details_area: ratatui::layout::Rect,
+}
+
+pub(super) struct VisibleCounts {
+    pub(super) types: usize,
+    pub(super) services: usize,
+}
+
+pub(super) fn create_main_layout(area: ratatui::layout::Rect) -> MainLayout {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(area);
+
+    let services_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(chunks[1]);
+
+    MainLayout {
+        left_panel: chunks[0],
+        services_area: services_chunks[0],
+        details_area: services_chunks[1],
+    }
+}
+
+pub(super) fn calculate_visible_counts(layout: &MainLayout) -> VisibleCounts {
+    VisibleCounts {
+        types: (layout.left_panel.height as usize).saturating_sub(2), // Account for borders
+        services: (layout.services_area.height as usize).saturating_sub(2), // Account for borders
+    }
+}
+
+fn create_filter_input_layout(area: ratatui::layout::Rect) -> MainLayout {
+    // Reserve 3 rows at the bottom for filter input
+    let remaining_height = area.height.saturating_sub(3);
+    let main_area = ratatui::layout::Rect::new(area.x, area.y, area.width, remaining_height);
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(main_area);
+
+    let services_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(chunks[1]);
+
+    MainLayout {
+        left_panel: chunks[0],
+        services_area: services_chunks[0],
+        details_area: services_chunks[1],
+    }
+}
+
+fn render_service_types_list(
+    f: &mut Frame,
+    app_state: &mut AppState,
+    area: ratatui::layout::Rect,
+    visible_types: usize,
+) {
+    let mut type_items = vec![ListItem::new(Line::from(Span::styled(
+        "All Types".to_string(),
+        if app_state.selected_type.is_none() {
+            Style::default().bg(Color::DarkGray).fg(Color::White)
+        } else {
+            Style::default()
+        },
+    )))];
+
+    type_items.extend(
+        app_state
+            .service_types
+            .iter()
+            .enumerate()
+            .map(|(i, service_type)| {
+                let style = if app_state.selected_type == Some(i) {
+                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                } else {
+                    Style::default()
+                };
+                if is_subtype(service_type) {
+                    return ListItem::new(Line::from(vec![
+                        Span::styled("  └ ", style.fg(Color::DarkGray)),
+                        Span::styled(format_subtype_for_display(service_type), style),
+                    ]));
+                }
+                let display_type = format_service_type_for_display(service_type);
+                match app_state.config.service_type_name(service_type) {
+                    Some(name) => ListItem::new(Line::from(vec![
+                        Span::styled(name.to_string(), style),
+                        Span::styled(format!(" {}", display_type), style.fg(Color::DarkGray)),
+                    ])),
+                    None => ListItem::new(Line::from(Span::styled(display_type, style))),
+                }
+            }),
+    );
+
+    let visible_type_items: Vec<ListItem> = type_items
+        .into_iter()
+        .skip(app_state.types_scroll_offset)
+        .take(visible_types)
+        .collect();
+
+    let types_list = List::new(visible_type_items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Service Types [{}] (←/→)",
+            app_state.service_types.len()
+        )))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+
+    let mut list_state = ListState::default();
+    let display_index = match app_state.selected_type {
+        None => 0,
+        Some(idx) => idx + 1,
+    }
+    .saturating_sub(app_state.types_scroll_offset);
+    list_state.select(Some(display_index));
+    f.render_stateful_widget(types_list, area, &mut list_state);
+}
+
+fn render_services_list(
+    f: &mut Frame,
+    app_state: &mut AppState,
+    area: ratatui::layout::Rect,
+    visible_services: usize,
+) {
+    let shown = app_state.get_filtered_services().len();
+    let title = services_list_title(app_state, shown);
+
+    // Only the lines scrolled into view are built, however many services are shown
+    let first = app_state.services_scroll_offset;
+    let visible_service_items: Vec<ListItem> =
+        service_list_lines(app_state, first..first + visible_services)
+            .into_iter()
+            .map(ListItem::new)
+            .collect();
+
+    let services_list = List::new(visible_service_items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+
+    let mut services_list_state = ListState::default();
+    services_list_state.select(Some(
+        app_state
+            .selected_service
+            .saturating_sub(app_state.services_scroll_offset),
+    ));
+    f.render_stateful_widget(services_list, area, &mut services_list_state);
+}
+
+pub(super) fn line_text(line: &Line) -> String {
+    line.spans
+        .iter()
+        .map(|span| span.content.as_ref())
+        .collect()
+}
+
+// The services list as drawn, one line per service shown in the range of
+// positions in the list
+pub(super) fn service_list_lines(
+    app_state: &mut AppState,
+    positions: std::ops::Range<usize>,
+) -> Vec<Line<'static>> {
+    // Brings the filtered and sorted indices up to date, the rest only reads
+    app_state.get_filtered_services();
+    let app_state: &AppState = app_state;
+    let selected_service_idx = app_state.selected_service;
+    let marked_services = &app_state.marked_services;
+    let watched_services = &app_state.watched_services;
+    let tcp_probe_enabled = app_state.probes.tcp_enabled();
+    let address_preference = app_state.address_preference();
+    let reverse_dns_enabled = app_state.reverse_dns_enabled;
+    let reverse_dns = &app_state.reverse_dns;
+    let category_icons = app_state.config.category_icons;
+    let highlight_unpaired_homekit = app_state.config.highlight_unpaired_homekit;
+    let show_domains = app_state.config.browse_domains().len() > 1;
+    let show_interfaces = app_state.known_interface_names().len() > 1;
+    let show_agents = app_state.agents.len() > 1;
+    let re_resolving = &app_state.re_resolving;
+    let show_refreshes = app_state.sort_field == SortField::Refreshes;
+    let now_micros = current_timestamp_micros();
+    let now = std::time::Instant::now();
+    let show_markers = !marked_services.is_empty()
+        || !watched_services.is_empty()
+        || !app_state.pins.store().is_empty();
+
+    let filtered = app_state.cached_filtered_services.rows();
+    let end = positions.end.min(filtered.len());
+    let start = positions.start.min(end);
+
+    filtered[start..end]
+        .iter()
+        .zip(start..)
+        .map(|(&service_idx, i)| {
+            let service = &app_state.services[service_idx];
+            let stale = app_state.is_stale(service, now_micros);
+            let mut style = create_service_list_item_style(i, selected_service_idx, service);
+            if stale || app_state.remembered_services.contains(&service.fullname) {
+                style = style.fg(Color::Gray).add_modifier(Modifier::DIM);
+            }
+            let display_text = format_service_for_display(service, address_preference);
+            let mut spans = Vec::new();
+            if show_markers {
+                let markers = format_service_markers(
+                    app_state.pins.store().contains(&service.fullname),
+                    marked_services.contains(&service.fullname),
+                    watched_services.contains(&service.fullname),
+                );
+                spans.push(Span::styled(markers, style.fg(Color::Yellow)));
+            }
+            if show_agents && let Some(agent) = &service.agent {
+                spans.push(Span::styled(
+                    format!("{} ", agent),
+                    style.fg(Color::LightYellow),
+                ));
+            }
+            if show_domains {
+                spans.push(Span::styled(
+                    format!("{} ", service_domain(&service.service_type)),
+                    style.fg(Color::Blue),
+                ));
+            }
+            if show_interfaces && !service.interfaces.is_empty() {
+                spans.push(Span::styled(
+                    format!("{} ", service.interfaces.join(",")),
+                    style.fg(Color::Cyan),
+                ));
+            }
+            if let Some(category) = device_category::classify(&service.service_type, &service.txt) {
+                spans.push(Span::styled(
+                    format!("[{}] ", category.icon(category_icons)),
+                    style.fg(Color::Magenta),
+                ));
+            }
+            spans.push(Span::styled(display_text, style));
+            if show_refreshes {
+                spans.push(Span::styled(
+                    format!(" ↻{}", service.refresh_count),
+                    style.fg(Color::Cyan),
+                ));
+            }
+            if !app_state.conflicting_targets(service, now).is_empty() {
+                spans.push(Span::styled(
+                    " ⚠ conflict",
+                    style.fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                ));
+            }
+            if stale {
+                spans.push(Span::styled(" ⌛ stale", style.fg(Color::Yellow)));
+            }
+            match app_state.baseline_status(service) {
+                Some(BaselineStatus::New) => spans.push(Span::styled(
+                    " ★ new",
+                    style.fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+                )),
+                Some(BaselineStatus::Missing) => spans.push(Span::styled(
+                    " ✗ missing",
+                    style.fg(Color::LightRed).add_modifier(Modifier::BOLD),
+                )),
+                None => {}
+            }
+            if let Some(started) = re_resolving.get(&service.fullname) {
+                spans.push(Span::styled(
+                    format!(" {}", spinner_frame(started.elapsed())),
+                    style.fg(Color::Cyan),
+                ));
+            }
+            if highlight_unpaired_homekit
+                && txt_schema::is_unpaired_homekit_accessory(&service.service_type, &service.txt)
+            {
+                spans.push(Span::styled(" ⚠ unpaired", style.fg(Color::LightRed)));
+            }
+            if reverse_dns_enabled && has_reverse_dns_mismatch(service, reverse_dns) {
+                spans.push(Span::styled(" ≠ rDNS", style.fg(Color::Yellow)));
+            }
+            if tcp_probe_enabled && service.online {
+                let (badge, color) = format_reachability_badge(service.probe.reachable);
+                spans.push(Span::styled(badge, style.fg(color)));
+                if let Some(latency_micros) = service.probe.latency_micros {
+                    spans.push(Span::styled(
+                        format!(" {}", format_latency(latency_micros)),
+                        style.fg(latency_color(latency_micros)),
+                    ));
+                }
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+pub(super) fn services_list_title(app_state: &AppState, shown: usize) -> Line<'static> {
+    let sort_field_display = format_sort_field_for_display(app_state.sort_field);
+    let sort_dir_display = format_sort_direction_for_display(app_state.sort_direction);
+    let sort_field_highlighted = Span::styled(
+        sort_field_display,
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    );
+    let sort_dir_highlighted = Span::styled(
+        sort_dir_display,
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let mut title = Line::from(vec![
+        Span::raw("Services ["),
+        Span::styled(
+            format!("{}/{}", shown, app_state.services.len()),
+            Style::default().fg(Color::Green),
+        ),
+        Span::styled(
+            if app_state.marked_services.is_empty() {
+                String::new()
+            } else {
+                format!(" {} marked", app_state.marked_services.len())
+            },
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::raw("] ["),
+        sort_field_highlighted,
+        Span::raw("/"),
+        sort_dir_highlighted,
+    ]);
+    if app_state.online_first {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            "Online first",
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(family) = format_address_family_for_display(app_state.preferred_address_family) {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("{} first", family),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(category) = app_state.category_filter {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("Only {}", category.label()),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(interface) = &app_state.interface_filter {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("On {}", interface),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(agent) = &app_state.agent_filter {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("At {}", agent),
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(domain) = &app_state.domain_filter {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("Only {}", domain),
+            Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(status) = app_state.baseline_filter {
+        title.push_span(Span::raw("/"));
+        title.push_span(Span::styled(
+            format!("Only {}", status.label()),
+            Style::default()
+                .fg(match status {
+                    BaselineStatus::New => Color::LightGreen,
+                    BaselineStatus::Missing => Color::LightRed,
+                })
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if !app_state.baseline.is_empty() {
+        let (new, missing) = app_state.baseline_drift();
+        title.push_span(Span::raw("] [Baseline "));
+        title.push_span(Span::styled(
+            format!("+{}", new),
+            Style::default().fg(Color::LightGreen),
+        ));
+        title.push_span(Span::raw(" "));
+        title.push_span(Span::styled(
+            format!("-{}", missing),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    title.push_span(Span::raw("] (↑/↓, s/S to sort, o to toggle)"));
+    title
+}
+
+fn render_service_details(f: &mut Frame, app_state: &mut AppState, area: ratatui::layout::Rect) {
+    app_state.get_filtered_services();
+    let app_state: &AppState = app_state;
+    let selected_service = app_state
+        .cached_filtered_services
+        .rows()
+        .get(app_state.selected_service)
+        .map(|&idx| &app_state.services[idx]);
+
+    if let Some(service) = selected_service {
+        let mut details_text = create_service_details_text(
+            service,
+            app_state.config.service_type_name(&service.service_type),
+        );
+        let conflicting_targets = app_state.conflicting_targets(service, std::time::Instant::now());
+        if !conflicting_targets.is_empty() {
+            details_text.push_str(&format!(
+                "\n\nConflict: announced for {}",
+                conflicting_targets.join(", ")
+            ));
+        }
+        let now_micros = current_timestamp_micros();
+        if app_state.is_stale(service, now_micros) {
+            details_text.push_str(&format!(
+                "\n\nStale: not announced or resolved for {}",
+                format_duration_secs(
+                    now_micros.saturating_sub(service.last_seen_micros) / 1_000_000
+                )
+            ));
+        }
+        if app_state.remembered_services.contains(&service.fullname) {
+            details_text.push_str("\n\nRemembered: seen in an earlier session, not since");
+        }
+        if let Some(pin) = app_state.pins.store().get(&service.fullname)
+            && !pin.note.is_empty()
+        {
+            details_text.push_str(&format!("\n\nNote: {}", pin.note));
+        }
+        if let Some(mac) = app_state.mac_store.get(&service.host) {
+            details_text.push_str(&format!("\n\nMAC: {}", wake_on_lan::format_mac(&mac)));
+        }
+        if let Some(records) = format_record_ttls(
+            service,
+            &app_state.observed_records,
+            std::time::Instant::now(),
+        ) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&records);
+        }
+        if let Some(interpretation) = format_txt_interpretation(service) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&interpretation);
+        }
+        if app_state.txt_decoding_enabled {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_decoded_txt(service));
+        }
+        if app_state.reverse_dns_enabled {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_reverse_dns(service, &app_state.reverse_dns));
+        }
+        if let Some(history) = app_state.txt_history.get(&service.fullname) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_txt_history(history));
+        }
+        if let Some(query) = app_state.probes.printer_query(&service.fullname) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_printer_query(query));
+        }
+        if let Some(query) = app_state.probes.cast_query(&service.fullname) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_cast_query(query, service));
+        }
+        if let Some(inspection) = app_state.probes.certificate_inspection(&service.fullname) {
+            details_text.push_str("\n\n");
+            details_text.push_str(&format_certificate_inspection(
+                inspection,
+                current_timestamp_micros() as i64 / 1_000_000,
+            ));
+        }
+        let details = Paragraph::new(details_text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Service Details"),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(details, area);
+    } else {
+        let details = Paragraph::new("No service selected").block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Service Details"),
+        );
+        f.render_widget(details, area);
+    }
+}
+
+fn render_filter_input(f: &mut Frame, app_state: &AppState, area: ratatui::layout::Rect) {
+    render_input_line(
+        f,
+        area,
+        "/",
+        "Quick Filter (Enter to apply, Esc to cancel)",
+        &app_state.filter_query,
+    );
+}
+
+fn render_search_input(f: &mut Frame, app_state: &AppState, area: ratatui::layout::Rect) {
+    render_input_line(
+        f,
+        area,
+        "F:",
+        "Search (Enter to confirm, Esc to cancel, n/N to repeat)",
+        &app_state.search_query,
+    );
+}
+
+fn render_input_line(
+    f: &mut Frame,
+    area: ratatui::layout::Rect,
+    prefix: &str,
+    title: &str,
+    line: &InputLine,
+) {
+    let input_area = ratatui::layout::Rect::new(area.x, area.y + area.height - 3, area.width, 3);
+
+    // Render the character under the cursor reversed, or a trailing '_' when at the end
+    let mut after_cursor = line.after_cursor().chars();
+    let cursor_span = match after_cursor.next() {
+        Some(ch) => Span::styled(
+            ch.to_string(),
+            Style::default().add_modifier(Modifier::REVERSED),
+        ),
+        None => Span::raw("_"),
+    };
+    let input_text = Line::from(vec![
+        Span::raw(prefix.to_string()),
+        Span::raw(line.before_cursor()),
+        cursor_span,
+        Span::raw(after_cursor.as_str()),
+    ]);
+
+    let input = Paragraph::new(input_text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title.to_string()),
+        )
+        .style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(input, input_area);
+}
+
+fn render_filter_status(f: &mut Frame, app_state: &AppState) {
+    let status_area = ratatui::layout::Rect::new(
+        f.area().x,
+        f.area().y + f.area().height - 1,
+        f.area().width,
+        1,
+    );
+
+    let mut parts = Vec::new();
+    if let Some(message) = &app_state.status_message {
+        parts.push(message.clone());
+    }
+    if !app_state.filter_query.is_empty() {
+        parts.push(format!(
+            "Filter: '{}' (Press 'c' to clear)",
+            app_state.filter_query
+        ));
+    }
+    if !app_state.search_query.is_empty() {
+        parts.push(format!(
+            "Search: '{}' (n/N for next/previous)",
+            app_state.search_query
+        ));
+    }
+    if let Some(count) = app_state.pending_count {
+        parts.push(format!("Count: {}", count));
+    }
+    let mut spans = Vec::new();
+    if app_state.unseen_errors > 0 {
+        spans.push(Span::styled(
+            format!(
+                "⚠ {} new error{} (! to review) ",
+                app_state.unseen_errors,
+                if app_state.unseen_errors == 1 {
+                    ""
+                } else {
+                    "s"
+                }
+            ),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::raw(parts.join(" | ")));
+
+    let status = Paragraph::new(Line::from(spans))
+        .style(Style::default().fg(Color::Cyan).bg(Color::DarkGray));
+
+    f.render_widget(status, status_area);
+}
+
+fn render_help_popup(f: &mut Frame) {
+    let help_content = vec![
+        Line::from(""),
+        Line::from(" Navigation:"),
+        Line::from("   ↑/↓ or j/k          - Navigate services list"),
+        Line::from("   ←/→ or h/l          - Switch between service types"),
+        Line::from("   t                   - Jump to the selected service's type"),
+        Line::from("   ya/yp/yn/yy         - Copy address/host:port/fullname/details"),
+        Line::from("   w                   - Open HTTP/HTTPS service in the browser"),
+        Line::from("   L                   - Log in to SSH/SFTP service with ssh"),
+        Line::from("   Enter               - Configured actions for the service"),
+        Line::from("   p                   - Toggle reachability, latency and HTTP probing"),
+        Line::from("   C                   - Inspect TLS certificates of HTTPS service"),
+        Line::from("   R                   - Toggle reverse DNS lookups of addresses"),
+        Line::from("   T                   - Toggle decoding of encoded TXT values"),
+        Line::from("   yc                  - Copy a ready-to-run command for the service"),
+        Line::from("   yv                  - Copy the services list as shown as text"),
+        Line::from("   Y                   - Write the services list as shown to a text file"),
+        Line::from("   W                   - Wake offline host with wake-on-LAN"),
+        Line::from("   I                   - Query device info of printers and cast devices"),
+        Line::from("   J                   - Export the service as JSON to a file or stdout"),
+        Line::from("   e                   - Export shown or all services or a report of them"),
+        Line::from("   P                   - Scan common ports of the service's host"),
+        Line::from("   PageUp/Down         - Scroll services list by page"),
+        Line::from("   b/f/Space           - Scroll services list by page"),
+        Line::from("   Home/End            - Jump to first/last service"),
+        Line::from("   gg/G                - Jump to first/last (or <count>th) service"),
+        Line::from("   <count> + key       - Repeat a move, e.g. 5j or 3l"),
+        Line::from(" "),
+        Line::from(" Actions:"),
+        Line::from("   d                   - Remove offline services"),
+        Line::from("   D                   - Clear stale service types"),
+        Line::from("   a                   - Browse a service type by name"),
+        Line::from("   r                   - Rescan all service types now"),
+        Line::from("   u                   - Re-resolve the selected service"),
+        Line::from("   M                   - Resolve a hostname over mDNS"),
+        Line::from("   Q                   - Query mDNS records by name and type"),
+        Line::from("   H                   - Hide or unhide service types"),
+        Line::from("   E                   - Enable or disable mDNS on network interfaces"),
+        Line::from("   X                   - Check services against the inventory"),
+        Line::from("   U                   - Save the online services as the baseline"),
+        Line::from("   B                   - Cycle filter: new / missing vs baseline / all"),
+        Line::from("   v                   - Mark/unmark service for bulk actions"),
+        Line::from("   V                   - Mark/unmark all services matching the filter"),
+        Line::from(
+            "   *                   - Pin/unpin service to the top, shown whatever the filters",
+        ),
+        Line::from("   '                   - Manage pinned services: jump to, unpin, add notes"),
+        Line::from("   x                   - Bulk actions on marked services"),
+        Line::from("   m                   - Show service metrics and daemon log"),
+        Line::from("   !                   - Review recent errors"),
+        Line::from("   /                   - Edit quick filter"),
+        Line::from("   \\                   - Start a new empty quick filter"),
+        Line::from("   c                   - Clear current filter"),
+        Line::from("   F                   - Search services without hiding any"),
+        Line::from("   n/N                 - Jump to next/previous search match"),
+        Line::from("   ?                   - Toggle this help popup"),
+        Line::from("   q or Ctrl+C         - Quit the application"),
+        Line::from(" "),
+        Line::from(" Sorting:"),
+        Line::from(
+            "   s                   - Cycle sort field: Host → Type → Name → Port → Addr → Addrs# → Time → First → Refreshes → Prio",
+        ),
+        Line::from("   S                   - Cycle sort field backward"),
+        Line::from("   o                   - Toggle sort direction (↑/↓), remembered per field"),
+        Line::from("   O                   - Toggle keeping online services above offline"),
+        Line::from("   A                   - Cycle preferred address family: Any → IPv4 → IPv6"),
+        Line::from("   K                   - Cycle device category filter"),
+        Line::from("   Z                   - Cycle browse domain filter"),
+        Line::from("   i                   - Cycle network interface filter"),
+        Line::from("   @                   - Cycle remote agent filter"),
+        Line::from(" "),
+        Line::from("   Sort field highlighted in yellow, direction in cyan"),
+        Line::from(" "),
+        Line::from(" Quick Filter:"),
+        Line::from("   /                   - Edit the current filter (cursor at end)"),
+        Line::from("   \\                   - Start a new empty filter"),
+        Line::from("   Enter               - Apply filter"),
+        Line::from("   Esc                 - Cancel filter input and restore the filter"),
+        Line::from("   Backspace/Delete    - Delete character before/under cursor"),
+        Line::from("   ←/→, Home/End       - Move cursor"),
+        Line::from("   Ctrl+A/Ctrl+E       - Move cursor to start/end"),
+        Line::from("   Ctrl+W              - Delete word before cursor"),
+        Line::from("   Ctrl+U              - Delete everything before cursor"),
+        Line::from("   c (normal mode)     - Clear current filter"),
+        Line::from(" "),
+        Line::from("   Filter searches all service fields case-insensitively"),
+        Line::from(" "),
+        Line::from(" Search:"),
+        Line::from("   F                   - Start typing to jump to the first match"),
+        Line::from("   Enter               - Keep the selection, n/N repeat the search"),
+        Line::from("   Esc                 - Cancel search and restore the selection"),
+        Line::from(" "),
+        Line::from(" Press any key to close this help"),
+    ];
+
+    let popup_area = create_centered_popup(f.area(), 60, 70);
+
+    // Clear the background first
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    // Create a solid background block to ensure readability
+    let background_block =
+        ratatui::widgets::Block::default().style(Style::default().bg(ratatui::style::Color::Black));
+    f.render_widget(background_block, popup_area);
+
+    // Create inner area with padding by reducing the popup area
+    let inner_area = ratatui::layout::Rect::new(
+        popup_area.x + 1,
+        popup_area.y + 1,
+        popup_area.width.saturating_sub(2),
+        popup_area.height.saturating_sub(2),
+    );
+
+    let help_paragraph = Paragraph::new(help_content)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(help_paragraph, inner_area);
+
+    // Render border on top
+    let border_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Key Bindings")
+        .title_style(Style::default().add_modifier(Modifier::BOLD));
+    f.render_widget(border_block, popup_area);
+}
+
+fn render_metrics_popup(f: &mut Frame, app_state: &AppState) {
+    let mut metrics_content: Vec<Line> = vec![
+        Line::from(""),
+        Line::from(" Service Discovery Metrics:"),
+        Line::from(" "),
+    ];
+
+    // Separate custom metrics from daemon metrics
+    let mut custom_metrics = Vec::new();
+    let mut daemon_metrics = Vec::new();
+
+    for (key, value) in app_state.metrics.iter() {
+        if *value > 0 {
+            if key.starts_with("daemon_") {
+                let clean_key = key.strip_prefix("daemon_").unwrap().replace('_', " ");
+                daemon_metrics.push((clean_key, *value));
+            } else {
+                let formatted_key = key.replace('_', " ");
+                custom_metrics.push((formatted_key, *value));
+            }
+        }
+    }
+
+    // Sort both alphabetically
+    custom_metrics.sort_by(|a, b| a.0.cmp(&b.0));
+    daemon_metrics.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Display custom metrics first
+    if !custom_metrics.is_empty() {
+        metrics_content.push(Line::from(" Custom Metrics:"));
+        for (key, value) in &custom_metrics {
+            metrics_content.push(Line::from(format!("   {}: {}", key, value)));
+        }
+        metrics_content.push(Line::from(" "));
+    }
+
+    // Display daemon metrics
+    if !daemon_metrics.is_empty() {
+        metrics_content.push(Line::from(" Daemon Metrics (from the discovery backend):"));
+        for (key, value) in &daemon_metrics {
+            metrics_content.push(Line::from(format!("   {}: {}", key, value)));
+        }
+        metrics_content.push(Line::from(" "));
+    }
+
+    if custom_metrics.is_empty() && daemon_metrics.is_empty() {
+        metrics_content.push(Line::from("   No metrics collected yet"));
+    }
+
+    let failures = app_state.browse_manager.failures();
+    metrics_content.push(Line::from(format!(
+        " Browses: {} running, {} queued, {} restarting",
+        app_state.browse_manager.started(),
+        app_state.browse_manager.queued(),
+        failures.len()
+    )));
+    for (service_type, failure) in failures {
+        metrics_content.push(Line::from(Span::styled(
+            format!(
+                "   {}: failed {}x, {}",
+                service_type, failure.attempts, failure.error
+            ),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    metrics_content.push(Line::from(" "));
+
+    metrics_content.push(Line::from(format!(
+        " Daemon Log ({}):",
+        daemon_log::level()
+    )));
+    let log_lines = daemon_log::recent_lines(DAEMON_LOG_LINES);
+    if log_lines.is_empty() {
+        metrics_content.push(Line::from("   No log lines"));
+    }
+    for line in log_lines {
+        metrics_content.push(Line::from(format!("   {}", line)));
+    }
+
+    metrics_content.push(Line::from(" "));
+    metrics_content.push(Line::from(
+        " v: cycle daemon log level, f: flush unanswered cached services, any other key to close",
+    ));
+
+    let popup_area = create_centered_popup(f.area(), 60, 70);
+
+    // Clear the background first
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    // Create a solid background block to ensure readability
+    let background_block =
+        ratatui::widgets::Block::default().style(Style::default().bg(ratatui::style::Color::Black));
+    f.render_widget(background_block, popup_area);
+
+    // Create inner area with padding by reducing the popup area
+    let inner_area = ratatui::layout::Rect::new(
+        popup_area.x + 1,
+        popup_area.y + 1,
+        popup_area.width.saturating_sub(2),
+        popup_area.height.saturating_sub(2),
+    );
+
+    let metrics_paragraph = Paragraph::new(metrics_content)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(metrics_paragraph, inner_area);
+
+    // Render border on top
+    let border_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Service Metrics")
+        .title_style(Style::default().add_modifier(Modifier::BOLD));
+    f.render_widget(border_block, popup_area);
+}
+
+fn render_bulk_actions_popup(f: &mut Frame, app_state: &AppState) {
+    let actions_content = vec![
+        Line::from(""),
+        Line::from(format!(
+            " Bulk actions for {} marked services:",
+            app_state.marked_services.len()
+        )),
+        Line::from(" "),
+        Line::from("   e                   - Export as JSON to the current directory"),
+        Line::from("   c                   - Copy addresses to the clipboard"),
+        Line::from("   d                   - Remove offline services"),
+        Line::from("   w                   - Add to the watch list"),
+        Line::from("   W                   - Remove from the watch list"),
+        Line::from("   u                   - Unmark all"),
+        Line::from(" "),
+        Line::from(" Press any other key to close"),
+    ];
+
+    render_text_popup(f, "Bulk Actions", actions_content);
+}
+
+fn render_actions_popup(f: &mut Frame, app_state: &mut AppState) {
+    let Some(service_idx) = app_state.selected_service_index() else {
+        return;
+    };
+    let actions = app_state.selected_service_actions();
+    let service = &app_state.services[service_idx];
+
+    let mut actions_content = vec![
+        Line::from(""),
+        Line::from(format!(" Actions for {}:", service.fullname)),
+        Line::from(" "),
+    ];
+    for (i, action) in actions.iter().take(9).enumerate() {
+        let command =
+            expand_action_command(&action.command, service, app_state.address_preference())
+                .map(|command| command.to_string())
+                .unwrap_or_else(|e| format!("invalid command: {}", e));
+        actions_content.push(Line::from(format!(
+            "   {}                   - {}",
+            i + 1,
+            action.name
+        )));
+        actions_content.push(Line::from(Span::styled(
+            format!("                         {}", command),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    actions_content.push(Line::from(" "));
+    actions_content.push(Line::from(" Press any other key to close"));
+
+    render_text_popup(f, "Actions", actions_content);
+}
+
+fn render_copy_commands_popup(f: &mut Frame, app_state: &mut AppState) {
+    let Some(service_idx) = app_state.selected_service_index() else {
+        return;
+    };
+    let copy_commands = app_state.selected_service_copy_commands();
+    let service = &app_state.services[service_idx];
+
+    let mut commands_content = vec![
+        Line::from(""),
+        Line::from(format!(" Copy {} as:", service.fullname)),
+        Line::from(" "),
+    ];
+    for (i, copy_command) in copy_commands.iter().take(9).enumerate() {
+        let command = expand_action_command(
+            &copy_command.command,
+            service,
+            app_state.address_preference(),
+        )
+        .map(|command| command.to_shell_line())
+        .unwrap_or_else(|e| format!("invalid command: {}", e));
+        commands_content.push(Line::from(format!(
+            "   {}                   - {}",
+            i + 1,
+            copy_command.name
+        )));
+        commands_content.push(Line::from(Span::styled(
+            format!("                         {}", command),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    commands_content.push(Line::from(" "));
+    commands_content.push(Line::from(" Press any other key to close"));
+
+    render_text_popup(f, "Copy as Command", commands_content);
+}
+
+fn render_hidden_types_popup(f: &mut Frame, app_state: &AppState) {
+    let popup_area = create_centered_popup(f.area(), 60, 60);
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let items: Vec<ListItem> = app_state
+        .managed_service_types()
+        .iter()
+        .map(|service_type| {
+            let hidden = app_state.hidden_service_types.contains(service_type);
+            let checkbox = if hidden { "[x] " } else { "[ ] " };
+            let style = if hidden {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(checkbox, Style::default().fg(Color::Yellow)),
+                Span::styled(format_service_type_for_display(service_type), style),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Hidden Types [{}] (Space to toggle, any other key to close)",
+                    app_state.hidden_service_types.len()
+                ))
+                .title_style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .style(Style::default().bg(Color::Black))
+        .highlight_style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default();
+    list_state.select(Some(app_state.hidden_types_cursor));
+    f.render_stateful_widget(list, popup_area, &mut list_state);
+}
+
+fn render_interfaces_popup(f: &mut Frame, app_state: &AppState) {
+    let popup_area = create_centered_popup(f.area(), 60, 60);
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let items: Vec<ListItem> = app_state
+        .interface_addresses()
+        .iter()
+        .map(|(name, addrs)| {
+            let enabled = !app_state.disabled_interfaces.contains(name);
+            let checkbox = if enabled { "[x] " } else { "[ ] " };
+            let style = if enabled {
+                Style::default().fg(Color::White)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            let addrs = addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            ListItem::new(Line::from(vec![
+                Span::styled(checkbox, Style::default().fg(Color::Yellow)),
+                Span::styled(name.clone(), style),
+                Span::styled(format!(" {}", addrs), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("mDNS Interfaces (Space to enable/disable, any other key to close)")
+                .title_style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .style(Style::default().bg(Color::Black))
+        .highlight_style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default();
+    list_state.select(Some(app_state.interfaces_cursor));
+    f.render_stateful_widget(list, popup_area, &mut list_state);
+}
+
+fn render_inventory_popup(f: &mut Frame, app_state: &AppState) {
+    let popup_area = create_centered_popup(f.area(), 70, 70);
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let checklist = app_state.inventory_checklist();
+    let count = |status: CheckStatus| checklist.iter().filter(|e| e.status == status).count();
+    let items: Vec<ListItem> = checklist
+        .iter()
+        .map(|entry| {
+            let (mark, color) = match entry.status {
+                CheckStatus::Present => ("[✓] ", Color::Green),
+                CheckStatus::Offline => ("[~] ", Color::Yellow),
+                CheckStatus::Missing => ("[✗] ", Color::Red),
+                CheckStatus::Unexpected => ("[+] ", Color::Magenta),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(mark, Style::default().fg(color)),
+                Span::styled(entry.label.clone(), Style::default().fg(Color::White)),
+                Span::styled(
+                    format!("  {}", entry.detail),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Inventory [{} present, {} offline, {} missing, {} unexpected] (any other key to close)",
+                    count(CheckStatus::Present),
+                    count(CheckStatus::Offline),
+                    count(CheckStatus::Missing),
+                    count(CheckStatus::Unexpected)
+                ))
+                .title_style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .style(Style::default().bg(Color::Black))
+        .highlight_style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default();
+    list_state.select(Some(app_state.inventory_cursor));
+    f.render_stateful_widget(list, popup_area, &mut list_state);
+}
+
+fn render_pins_popup(f: &mut Frame, app_state: &AppState) {
+    let popup_area = create_centered_popup(f.area(), 80, 70);
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    let items: Vec<ListItem> = app_state
+        .pins
+        .store()
+        .iter()
+        .map(|(fullname, pin)| {
+            // Services of this session are told about as listed, the others
+            // as they were when the pins were last saved
+            let listed = app_state.services.iter().find(|service| {
+                service.fullname == *fullname
+                    && !app_state.remembered_services.contains(&service.fullname)
+            });
+            let (mark, color, state) = match (listed, &pin.last_seen) {
+                (Some(service), _) => (
+                    if service.online { "● " } else { "○ " },
+                    if service.online {
+                        Color::Green
+                    } else {
+                        Color::Yellow
+                    },
+                    format!(
+                        "{}:{}, last seen {}",
+                        service.host,
+                        service.port,
+                        format_timestamp_micros(service.last_seen_micros)
+                    ),
+                ),
+                (None, Some(sighting)) => (
+                    "? ",
+                    Color::DarkGray,
+                    format!(
+                        "{}:{}, {} when last seen {}",
+                        sighting.host,
+                        sighting.port,
+                        if sighting.online { "online" } else { "offline" },
+                        format_timestamp_micros(sighting.last_seen_micros)
+                    ),
+                ),
+                (None, None) => ("? ", Color::DarkGray, "never seen".to_string()),
+            };
+            let mut spans = vec![
+                Span::styled(mark, Style::default().fg(color)),
+                Span::styled(fullname.clone(), Style::default().fg(Color::White)),
+                Span::styled(format!("  {}", state), Style::default().fg(Color::DarkGray)),
+            ];
+            if !pin.note.is_empty() {
+                spans.push(Span::styled(
+                    format!("  {}", pin.note),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Pinned services [{}] (Enter: jump to, d: unpin, n: note, any other key to close)",
+                    app_state.pins.store().len()
+                ))
+                .title_style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .style(Style::default().bg(Color::Black))
+        .highlight_style(Style::default().bg(Color::DarkGray).fg(Color::White));
+    let mut list_state = ListState::default();
+    list_state.select(Some(app_state.pins.cursor()));
+    f.render_stateful_widget(list, popup_area, &mut list_state);
+
+    if let Some(note) = app_state.pins.note_input() {
+        let input_area = ratatui::layout::Rect::new(
+            popup_area.x,
+            popup_area.y + popup_area.height.saturating_sub(3),
+            popup_area.width,
+            popup_area.height.min(3),
+        );
+        f.render_widget(ratatui::widgets::Clear, input_area);
+        render_input_line(
+            f,
+            popup_area,
+            "n:",
+            "Note for the pinned service, empty to remove it (Enter to save, Esc to cancel)",
+            note,
+        );
+    }
+}
+
+fn render_port_scan_popup(f: &mut Frame, app_state: &AppState) {
+    let Some(scan) = app_state.probes.port_scan() else {
+        return;
+    };
+
+    let progress = if scan.scanned < scan.total {
+        format!("scanning {}/{}", scan.scanned, scan.total)
+    } else {
+        format!("{} ports scanned", scan.total)
+    };
+    let mut scan_content = vec![
+        Line::from(""),
+        Line::from(format!(" {} ({}), {}:", scan.host, scan.address, progress)),
+        Line::from(" "),
+    ];
+    if scan.open_ports.is_empty() {
+        scan_content.push(Line::from("   No open ports found"));
+    }
+    for port in &scan.open_ports {
+        scan_content.push(Line::from(vec![
+            Span::styled(
+                format!("   {:<20}", port),
+                Style::default().fg(Color::Green),
+            ),
+            Span::raw(format!(
+                "- {}",
+                well_known_port_name(*port).unwrap_or("unknown")
+            )),
+        ]));
+    }
+    scan_content.push(Line::from(" "));
+    scan_content.push(Line::from(" Press any key to close"));
+
+    render_text_popup(f, "Port Scan", scan_content);
+}
+
+fn render_errors_popup(f: &mut Frame, app_state: &AppState) {
+    let mut content = vec![Line::from("")];
+    if app_state.errors.is_empty() {
+        content.push(Line::from("   No errors"));
+    }
+    // Newest first
+    for error in app_state.errors.iter().rev() {
+        content.push(Line::from(vec![
+            Span::styled(
+                format!(" {} ", error.at.format("%H:%M:%S")),
+                Style::default().fg(Color::Gray),
+            ),
+            Span::styled(error.message.clone(), Style::default().fg(Color::Red)),
+        ]));
+    }
+    content.push(Line::from(" "));
+    content.push(Line::from(" c - Clear, any other key closes"));
+
+    render_text_popup(f, "Errors", content);
+}
+
+fn render_host_lookup_popup(f: &mut Frame, app_state: &AppState) {
+    let Some(lookup) = app_state.queries.host_lookup() else {
+        return;
+    };
+
+    let mut content = vec![Line::from(""), Line::from(format!(" {}", lookup.hostname))];
+    content.push(Line::from(" "));
+    match &lookup.result {
+        None => content.push(Line::from(format!(
+            "   {} Resolving...",
+            spinner_frame(lookup.started.elapsed())
+        ))),
+        Some(Ok(addrs)) if addrs.is_empty() => {
+            content.push(Line::from("   No answer"));
+        }
+        Some(Ok(addrs)) => {
+            for addr in addrs {
+                content.push(Line::from(Span::styled(
+                    format!("   {}", addr),
+                    Style::default().fg(Color::Green),
+                )));
+            }
+        }
+        Some(Err(e)) => content.push(Line::from(Span::styled(
+            format!("   {}", e),
+            Style::default().fg(Color::Red),
+        ))),
+    }
+    content.push(Line::from(" "));
+    content.push(Line::from(" Press any key to close"));
+
+    render_text_popup(f, "Resolve Hostname", content);
+}
+
+fn render_record_query_popup(f: &mut Frame, app_state: &AppState) {
+    let Some(query) = app_state.queries.record_query() else {
+        return;
+    };
+
+    let mut content = vec![Line::from(""), Line::from(format!(" {}", query.question))];
+    content.push(Line::from(" "));
+    match &query.result {
+        None => content.push(Line::from(format!(
+            "   {} Waiting for answers...",
+            spinner_frame(query.started.elapsed())
+        ))),
+        Some(Ok(answers)) if answers.is_empty() => {
+            content.push(Line::from("   No answer"));
+        }
+        Some(Ok(answers)) => {
+            content.extend(
+                answers
+                    .iter()
+                    .skip(query.scroll)
+                    .map(|answer| Line::from(format!("   {}", format_mdns_answer(answer)))),
+            );
+        }
+        Some(Err(e)) => content.push(Line::from(Span::styled(
+            format!("   {}", e),
+            Style::default().fg(Color::Red),
+        ))),
+    }
+    content.push(Line::from(" "));
+    content.push(Line::from(" j/k to scroll, any other key to close"));
+
+    render_text_popup(f, "mDNS Query", content);
+}
+
+fn render_text_popup(f: &mut Frame, title: &str, content: Vec<Line>) {
+    let popup_area = create_centered_popup(f.area(), 60, 50);
+
+    // Clear the background first
+    f.render_widget(ratatui::widgets::Clear, popup_area);
+
+    // Create a solid background block to ensure readability
+    let background_block =
+        ratatui::widgets::Block::default().style(Style::default().bg(ratatui::style::Color::Black));
+    f.render_widget(background_block, popup_area);
+
+    // Create inner area with padding by reducing the popup area
+    let inner_area = ratatui::layout::Rect::new(
+        popup_area.x + 1,
+        popup_area.y + 1,
+        popup_area.width.saturating_sub(2),
+        popup_area.height.saturating_sub(2),
+    );
+
+    let paragraph = Paragraph::new(content)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(paragraph, inner_area);
+
+    // Render border on top
+    let border_block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_style(Style::default().add_modifier(Modifier::BOLD));
+    f.render_widget(border_block, popup_area);
+}
+
+pub(super) fn create_centered_popup(
+    parent_area: ratatui::layout::Rect,
+    width_percent: u16,
+    height_percent: u16,
+) -> ratatui::layout::Rect {
+    let popup_width = (parent_area.width * width_percent) / 100;
+    let popup_height = (parent_area.height * height_percent) / 100;
+
+    // Add margins (at least 2 cells on each side if possible)
+    let margin_x = std::cmp::min(2, parent_area.width.saturating_sub(popup_width) / 2);
+    let margin_y = std::cmp::min(1, parent_area.height.saturating_sub(popup_height) / 2);
+
+    let x = parent_area.x + (parent_area.width - popup_width) / 2 + margin_x;
+    let y = parent_area.y + (parent_area.height - popup_height) / 2 + margin_y;
+
+    // Adjust width and height to account for margins
+    let adjusted_width = popup_width - (margin_x * 2);
+    let adjusted_height = popup_height - (margin_y * 2);
+
+    ratatui::layout::Rect::new(x, y, adjusted_width, adjusted_height)
+}
+
+pub(super) fn create_service_list_item_style(
+    index: usize,
+    selected_index: usize,
+    service: &ServiceEntry,
+) -> Style {
+    let foreground = if service.online {
+        Color::White
+    } else {
+        Color::LightMagenta
+    };
+
+    let mut style = if index == selected_index {
+        Style::default().bg(Color::DarkGray).fg(foreground)
+    } else {
+        Style::default().fg(foreground)
+    };
+
+    if !service.online {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+
+    style
+}
+
+pub(super) fn latency_color(latency_micros: u64) -> Color {
+    match latency_micros {
+        0..20_000 => Color::Green,
+        20_000..100_000 => Color::Yellow,
+        _ => Color::Red,
+    }
+}
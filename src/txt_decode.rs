@@ -0,0 +1,178 @@
+#![forbid(unsafe_code)]
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::fmt;
+
+// Shortest value considered encoded, shorter ones are too likely plain words
+const MIN_ENCODED_LEN: usize = 8;
+
+// Printable text stays as is, anything else is escaped as \xNN so binary values
+// stay distinguishable instead of collapsing into replacement characters
+pub fn format_txt_value(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() {
+                text.push_str(&escape_bytes(c.to_string().as_bytes()));
+            } else if c == '\\' {
+                text.push_str("\\\\");
+            } else {
+                text.push(c);
+            }
+        }
+        text.push_str(&escape_bytes(chunk.invalid()));
+    }
+    text
+}
+
+fn escape_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("\\x{:02x}", byte))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxtEncoding {
+    Url,
+    Hex,
+    Base64,
+}
+
+impl fmt::Display for TxtEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxtEncoding::Url => write!(f, "URL"),
+            TxtEncoding::Hex => write!(f, "hex"),
+            TxtEncoding::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+// The encoding a value looks like and its decoded form, rendered safely
+pub fn decode_txt_value(value: &str) -> Option<(TxtEncoding, String)> {
+    if looks_like_url(value) {
+        let decoded = percent_encoding::percent_decode_str(value)
+            .decode_utf8()
+            .map(|decoded| decoded.into_owned())
+            .unwrap_or_else(|_| value.to_string());
+        return Some((TxtEncoding::Url, decoded));
+    }
+    if value.len() < MIN_ENCODED_LEN {
+        return None;
+    }
+    if value.len() % 2 == 0 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        let bytes: Vec<u8> = (0..value.len())
+            .step_by(2)
+            .filter_map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+            .collect();
+        return Some((TxtEncoding::Hex, describe_bytes(&bytes)));
+    }
+    if looks_like_base64(value) {
+        let bytes = STANDARD.decode(value).ok()?;
+        // Without padding or symbols only printable results make it likely to be base64
+        let has_markers = value.contains(['=', '+', '/']);
+        if has_markers || is_printable_text(&bytes) {
+            return Some((TxtEncoding::Base64, describe_bytes(&bytes)));
+        }
+    }
+    None
+}
+
+fn looks_like_url(value: &str) -> bool {
+    match value.split_once("://") {
+        Some((scheme, rest)) => {
+            !scheme.is_empty()
+                && !rest.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+fn looks_like_base64(value: &str) -> bool {
+    let data = value.trim_end_matches('=');
+    value.len() % 4 == 0
+        && value.len() - data.len() <= 2
+        && !value.starts_with('/')
+        && data
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/'))
+}
+
+fn is_printable_text(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_ok_and(|text| !text.chars().any(char::is_control))
+}
+
+fn describe_bytes(bytes: &[u8]) -> String {
+    if is_printable_text(bytes) {
+        format!("\"{}\"", format_txt_value(bytes))
+    } else {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{} bytes: {}", bytes.len(), hex.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_txt_value() {
+        assert_eq!(format_txt_value(b"plain text"), "plain text");
+        assert_eq!(format_txt_value("grüße".as_bytes()), "grüße");
+        assert_eq!(format_txt_value(b"\x00\xffab"), "\\x00\\xffab");
+        assert_eq!(format_txt_value(b"a\nb"), "a\\x0ab");
+        assert_eq!(format_txt_value(b"a\\x00"), "a\\\\x00");
+    }
+
+    #[test]
+    fn test_decode_url() {
+        assert_eq!(
+            decode_txt_value("http://printer.local/admin%20page"),
+            Some((
+                TxtEncoding::Url,
+                "http://printer.local/admin page".to_string()
+            ))
+        );
+        assert_eq!(decode_txt_value("://nothing"), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(
+            decode_txt_value("68656c6c6f21"),
+            Some((TxtEncoding::Hex, "\"hello!\"".to_string()))
+        );
+        assert_eq!(decode_txt_value("0001ff020"), None, "odd number of digits");
+        assert_eq!(
+            decode_txt_value("0001ff02"),
+            Some((TxtEncoding::Hex, "4 bytes: 00 01 ff 02".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(
+            decode_txt_value("aGVsbG8gd29ybGQ="),
+            Some((TxtEncoding::Base64, "\"hello world\"".to_string()))
+        );
+        assert_eq!(
+            decode_txt_value("AAH/Ag=="),
+            Some((TxtEncoding::Base64, "4 bytes: 00 01 ff 02".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_plain_values_are_not_decoded() {
+        assert_eq!(decode_txt_value("1.2.3"), None);
+        assert_eq!(decode_txt_value("Living Room"), None);
+        assert_eq!(decode_txt_value("printers"), None);
+        assert_eq!(decode_txt_value("/printers/office"), None);
+        assert_eq!(decode_txt_value("abc"), None);
+    }
+}
@@ -0,0 +1,415 @@
+#![forbid(unsafe_code)]
+
+// Reads the TXT records of one schema, returning labelled human readable lines
+type Interpreter = fn(&TxtRecords) -> Vec<(&'static str, String)>;
+
+// Service types without domain and the interpreter for their TXT records
+const INTERPRETERS: &[(&str, Interpreter)] = &[
+    ("_airplay._tcp", interpret_airplay),
+    ("_raop._tcp", interpret_airplay),
+    ("_googlecast._tcp", interpret_chromecast),
+    ("_hap._tcp", interpret_homekit),
+    ("_hap._udp", interpret_homekit),
+    ("_ipp._tcp", interpret_printer),
+    ("_ipps._tcp", interpret_printer),
+    ("_printer._tcp", interpret_printer),
+    ("_pdl-datastream._tcp", interpret_printer),
+];
+
+// Labelled interpretation of the well-known TXT keys of a service, empty for
+// types without a known schema
+pub fn interpret_txt(service_type: &str, txt: &[String]) -> Vec<(&'static str, String)> {
+    let records = TxtRecords::parse(txt);
+    INTERPRETERS
+        .iter()
+        .filter(|(schema_type, _)| {
+            service_type
+                .strip_prefix(schema_type)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .flat_map(|(_, interpret)| interpret(&records))
+        .collect()
+}
+
+// TXT keys are case insensitive (RFC 6763 section 6.4)
+struct TxtRecords<'a> {
+    entries: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> TxtRecords<'a> {
+    fn parse(txt: &'a [String]) -> Self {
+        Self {
+            entries: txt
+                .iter()
+                .filter_map(|entry| entry.split_once('='))
+                .collect(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _)| entry_key.eq_ignore_ascii_case(key))
+            .map(|(_, value)| *value)
+            .filter(|value| !value.is_empty())
+    }
+}
+
+const AIRPLAY_FEATURES: &[(u32, &str)] = &[
+    (0, "video"),
+    (1, "photo"),
+    (5, "slideshow"),
+    (7, "screen mirroring"),
+    (9, "audio"),
+    (15, "artwork"),
+    (16, "progress"),
+    (17, "now playing info"),
+    (30, "AirTunes (RAOP)"),
+    (38, "buffered audio"),
+    (40, "PTP clock"),
+    (46, "HomeKit pairing"),
+    (48, "CoreUtils pairing"),
+];
+
+fn interpret_airplay(records: &TxtRecords) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::new();
+    // RAOP uses short keys for the same information
+    if let Some(model) = records.get("model").or(records.get("am")) {
+        lines.push(("Model", model.to_string()));
+    }
+    if let Some(features) = records.get("features").or(records.get("ft")) {
+        match parse_airplay_features(features) {
+            Some(bits) => {
+                let names: Vec<&str> = AIRPLAY_FEATURES
+                    .iter()
+                    .filter(|(bit, _)| bits & (1 << bit) != 0)
+                    .map(|(_, name)| *name)
+                    .collect();
+                lines.push(("AirPlay features", names.join(", ")));
+            }
+            None => lines.push(("AirPlay features", format!("unparsable ({})", features))),
+        }
+    }
+    if let Some(version) = records.get("srcvers").or(records.get("vs")) {
+        lines.push(("AirPlay version", version.to_string()));
+    }
+    lines
+}
+
+// A 32 bit hex number, or two of them separated by a comma for the low and high half
+fn parse_airplay_features(features: &str) -> Option<u64> {
+    let parse_half = |half: &str| {
+        let half = half.trim();
+        let digits = half
+            .strip_prefix("0x")
+            .or(half.strip_prefix("0X"))
+            .unwrap_or(half);
+        u32::from_str_radix(digits, 16).ok()
+    };
+    match features.split_once(',') {
+        Some((low, high)) => Some(u64::from(parse_half(low)?) | u64::from(parse_half(high)?) << 32),
+        None => parse_half(features).map(u64::from),
+    }
+}
+
+fn interpret_chromecast(records: &TxtRecords) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::new();
+    if let Some(name) = records.get("fn") {
+        lines.push(("Friendly name", name.to_string()));
+    }
+    if let Some(model) = records.get("md") {
+        lines.push(("Model", model.to_string()));
+    }
+    if let Some(status) = records.get("rs") {
+        lines.push(("Now casting", status.to_string()));
+    }
+    lines
+}
+
+const HOMEKIT_CATEGORIES: &[(u32, &str)] = &[
+    (1, "Other"),
+    (2, "Bridge"),
+    (3, "Fan"),
+    (4, "Garage door opener"),
+    (5, "Lightbulb"),
+    (6, "Door lock"),
+    (7, "Outlet"),
+    (8, "Switch"),
+    (9, "Thermostat"),
+    (10, "Sensor"),
+    (11, "Security system"),
+    (12, "Door"),
+    (13, "Window"),
+    (14, "Window covering"),
+    (15, "Programmable switch"),
+    (16, "Range extender"),
+    (17, "IP camera"),
+    (18, "Video doorbell"),
+    (19, "Air purifier"),
+    (20, "Heater"),
+    (21, "Air conditioner"),
+    (22, "Humidifier"),
+    (23, "Dehumidifier"),
+    (28, "Sprinkler"),
+    (29, "Faucet"),
+    (30, "Shower system"),
+    (31, "Television"),
+    (32, "Remote control"),
+    (33, "Wi-Fi router"),
+    (34, "Audio receiver"),
+    (35, "TV set top box"),
+    (36, "TV streaming stick"),
+];
+
+fn interpret_homekit(records: &TxtRecords) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::new();
+    if let Some(model) = records.get("md") {
+        lines.push(("Model", model.to_string()));
+    }
+    if let Some(category) = records.get("ci") {
+        let name = category
+            .parse::<u32>()
+            .ok()
+            .and_then(|id| HOMEKIT_CATEGORIES.iter().find(|(known, _)| *known == id))
+            .map_or("Unknown", |(_, name)| *name);
+        lines.push(("HomeKit category", format!("{} ({})", name, category)));
+    }
+    if let Some(flags) = records.get("sf") {
+        let status = match parse_homekit_status_flags(flags) {
+            Ok(flags) => {
+                let mut notes = Vec::new();
+                notes.push(if flags & 0x1 != 0 {
+                    "not paired"
+                } else {
+                    "paired"
+                });
+                if flags & 0x2 != 0 {
+                    notes.push("Wi-Fi not configured");
+                }
+                if flags & 0x4 != 0 {
+                    notes.push("problem detected");
+                }
+                notes.join(", ")
+            }
+            Err(_) => format!("unparsable ({})", flags),
+        };
+        lines.push(("HomeKit status", status));
+    }
+    if let Some(number) = records.get("c#") {
+        // Bumped by the accessory whenever its set of services changes
+        lines.push(("Configuration number", number.to_string()));
+    }
+    if let Some(flags) = records.get("ff") {
+        let support = match flags.parse::<u32>() {
+            Ok(0) => "no".to_string(),
+            Ok(flags) => {
+                let mut kinds = Vec::new();
+                if flags & 0x1 != 0 {
+                    kinds.push("hardware");
+                }
+                if flags & 0x2 != 0 {
+                    kinds.push("software");
+                }
+                if kinds.is_empty() {
+                    format!("unknown ({})", flags)
+                } else {
+                    kinds.join(", ")
+                }
+            }
+            Err(_) => format!("unparsable ({})", flags),
+        };
+        lines.push(("Apple authentication", support));
+    }
+    lines
+}
+
+// Status flags are a decimal number, some accessories announce them in hex
+fn parse_homekit_status_flags(flags: &str) -> Result<u32, std::num::ParseIntError> {
+    match flags.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => flags.parse::<u32>(),
+    }
+}
+
+// Unpaired accessories can be taken over by anyone on the network who knows
+// or guesses the setup code
+pub fn is_unpaired_homekit_accessory(service_type: &str, txt: &[String]) -> bool {
+    let is_homekit = ["_hap._tcp", "_hap._udp"].iter().any(|homekit_type| {
+        service_type
+            .strip_prefix(homekit_type)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    });
+    let records = TxtRecords::parse(txt);
+    is_homekit
+        && records
+            .get("sf")
+            .and_then(|flags| parse_homekit_status_flags(flags).ok())
+            .is_some_and(|flags| flags & 0x1 != 0)
+}
+
+const PRINTER_FORMATS: &[(&str, &str)] = &[
+    ("application/pdf", "PDF"),
+    ("application/postscript", "PostScript"),
+    ("image/urf", "AirPrint raster"),
+    ("image/pwg-raster", "PWG raster"),
+    ("application/vnd.hp-pcl", "PCL"),
+    ("application/vnd.hp-pclxl", "PCL XL"),
+    ("image/jpeg", "JPEG"),
+    ("image/png", "PNG"),
+    ("application/octet-stream", "raw"),
+];
+
+fn interpret_printer(records: &TxtRecords) -> Vec<(&'static str, String)> {
+    let mut lines = Vec::new();
+    if let Some(model) = records.get("ty") {
+        lines.push(("Model", model.to_string()));
+    }
+    if let Some(location) = records.get("note") {
+        lines.push(("Location", location.to_string()));
+    }
+    if let Some(formats) = records.get("pdl") {
+        let formats: Vec<&str> = formats
+            .split(',')
+            .map(str::trim)
+            .filter(|format| !format.is_empty())
+            .map(|format| {
+                PRINTER_FORMATS
+                    .iter()
+                    .find(|(mime, _)| mime.eq_ignore_ascii_case(format))
+                    .map_or(format, |(_, name)| *name)
+            })
+            .collect();
+        lines.push(("Document formats", formats.join(", ")));
+    }
+    let capabilities: Vec<&str> = [("Color", "color"), ("Duplex", "duplex")]
+        .iter()
+        .filter(|(key, _)| records.get(key) == Some("T"))
+        .map(|(_, name)| *name)
+        .collect();
+    if !capabilities.is_empty() {
+        lines.push(("Capabilities", capabilities.join(", ")));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txt(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn test_unknown_type_has_no_interpretation() {
+        assert!(interpret_txt("_http._tcp.local.", &txt(&["md=x"])).is_empty());
+    }
+
+    #[test]
+    fn test_interpret_airplay() {
+        let lines = interpret_txt(
+            "_airplay._tcp.local.",
+            &txt(&["features=0x5A7FFEE6,0x0", "model=AppleTV6,2"]),
+        );
+        assert_eq!(lines[0], ("Model", "AppleTV6,2".to_string()));
+        assert_eq!(lines[1].0, "AirPlay features");
+        assert!(lines[1].1.contains("screen mirroring"));
+        assert!(lines[1].1.contains("audio"));
+        assert!(!lines[1].1.contains("video,"));
+    }
+
+    #[test]
+    fn test_parse_airplay_features() {
+        assert_eq!(parse_airplay_features("0x1"), Some(1));
+        assert_eq!(parse_airplay_features("0x1,0x40"), Some(1 | 0x40 << 32));
+        assert_eq!(parse_airplay_features("nope"), None);
+    }
+
+    #[test]
+    fn test_interpret_chromecast() {
+        let lines = interpret_txt(
+            "_googlecast._tcp.local.",
+            &txt(&["fn=Living Room TV", "md=Chromecast Ultra", "rs="]),
+        );
+        assert_eq!(
+            lines,
+            vec![
+                ("Friendly name", "Living Room TV".to_string()),
+                ("Model", "Chromecast Ultra".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpret_homekit() {
+        let lines = interpret_txt("_hap._tcp.local.", &txt(&["ci=5", "sf=1"]));
+        assert_eq!(
+            lines,
+            vec![
+                ("HomeKit category", "Lightbulb (5)".to_string()),
+                ("HomeKit status", "not paired".to_string()),
+            ]
+        );
+        let lines = interpret_txt("_hap._udp.local.", &txt(&["ci=99", "sf=4"]));
+        assert_eq!(lines[0].1, "Unknown (99)");
+        assert_eq!(lines[1].1, "paired, problem detected");
+    }
+
+    #[test]
+    fn test_interpret_homekit_configuration() {
+        let lines = interpret_txt("_hap._tcp.local.", &txt(&["c#=12", "ff=2", "sf=0x1"]));
+        assert_eq!(
+            lines,
+            vec![
+                ("HomeKit status", "not paired".to_string()),
+                ("Configuration number", "12".to_string()),
+                ("Apple authentication", "software".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_unpaired_homekit_accessory() {
+        assert!(is_unpaired_homekit_accessory(
+            "_hap._tcp.local.",
+            &txt(&["sf=1"])
+        ));
+        assert!(is_unpaired_homekit_accessory(
+            "_hap._udp.local.",
+            &txt(&["sf=5"])
+        ));
+        assert!(!is_unpaired_homekit_accessory(
+            "_hap._tcp.local.",
+            &txt(&["sf=0"])
+        ));
+        assert!(!is_unpaired_homekit_accessory("_hap._tcp.local.", &[]));
+        assert!(!is_unpaired_homekit_accessory(
+            "_http._tcp.local.",
+            &txt(&["sf=1"])
+        ));
+    }
+
+    #[test]
+    fn test_interpret_printer() {
+        let lines = interpret_txt(
+            "_ipp._tcp.local.",
+            &txt(&[
+                "ty=Office Laser",
+                "pdl=application/pdf,image/urf,application/x-custom",
+                "Color=T",
+                "duplex=F",
+            ]),
+        );
+        assert_eq!(
+            lines,
+            vec![
+                ("Model", "Office Laser".to_string()),
+                (
+                    "Document formats",
+                    "PDF, AirPrint raster, application/x-custom".to_string()
+                ),
+                ("Capabilities", "color".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,315 @@
+#![forbid(unsafe_code)]
+
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::rr::{Name, RData, RecordType};
+use hickory_resolver::{ResolveError, TokioResolver};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A service instance resolved over unicast DNS
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnicastService {
+    pub fullname: String,
+    pub service_type: String,
+    pub host: String,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+    pub addrs: Vec<IpAddr>,
+    // Raw TXT strings, usually key=value
+    pub txt: Vec<Vec<u8>>,
+}
+
+// DNS-SD over unicast DNS (RFC 6763) for wide-area domains, there are no
+// announcements, so callers have to poll
+pub struct UnicastBrowser {
+    resolver: TokioResolver,
+}
+
+impl UnicastBrowser {
+    // Without a configured server the system's resolvers are asked
+    pub fn new(server: Option<SocketAddr>) -> Result<Self, String> {
+        let mut builder = match server {
+            Some(server) => TokioResolver::builder_with_config(
+                ResolverConfig::from_parts(
+                    None,
+                    Vec::new(),
+                    NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true),
+                ),
+                TokioConnectionProvider::default(),
+            ),
+            None => TokioResolver::builder_tokio().map_err(|e| e.to_string())?,
+        };
+        builder.options_mut().timeout = LOOKUP_TIMEOUT;
+        Ok(Self {
+            resolver: builder.build(),
+        })
+    }
+
+    // The domain itself plus the ones it recommends for browsing via b._dns-sd._udp
+    pub async fn browse_domains(&self, domain: &str) -> Vec<String> {
+        let mut domains = vec![domain.to_string()];
+        let recommended = self
+            .ptr_names(&format!("b._dns-sd._udp.{}", domain))
+            .await
+            .unwrap_or_default();
+        for name in recommended {
+            let name = name_to_string(&name).to_lowercase();
+            if !domains.contains(&name) {
+                domains.push(name);
+            }
+        }
+        domains
+    }
+
+    pub async fn service_types(&self, domain: &str) -> Result<Vec<String>, String> {
+        let names = self
+            .ptr_names(&format!("_services._dns-sd._udp.{}", domain))
+            .await?;
+        Ok(names.iter().map(name_to_string).collect())
+    }
+
+    pub async fn instances(&self, service_type: &str) -> Result<Vec<Name>, String> {
+        self.ptr_names(service_type).await
+    }
+
+    pub async fn resolve(
+        &self,
+        instance: &Name,
+        service_type: &str,
+    ) -> Result<UnicastService, String> {
+        let srv = self
+            .resolver
+            .srv_lookup(instance.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(srv) = srv.iter().min_by_key(|srv| srv.priority()) else {
+            return Err(format!("No SRV record for {}", name_to_string(instance)));
+        };
+        let host = srv.target().clone();
+
+        let (addrs, txt) = tokio::join!(
+            self.resolver.lookup_ip(host.clone()),
+            self.resolver.txt_lookup(instance.clone())
+        );
+        let mut addrs: Vec<IpAddr> = match addrs {
+            Ok(addrs) => addrs.iter().collect(),
+            Err(e) if e.is_no_records_found() => Vec::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+        addrs.sort();
+        addrs.dedup();
+        let txt = match txt {
+            Ok(txt) => txt
+                .iter()
+                .flat_map(|txt| txt.iter().map(|data| data.to_vec()))
+                .filter(|data| !data.is_empty())
+                .collect(),
+            Err(e) if e.is_no_records_found() => Vec::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        Ok(UnicastService {
+            fullname: name_to_string(instance),
+            service_type: service_type.to_string(),
+            host: name_to_string(&host),
+            port: srv.port(),
+            priority: srv.priority(),
+            weight: srv.weight(),
+            addrs,
+            txt,
+        })
+    }
+
+    // A name without records is an empty result rather than an error
+    async fn ptr_names(&self, name: &str) -> Result<Vec<Name>, String> {
+        let name = Name::from_utf8(name).map_err(|e| e.to_string())?;
+        match self.resolver.lookup(name, RecordType::PTR).await {
+            Ok(lookup) => Ok(lookup
+                .iter()
+                .filter_map(|data| match data {
+                    RData::PTR(ptr) => Some(ptr.0.clone()),
+                    _ => None,
+                })
+                .collect()),
+            Err(e) if is_empty_answer(&e) => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+fn is_empty_answer(error: &ResolveError) -> bool {
+    error.is_no_records_found() || error.is_nx_domain()
+}
+
+// Instance labels may contain dots and spaces, which Display would escape
+pub fn name_to_string(name: &Name) -> String {
+    let labels: Vec<String> = name
+        .iter()
+        .map(|label| String::from_utf8_lossy(label).into_owned())
+        .collect();
+    format!("{}.", labels.join("."))
+}
+
+// "192.168.1.1" or "192.168.1.1:5353", "[::1]:53"
+pub fn parse_server_address(server: &str) -> Result<SocketAddr, String> {
+    server
+        .parse::<SocketAddr>()
+        .or_else(|_| server.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("Invalid DNS server address: {}", server))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use hickory_resolver::proto::rr::Record;
+    use hickory_resolver::proto::rr::rdata::{A, PTR, SRV, TXT};
+    use tokio::net::UdpSocket;
+
+    fn name(text: &str) -> Name {
+        Name::from_utf8(text).unwrap()
+    }
+
+    fn zone() -> Vec<Record> {
+        let instance = Name::from_labels(vec![
+            "Office Printer".as_bytes(),
+            b"_ipp",
+            b"_tcp",
+            b"example",
+            b"com",
+        ])
+        .unwrap();
+        vec![
+            Record::from_rdata(
+                name("b._dns-sd._udp.example.com."),
+                60,
+                RData::PTR(PTR(name("lab.example.com."))),
+            ),
+            Record::from_rdata(
+                name("_services._dns-sd._udp.example.com."),
+                60,
+                RData::PTR(PTR(name("_ipp._tcp.example.com."))),
+            ),
+            Record::from_rdata(
+                name("_ipp._tcp.example.com."),
+                60,
+                RData::PTR(PTR(instance.clone())),
+            ),
+            Record::from_rdata(
+                instance.clone(),
+                60,
+                RData::SRV(SRV::new(10, 5, 631, name("printer.example.com."))),
+            ),
+            Record::from_rdata(
+                instance,
+                60,
+                RData::TXT(TXT::new(vec!["rp=ipp/print".to_string(), String::new()])),
+            ),
+            Record::from_rdata(
+                name("printer.example.com."),
+                60,
+                RData::A(A::new(192, 0, 2, 7)),
+            ),
+        ]
+    }
+
+    // Answers queries from a fixed zone, NXDOMAIN for unknown names
+    async fn start_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let zone = zone();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let Ok((len, peer)) = socket.recv_from(&mut buffer).await else {
+                    return;
+                };
+                let query = Message::from_vec(&buffer[..len]).unwrap();
+                let mut response = Message::new();
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .set_recursion_desired(query.recursion_desired())
+                    .set_recursion_available(true)
+                    .add_queries(query.queries().to_vec());
+                let question = &query.queries()[0];
+                let answers: Vec<Record> = zone
+                    .iter()
+                    .filter(|record| record.name() == question.name())
+                    .cloned()
+                    .collect();
+                if answers.is_empty() {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+                response.add_answers(
+                    answers
+                        .into_iter()
+                        .filter(|record| record.record_type() == question.query_type()),
+                );
+                socket
+                    .send_to(&response.to_vec().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_browse_and_resolve() {
+        let server = start_server().await;
+        let browser = UnicastBrowser::new(Some(server)).unwrap();
+
+        assert_eq!(
+            browser.browse_domains("example.com.").await,
+            vec!["example.com.", "lab.example.com."]
+        );
+        assert!(
+            browser
+                .service_types("lab.example.com.")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let service_types = browser.service_types("example.com.").await.unwrap();
+        assert_eq!(service_types, vec!["_ipp._tcp.example.com."]);
+        let instances = browser.instances(&service_types[0]).await.unwrap();
+        assert_eq!(instances.len(), 1);
+
+        let service = browser
+            .resolve(&instances[0], &service_types[0])
+            .await
+            .unwrap();
+        assert_eq!(
+            service,
+            UnicastService {
+                fullname: "Office Printer._ipp._tcp.example.com.".to_string(),
+                service_type: "_ipp._tcp.example.com.".to_string(),
+                host: "printer.example.com.".to_string(),
+                port: 631,
+                priority: 10,
+                weight: 5,
+                addrs: vec![IpAddr::from([192, 0, 2, 7])],
+                txt: vec![b"rp=ipp/print".to_vec()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_server_address() {
+        assert_eq!(
+            parse_server_address("192.168.1.1"),
+            Ok(SocketAddr::from(([192, 168, 1, 1], 53)))
+        );
+        assert_eq!(
+            parse_server_address("[::1]:5353"),
+            Ok("[::1]:5353".parse().unwrap())
+        );
+        assert!(parse_server_address("dns.example.com").is_err());
+    }
+}
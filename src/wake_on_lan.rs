@@ -0,0 +1,181 @@
+#![forbid(unsafe_code)]
+
+use crate::data_file;
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::path::{Path, PathBuf};
+
+const WOL_PORT: u16 = 9;
+
+// A hardware address as raw bytes, shown in lowercase colon separated notation
+pub type MacAddress = [u8; 6];
+
+// Accepts colon, dash or dot separated notations as well as plain hex digits
+pub fn parse_mac(text: &str) -> Option<MacAddress> {
+    let digits: String = text
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    // All zero and broadcast addresses show up as placeholders, never as real devices
+    if mac == [0; 6] || mac == [0xff; 6] {
+        return None;
+    }
+    Some(mac)
+}
+
+pub fn format_mac(mac: &MacAddress) -> String {
+    mac.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn magic_packet(mac: &MacAddress) -> [u8; 102] {
+    let mut packet = [0xffu8; 102];
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(mac);
+    }
+    packet
+}
+
+pub fn send_magic_packet(mac: &MacAddress) -> std::io::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    socket.send_to(&magic_packet(mac), (Ipv4Addr::BROADCAST, WOL_PORT))?;
+    Ok(())
+}
+
+// The kernel's neighbour table, Linux only, empty elsewhere
+pub fn read_arp_table() -> String {
+    std::fs::read_to_string("/proc/net/arp").unwrap_or_default()
+}
+
+// Looks up an IPv4 address in a neighbour table read by read_arp_table
+pub fn mac_from_arp_table(table: &str, address: &str) -> Option<MacAddress> {
+    // IP address, HW type, Flags, HW address, Mask, Device
+    table.lines().skip(1).find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        match columns.as_slice() {
+            [ip, _, _, mac, ..] if *ip == address => parse_mac(mac),
+            _ => None,
+        }
+    })
+}
+
+// Host names to MAC addresses learned over time, kept on disk so hosts can be
+// woken after a restart while they are asleep
+#[derive(Clone)]
+pub struct MacStore {
+    path: Option<PathBuf>,
+    macs: BTreeMap<String, String>,
+}
+
+impl MacStore {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            macs: BTreeMap::new(),
+        }
+    }
+
+    pub fn load_default() -> Self {
+        match data_file::path("macs.json") {
+            Some(path) => Self::load(&path),
+            None => Self::in_memory(),
+        }
+    }
+
+    // A missing or unreadable file starts an empty store
+    pub fn load(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+            macs: data_file::load_json(path),
+        }
+    }
+
+    pub fn get(&self, host: &str) -> Option<MacAddress> {
+        self.macs.get(host).and_then(|mac| parse_mac(mac))
+    }
+
+    // Returns whether the MAC was new or changed for the host
+    pub fn insert(&mut self, host: &str, mac: &MacAddress) -> bool {
+        let mac = format_mac(mac);
+        if self.macs.get(host) == Some(&mac) {
+            return false;
+        }
+        self.macs.insert(host.to_string(), mac);
+        true
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        data_file::save_json(path, &self.macs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: MacAddress = [0xaa, 0xbb, 0xcc, 0x01, 0x02, 0x03];
+
+    #[test]
+    fn test_parse_mac() {
+        assert_eq!(parse_mac("aa:bb:cc:01:02:03"), Some(MAC));
+        assert_eq!(parse_mac("AA-BB-CC-01-02-03"), Some(MAC));
+        assert_eq!(parse_mac("aabb.cc01.0203"), Some(MAC));
+        assert_eq!(parse_mac("AABBCC010203"), Some(MAC));
+        assert_eq!(parse_mac("aa:bb:cc:01:02"), None);
+        assert_eq!(parse_mac("gg:bb:cc:01:02:03"), None);
+        assert_eq!(parse_mac("00:00:00:00:00:00"), None);
+        assert_eq!(parse_mac("ff:ff:ff:ff:ff:ff"), None);
+    }
+
+    #[test]
+    fn test_format_mac() {
+        assert_eq!(format_mac(&MAC), "aa:bb:cc:01:02:03");
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let packet = magic_packet(&MAC);
+        assert_eq!(&packet[..6], &[0xff; 6]);
+        for repetition in packet[6..].chunks(6) {
+            assert_eq!(repetition, &MAC);
+        }
+    }
+
+    #[test]
+    fn test_mac_from_arp_table() {
+        let table = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                     192.168.1.2      0x1         0x2         aa:bb:cc:01:02:03     *        eth0\n\
+                     192.168.1.3      0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert_eq!(mac_from_arp_table(table, "192.168.1.2"), Some(MAC));
+        assert_eq!(mac_from_arp_table(table, "192.168.1.3"), None);
+        assert_eq!(mac_from_arp_table(table, "192.168.1.4"), None);
+    }
+
+    #[test]
+    fn test_mac_store_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("macs.json");
+
+        let mut store = MacStore::load(&path);
+        assert_eq!(store.get("nas.local."), None);
+        assert!(store.insert("nas.local.", &MAC));
+        assert!(!store.insert("nas.local.", &MAC));
+        store.save().unwrap();
+
+        let store = MacStore::load(&path);
+        assert_eq!(store.get("nas.local."), Some(MAC));
+    }
+}